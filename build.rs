@@ -0,0 +1,36 @@
+use std::process::Command;
+
+// the commit the binary was built from, for --version and bug reports. "unknown" when
+// it's built from a source tarball, or without git around
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or_else(|| String::from("unknown"));
+    println!("cargo:rustc-env=CHIP8RUST_COMMIT={}", commit);
+    #[cfg(feature = "ffi")]
+    c_header();
+}
+
+// include/chip8rust.h from src/ffi.rs, kept in the tree for C programs that don't build this
+#[cfg(feature = "ffi")]
+fn c_header() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let dir = std::env::var("CARGO_MANIFEST_DIR").expect("cargo sets it");
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", dir))
+        .expect("cbindgen.toml is readable");
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/ffi.rs", dir)) // only it, not every pub item in the crate
+        .generate()
+        .expect("src/ffi.rs goes through cbindgen")
+        .write_to_file(format!("{}/include/chip8rust.h", dir));
+}
@@ -0,0 +1,34 @@
+/* the C API driving a machine: a ROM that draws a 0, run for a second, then printed.
+ *
+ *   cargo build --release --no-default-features --features ffi
+ *   cc examples/c/main.c -Iinclude -Ltarget/release -lchip8rust -o main
+ *   LD_LIBRARY_PATH=target/release ./main
+ */
+#include <stdio.h>
+
+#include "chip8rust.h"
+
+/* V0 = 0, I = DGT(V0), draw it at (V0, V0), then stay put */
+static const uint8_t ROM[] = {0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06};
+
+int main(void) {
+    Chip8 *chip8 = chip8_new();
+    if (chip8 == NULL || chip8_load_rom(chip8, ROM, sizeof ROM) != CHIP8_OK) {
+        fprintf(stderr, "Failed to start the machine\n");
+        return 1;
+    }
+    chip8_set_quirks(chip8, CHIP8_QUIRK_CLIP_X | CHIP8_QUIRK_CLIP_Y);
+    for (int frame = 0; frame < 60; frame++) {
+        chip8_step_frame(chip8, 0);
+    }
+    static uint8_t pixels[CHIP8_FRAMEBUFFER_SIZE];
+    int rows = chip8_framebuffer(chip8, pixels);
+    for (int y = 0; y < 8 && y < rows; y++) {
+        for (int x = 0; x < 8; x++) {
+            putchar(pixels[y * 64 + x] ? '#' : '.');
+        }
+        putchar('\n');
+    }
+    chip8_free(chip8);
+    return 0;
+}
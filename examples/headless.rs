@@ -0,0 +1,62 @@
+// the interpreter embedded in another program: no window, no audio and no files. it runs
+// a ROM for 300 frames, pressing a key partway through, then checks and prints the display
+//
+//   cargo run --example headless
+use chip8rust::{Emulator, RunMode};
+
+// waits for a key, then draws its digit from the font at (24, 13) and stays there
+//
+//   0x200  F00A   V0 = KEY
+//   0x202  F029   I = DGT(V0)
+//   0x204  6118   V1 = 24
+//   0x206  620D   V2 = 13
+//   0x208  D125   DRAW 5 @ V1,V2
+//   0x20A  120A   JMP 0x20A
+const ROM: [u8; 12] = [0xF0, 0x0A, 0xF0, 0x29, 0x61, 0x18, 0x62, 0x0D, 0xD1, 0x25, 0x12, 0x0A];
+const FRAMES: u32 = 300;
+const CYCLES_PER_FRAME: u32 = 10; // 600 instructions a second
+// the key, and the frames it's held down for. FX0A takes it once it's let go
+const KEY: usize = 0x5;
+const HELD: std::ops::Range<u32> = 100..110;
+// the font's 5, where the ROM draws it
+const DIGIT: [u8; 5] = [0xF0, 0x80, 0xF0, 0x10, 0xF0];
+const AT: (usize, usize) = (24, 13);
+
+fn run() -> Emulator {
+    let mut emulator = Emulator::new();
+    emulator.state = RunMode::Play.into();
+    emulator.seed_rng(0);
+    emulator.load_rom_bytes(&ROM).expect("the ROM fits");
+    for frame in 0..FRAMES {
+        emulator.keys[KEY] = HELD.contains(&frame);
+        emulator.step_frame(CYCLES_PER_FRAME);
+    }
+    emulator
+}
+
+// whether the pixels at AT are the digit's, and nothing else is lit
+fn shows_digit(emulator: &Emulator) -> bool {
+    let display = emulator.display();
+    let (x0, y0) = AT;
+    let lit = display.iter().flatten().filter(|pixel| **pixel).count();
+    let expected = DIGIT.iter().map(|row| row.count_ones() as usize).sum::<usize>();
+    let matches = DIGIT.iter().enumerate().all(|(dy, row)| {
+        (0..8).all(|dx| display[x0 + dx][y0 + dy] == (row & (0x80 >> dx) != 0))
+    });
+    matches && lit == expected
+}
+
+fn main() {
+    let mut emulator = run();
+    print!("{}", emulator.text_display());
+    assert_eq!(emulator.registers.get(0), KEY as u8);
+    assert!(shows_digit(&emulator), "the display doesn't show the {:X}", KEY);
+    println!("V0 = {:X} after {} frames", KEY, FRAMES);
+}
+
+#[test]
+fn draws_the_key_pressed() {
+    let mut emulator = run();
+    assert_eq!(emulator.registers.get(0), KEY as u8);
+    assert!(shows_digit(&emulator));
+}
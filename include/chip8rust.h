@@ -0,0 +1,81 @@
+/* the C API of chip8rust (src/ffi.rs), made by cbindgen: don't edit by hand */
+
+#ifndef CHIP8RUST_H
+#define CHIP8RUST_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define CHIP8_OK 0
+
+#define CHIP8_ERR_NULL -1
+
+#define CHIP8_ERR_LOAD -2
+
+#define CHIP8_ERR_PANIC -3
+
+#define CHIP8_QUIRK_JUMP_X 1
+
+#define CHIP8_QUIRK_CLIP_X 2
+
+#define CHIP8_QUIRK_CLIP_Y 4
+
+#define CHIP8_FRAMEBUFFER_SIZE (64 * 64)
+
+typedef struct Chip8 Chip8;
+
+/**
+ * A machine with nothing loaded, in Play, or null if making one panicked.
+ */
+struct Chip8 *chip8_new(void);
+
+/**
+ * Frees a machine from chip8_new.
+ *
+ * # Safety
+ * `chip8` is null or from chip8_new, and isn't used again.
+ */
+void chip8_free(struct Chip8 *chip8);
+
+/**
+ * Resets the machine and loads `len` bytes of ROM at 0x200. On CHIP8_ERR_LOAD nothing is.
+ *
+ * # Safety
+ * `chip8` is null or from chip8_new, and `rom` points at `len` readable bytes.
+ */
+int32_t chip8_load_rom(struct Chip8 *chip8, const uint8_t *rom, size_t len);
+
+/**
+ * One 60Hz frame with `keys` held, bit N for key N.
+ *
+ * # Safety
+ * `chip8` is null or from chip8_new.
+ */
+int32_t chip8_step_frame(struct Chip8 *chip8, uint16_t keys);
+
+/**
+ * Writes the display to `out`, a byte a pixel (0 or 1) row by row, 64 pixels a row.
+ * Returns the number of rows, 32 or 64 for a hires ROM.
+ *
+ * # Safety
+ * `chip8` is null or from chip8_new, and `out` has room for CHIP8_FRAMEBUFFER_SIZE bytes.
+ */
+int32_t chip8_framebuffer(struct Chip8 *chip8, uint8_t *out);
+
+/**
+ * 1 while the sound timer runs, 0 when it doesn't.
+ *
+ * # Safety
+ * `chip8` is null or from chip8_new.
+ */
+int32_t chip8_sound_active(struct Chip8 *chip8);
+
+/**
+ * The CHIP8_QUIRK_ bits that are set, the defaults for the rest.
+ *
+ * # Safety
+ * `chip8` is null or from chip8_new.
+ */
+int32_t chip8_set_quirks(struct Chip8 *chip8, uint32_t quirks);
+
+#endif  /* CHIP8RUST_H */
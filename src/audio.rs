@@ -0,0 +1,561 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "audio-rodio")]
+use std::time::Duration;
+
+#[cfg(feature = "audio-rodio")]
+use rodio::{OutputStream, Sink, Source};
+
+use crate::status::NOTICE_TIME;
+use crate::Emulator;
+
+// the beeper's volume when fully on
+pub const BEEP_VOLUME: f32 = 0.2;
+// a few milliseconds of ramp either side of a beep, cutting the sound dead makes it click
+const ATTACK_TIME: f32 = 0.008;
+const RELEASE_TIME: f32 = 0.015;
+
+// how loud the beeper should be right now, from 0.0 (silent) to 1.0
+pub struct Envelope {
+    level: f32,
+}
+impl Envelope {
+    pub fn new() -> Envelope {
+        Envelope { level: 0.0 }
+    }
+    // moves towards fully on or fully off and returns the new level
+    pub fn update(&mut self, on: bool, delta: f32) -> f32 {
+        self.level = if on {
+            (self.level + delta / ATTACK_TIME).min(1.0)
+        } else {
+            (self.level - delta / RELEASE_TIME).max(0.0)
+        };
+        self.level
+    }
+}
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+    Saw,
+    Noise,
+}
+impl Waveform {
+    const ALL: [Waveform; 5] = [
+        Waveform::Square,
+        Waveform::Sine,
+        Waveform::Triangle,
+        Waveform::Saw,
+        Waveform::Noise,
+    ];
+    pub fn next(self) -> Waveform {
+        Waveform::ALL[(self as usize + 1) % Waveform::ALL.len()]
+    }
+    // one sample at `phase` (0.0..1.0) through the period, from -1.0 to 1.0.
+    // noise has no shape, it holds a random bit of `noise` (new every period) instead
+    pub fn sample(self, phase: f32, noise: u32) -> f32 {
+        let sign = |high: bool| if high { 1.0 } else { -1.0 };
+        match self {
+            Waveform::Square => sign(phase < 0.5),
+            Waveform::Noise => sign(noise & 1 == 1),
+            Waveform::Sine => (phase * std::f32::consts::TAU).sin(),
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Waveform::Saw => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+// the waveform, shared with the audio thread so it can be changed while a beep plays
+#[derive(Clone)]
+pub struct WaveformSetting(Arc<AtomicU8>);
+impl WaveformSetting {
+    pub fn new(waveform: Waveform) -> WaveformSetting {
+        WaveformSetting(Arc::new(AtomicU8::new(waveform as u8)))
+    }
+    pub fn get(&self) -> Waveform {
+        Waveform::ALL[self.0.load(Ordering::Relaxed) as usize]
+    }
+    pub fn set(&self, waveform: Waveform) {
+        self.0.store(waveform as u8, Ordering::Relaxed);
+    }
+}
+
+// the pitch, shared the same way (as the bits of an f32) so it can bend while playing
+#[derive(Clone)]
+pub struct FrequencySetting(Arc<AtomicU32>);
+impl FrequencySetting {
+    pub fn new(frequency: f32) -> FrequencySetting {
+        FrequencySetting(Arc::new(AtomicU32::new(frequency.to_bits())))
+    }
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+    pub fn set(&self, frequency: f32) {
+        self.0.store(frequency.to_bits(), Ordering::Relaxed);
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+pub enum BeepStyle {
+    Plain,
+    Pitch,
+}
+
+// how much higher the pitch beep is for each count left on the sound timer
+const PITCH_STEP: f32 = 4.0;
+
+// the beep's pitch for a sound timer value. with the pitch style it falls as the timer
+// runs down, so long beeps sweep downward
+pub fn beep_frequency(style: BeepStyle, base: f32, sound_timer: u8) -> f32 {
+    match style {
+        BeepStyle::Plain => base,
+        BeepStyle::Pitch => base + PITCH_STEP * sound_timer as f32,
+    }
+}
+
+// an endless tone for the beeper sink, which is paused and resumed around it
+pub struct Tone {
+    waveform: WaveformSetting,
+    frequency: FrequencySetting,
+    phase: f32,
+    noise: u32, // xorshift state, stepped once per period
+}
+impl Tone {
+    pub fn new(frequency: FrequencySetting, waveform: WaveformSetting) -> Tone {
+        Tone {
+            waveform,
+            frequency,
+            phase: 0.0,
+            noise: 0x2545F491,
+        }
+    }
+}
+impl Iterator for Tone {
+    type Item = f32;
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.waveform.get().sample(self.phase, self.noise);
+        self.phase += self.frequency.get() / SAMPLE_RATE as f32;
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+            self.noise ^= self.noise << 13;
+            self.noise ^= self.noise >> 17;
+            self.noise ^= self.noise << 5;
+        }
+        Some(sample)
+    }
+}
+#[cfg(feature = "audio-rodio")]
+impl Source for Tone {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        1
+    }
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// whatever makes the beep, handed to the emulator by the frontend
+pub trait Beeper {
+    // 0.0 pauses the beep, anything above plays it at that volume
+    fn set_level(&mut self, level: f32);
+}
+
+// for running without sound: headless, or when there's no audio device
+pub struct NullBeeper;
+impl Beeper for NullBeeper {
+    fn set_level(&mut self, _level: f32) {}
+}
+
+// the best beeper there is: rodio if it's built in, or silence without it. fails if rodio
+// can't open a device, the caller falls back to a NullBeeper then.
+// the returned beeper owns the output stream, so keep it around for as long as it beeps
+#[cfg(feature = "audio-rodio")]
+pub fn init(
+    frequency: FrequencySetting,
+    waveform: WaveformSetting,
+) -> Result<Box<dyn Beeper>, String> {
+    Ok(Box::new(SinkBeeper::open(frequency, waveform)?))
+}
+#[cfg(not(feature = "audio-rodio"))]
+pub fn init(
+    _frequency: FrequencySetting,
+    _waveform: WaveformSetting,
+) -> Result<Box<dyn Beeper>, String> {
+    Ok(Box::new(NullBeeper))
+}
+
+impl Emulator {
+    // the frontend's beeper, or if it couldn't open one (no audio device, most often) the
+    // NullBeeper from new() stays in and it says so. returns whether there's sound
+    pub fn use_beeper(&mut self, opened: Result<Box<dyn Beeper>, String>) -> bool {
+        match opened {
+            Ok(beeper) => {
+                self.beeper = beeper;
+                true
+            }
+            Err(e) => {
+                log::warn!("No audio ({}), running without sound", e);
+                self.notify_error("No audio, running without sound", NOTICE_TIME);
+                false
+            }
+        }
+    }
+}
+
+#[cfg(feature = "audio-rodio")]
+pub struct SinkBeeper {
+    _stream: OutputStream, // the sink goes quiet if this is dropped
+    sink: Sink,
+}
+#[cfg(feature = "audio-rodio")]
+impl SinkBeeper {
+    pub fn open(frequency: FrequencySetting, waveform: WaveformSetting) -> Result<SinkBeeper, String> {
+        let (stream, stream_handle) = OutputStream::try_default().map_err(|e| e.to_string())?;
+        let sink = Sink::try_new(&stream_handle).map_err(|e| e.to_string())?;
+        sink.append(Tone::new(frequency, waveform));
+        sink.pause();
+        Ok(SinkBeeper {
+            _stream: stream,
+            sink,
+        })
+    }
+}
+#[cfg(feature = "audio-rodio")]
+impl Beeper for SinkBeeper {
+    fn set_level(&mut self, level: f32) {
+        self.sink.set_volume(level);
+        if level > 0.0 {
+            self.sink.play();
+        } else {
+            self.sink.pause();
+        }
+    }
+}
+
+// 60Hz timer frames divide evenly into the recording's samples
+const SAMPLES_PER_FRAME: u64 = SAMPLE_RATE as u64 / 60;
+
+// a stretch of beeping with one sound, in timer frames since the recording started
+struct Segment {
+    start: u64,
+    stop: Option<u64>,
+    waveform: Waveform,
+    frequency: f32,
+}
+
+// logs when the buzzer was on during a session, and renders it to a WAV file at the end
+pub struct Recording {
+    path: String,
+    frames: u64,
+    segments: Vec<Segment>,
+}
+impl Recording {
+    pub fn new(path: String) -> Recording {
+        Recording {
+            path,
+            frames: 0,
+            segments: Vec::new(),
+        }
+    }
+    // called once per 60Hz timer tick with whether (and how) the buzzer sounds for that frame
+    pub fn frame(&mut self, sounding: bool, waveform: Waveform, frequency: f32) {
+        let frames = self.frames;
+        self.frames += 1;
+        if let Some(open) = self.segments.last_mut().filter(|segment| segment.stop.is_none()) {
+            if sounding && open.waveform == waveform && open.frequency == frequency {
+                return;
+            }
+            open.stop = Some(frames);
+        }
+        if sounding {
+            self.segments.push(Segment {
+                start: frames,
+                stop: None,
+                waveform,
+                frequency,
+            });
+        }
+    }
+    // the whole session as samples, with the same ramps as the live beeper
+    pub fn render(&self) -> Vec<i16> {
+        let mut samples = Vec::with_capacity((self.frames * SAMPLES_PER_FRAME) as usize);
+        let mut envelope = Envelope::new();
+        let mut tone = Tone::new(
+            FrequencySetting::new(0.0),
+            WaveformSetting::new(Waveform::Square),
+        );
+        let mut segments = self.segments.iter().peekable();
+        for frame in 0..self.frames {
+            while segments
+                .peek()
+                .is_some_and(|segment| segment.stop.is_some_and(|stop| stop <= frame))
+            {
+                segments.next();
+            }
+            let sounding = match segments.peek() {
+                Some(segment) if segment.start <= frame => {
+                    tone.waveform.set(segment.waveform);
+                    tone.frequency.set(segment.frequency);
+                    true
+                }
+                _ => false,
+            };
+            for _ in 0..SAMPLES_PER_FRAME {
+                let level = envelope.update(sounding, 1.0 / SAMPLE_RATE as f32);
+                let sample = tone.next().unwrap_or(0.0) * level * BEEP_VOLUME;
+                samples.push((sample * i16::MAX as f32) as i16);
+            }
+        }
+        samples
+    }
+    pub fn write(&self) -> io::Result<()> {
+        write_wav(&self.path, &self.render())
+    }
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+// 16-bit mono PCM
+fn write_wav(path: &str, samples: &[i16]) -> io::Result<()> {
+    let data_size = samples.len() as u32 * 2;
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"RIFF")?;
+    file.write_all(&(36 + data_size).to_le_bytes())?;
+    file.write_all(b"WAVEfmt ")?;
+    file.write_all(&16u32.to_le_bytes())?; // fmt chunk size
+    file.write_all(&1u16.to_le_bytes())?; // PCM
+    file.write_all(&1u16.to_le_bytes())?; // channels
+    file.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    file.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?; // byte rate
+    file.write_all(&2u16.to_le_bytes())?; // block align
+    file.write_all(&16u16.to_le_bytes())?; // bits per sample
+    file.write_all(b"data")?;
+    file.write_all(&data_size.to_le_bytes())?;
+    for sample in samples {
+        file.write_all(&sample.to_le_bytes())?;
+    }
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_TIME: f32 = 1.0 / SAMPLE_RATE as f32;
+
+    #[test]
+    fn the_envelope_ramps_instead_of_stepping() {
+        let mut envelope = Envelope::new();
+        let attack: Vec<f32> = (0..1000).map(|_| envelope.update(true, SAMPLE_TIME)).collect();
+        let release: Vec<f32> = (0..1000).map(|_| envelope.update(false, SAMPLE_TIME)).collect();
+        let samples = |time: f32| (time * SAMPLE_RATE as f32) as usize;
+        // fully on after ATTACK_TIME, fully off RELEASE_TIME after letting go, a sample
+        // or so either way for the rounding
+        let on = attack.iter().position(|&level| level == 1.0).unwrap();
+        assert!(on.abs_diff(samples(ATTACK_TIME)) <= 1, "on after {} samples", on);
+        let off = release.iter().position(|&level| level == 0.0).unwrap();
+        assert!(off.abs_diff(samples(RELEASE_TIME)) <= 1, "off after {} samples", off);
+        // and never more than a ramp's step from one sample to the next
+        let levels: Vec<f32> = attack.into_iter().chain(release).collect();
+        let biggest = levels.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max);
+        assert!(biggest <= SAMPLE_TIME / ATTACK_TIME + 1e-6);
+    }
+
+    // a beep from frame 2 to frame 5 of a recording: it fades in from silence and out to it,
+    // where the square wave alone would jump straight to full volume
+    #[test]
+    fn a_recorded_beep_fades_in_and_out() {
+        let mut recording = Recording::new(String::new());
+        for frame in 0..10 {
+            recording.frame((2..5).contains(&frame), Waveform::Square, 440.0);
+        }
+        let samples = recording.render();
+        let (start, stop) = (2 * SAMPLES_PER_FRAME as usize, 5 * SAMPLES_PER_FRAME as usize);
+        let full = (BEEP_VOLUME * i16::MAX as f32) as i16;
+        assert!(samples[..start].iter().all(|&sample| sample == 0));
+        assert!(samples[start].abs() < full / 100);
+        assert!(samples[start..start + 10].iter().all(|&sample| sample.abs() < full / 10));
+        assert!(samples[start + 400..stop].iter().any(|&sample| sample.abs() >= full - 1));
+        // the release carries on past the end of the beep
+        assert!(samples[stop..stop + 10].iter().all(|&sample| sample.abs() > full / 2));
+        assert!(samples[stop + 700..].iter().all(|&sample| sample == 0));
+    }
+
+    // a period of each shape, at its quarters
+    #[test]
+    fn each_waveform_has_its_shape() {
+        let at = |waveform: Waveform| [0.0, 0.25, 0.5, 0.75].map(|phase| waveform.sample(phase, 0));
+        assert_eq!(at(Waveform::Square), [1.0, 1.0, -1.0, -1.0]);
+        assert_eq!(at(Waveform::Triangle), [1.0, 0.0, -1.0, 0.0]);
+        assert_eq!(at(Waveform::Saw), [-1.0, -0.5, 0.0, 0.5]);
+        let sine = at(Waveform::Sine);
+        for (got, want) in sine.iter().zip([0.0, 1.0, 0.0, -1.0]) {
+            assert!((got - want).abs() < 1e-6, "{:?}", sine);
+        }
+        assert_eq!(Waveform::Noise.sample(0.3, 1), 1.0);
+        assert_eq!(Waveform::Noise.sample(0.3, 2), -1.0);
+    }
+
+    // a period of 128 samples, so the phase steps come out exact. every shape repeats that
+    // often, peaks at full scale and averages out to nothing, and noise holds still within
+    // a period
+    #[test]
+    fn the_tone_repeats_every_period() {
+        const PERIOD: usize = 128;
+        let frequency = FrequencySetting::new(SAMPLE_RATE as f32 / PERIOD as f32);
+        for waveform in Waveform::ALL {
+            let mut tone = Tone::new(frequency.clone(), WaveformSetting::new(waveform));
+            let samples: Vec<f32> = tone.by_ref().take(4 * PERIOD).collect();
+            let peak = samples.iter().fold(0.0, |peak: f32, sample| peak.max(sample.abs()));
+            assert!(peak <= 1.0 && peak > 0.99, "{:?} peaks at {}", waveform, peak);
+            if waveform == Waveform::Noise {
+                for period in samples.chunks(PERIOD) {
+                    assert!(period.iter().all(|&sample| sample == period[0]));
+                }
+                continue;
+            }
+            let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+            assert!(mean.abs() < 0.02, "{:?} averages {}", waveform, mean);
+            for (i, pair) in samples.windows(PERIOD + 1).enumerate() {
+                let (now, later) = (pair[0], pair[PERIOD]);
+                assert!((now - later).abs() < 1e-5, "{:?} {}: {} then {}", waveform, i, now, later);
+            }
+        }
+    }
+
+    #[test]
+    fn the_waveforms_cycle_round() {
+        let cycled: Vec<Waveform> =
+            std::iter::successors(Some(Waveform::Square), |w| Some(w.next())).take(6).collect();
+        assert_eq!(cycled[..5], Waveform::ALL);
+        assert_eq!(cycled[5], Waveform::Square);
+    }
+
+    #[test]
+    fn the_pitch_style_falls_with_the_sound_timer() {
+        assert_eq!(beep_frequency(BeepStyle::Plain, 440.0, 0), 440.0);
+        assert_eq!(beep_frequency(BeepStyle::Plain, 440.0, 30), 440.0);
+        assert_eq!(beep_frequency(BeepStyle::Pitch, 440.0, 0), 440.0);
+        assert_eq!(beep_frequency(BeepStyle::Pitch, 440.0, 1), 440.0 + PITCH_STEP);
+        assert_eq!(beep_frequency(BeepStyle::Pitch, 440.0, 30), 440.0 + 30.0 * PITCH_STEP);
+    }
+
+    // the emulator sets the shared pitch as the timer runs down, and a tone playing it picks
+    // the new one up on its next sample
+    #[test]
+    fn the_bend_reaches_the_tone() {
+        use crate::{Emulator, RunMode};
+
+        let mut emulator = Emulator::new();
+        emulator.state = RunMode::Play.into();
+        emulator.beep_style = BeepStyle::Pitch;
+        emulator.beep_freq = 400.0;
+        let mut tone = Tone::new(emulator.beep_pitch.clone(), WaveformSetting::new(Waveform::Saw));
+        emulator.sound_timer = 10;
+        emulator.update_beeper(1.0 / 60.0);
+        assert_eq!(emulator.beep_pitch.get(), 400.0 + 10.0 * PITCH_STEP);
+        let step = |tone: &mut Tone| {
+            let (a, b) = (tone.next().unwrap(), tone.next().unwrap());
+            b - a
+        };
+        assert!((step(&mut tone) - 2.0 * 440.0 / SAMPLE_RATE as f32).abs() < 1e-5);
+
+        emulator.tick_timers();
+        emulator.update_beeper(1.0 / 60.0);
+        assert_eq!(emulator.beep_pitch.get(), 400.0 + 9.0 * PITCH_STEP);
+        assert!((step(&mut tone) - 2.0 * 436.0 / SAMPLE_RATE as f32).abs() < 1e-5);
+
+        // silent, it keeps the last pitch for the release
+        emulator.sound_timer = 0;
+        emulator.update_beeper(1.0 / 60.0);
+        assert_eq!(emulator.beep_pitch.get(), 436.0);
+    }
+
+    // three frames of beep in a second of recording: a header for 44.1kHz 16-bit mono, a
+    // second of samples after it, and sound only where the beep was
+    #[test]
+    fn a_recording_is_a_wav_of_the_whole_session() {
+        let path = std::env::temp_dir().join(format!("chip8rust-{}.wav", std::process::id()));
+        let mut recording = Recording::new(path.to_string_lossy().to_string());
+        for frame in 0..60 {
+            recording.frame((10..13).contains(&frame), Waveform::Square, 440.0);
+        }
+        recording.write().unwrap();
+        let wav = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let u16_at = |at: usize| u16::from_le_bytes([wav[at], wav[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes(wav[at..at + 4].try_into().unwrap());
+        let data_size = SAMPLE_RATE * 2; // a second of 16-bit samples
+        assert_eq!(wav.len(), 44 + data_size as usize);
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(4), 36 + data_size);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!((u32_at(16), u16_at(20), u16_at(22)), (16, 1, 1)); // PCM, mono
+        assert_eq!((u32_at(24), u32_at(28)), (SAMPLE_RATE, SAMPLE_RATE * 2));
+        assert_eq!((u16_at(32), u16_at(34)), (2, 16));
+        assert_eq!((&wav[36..40], u32_at(40)), (&b"data"[..], data_size));
+
+        let samples = recording.render();
+        assert_eq!(samples.len(), SAMPLE_RATE as usize);
+        let written: Vec<i16> =
+            wav[44..].chunks(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+        assert_eq!(written, samples);
+        let frame = SAMPLES_PER_FRAME as usize;
+        let sounding: Vec<usize> = samples
+            .chunks(frame)
+            .enumerate()
+            .filter(|(_, frame)| frame.iter().any(|&sample| sample != 0))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(sounding, [10, 11, 12, 13]); // 13 for the release
+    }
+
+    #[test]
+    fn without_an_audio_device_it_runs_silently() {
+        let mut emulator = Emulator::new();
+        emulator.state = crate::RunMode::Play.into();
+        assert!(!emulator.use_beeper(Err(String::from("no default output device"))));
+        assert_eq!(emulator.status.current(), Some(("No audio, running without sound", true)));
+        // half a second of beep, played through to the end on the NullBeeper
+        emulator.load_rom_bytes(&[0x60, 0x1E, 0xF0, 0x18, 0x12, 0x04]).unwrap();
+        for _ in 0..60 {
+            emulator.step_frame(10);
+            emulator.update_beeper(1.0 / 60.0);
+        }
+        assert_eq!((emulator.stats.sound_activations, emulator.sound_timer), (1, 0));
+    }
+
+    // the levels it was set to
+    struct Levels(std::rc::Rc<std::cell::RefCell<Vec<f32>>>);
+    impl Beeper for Levels {
+        fn set_level(&mut self, level: f32) {
+            self.0.borrow_mut().push(level);
+        }
+    }
+
+    #[test]
+    fn an_opened_beeper_is_played_through() {
+        let levels = std::rc::Rc::default();
+        let mut emulator = Emulator::new();
+        emulator.state = crate::RunMode::Play.into();
+        assert!(emulator.use_beeper(Ok(Box::new(Levels(std::rc::Rc::clone(&levels))))));
+        assert_eq!(emulator.status.current(), None);
+        emulator.sound_timer = 2;
+        emulator.update_beeper(1.0 / 60.0);
+        assert_eq!(*levels.borrow(), [BEEP_VOLUME]);
+    }
+}
@@ -0,0 +1,434 @@
+use std::any::Any;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::output;
+use crate::pacing;
+use crate::rom_source::LoadError;
+use crate::run_state::Halt;
+use crate::stats::{RunStats, StatsReport};
+use crate::Emulator;
+
+// extensions picked up from the ROM directory (files without an extension are included too)
+const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+
+#[derive(Debug, clap::Args)]
+pub struct BatchArgs {
+    /// The directory of ROMs to run
+    #[clap(value_parser)]
+    dir: PathBuf,
+    /// How many 60Hz frames to run each ROM for
+    #[clap(short, long, default_value_t = 600)]
+    frames: u32,
+    /// The execution speed for the processor (in cycles per second)
+    #[clap(short, long, default_value_t = 600.0, value_parser = pacing::parse_rate)]
+    cycle_speed: f32,
+    /// Run exactly this many instructions per frame instead of working it out from the speed
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    ipf: Option<u32>,
+    /// Stop a ROM early once an FX0A key wait lasts longer than this (in seconds of emulated time)
+    #[clap(long, value_name = "SECS")]
+    keywait_timeout: Option<f32>,
+    /// The seed for the random number generator, so runs are reproducible
+    #[clap(short, long, default_value_t = 0)]
+    seed: u64,
+    /// Where to write the JSON report
+    #[clap(short, long, default_value = "batch_report.json")]
+    output: String,
+    /// An earlier report to diff against
+    #[clap(long, value_parser)]
+    compare: Option<String>,
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum RunStatus {
+    Running,
+    Halted,
+    WaitingForKey,
+    Crashed,
+}
+
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
+struct RomResult {
+    rom: String,
+    status: RunStatus,
+    display_hash: Option<String>,
+    #[serde(default)] // reports from before it was kept
+    state_hash: Option<String>,
+    error: Option<String>,
+}
+impl RomResult {
+    // an earlier report without state hashes is only compared on what it has
+    fn same_as(&self, old: &RomResult) -> bool {
+        let mut result = self.clone();
+        if old.state_hash.is_none() {
+            result.state_hash = None;
+        }
+        result == *old
+    }
+}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+struct BatchReport {
+    frames: u32,
+    cycle_speed: f32,
+    ipf: Option<u32>,
+    keywait_timeout: Option<f32>,
+    seed: u64,
+    results: Vec<RomResult>,
+}
+
+// a ROM whose result differs from the earlier report's
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+struct RomChange {
+    before: RomResult,
+    after: RomResult,
+}
+
+// the report diffed against an earlier one, by ROM name
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+struct Comparison {
+    changed: Vec<RomChange>,
+    new: Vec<String>,
+    missing: Vec<String>,
+}
+
+// what --json prints instead of the text lines
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+struct JsonOutput {
+    report: BatchReport,
+    stats: StatsReport,
+    comparison: Option<Comparison>,
+}
+
+// returns false if a comparison found ROMs whose behavior changed
+pub fn run(args: BatchArgs, json: bool) -> bool {
+    let roms = match find_roms(&args.dir) {
+        Ok(roms) => roms,
+        Err(e) => {
+            let message = format!("Failed to read ROM directory {}: {}", args.dir.display(), e);
+            output::error(json, &message);
+            return false;
+        }
+    };
+    let cycles_per_frame = args
+        .ipf
+        .unwrap_or_else(|| Emulator::cycles_per_frame(args.cycle_speed));
+
+    // panics are caught per ROM and recorded, so keep the default hook from spamming stderr
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut stats = RunStats::new();
+    let results: Vec<RomResult> = roms
+        .iter()
+        .map(|rom| run_rom(rom, &args, cycles_per_frame, &mut stats))
+        .collect();
+    panic::set_hook(default_hook);
+    stats.finish();
+
+    for result in results.iter().filter(|_| !json) {
+        match &result.error {
+            Some(error) => println!("{:<32} {:?}: {}", result.rom, result.status, error),
+            None => println!(
+                "{:<32} {:?} {}",
+                result.rom,
+                result.status,
+                result.display_hash.as_deref().unwrap_or("")
+            ),
+        }
+    }
+
+    let report = BatchReport {
+        frames: args.frames,
+        cycle_speed: args.cycle_speed,
+        ipf: args.ipf,
+        keywait_timeout: args.keywait_timeout,
+        seed: args.seed,
+        results,
+    };
+    match serde_json::to_string_pretty(&report) {
+        Ok(text) => {
+            if let Err(e) = fs::write(&args.output, text) {
+                output::error(json, &format!("Failed to write report to {}: {}", args.output, e));
+            }
+        }
+        Err(e) => output::error(json, &format!("Failed to serialize report: {}", e)),
+    }
+
+    let comparison = match &args.compare {
+        Some(previous) => match compare(&report, previous) {
+            Ok(comparison) => Some(comparison),
+            Err(e) => {
+                output::error(json, &format!("Failed to load previous report {}: {}", previous, e));
+                return false;
+            }
+        },
+        None => None,
+    };
+    let unchanged = comparison.as_ref().is_none_or(|c| c.changed.is_empty());
+    if json {
+        let stats = stats.report();
+        output::print_json(&JsonOutput { report, stats, comparison });
+    } else {
+        println!("{}", stats);
+        if let Some(comparison) = &comparison {
+            print_comparison(comparison, report.results.len());
+        }
+    }
+    unchanged
+}
+
+fn find_roms(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let is_rom = match path.extension() {
+            Some(ext) => ROM_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()),
+            None => true,
+        };
+        if is_rom {
+            roms.push(path);
+        }
+    }
+    roms.sort();
+    Ok(roms)
+}
+
+// crashed ROMs take their counters down with them, finished ones are merged into `stats`
+fn run_rom(rom: &Path, args: &BatchArgs, cycles_per_frame: u32, stats: &mut RunStats) -> RomResult {
+    let name = rom
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let outcome = isolated(|| {
+        let mut emulator = Emulator::new();
+        emulator.seed_rng(args.seed);
+        emulator.keywait_timeout = args.keywait_timeout;
+        emulator.load_rom(&rom.to_string_lossy())?;
+        for _ in 0..args.frames {
+            if emulator.step_frame(cycles_per_frame).key_wait_expired {
+                break; // it wants input, so it's reported as waiting for a key
+            }
+        }
+        // a ROM that stops on an error counts as crashed, but its display is still there
+        let error = emulator.halt.filter(Halt::is_error).map(|halt| halt.to_string());
+        let status = if error.is_some() {
+            RunStatus::Crashed
+        } else if emulator.is_waiting_for_key() {
+            RunStatus::WaitingForKey
+        } else if emulator.is_halted() {
+            RunStatus::Halted
+        } else {
+            RunStatus::Running
+        };
+        stats.merge(&emulator.stats);
+        Ok((status, emulator.display_hash(), emulator.state_hash(), error))
+    });
+
+    match outcome {
+        Ok((status, display_hash, state_hash, error)) => RomResult {
+            rom: name,
+            status,
+            display_hash: Some(format!("{:016x}", display_hash)),
+            state_hash: Some(format!("{:016x}", state_hash)),
+            error,
+        },
+        Err(error) => RomResult {
+            rom: name,
+            status: RunStatus::Crashed,
+            display_hash: None,
+            state_hash: None,
+            error: Some(error),
+        },
+    }
+}
+
+// `run`, with a panic in it caught and turned into an error like a failed load is, so one
+// broken ROM (or emulator bug) doesn't take the rest of the batch with it
+fn isolated<T>(run: impl FnOnce() -> Result<T, LoadError>) -> Result<T, String> {
+    match panic::catch_unwind(AssertUnwindSafe(run)) {
+        Ok(outcome) => outcome.map_err(|e| e.to_string()),
+        Err(payload) => Err(panic_message(payload)),
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic")
+    }
+}
+
+fn compare(report: &BatchReport, previous_path: &str) -> Result<Comparison, String> {
+    let json = fs::read_to_string(previous_path).map_err(|e| e.to_string())?;
+    let previous: BatchReport = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+    let mut comparison = Comparison {
+        changed: Vec::new(),
+        new: Vec::new(),
+        missing: Vec::new(),
+    };
+    for result in &report.results {
+        match previous.results.iter().find(|old| old.rom == result.rom) {
+            Some(old) if !result.same_as(old) => comparison.changed.push(RomChange {
+                before: old.clone(),
+                after: result.clone(),
+            }),
+            Some(_) => {}
+            None => comparison.new.push(result.rom.clone()),
+        }
+    }
+    for old in &previous.results {
+        if !report.results.iter().any(|result| result.rom == old.rom) {
+            comparison.missing.push(old.rom.clone());
+        }
+    }
+    Ok(comparison)
+}
+
+fn print_comparison(comparison: &Comparison, roms: usize) {
+    for RomChange { before, after } in &comparison.changed {
+        println!(
+            "changed: {} ({:?} {} -> {:?} {})",
+            after.rom,
+            before.status,
+            before.display_hash.as_deref().unwrap_or("-"),
+            after.status,
+            after.display_hash.as_deref().unwrap_or("-"),
+        );
+        if before.state_hash.is_some() && before.state_hash != after.state_hash {
+            println!(
+                "  state {} -> {}",
+                before.state_hash.as_deref().unwrap_or("-"),
+                after.state_hash.as_deref().unwrap_or("-"),
+            );
+        }
+    }
+    for rom in &comparison.new {
+        println!("new: {}", rom);
+    }
+    for rom in &comparison.missing {
+        println!("missing: {}", rom);
+    }
+    println!("{} of {} ROMs changed", comparison.changed.len(), roms);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(rom: &str, status: RunStatus, state_hash: Option<&str>) -> RomResult {
+        RomResult {
+            rom: String::from(rom),
+            status,
+            display_hash: Some(String::from("00000000deadbeef")),
+            state_hash: state_hash.map(String::from),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn the_json_output_reads_back() {
+        let before = result("pong.ch8", RunStatus::Running, None);
+        let after = result("pong.ch8", RunStatus::Halted, Some("0123456789abcdef"));
+        let output = JsonOutput {
+            report: BatchReport {
+                frames: 600,
+                cycle_speed: 600.0,
+                ipf: None,
+                keywait_timeout: Some(2.5),
+                seed: 7,
+                results: vec![after.clone(), result("tank.ch8", RunStatus::WaitingForKey, None)],
+            },
+            stats: RunStats::new().report(),
+            comparison: Some(Comparison {
+                changed: vec![RomChange { before, after }],
+                new: vec![String::from("tank.ch8")],
+                missing: Vec::new(),
+            }),
+        };
+        let json = serde_json::to_string_pretty(&output).unwrap();
+        assert_eq!(serde_json::from_str::<JsonOutput>(&json).unwrap(), output);
+    }
+
+    #[test]
+    fn a_panic_is_caught_as_an_error() {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+        let outcome = isolated(|| -> Result<(), LoadError> { panic!("sprite at {:03X}", 0xFFE) });
+        panic::set_hook(default_hook);
+        assert_eq!(outcome, Err(String::from("sprite at FFE")));
+        assert_eq!(isolated(|| Ok(5)), Ok(5));
+    }
+
+    fn args(dir: &Path, output: &Path, compare: Option<&Path>) -> BatchArgs {
+        BatchArgs {
+            dir: dir.to_path_buf(),
+            frames: 120,
+            cycle_speed: 600.0,
+            ipf: None,
+            keywait_timeout: None,
+            seed: 0,
+            output: output.to_string_lossy().to_string(),
+            compare: compare.map(|path| path.to_string_lossy().to_string()),
+        }
+    }
+
+    fn read_report(path: &Path) -> BatchReport {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    // two fixture ROMs and one too big to load, which is recorded and the batch goes on.
+    // then again against the first report with the ROMs' hashes changed in it
+    #[test]
+    fn the_batch_runs_every_rom_and_compares_against_the_last_report() {
+        let dir = std::env::temp_dir().join(format!("chip8rust-batch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let roms = dir.join("roms");
+        fs::create_dir_all(&roms).unwrap();
+        for name in ["ibm", "alu"] {
+            fs::write(roms.join(format!("{}.ch8", name)), crate::fixtures::find(name).unwrap().rom)
+                .unwrap();
+        }
+        fs::write(roms.join("big.ch8"), vec![0; 0x1000]).unwrap();
+        let first = dir.join("first.json");
+        assert!(run(args(&roms, &first, None), true));
+
+        let report = read_report(&first);
+        let names: Vec<&str> = report.results.iter().map(|result| result.rom.as_str()).collect();
+        assert_eq!(names, ["alu.ch8", "big.ch8", "ibm.ch8"]);
+        let statuses: Vec<&RunStatus> = report.results.iter().map(|rom| &rom.status).collect();
+        assert_eq!(statuses, [&RunStatus::Halted, &RunStatus::Crashed, &RunStatus::Halted]);
+        assert!(report.results[1].error.is_some());
+
+        let second = dir.join("second.json");
+        assert!(run(args(&roms, &second, Some(&first)), true)); // the same run, the same hashes
+        let comparison = compare(&report, &first.to_string_lossy()).unwrap();
+        assert_eq!(comparison, Comparison { changed: vec![], new: vec![], missing: vec![] });
+
+        // alu's display and ibm's state
+        let mut previous = report;
+        previous.results[0].display_hash = Some(String::from("0000000000000000"));
+        previous.results[2].state_hash = Some(String::from("0000000000000000"));
+        fs::write(&first, serde_json::to_string(&previous).unwrap()).unwrap();
+        assert!(!run(args(&roms, &second, Some(&first)), true));
+        let report = read_report(&second);
+        let comparison = compare(&report, &first.to_string_lossy()).unwrap();
+        let changed: Vec<&str> =
+            comparison.changed.iter().map(|change| change.after.rom.as_str()).collect();
+        assert_eq!(changed, ["alu.ch8", "ibm.ch8"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,174 @@
+#[cfg(feature = "frontend")]
+use std::collections::HashMap;
+use std::collections::VecDeque;
+
+#[cfg(feature = "frontend")]
+use olc_pge as olc;
+
+use crate::Emulator;
+
+// the calls and returns kept, the oldest going first
+const MAX_EVENTS: usize = 4096;
+// the timeline: a row per stack level under the title, as wide as the window allows
+#[cfg(feature = "frontend")]
+const TOP: i32 = 20;
+#[cfg(feature = "frontend")]
+const LEVEL_HEIGHT: i32 = 9;
+#[cfg(feature = "frontend")]
+const MAX_LEVELS: usize = 16;
+#[cfg(feature = "frontend")]
+const WIDTH: u64 = 368;
+// bars are colored by the subroutine, so the same one looks the same at every level
+#[cfg(feature = "frontend")]
+const BAR_COLORS: [olc::Pixel; 6] =
+    [olc::CYAN, olc::GREEN, olc::YELLOW, olc::MAGENTA, olc::RED, olc::DARK_CYAN];
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum CallKind {
+    Call,
+    Return,
+}
+
+// a CALL, or a RET that had somewhere to go, on the cycle it ran (stats.instructions)
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct CallEvent {
+    pub kind: CallKind,
+    pub cycle: u64,
+    pub subroutine: u16, // the one called, or returned from
+    pub depth: usize,    // the call stack's depth after it
+}
+
+pub struct CallLog {
+    events: VecDeque<CallEvent>,
+    entered: Vec<u16>, // the subroutines on the call stack, for the returns to name
+}
+impl CallLog {
+    pub fn new() -> CallLog {
+        CallLog {
+            events: VecDeque::new(),
+            entered: Vec::new(),
+        }
+    }
+    fn push(&mut self, event: CallEvent) {
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+}
+
+// a subroutine's time on the stack
+#[cfg(feature = "frontend")]
+struct Bar {
+    start: u64,
+    end: u64,
+    level: usize,
+    subroutine: u16,
+}
+
+// pairs calls up with their returns. a return whose call has dropped out of the log starts
+// at `since`, and a call that hasn't returned yet runs to `now`
+#[cfg(feature = "frontend")]
+fn bars<'a>(events: impl Iterator<Item = &'a CallEvent>, since: u64, now: u64) -> Vec<Bar> {
+    let mut open: Vec<Option<(u64, u16)>> = Vec::new(); // each level's call, if it's logged
+    let mut bars = Vec::new();
+    for event in events {
+        match event.kind {
+            CallKind::Call => {
+                open.resize(event.depth - 1, None);
+                open.push(Some((event.cycle, event.subroutine)));
+            }
+            CallKind::Return => {
+                let level = event.depth;
+                let start = open.get(level).copied().flatten().map_or(since, |(start, _)| start);
+                open.truncate(level);
+                bars.push(Bar {
+                    start,
+                    end: event.cycle,
+                    level,
+                    subroutine: event.subroutine,
+                });
+            }
+        }
+    }
+    for (level, call) in open.into_iter().enumerate() {
+        if let Some((start, subroutine)) = call {
+            bars.push(Bar { start, end: now, level, subroutine });
+        }
+    }
+    bars
+}
+
+impl Emulator {
+    // the calls and returns recorded so far, oldest first
+    pub fn call_events(&self) -> impl Iterator<Item = &CallEvent> {
+        self.call_log.events.iter()
+    }
+    // from CALL, after the push
+    pub fn log_call(&mut self, subroutine: u16) {
+        self.call_log.entered.push(subroutine);
+        let event = CallEvent {
+            kind: CallKind::Call,
+            cycle: self.stats.instructions,
+            subroutine,
+            depth: self.call_stack.len(),
+        };
+        self.call_log.push(event);
+    }
+    // from RET, after the pop
+    pub fn log_return(&mut self) {
+        let subroutine = self.call_log.entered.pop().unwrap_or(0);
+        let event = CallEvent {
+            kind: CallKind::Return,
+            cycle: self.stats.instructions,
+            subroutine,
+            depth: self.call_stack.len(),
+        };
+        self.call_log.push(event);
+    }
+}
+
+// every subroutine in the log as a bar from its CALL to its RET, nested by depth, with the
+// ones the most cycles went to under it
+#[cfg(feature = "frontend")]
+pub fn render(emulator: &Emulator, pge: &mut olc::PixelGameEngine) {
+    let now = emulator.stats.instructions;
+    let since = emulator.call_events().next().map_or(now, |event| event.cycle);
+    let per_pixel = (now - since).div_ceil(WIDTH).max(1);
+    let x_at = |cycle: u64| 8 + ((cycle - since) / per_pixel) as i32;
+    let title = format!("CALLS OVER {} CYCLES, {} A PIXEL", now - since, per_pixel);
+    pge.draw_string(8, 8, &title, olc::WHITE);
+
+    let bars = bars(emulator.call_events(), since, now);
+    let mut totals: HashMap<u16, u64> = HashMap::new();
+    for bar in &bars {
+        *totals.entry(bar.subroutine).or_insert(0) += bar.end - bar.start;
+        if bar.level >= MAX_LEVELS {
+            continue;
+        }
+        let (x, y) = (x_at(bar.start), TOP + bar.level as i32 * LEVEL_HEIGHT);
+        let width = (x_at(bar.end) - x).max(1) as u32;
+        let color = BAR_COLORS[(bar.subroutine as usize >> 1) % BAR_COLORS.len()];
+        pge.fill_rect(x, y, width, LEVEL_HEIGHT as u32 - 1, color);
+        if width >= 26 {
+            pge.draw_string(x + 1, y, &format!("{:03X}", bar.subroutine), olc::BLACK);
+        }
+    }
+
+    let y = TOP + MAX_LEVELS as i32 * LEVEL_HEIGHT + 4;
+    if bars.is_empty() {
+        pge.draw_string(8, y, &String::from("no calls yet"), olc::GREY);
+    } else {
+        // inclusive, so a subroutine's callees count towards it too
+        let mut totals: Vec<(u16, u64)> = totals.into_iter().collect();
+        totals.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let span = (now - since).max(1);
+        let most: Vec<String> = totals
+            .iter()
+            .take(4)
+            .map(|(subroutine, cycles)| format!("{:03X} {}%", subroutine, cycles * 100 / span))
+            .collect();
+        pge.draw_string(8, y, &format!("MOST TIME: {}", most.join("  ")), olc::GREY);
+    }
+    pge.draw_string(8, y + 10, &String::from("esc: close"), olc::GREY);
+}
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+use crate::pause_at::PauseAt;
+use crate::Emulator;
+
+// --guard-pc: what to do about a write landing just ahead of the PC, which is legal but
+// much more often FX55 or FX33 with I pointing somewhere it shouldn't
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+pub enum GuardPc {
+    Log,   // once per writing instruction
+    Pause, // drop into step mode every time
+}
+
+pub struct CodeGuard {
+    mode: GuardPc,
+    bytes: u16, // how far past the writing instruction counts as ahead of the PC
+    reported: HashSet<u16>,
+}
+impl CodeGuard {
+    pub fn new(mode: GuardPc, bytes: u16) -> CodeGuard {
+        CodeGuard {
+            mode,
+            bytes,
+            reported: HashSet::new(),
+        }
+    }
+}
+
+impl Emulator {
+    // a write by the program, from an instruction that's already moved the PC past itself
+    pub fn store(&mut self, addr: u16, val: u8) {
+        let old = self.ram.get(addr & 0xFFF);
+        if let Some(written) = self.ram.store(addr, val) {
+            self.log_write(written, old, val);
+        }
+        let Some(guard) = &mut self.code_guard else {
+            return;
+        };
+        let (by, next) = (self.program_counter.wrapping_sub(2), self.program_counter);
+        if addr < next || addr >= next.saturating_add(guard.bytes) {
+            return;
+        }
+        let first = guard.reported.insert(by);
+        let pause = guard.mode == GuardPc::Pause;
+        if first || pause {
+            log::warn!("{:#05X} wrote over the code just ahead of it, at {:#05X}", by, addr);
+        }
+        if pause {
+            self.paused_at = Some(PauseAt::CodeWrite { by, addr });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // I = `addr`, then V0 stored there by the FX55 at 0x202, and round again
+    fn storing_to(addr: u16, mode: GuardPc) -> Emulator {
+        let [high, low] = (0xA000 | addr).to_be_bytes();
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[high, low, 0xF0, 0x55, 0x12, 0x00]).unwrap();
+        emulator.code_guard = Some(CodeGuard::new(mode, 4));
+        emulator
+    }
+
+    #[test]
+    fn a_write_over_the_next_instruction_pauses() {
+        let mut emulator = storing_to(0x204, GuardPc::Pause);
+        emulator.run_cycles(2);
+        assert_eq!(emulator.paused_at, Some(PauseAt::CodeWrite { by: 0x202, addr: 0x204 }));
+    }
+
+    #[test]
+    fn only_the_bytes_just_ahead_count() {
+        for addr in [0x202, 0x208, 0x300, 0xE00] {
+            let mut emulator = storing_to(addr, GuardPc::Pause);
+            emulator.run_cycles(2);
+            assert_eq!(emulator.paused_at, None, "a write to {:#05X}", addr);
+        }
+    }
+
+    #[test]
+    fn logging_reports_each_writer_once_without_pausing() {
+        let mut emulator = storing_to(0x206, GuardPc::Log);
+        emulator.run_cycles(9);
+        assert_eq!(emulator.paused_at, None);
+        let guard = emulator.code_guard.unwrap();
+        assert_eq!(guard.reported, HashSet::from([0x202]));
+    }
+}
@@ -0,0 +1,227 @@
+use olc_pge as olc;
+
+use crate::frame_driver::FrameDriver;
+use crate::run_state::RunState;
+use crate::window::{DRAW_BIGGER_PIXELS, KEYS};
+use crate::{Emulator, SCR_H, SCR_W};
+
+// the gap between the two displays, and the text rows under them
+const GAP: i32 = 4;
+const TEXT_HEIGHT: i32 = 20;
+
+// --compare-rom: two machines with the same settings, seed and keys, a frame at a time in
+// lockstep, side by side. the first frame their displays differ on stops them both there
+struct Compare {
+    machines: [Emulator; 2],
+    cycles_per_frame: u32,
+    driver: FrameDriver,
+    frame: u64,
+    diverged: Option<u64>, // the first frame the display hashes didn't match
+    paused: bool,
+}
+
+// room for `rows` of display, the taller of the two when a hires ROM is in it
+fn window_size(rows: usize) -> (usize, usize) {
+    let width = 2 * SCR_W as i32 * DRAW_BIGGER_PIXELS + GAP;
+    let height = rows as i32 * DRAW_BIGGER_PIXELS + TEXT_HEIGHT;
+    (width as usize, height as usize)
+}
+
+pub fn run(left: Emulator, right: Emulator, cycle_speed: f32, seed: u64) {
+    let compare = Compare::new(left, right, cycle_speed, seed);
+    let rows = compare.rows();
+    let (width, height) = window_size(rows);
+    olc::PixelGameEngine::construct(compare, width, height, 2, 2).start();
+}
+
+impl Compare {
+    // `right` only brings its ROM, everything that changes how it runs comes from `left`
+    fn new(left: Emulator, mut right: Emulator, cycle_speed: f32, seed: u64) -> Compare {
+        right.ipf = left.ipf;
+        right.keywait_timeout = left.keywait_timeout;
+        right.font_style = left.font_style;
+        right.reserve_vip_areas = left.reserve_vip_areas;
+        right.wrap_x = left.wrap_x;
+        right.wrap_y = left.wrap_y;
+        right.jump_x = left.jump_x;
+        right.display_wait = left.display_wait;
+        right.shift_vy = left.shift_vy;
+        right.call_stack = left.call_stack.clone();
+        right.platform = left.platform;
+        right.available_keys = left.available_keys;
+        right.ram.policy = left.ram.policy;
+        right.stats.memory = left.stats.memory;
+        right.hard_reset(); // the font, and a hires ROM's start address
+        let mut machines = [left, right];
+        for machine in machines.iter_mut() {
+            machine.seed_rng(seed);
+        }
+        let cycles_per_frame = machines[0]
+            .ipf
+            .unwrap_or_else(|| Emulator::cycles_per_frame(cycle_speed));
+        log::info!(
+            "Comparing {} and {} at {} cycles per frame, seed {}",
+            machines[0].rom_name,
+            machines[1].rom_name,
+            cycles_per_frame,
+            seed
+        );
+        let driver = machines[0].driver.in_frames();
+        Compare {
+            machines,
+            cycles_per_frame,
+            driver,
+            frame: 0,
+            diverged: None,
+            paused: false,
+        }
+    }
+    fn rows(&self) -> usize {
+        self.machines.iter().map(|machine| machine.screen_height()).max().unwrap_or(SCR_H)
+    }
+    // one 60Hz frame on both, with a check of their displays after
+    fn step(&mut self) {
+        let [left, right] = &mut self.machines;
+        let paused_at = left.step_frame(self.cycles_per_frame).paused_at;
+        right.step_frame(self.cycles_per_frame);
+        self.frame += 1;
+        if let Some(trigger) = paused_at {
+            log::info!("Paused at {} on frame {}", trigger, self.frame);
+            self.paused = true;
+        }
+        let [left, right] = &self.machines;
+        if self.diverged.is_none() && left.display_hash() != right.display_hash() {
+            let first = left.state_eq(right).err().map_or(String::new(), |component| {
+                format!(", the {} being the first part of the state that differs", component)
+            });
+            log::info!("The displays diverged on frame {}{}", self.frame, first);
+            self.diverged = Some(self.frame);
+            self.paused = true;
+        }
+    }
+    fn draw(&mut self, pge: &mut olc::PixelGameEngine) {
+        let palette = &self.machines[0].palettes[self.machines[0].palette];
+        let (on, off): (olc::Pixel, olc::Pixel) = (palette.on().into(), palette.off().into());
+        let [left, right] = &self.machines;
+        let rows = self.rows();
+        pge.clear(olc::BLACK);
+        for (i, machine) in self.machines.iter().enumerate() {
+            let left_x = i as i32 * (SCR_W as i32 * DRAW_BIGGER_PIXELS + GAP);
+            for x in 0..SCR_W {
+                for y in 0..rows {
+                    let color = if left.display[x][y] != right.display[x][y] {
+                        olc::RED // where they differ
+                    } else if machine.display[x][y] {
+                        on
+                    } else {
+                        off
+                    };
+                    let size = DRAW_BIGGER_PIXELS as u32;
+                    let (px, py) = (x as i32 * DRAW_BIGGER_PIXELS, y as i32 * DRAW_BIGGER_PIXELS);
+                    pge.fill_rect(left_x + px, py, size, size, color);
+                }
+            }
+            let y = rows as i32 * DRAW_BIGGER_PIXELS + 2;
+            pge.draw_string(left_x + 2, y, &machine.rom_name, olc::GREY);
+        }
+        let y = rows as i32 * DRAW_BIGGER_PIXELS + 11;
+        let (status, color) = match self.diverged {
+            Some(frame) => (format!("DIVERGED ON FRAME {} (now {})", frame, self.frame), olc::RED),
+            None => (format!("FRAME {}, SAME SO FAR", self.frame), olc::WHITE),
+        };
+        let hint = if self.paused { "  space: run, tab: frame" } else { "  space: pause" };
+        pge.draw_string(2, y, &(status + hint), color);
+    }
+}
+
+impl olc::PGEApplication for Compare {
+    const APP_NAME: &'static str = "Chip8 Emulator - Rust (compare)";
+
+    fn on_user_update(&mut self, pge: &mut olc::PixelGameEngine, delta: f32) -> bool {
+        let hotkeys = &self.machines[0].hotkeys;
+        if pge.get_key(hotkeys.quit).pressed {
+            return false;
+        }
+        let space = pge.get_key(hotkeys.play_step).pressed;
+        let tab = pge.get_key(hotkeys.step).pressed;
+        // both machines get exactly the same keys
+        let down = KEYS.map(|key| pge.get_key(key).held);
+        let keys = self.machines[0].key_repeat.shape(down, delta, false);
+        for machine in self.machines.iter_mut() {
+            machine.keys = keys;
+        }
+        if space {
+            self.paused = !self.paused;
+        }
+        if self.paused {
+            if tab {
+                self.step();
+            }
+        } else {
+            // the pair only stop together, so one run state does for both
+            let due = self.driver.advance(delta, &RunState::Play, u32::MAX);
+            for _ in 0..due.frames {
+                self.step();
+                if self.paused {
+                    break;
+                }
+            }
+        }
+        self.draw(pge);
+        true
+    }
+
+    fn on_user_destroy(&mut self) -> bool {
+        self.machines[0].shutdown(); // the right one has no reports or saves of its own
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RunMode;
+
+    // draws a 0 once the delay timer set from V0's first value has run out
+    fn timed_draw(ticks: u8) -> Emulator {
+        let rom = [
+            0x60, ticks, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x04, 0xD1, 0x15, 0x12, 0x0C,
+        ];
+        let mut emulator = Emulator::new();
+        emulator.state = RunMode::Play.into();
+        emulator.load_rom_bytes(&rom).unwrap();
+        emulator
+    }
+
+    fn run_frames(compare: &mut Compare, frames: usize) {
+        for _ in 0..frames {
+            if compare.paused {
+                break;
+            }
+            compare.step();
+        }
+    }
+
+    #[test]
+    fn the_same_rom_never_diverges() {
+        let mut compare = Compare::new(timed_draw(3), timed_draw(3), 600.0, 1);
+        run_frames(&mut compare, 120);
+        assert_eq!((compare.frame, compare.diverged, compare.paused), (120, None, false));
+    }
+
+    // on the frame the one drawing first draws, and not a frame later
+    #[test]
+    fn a_divergence_stops_on_its_frame() {
+        let mut alone = timed_draw(3);
+        let blank = alone.display_hash();
+        let draws_on = (1..).find(|_| {
+            alone.step_frame(10);
+            alone.display_hash() != blank
+        });
+        let mut compare = Compare::new(timed_draw(3), timed_draw(20), 600.0, 1);
+        run_frames(&mut compare, 120);
+        assert_eq!(compare.diverged, draws_on);
+        assert_eq!(compare.frame, draws_on.unwrap()); // paused there
+        assert!(compare.paused);
+    }
+}
@@ -1,43 +1,175 @@
-use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use crate::regions::Regions;
+use crate::rom_source::LoadError;
 
 const RAM_SIZE: usize = 4096;
+
+// what the program gets reading past 0xFFF, usually I run off the end after FX1Es.
+// interpreters differ, and a ROM that does it by accident leans on whichever it was made on
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+pub enum OutOfRangeRead {
+    Wrap, // back around to 0x000
+    Zero,
+    Ff, // open bus
+}
+// and writing there
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+pub enum OutOfRangeWrite {
+    Wrap,
+    Ignore,
+}
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct RamPolicy {
+    pub read: OutOfRangeRead,
+    pub write: OutOfRangeWrite,
+}
+impl RamPolicy {
+    pub fn new() -> RamPolicy {
+        RamPolicy {
+            read: OutOfRangeRead::Wrap,
+            write: OutOfRangeWrite::Wrap,
+        }
+    }
+}
+impl fmt::Display for RamPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let read = match self.read {
+            OutOfRangeRead::Wrap => "wrap",
+            OutOfRangeRead::Zero => "get 0",
+            OutOfRangeRead::Ff => "get 0xFF",
+        };
+        let write = match self.write {
+            OutOfRangeWrite::Wrap => "wrap",
+            OutOfRangeWrite::Ignore => "are dropped",
+        };
+        write!(f, "reads past 0xFFF {}, writes {}", read, write)
+    }
+}
+
 pub struct Ram {
     space: [u8; RAM_SIZE],
+    exec_counts: [u32; RAM_SIZE], // how many times each byte was fetched as part of an instruction
+    data_reads: [bool; RAM_SIZE], // whether each byte was ever read as data (sprites, FX65)
+    writes: [bool; RAM_SIZE],     // whether each byte was written since take_writes last looked
+    stores: [bool; RAM_SIZE],     // whether each byte was ever stored to by the program
+    pub policy: RamPolicy,
 }
 impl Ram {
     pub fn new() -> Ram {
         Ram {
             space: [0; RAM_SIZE],
+            exec_counts: [0; RAM_SIZE],
+            data_reads: [false; RAM_SIZE],
+            writes: [false; RAM_SIZE],
+            stores: [false; RAM_SIZE],
+            policy: RamPolicy::new(),
         }
     }
-    pub fn get(&mut self, addr: u16) -> u8 {
+    // all 4KB, without counting as reads
+    pub fn as_slice(&self) -> &[u8] {
+        &self.space
+    }
+    pub fn get(&self, addr: u16) -> u8 {
+        self.space[addr as usize]
+    }
+    // instruction fetch, counted for the execution heatmap
+    pub fn fetch(&mut self, addr: u16) -> u8 {
+        let count = &mut self.exec_counts[addr as usize];
+        *count = count.saturating_add(1);
         self.space[addr as usize]
     }
+    // data read, marked in the data-read bitmap. past the end it goes by the policy
+    pub fn read(&mut self, addr: u16) -> u8 {
+        if addr as usize >= RAM_SIZE {
+            match self.policy.read {
+                OutOfRangeRead::Wrap => {}
+                OutOfRangeRead::Zero => return 0,
+                OutOfRangeRead::Ff => return 0xFF,
+            }
+        }
+        let addr = addr as usize % RAM_SIZE;
+        self.data_reads[addr] = true;
+        self.space[addr]
+    }
+    // big-endian words. the address wraps at 4KB, so a word at 0xFFF takes its low byte
+    // from 0x000
+    pub fn get_u16(&self, addr: u16) -> u16 {
+        u16::from_be_bytes([self.get(addr & 0xFFF), self.get(addr.wrapping_add(1) & 0xFFF)])
+    }
+    pub fn set_u16(&mut self, addr: u16, val: u16) {
+        let [msb, lsb] = val.to_be_bytes();
+        self.set(addr & 0xFFF, msb);
+        self.set(addr.wrapping_add(1) & 0xFFF, lsb);
+    }
+    pub fn fetch_u16(&mut self, addr: u16) -> u16 {
+        u16::from_be_bytes([self.fetch(addr & 0xFFF), self.fetch(addr.wrapping_add(1) & 0xFFF)])
+    }
+    pub fn exec_count(&self, addr: u16) -> u32 {
+        self.exec_counts[addr as usize]
+    }
+    pub fn was_read(&self, addr: u16) -> bool {
+        self.data_reads[addr as usize]
+    }
+    pub fn was_written(&self, addr: u16) -> bool {
+        self.stores[addr as usize]
+    }
     pub fn set(&mut self, addr: u16, val: u8) {
         self.space[addr as usize] = val;
+        self.writes[addr as usize] = true;
+    }
+    // a write by the program, marked in the store bitmap. past the end it goes by the policy,
+    // and where it went comes back, if anywhere
+    pub fn store(&mut self, addr: u16, val: u8) -> Option<u16> {
+        if addr as usize >= RAM_SIZE && self.policy.write == OutOfRangeWrite::Ignore {
+            return None;
+        }
+        let addr = addr & 0xFFF;
+        self.stores[addr as usize] = true;
+        self.set(addr, val);
+        Some(addr)
     }
-    pub fn load_from_rom(&mut self, loc: u16, file: PathBuf) {
-        let mut rom_file = File::open(file).expect("Failed to open ROM file.");
-        let mut buf = Vec::new();
-        rom_file
-            .read_to_end(&mut buf)
-            .expect("Failed to read ROM file.");
-        for i in 0..buf.len() as u16 {
-            self.set(i + loc, buf[i as usize]);
+    // whether anything in `range` was written since the last call, which clears it
+    pub fn take_writes(&mut self, range: RangeInclusive<u16>) -> bool {
+        let writes = &mut self.writes[*range.start() as usize..=*range.end() as usize];
+        let written = writes.contains(&true);
+        writes.fill(false);
+        written
+    }
+    // `data` at `loc`, or nothing written at all if it would run past 0xFFF
+    pub fn load_bytes(&mut self, loc: u16, data: &[u8]) -> Result<(), LoadError> {
+        let max = RAM_SIZE.saturating_sub(loc as usize);
+        if data.len() > max {
+            return Err(LoadError::TooBig {
+                size: data.len(),
+                max,
+            });
+        }
+        for (i, byte) in data.iter().enumerate() {
+            self.set(i as u16 + loc, *byte);
         }
+        Ok(())
     }
-    pub fn generate_dump(&mut self, start_loc: u16, end_loc: u16) -> String {
+    // 16 bytes a row, with the regions each row is in down the right
+    pub fn generate_dump(&self, start_loc: u16, end_loc: u16, regions: &Regions) -> String {
         let mut dump = String::new();
-        for i in start_loc..end_loc {
-            if i % 16 == 0 {
-                dump += format!("{:#5X} =>", i).as_str();
+        for row in (start_loc..end_loc).step_by(16) {
+            let row_end = (row + 16).min(end_loc);
+            dump += format!("{:03X} =>", row).as_str();
+            for i in row..row_end {
+                dump += format!(" {:02X}", self.get(i)).as_str();
             }
-            if i % 16 == 15 {
-                dump += "\n";
+            let names: Vec<&str> =
+                regions.overlapping(row..=row_end - 1).map(|region| region.name.as_str()).collect();
+            if !names.is_empty() {
+                // a short last row still lines its gutter up
+                dump += " ".repeat(3 * (16 - (row_end - row) as usize)).as_str();
+                dump += format!("  | {}", names.join(", ")).as_str();
             }
-            dump += format!(" {:#4X}", self.get(i)).as_str();
+            dump += "\n";
         }
         dump
     }
@@ -59,4 +191,271 @@ impl Registers {
     pub fn set(&mut self, addr: u8, val: u8) {
         self.space[addr as usize] = val;
     }
+    // V0 first
+    pub fn as_slice(&self) -> &[u8] {
+        &self.space
+    }
+}
+
+// how many return addresses the call stack holds unless --stack-depth says otherwise. the
+// VIP's held 12, most interpreters since take 16
+pub const CALL_STACK_SIZE: usize = 16;
+// the most --stack-depth can give it, for Octo ROMs that recurse deep
+pub const MAX_CALL_STACK_SIZE: usize = 256;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum StackError {
+    Overflow,
+    Underflow,
+}
+
+// a stack of at most `limit` items, room for N, that never allocates, and says so instead
+// of growing or panicking when it's full or empty. saved as its limit and items
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "SavedStack<T>", try_from = "SavedStack<T>")]
+#[serde(bound(
+    serialize = "T: Copy + Default + Serialize",
+    deserialize = "T: Copy + Default + Deserialize<'de>"
+))]
+pub struct ArrayStack<T, const N: usize> {
+    space: [T; N],
+    len: usize,
+    limit: usize,
+}
+impl<T: Copy + Default, const N: usize> ArrayStack<T, N> {
+    pub fn new() -> ArrayStack<T, N> {
+        ArrayStack::with_limit(N)
+    }
+    // full at `limit` items, or at N if that's more than there's room for
+    pub fn with_limit(limit: usize) -> ArrayStack<T, N> {
+        ArrayStack {
+            space: [T::default(); N],
+            len: 0,
+            limit: limit.min(N),
+        }
+    }
+    pub fn push(&mut self, val: T) -> Result<(), StackError> {
+        if self.len == self.limit {
+            return Err(StackError::Overflow);
+        }
+        self.space[self.len] = val;
+        self.len += 1;
+        Ok(())
+    }
+    pub fn pop(&mut self) -> Result<T, StackError> {
+        self.len = self.len.checked_sub(1).ok_or(StackError::Underflow)?;
+        Ok(self.space[self.len])
+    }
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    // bottom first, the order they were pushed in
+    pub fn as_slice(&self) -> &[T] {
+        &self.space[..self.len]
+    }
+    pub fn iter(&self) -> std::slice::Iter<'_, T> {
+        self.as_slice().iter()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedStack<T> {
+    limit: usize,
+    items: Vec<T>,
+}
+impl<T: Copy + Default, const N: usize> From<ArrayStack<T, N>> for SavedStack<T> {
+    fn from(stack: ArrayStack<T, N>) -> SavedStack<T> {
+        SavedStack {
+            limit: stack.limit,
+            items: stack.as_slice().to_vec(),
+        }
+    }
+}
+impl<T: Copy + Default, const N: usize> TryFrom<SavedStack<T>> for ArrayStack<T, N> {
+    type Error = String;
+    fn try_from(saved: SavedStack<T>) -> Result<ArrayStack<T, N>, String> {
+        if saved.limit > N {
+            return Err(format!("a stack of {}, more than the {} there's room for", saved.limit, N));
+        }
+        let mut stack = ArrayStack::with_limit(saved.limit);
+        for item in saved.items {
+            stack.push(item).map_err(|_| format!("more items than its limit of {}", saved.limit))?;
+        }
+        Ok(stack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regions::Region;
+
+    fn region(name: &str, range: RangeInclusive<u16>) -> Region {
+        Region {
+            name: String::from(name),
+            range,
+        }
+    }
+
+    #[test]
+    fn the_dump_names_each_rows_regions_in_the_gutter() {
+        let mut ram = Ram::new();
+        for addr in 0x000..0x034 {
+            ram.set(addr, addr as u8);
+        }
+        let regions = Regions::new(vec![
+            region("a", 0x000..=0x00F),
+            region("b", 0x008..=0x017),
+            region("c", 0x030..=0x033),
+        ]);
+        let expected = "\
+            000 => 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F  | a, b\n\
+            010 => 10 11 12 13 14 15 16 17 18 19 1A 1B 1C 1D 1E 1F  | b\n\
+            020 => 20 21 22 23 24 25 26 27 28 29 2A 2B 2C 2D 2E 2F\n\
+            030 => 30 31 32 33                                      | c\n";
+        assert_eq!(ram.generate_dump(0x000, 0x034, &regions), expected);
+    }
+
+    #[test]
+    fn the_stack_pops_what_was_pushed_last_first() {
+        let mut stack: ArrayStack<u16, 4> = ArrayStack::new();
+        assert!(stack.is_empty());
+        stack.push(0x202).unwrap();
+        stack.push(0x30A).unwrap();
+        assert_eq!(stack.len(), 2);
+        assert_eq!(stack.pop(), Ok(0x30A));
+        assert_eq!(stack.pop(), Ok(0x202));
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn a_full_stack_overflows_and_keeps_what_it_has() {
+        let mut stack: ArrayStack<u16, 2> = ArrayStack::new();
+        stack.push(1).unwrap();
+        stack.push(2).unwrap();
+        assert_eq!(stack.push(3), Err(StackError::Overflow));
+        assert_eq!(stack.as_slice(), [1, 2]);
+    }
+
+    #[test]
+    fn an_empty_stack_underflows() {
+        let mut stack: ArrayStack<u16, 2> = ArrayStack::new();
+        assert_eq!(stack.pop(), Err(StackError::Underflow));
+        stack.push(1).unwrap();
+        stack.pop().unwrap();
+        assert_eq!(stack.pop(), Err(StackError::Underflow));
+        assert_eq!(stack.len(), 0);
+    }
+
+    #[test]
+    fn the_stack_iterates_bottom_first() {
+        let mut stack: ArrayStack<u16, 4> = ArrayStack::new();
+        for addr in [0x200, 0x210, 0x220] {
+            stack.push(addr).unwrap();
+        }
+        stack.pop().unwrap();
+        assert_eq!(stack.iter().copied().collect::<Vec<u16>>(), [0x200, 0x210]);
+    }
+
+    #[test]
+    fn a_limit_fills_the_stack_before_its_room_does() {
+        let mut stack: ArrayStack<u16, 8> = ArrayStack::with_limit(2);
+        stack.push(1).unwrap();
+        stack.push(2).unwrap();
+        assert_eq!(stack.push(3), Err(StackError::Overflow));
+        stack.clear();
+        assert_eq!(stack.limit(), 2); // a reset keeps it
+        assert_eq!(ArrayStack::<u16, 8>::with_limit(100).limit(), 8);
+    }
+
+    #[test]
+    fn the_stack_reads_back_from_json() {
+        let mut stack: ArrayStack<u16, 8> = ArrayStack::with_limit(3);
+        stack.push(0x204).unwrap();
+        stack.push(0x2F0).unwrap();
+        let json = serde_json::to_string(&stack).unwrap();
+        assert_eq!(json, r#"{"limit":3,"items":[516,752]}"#);
+        let back: ArrayStack<u16, 8> = serde_json::from_str(&json).unwrap();
+        assert_eq!((back.limit(), back.as_slice()), (3, stack.as_slice()));
+
+        let too_many = r#"{"limit":1,"items":[1,2]}"#;
+        assert!(serde_json::from_str::<ArrayStack<u16, 8>>(too_many).is_err());
+        let too_deep = r#"{"limit":9,"items":[]}"#;
+        assert!(serde_json::from_str::<ArrayStack<u16, 8>>(too_deep).is_err());
+    }
+
+    fn ram_with(read: OutOfRangeRead, write: OutOfRangeWrite) -> Ram {
+        let mut ram = Ram::new();
+        ram.policy = RamPolicy { read, write };
+        ram.set(0x000, 0x12);
+        ram.set(0xFFF, 0x34);
+        ram
+    }
+
+    #[test]
+    fn reads_past_0xfff_go_by_the_policy() {
+        for (read, expected) in [
+            (OutOfRangeRead::Wrap, [0x12, 0x34]),
+            (OutOfRangeRead::Zero, [0x00, 0x00]),
+            (OutOfRangeRead::Ff, [0xFF, 0xFF]),
+        ] {
+            let mut ram = ram_with(read, OutOfRangeWrite::Wrap);
+            assert_eq!([ram.read(0x1000), ram.read(0xFFFF)], expected, "{:?}", read);
+            assert_eq!(ram.read(0xFFF), 0x34); // inside, the policy doesn't come into it
+        }
+        // only a wrapped read lands on a byte to mark
+        let mut ram = ram_with(OutOfRangeRead::Zero, OutOfRangeWrite::Wrap);
+        ram.read(0x1000);
+        assert!(!ram.was_read(0x000));
+        let mut ram = ram_with(OutOfRangeRead::Wrap, OutOfRangeWrite::Wrap);
+        ram.read(0x1000);
+        assert!(ram.was_read(0x000));
+    }
+
+    #[test]
+    fn stores_past_0xfff_go_by_the_policy() {
+        let mut ram = ram_with(OutOfRangeRead::Wrap, OutOfRangeWrite::Wrap);
+        assert_eq!(ram.store(0x1000, 0xAA), Some(0x000));
+        assert_eq!(ram.store(0xFFFF, 0xBB), Some(0xFFF));
+        assert_eq!([ram.get(0x000), ram.get(0xFFF)], [0xAA, 0xBB]);
+        assert!(ram.was_written(0x000) && ram.was_written(0xFFF));
+
+        let mut ram = ram_with(OutOfRangeRead::Wrap, OutOfRangeWrite::Ignore);
+        assert_eq!(ram.store(0x1000, 0xAA), None);
+        assert_eq!(ram.store(0xFFFF, 0xBB), None);
+        assert_eq!([ram.get(0x000), ram.get(0xFFF)], [0x12, 0x34]);
+        assert!(!ram.was_written(0x000) && !ram.was_written(0xFFF));
+        assert_eq!(ram.store(0xFFF, 0xCC), Some(0xFFF)); // inside, it's stored either way
+    }
+
+    #[test]
+    fn words_are_big_endian() {
+        let mut ram = Ram::new();
+        ram.set_u16(0x200, 0x1234);
+        assert_eq!([ram.get(0x200), ram.get(0x201)], [0x12, 0x34]);
+        assert_eq!(ram.get_u16(0x200), 0x1234);
+        assert_eq!(ram.fetch_u16(0x200), 0x1234);
+        assert_eq!((ram.exec_count(0x200), ram.exec_count(0x201)), (1, 1));
+    }
+
+    #[test]
+    fn a_word_at_0xfff_wraps_to_0x000() {
+        let mut ram = Ram::new();
+        ram.set_u16(0xFFF, 0xABCD);
+        assert_eq!([ram.get(0xFFF), ram.get(0x000)], [0xAB, 0xCD]);
+        assert_eq!(ram.get_u16(0xFFF), 0xABCD);
+        assert_eq!(ram.fetch_u16(0xFFF), 0xABCD);
+        assert_eq!(ram.exec_count(0x000), 1);
+        // and one past the end starts over at 0x000
+        assert_eq!(ram.get_u16(0x1000), 0xCD00);
+    }
 }
@@ -0,0 +1,140 @@
+use std::fmt;
+use std::fs;
+use std::io;
+
+use crate::components::Ram;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum ByteUse {
+    Untouched,
+    Fetched,
+    Read,
+    Both,
+}
+
+// a snapshot of which ROM bytes have been executed / read as data so far
+pub struct Coverage {
+    start: u16,
+    bytes: Vec<(u8, ByteUse)>,
+}
+impl Coverage {
+    pub fn measure(ram: &mut Ram, start: u16, len: usize) -> Coverage {
+        let mut bytes = Vec::with_capacity(len);
+        for addr in start..start + len as u16 {
+            let fetched = ram.exec_count(addr) > 0;
+            let read = ram.was_read(addr);
+            let usage = match (fetched, read) {
+                (false, false) => ByteUse::Untouched,
+                (true, false) => ByteUse::Fetched,
+                (false, true) => ByteUse::Read,
+                (true, true) => ByteUse::Both,
+            };
+            bytes.push((ram.get(addr), usage));
+        }
+        Coverage { start, bytes }
+    }
+    fn count(&self, pred: impl Fn(ByteUse) -> bool) -> usize {
+        self.bytes.iter().filter(|(_, usage)| pred(*usage)).count()
+    }
+    fn percent(&self, count: usize) -> f32 {
+        if self.bytes.is_empty() {
+            0.0
+        } else {
+            count as f32 * 100.0 / self.bytes.len() as f32
+        }
+    }
+    pub fn fetched(&self) -> usize {
+        self.count(|u| u == ByteUse::Fetched || u == ByteUse::Both)
+    }
+    pub fn read(&self) -> usize {
+        self.count(|u| u == ByteUse::Read || u == ByteUse::Both)
+    }
+    pub fn untouched(&self) -> usize {
+        self.count(|u| u == ByteUse::Untouched)
+    }
+    // hex dump of the ROM with a second column marking how each byte was used:
+    // X = executed, D = read as data, B = both, . = never touched
+    pub fn hex_map(&self) -> String {
+        let mut map = String::from("; X = executed, D = read as data, B = both, . = never touched\n");
+        for (row, chunk) in self.bytes.chunks(16).enumerate() {
+            let addr = self.start as usize + row * 16;
+            let mut hex = String::new();
+            let mut marks = String::new();
+            for (byte, usage) in chunk {
+                hex += format!(" {:02X}", byte).as_str();
+                marks.push(match usage {
+                    ByteUse::Untouched => '.',
+                    ByteUse::Fetched => 'X',
+                    ByteUse::Read => 'D',
+                    ByteUse::Both => 'B',
+                });
+            }
+            map += format!("{:03X} =>{:<48} | {}\n", addr, hex, marks).as_str();
+        }
+        map
+    }
+    pub fn write_map(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.hex_map())
+    }
+}
+
+impl fmt::Display for Coverage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "ROM coverage ({} bytes):", self.bytes.len())?;
+        writeln!(
+            f,
+            "  executed:  {:5} ({:.1}%)",
+            self.fetched(),
+            self.percent(self.fetched())
+        )?;
+        writeln!(
+            f,
+            "  data:      {:5} ({:.1}%)",
+            self.read(),
+            self.percent(self.read())
+        )?;
+        write!(
+            f,
+            "  untouched: {:5} ({:.1}%)",
+            self.untouched(),
+            self.percent(self.untouched())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Emulator;
+
+    // I = 0x204, a 3-row sprite from there (the jump and the byte after it), a jump to
+    // itself, and 4 bytes nothing gets to
+    const ROM: [u8; 10] = [0xA2, 0x04, 0xD0, 0x03, 0x12, 0x04, 0x00, 0xE0, 0x00, 0xE0];
+
+    fn coverage() -> Coverage {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&ROM).unwrap();
+        emulator.run_cycles(10);
+        Coverage::measure(&mut emulator.ram, 0x200, ROM.len())
+    }
+
+    #[test]
+    fn each_byte_is_classified() {
+        let coverage = coverage();
+        assert_eq!((coverage.fetched(), coverage.read(), coverage.untouched()), (6, 3, 3));
+        let expected = "\
+            ; X = executed, D = read as data, B = both, . = never touched\n\
+            200 => A2 04 D0 03 12 04 00 E0 00 E0                   | XXXXBBD...\n";
+        assert_eq!(coverage.hex_map(), expected);
+    }
+
+    #[test]
+    fn the_report_gives_percentages() {
+        let expected = "\
+            ROM coverage (10 bytes):\n  \
+            executed:      6 (60.0%)\n  \
+            data:          3 (30.0%)\n  \
+            untouched:     3 (30.0%)";
+        assert_eq!(coverage().to_string(), expected);
+    }
+}
@@ -0,0 +1,209 @@
+use std::cell::{Cell, RefCell};
+use std::fs;
+use std::io;
+use std::panic;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::components::CALL_STACK_SIZE;
+use crate::instructions::{hex_addr, mnemonic, step_line};
+use crate::Emulator;
+
+// where post-mortems are written, under the working directory
+const CRASH_DIR: &str = "crashdumps";
+// the instructions kept for the .trace, and how many of the newest go to stderr
+const HISTORY_SIZE: usize = 256;
+const HISTORY_SHOWN: usize = 10;
+
+// the last HISTORY_SIZE instructions run, as their address and word. a fixed ring, so
+// keeping it costs a store a cycle
+#[derive(Clone)]
+pub struct History {
+    ring: [(u16, u16); HISTORY_SIZE],
+    next: usize,
+    len: usize,
+}
+impl History {
+    pub fn new() -> History {
+        History {
+            ring: [(0, 0); HISTORY_SIZE],
+            next: 0,
+            len: 0,
+        }
+    }
+    pub fn push(&mut self, addr: u16, word: u16) {
+        self.ring[self.next] = (addr, word);
+        self.next = (self.next + 1) % HISTORY_SIZE;
+        self.len = (self.len + 1).min(HISTORY_SIZE);
+    }
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+    // oldest first
+    fn to_vec(&self) -> Vec<(u16, u16)> {
+        let start = (self.next + HISTORY_SIZE - self.len) % HISTORY_SIZE;
+        (0..self.len).map(|i| self.ring[(start + i) % HISTORY_SIZE]).collect()
+    }
+}
+
+// the machine as of the last frame, what a panic has to go on. there's one, written over
+// in place every frame, so keeping it doesn't allocate
+pub struct PostMortem {
+    rom_name: String,
+    cycle: u64,
+    program_counter: u16,
+    stack_pointer: u16,
+    timer: u8,
+    sound_timer: u8,
+    registers: [u8; 0x10],
+    call_stack: Vec<u16>,
+    ram: Vec<u8>,
+    history: History,
+    jump_x: bool, // for decoding the trace the way it ran
+    hires: bool,
+}
+impl PostMortem {
+    fn new() -> PostMortem {
+        PostMortem {
+            rom_name: String::new(),
+            cycle: 0,
+            program_counter: 0,
+            stack_pointer: 0,
+            timer: 0,
+            sound_timer: 0,
+            registers: [0; 0x10],
+            call_stack: Vec::with_capacity(CALL_STACK_SIZE),
+            ram: vec![0; 0x1000],
+            history: History::new(),
+            jump_x: false,
+            hires: false,
+        }
+    }
+    fn state_text(&self) -> String {
+        let mut text = format!("rom: {}\ncycle: {}\n", self.rom_name, self.cycle);
+        text += format!(
+            "PC:{:03X} I:{:03X} DT:{:02X} ST:{:02X}\n",
+            self.program_counter, self.stack_pointer, self.timer, self.sound_timer
+        )
+        .as_str();
+        let registers: Vec<String> = (0..0x10)
+            .map(|i| format!("R{:X}:{:02X}", i, self.registers[i]))
+            .collect();
+        text += format!("{}\n", registers.join(" ")).as_str();
+        let stack: String = self.call_stack.iter().map(|addr| format!(" {:03X}", addr)).collect();
+        text += format!("stack:{}\n\n", stack).as_str();
+        for (row, bytes) in self.ram.chunks(16).enumerate() {
+            let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            text += format!("{:03X}: {}\n", row * 16, bytes.join(" ")).as_str();
+        }
+        text
+    }
+    fn trace_lines(&self) -> Vec<String> {
+        self.history
+            .to_vec()
+            .iter()
+            .map(|(addr, word)| {
+                let mnemonic = mnemonic(*word, self.jump_x, self.hires, hex_addr);
+                step_line(*addr, *word, &mnemonic)
+            })
+            .collect()
+    }
+    // <rom>-<unix time>.state and .trace, returning the path without the extension
+    fn write(&self) -> io::Result<String> {
+        fs::create_dir_all(CRASH_DIR)?;
+        let name: String = self
+            .rom_name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+            .collect();
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        let path = format!("{}/{}-{}", CRASH_DIR, name, time);
+        fs::write(format!("{}.state", path), self.state_text())?;
+        fs::write(format!("{}.trace", path), self.trace_lines().join("\n") + "\n")?;
+        Ok(path)
+    }
+}
+
+thread_local! {
+    static ARMED: Cell<bool> = const { Cell::new(false) };
+    static LAST_STATE: RefCell<Option<PostMortem>> = const { RefCell::new(None) };
+}
+
+// for the emulator frontends (not batch, which catches its own): a panic writes the last
+// frame's state and trace to CRASH_DIR and shows where the program was, before the
+// default hook reports it
+pub fn install() {
+    ARMED.with(|armed| armed.set(true));
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        LAST_STATE.with(|state| {
+            let Ok(state) = state.try_borrow() else {
+                return; // it panicked taking the snapshot
+            };
+            let Some(state) = state.as_ref() else {
+                return;
+            };
+            match state.write() {
+                Ok(path) => eprintln!("Crash dump written to {}.state and .trace", path),
+                Err(e) => eprintln!("Failed to write a crash dump: {}", e),
+            }
+            eprintln!("Last instructions:");
+            let lines = state.trace_lines();
+            for line in &lines[lines.len().saturating_sub(HISTORY_SHOWN)..] {
+                eprintln!("  {}", line);
+            }
+        });
+        default_hook(info);
+    }));
+}
+
+impl Emulator {
+    // once a frame, from the timer tick, while the hook is installed
+    pub fn keep_post_mortem(&mut self) {
+        if !ARMED.with(|armed| armed.get()) {
+            return;
+        }
+        LAST_STATE.with(|last| {
+            let mut last = last.borrow_mut();
+            let state = last.get_or_insert_with(PostMortem::new);
+            state.rom_name.clone_from(&self.rom_name); // the same name, so no new buffer
+            state.cycle = self.stats.instructions;
+            state.program_counter = self.program_counter;
+            state.stack_pointer = self.stack_pointer;
+            state.timer = self.timer;
+            state.sound_timer = self.sound_timer;
+            state.registers.copy_from_slice(self.registers.as_slice());
+            state.call_stack.clear();
+            state.call_stack.extend_from_slice(self.call_stack.as_slice());
+            state.ram.copy_from_slice(self.ram.as_slice());
+            state.history.clone_from(&self.history);
+            state.jump_x = self.jump_x;
+            state.hires = self.hires;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_post_mortem_is_kept_in_place() {
+        ARMED.with(|armed| armed.set(true)); // without install's hook, which is process-wide
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[0x6A, 0x42, 0x12, 0x02]).unwrap(); // VA = 42, then spin
+        emulator.run_cycles(2);
+        emulator.keep_post_mortem();
+        let ram = LAST_STATE.with(|last| last.borrow().as_ref().unwrap().ram.as_ptr());
+        emulator.run_cycles(3);
+        emulator.keep_post_mortem();
+        LAST_STATE.with(|last| {
+            let last = last.borrow();
+            let state = last.as_ref().unwrap();
+            assert_eq!(state.ram.as_ptr(), ram); // the same buffer, written over
+            assert_eq!(state.cycle, 5);
+            assert!(state.state_text().contains("RA:42"));
+            assert_eq!(state.trace_lines().len(), 5);
+            assert_eq!(state.trace_lines()[0], step_line(0x200, 0x6A42, "IMM 42 => RA"));
+        });
+    }
+}
@@ -0,0 +1,185 @@
+use olc_pge as olc;
+
+use crate::window::DRAW_BIGGER_PIXELS;
+use crate::{Emulator, RunMode};
+
+const TIMER_BAR_WIDTH: u32 = 64;
+// call stack entries listed before the rest are cut off
+const STACK_LINES: usize = 7;
+// a row of text under the display, 8 pixels a character, stopping short of the panel
+const SUMMARY_CHARS: usize = 32;
+
+// what the panel shows, kept from before the last step to pick out the values that changed
+#[derive(Default)]
+pub struct PanelValues {
+    registers: [u8; 0x10],
+    program_counter: u16,
+    stack_pointer: u16,
+    timer: u8,
+    sound_timer: u8,
+}
+
+impl Emulator {
+    pub fn panel_values(&mut self) -> PanelValues {
+        let mut values = PanelValues {
+            program_counter: self.program_counter,
+            stack_pointer: self.stack_pointer,
+            timer: self.timer,
+            sound_timer: self.sound_timer,
+            ..PanelValues::default()
+        };
+        for i in 0..0x10 {
+            values.registers[i] = self.registers.get(i as u8);
+        }
+        values
+    }
+    // values are highlighted against last_panel, taken before the last step, so redrawing
+    // the panel doesn't lose them
+    pub fn draw_debug(&mut self, pge: &mut olc::PixelGameEngine, summary: String) {
+        if self.state.mode() != RunMode::Step {
+            return;
+        }
+        let now = self.panel_values();
+        let hash = if self.show_hash { Some(self.frame_hash()) } else { None };
+        let last = &self.last_panel;
+        let highlight = |changed: bool, color| if changed { olc::YELLOW } else { color };
+        let panel_x = 64 * DRAW_BIGGER_PIXELS + 4;
+
+        for i in 0..0x10 {
+            let mut string = String::new();
+            string += format!("R{:1X}:", i).as_str();
+            string += format!("{:2X}", now.registers[i]).as_str();
+            let (x, color) = if i < 0x8 {
+                (panel_x, olc::WHITE)
+            } else {
+                (panel_x + 60, olc::GREY)
+            };
+            let changed = now.registers[i] != last.registers[i];
+            pge.draw_string(x, 4 + ((i as i32 % 8) * 8), &string, highlight(changed, color));
+        }
+        // nothing has run yet when the panel first comes up
+        let (stringa, stringb) = split_summary(&summary);
+        pge.draw_string(
+            4,
+            32 * DRAW_BIGGER_PIXELS + 8,
+            &stringa.to_string(),
+            olc::WHITE,
+        );
+        pge.draw_string(
+            4,
+            32 * DRAW_BIGGER_PIXELS + 16,
+            &stringb.to_string(),
+            olc::WHITE,
+        );
+        if let Some(hash) = hash {
+            let hash = format!("HASH:{:016x}", hash);
+            pge.draw_string(4, 32 * DRAW_BIGGER_PIXELS + 32, &hash, olc::GREY);
+        }
+
+        // the timer bars also light up on the step that set them (FX15 / FX18)
+        let timers = [
+            ("DT", now.timer, now.timer != last.timer || summary.ends_with("=> TIMER")),
+            (
+                "ST",
+                now.sound_timer,
+                now.sound_timer != last.sound_timer || summary.ends_with("=> SOUND"),
+            ),
+        ];
+        for (i, (label, value, changed)) in timers.into_iter().enumerate() {
+            let y = 4 + 8 * 8 + 4 + (i as i32 * 10);
+            let color = highlight(changed, olc::WHITE);
+            pge.draw_string(panel_x, y, &label.to_string(), color);
+            pge.fill_rect(panel_x + 20, y, TIMER_BAR_WIDTH, 7, olc::VERY_DARK_GREY);
+            pge.fill_rect(panel_x + 20, y, timer_bar_width(value), 7, color);
+            pge.draw_string(
+                panel_x + 20 + TIMER_BAR_WIDTH as i32 + 4,
+                y,
+                &format!("{:02X}", value),
+                color,
+            );
+        }
+
+        // PC only counts as changed when something moved it other than stepping past
+        let y = 4 + 8 * 8 + 4 + 2 * 10 + 2;
+        pge.draw_string(
+            panel_x,
+            y,
+            &format!("PC:{:03X}", now.program_counter),
+            highlight(now.program_counter != last.program_counter.wrapping_add(2), olc::WHITE),
+        );
+        pge.draw_string(
+            panel_x + 60,
+            y,
+            &format!("I:{:03X}", now.stack_pointer),
+            highlight(now.stack_pointer != last.stack_pointer, olc::WHITE),
+        );
+        let mut key = format!("KEY:{}", key_block_text(self.key_block));
+        if self.is_waiting_for_key() {
+            key += format!(" {:.1}s", self.key_wait_time).as_str();
+        }
+        pge.draw_string(panel_x, y + 8, &key, highlight(self.key_block != 0x10, olc::WHITE));
+        pge.draw_string(
+            panel_x,
+            y + 16,
+            &format!("STK:{}", self.call_stack.len()),
+            olc::WHITE,
+        );
+        for (i, line) in call_stack_lines(self.call_stack.as_slice()).iter().enumerate() {
+            pge.draw_string(panel_x, y + 24 + (i as i32 * 8), line, olc::GREY);
+        }
+        let y = y + 24 + (STACK_LINES as i32 + 1) * 8 + 2;
+        pge.draw_string(panel_x, y, &self.quirk_summary(), olc::GREY);
+    }
+}
+
+// the step line on two rows under the display. it's broken before a => or a (, or failing
+// that the last space that fits, but never inside the brackets
+fn split_summary(summary: &str) -> (&str, &str) {
+    if summary.len() <= SUMMARY_CHARS {
+        return (summary, "");
+    }
+    let fits = &summary[..=SUMMARY_CHARS];
+    let mut depth = 0;
+    let mut space = None;
+    for (i, c) in fits.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ' ' if depth == 0 => space = Some(i),
+            _ => {}
+        }
+    }
+    let before = [" => ", " ("].iter().filter_map(|mark| fits.rfind(mark)).max();
+    match before.or(space) {
+        Some(at) => (&summary[..at], summary[at..].trim_start()),
+        None => summary.split_at(SUMMARY_CHARS),
+    }
+}
+
+// how much of a timer bar a 0-255 timer value fills, rounded down
+fn timer_bar_width(value: u8) -> u32 {
+    value as u32 * TIMER_BAR_WIDTH / 255
+}
+
+// the register a pending FX0A will put the key in, or dashes when nothing is waiting
+fn key_block_text(key_block: u8) -> String {
+    if key_block == 0x10 {
+        String::from("--")
+    } else {
+        format!("R{:1X}", key_block)
+    }
+}
+
+// return addresses, most recent first, cut off with "..." past STACK_LINES
+fn call_stack_lines(call_stack: &[u16]) -> Vec<String> {
+    let mut lines: Vec<String> = call_stack
+        .iter()
+        .rev()
+        .take(STACK_LINES)
+        .map(|addr| format!(" {:03X}", addr))
+        .collect();
+    if call_stack.len() > STACK_LINES {
+        lines.push(String::from(" ..."));
+    }
+    lines
+}
@@ -0,0 +1,108 @@
+// The built-in demo ROM, run when no ROM file is given (or with --demo).
+// It writes "CHIP8" with its own letter sprites and the font's 8,
+// beeps once, then bounces a ball around the bottom of the screen forever.
+// That exercises drawing, both timers and the beeper, so it doubles as a smoke test.
+//
+// Source (system/demo.ch8):
+//
+//   0x200  00E0   CLS
+//   0x202  6A12   VA = 18          ; title x
+//   0x204  6B04   VB = 4           ; title y
+//   0x206  A258   I = 0x258        ; "C"
+//   0x208  DAB5   DRAW 5 @ VA,VB
+//   0x20A  7A06   VA += 6
+//   0x20C  A25D   I = 0x25D        ; "H"
+//   0x20E  DAB5   DRAW 5 @ VA,VB
+//   0x210  7A06   VA += 6
+//   0x212  A262   I = 0x262        ; "I"
+//   0x214  DAB5   DRAW 5 @ VA,VB
+//   0x216  7A06   VA += 6
+//   0x218  A267   I = 0x267        ; "P"
+//   0x21A  DAB5   DRAW 5 @ VA,VB
+//   0x21C  7A06   VA += 6
+//   0x21E  6008   V0 = 8
+//   0x220  F029   I = DGT(V0)      ; "8" comes from the font
+//   0x222  DAB5   DRAW 5 @ VA,VB
+//   0x224  600F   V0 = 15
+//   0x226  F018   SOUND = V0       ; one quarter-second beep
+//   0x228  6120   V1 = 32          ; ball x
+//   0x22A  6210   V2 = 16          ; ball y
+//   0x22C  6301   V3 = 1           ; ball dx
+//   0x22E  6401   V4 = 1           ; ball dy
+//   0x230  A26C   I = 0x26C        ; ball sprite
+//   0x232  D124   DRAW 4 @ V1,V2
+//   loop:
+//   0x234  6502   V5 = 2
+//   0x236  F515   TIMER = V5       ; move every other frame
+//   wait:
+//   0x238  F507   V5 = TIMER
+//   0x23A  3500   SKIPIF V5 == 0
+//   0x23C  1238   JMP wait
+//   0x23E  D124   DRAW 4 @ V1,V2   ; erase the ball
+//   0x240  8134   V1 += V3
+//   0x242  8244   V2 += V4
+//   0x244  4100   SKIPIF V1 != 0
+//   0x246  6301   V3 = 1           ; bounce off the left edge
+//   0x248  413C   SKIPIF V1 != 60
+//   0x24A  63FF   V3 = -1          ; bounce off the right edge
+//   0x24C  420C   SKIPIF V2 != 12
+//   0x24E  6401   V4 = 1           ; bounce off the title area
+//   0x250  421C   SKIPIF V2 != 28
+//   0x252  64FF   V4 = -1          ; bounce off the bottom edge
+//   0x254  D124   DRAW 4 @ V1,V2
+//   0x256  1234   JMP loop
+//   0x258  F0 80 80 80 F0          ; "C"
+//   0x25D  90 90 F0 90 90          ; "H"
+//   0x262  E0 40 40 40 E0          ; "I"
+//   0x267  F0 90 F0 80 80          ; "P"
+//   0x26C  60 F0 F0 60             ; ball
+pub const DEMO_ROM: &[u8] = include_bytes!("../system/demo.ch8");
+
+pub const DEFAULT_DEMO: &str = "demo";
+
+// looks up a ROM for --demo <name>, the test fixtures are included with the test-roms feature
+pub fn find(name: &str) -> Option<&'static [u8]> {
+    if name == DEFAULT_DEMO {
+        return Some(DEMO_ROM);
+    }
+    #[cfg(feature = "test-roms")]
+    if let Some(fixture) = crate::fixtures::find(name) {
+        return Some(fixture.rom);
+    }
+    None
+}
+
+pub fn names() -> Vec<&'static str> {
+    #[cfg(feature = "test-roms")]
+    let fixtures = crate::fixtures::FIXTURES.iter().map(|fixture| fixture.name);
+    #[cfg(not(feature = "test-roms"))]
+    let fixtures = std::iter::empty();
+    std::iter::once(DEFAULT_DEMO).chain(fixtures).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Emulator, RunMode};
+
+    // a second in, the title drawn, the beep over and the ball partway along. pinned, so a
+    // change to the ROM or to anything it runs on shows up
+    #[test]
+    fn the_demo_reaches_the_same_screen_every_time() {
+        let mut emulator = Emulator::new();
+        emulator.state = RunMode::Play.into();
+        emulator.load_rom_bytes(find(DEFAULT_DEMO).unwrap()).unwrap();
+        for _ in 0..60 {
+            emulator.step_frame(10);
+        }
+        assert!(!emulator.is_halted());
+        assert_eq!(emulator.sound_timer, 0);
+        assert_eq!(emulator.display_hash(), 0xDA4F_052E_2C54_D517);
+    }
+
+    #[test]
+    fn the_demo_comes_first() {
+        assert_eq!(names()[0], DEFAULT_DEMO);
+        assert!(names().iter().all(|&name| find(name).is_some()));
+    }
+}
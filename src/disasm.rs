@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::instructions::{self, hex_addr, Instruction};
+use crate::output;
+use crate::regions;
+use crate::rom_source;
+
+const ROM_START: u16 = 0x200;
+
+#[derive(Debug, clap::Args)]
+pub struct DisasmArgs {
+    /// The ROM file to disassemble
+    #[clap(value_parser)]
+    rom_file: PathBuf,
+    /// Write the labels found by static analysis to this symbol file
+    #[clap(long, value_parser)]
+    emit_symbols: Option<String>,
+    /// Name addresses from this symbol file, over the labels the analysis makes up
+    #[clap(long, value_parser)]
+    symbols: Option<String>,
+    /// List BNNN as SUPER-CHIP's BXNN, the way --quirk-jump-x runs it
+    #[clap(long)]
+    quirk_jump_x: bool,
+}
+
+// what the static analysis found: which bytes are reachable code, and the labels it made up
+pub struct Analysis {
+    code: Vec<bool>,
+    labels: BTreeMap<u16, String>,
+}
+
+#[derive(PartialEq, PartialOrd, Clone, Copy)]
+enum LabelKind {
+    Data,
+    Jump,
+    Call,
+}
+
+// the big-endian word at rom[i], like Ram::get_u16 but padded with a zero past the end
+// instead of wrapping
+fn word_at(rom: &[u8], i: usize) -> u16 {
+    u16::from_be_bytes([rom[i], rom.get(i + 1).copied().unwrap_or(0)])
+}
+
+// follows jumps and calls from the entry point to find reachable code, in a ROM no bigger
+// than rom_source::MAX_ROM_SIZE. Every address is visited at most once, so cyclic control
+// flow terminates. BNNN targets depend on V0 at runtime, so only the base address gets a
+// label and nothing is traced from it.
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let rom_end = ROM_START as usize + rom.len();
+    let in_rom = |addr: u16| addr >= ROM_START && (addr as usize) < rom_end;
+    let word_at = |addr: u16| word_at(rom, (addr - ROM_START) as usize);
+
+    let mut code = vec![false; rom.len()];
+    let mut kinds: BTreeMap<u16, LabelKind> = BTreeMap::new();
+    let mut visited = vec![false; rom.len()];
+    let mut pending = vec![ROM_START];
+
+    while let Some(addr) = pending.pop() {
+        if !in_rom(addr) || visited[(addr - ROM_START) as usize] {
+            continue;
+        }
+        visited[(addr - ROM_START) as usize] = true;
+        code[(addr - ROM_START) as usize] = true;
+        if in_rom(addr + 1) {
+            code[(addr + 1 - ROM_START) as usize] = true;
+        }
+
+        let word = word_at(addr);
+        let instruction = Instruction::from(word);
+        let next = addr + 2;
+        match (instruction.opcode, instruction.byte) {
+            (0x0, 0xEE) => {} // RET ends this path
+            (0x0, 0xFD) if word == 0x00FD => {} // as does EXIT
+            (0x1, _) => {
+                if instruction.addr != addr {
+                    add_label(&mut kinds, instruction.addr, LabelKind::Jump);
+                }
+                pending.push(instruction.addr);
+            }
+            (0x2, _) => {
+                add_label(&mut kinds, instruction.addr, LabelKind::Call);
+                pending.push(instruction.addr);
+                pending.push(next);
+            }
+            (0x3, _) | (0x4, _) | (0x5, _) | (0x9, _) | (0xE, 0x9E) | (0xE, 0xA1) => {
+                pending.push(next);
+                pending.push(next + 2);
+            }
+            (0xA, _) => {
+                if in_rom(instruction.addr) {
+                    add_label(&mut kinds, instruction.addr, LabelKind::Data);
+                }
+                pending.push(next);
+            }
+            (0xB, _) => {
+                add_label(&mut kinds, instruction.addr, LabelKind::Jump);
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    let mut labels = BTreeMap::new();
+    labels.insert(ROM_START, String::from("start"));
+    for (addr, kind) in kinds {
+        if addr == ROM_START {
+            continue;
+        }
+        let prefix = match kind {
+            LabelKind::Call => "sub",
+            LabelKind::Jump => "loc",
+            LabelKind::Data => "dat",
+        };
+        labels.insert(addr, format!("{}_{:03X}", prefix, addr));
+    }
+    Analysis { code, labels }
+}
+
+// an address can be both jumped to and called, the stronger kind names it
+fn add_label(kinds: &mut BTreeMap<u16, LabelKind>, addr: u16, kind: LabelKind) {
+    let entry = kinds.entry(addr).or_insert(kind);
+    if kind > *entry {
+        *entry = kind;
+    }
+}
+
+impl Analysis {
+    // the names a --symbols file gives, taking over from made-up ones at the same address
+    pub fn name(&mut self, labels: BTreeMap<u16, String>) {
+        self.labels.extend(labels);
+    }
+    // one `NNN name` line per label, which --symbols reads back
+    pub fn symbol_file(&self) -> String {
+        let mut symbols = String::new();
+        for (addr, name) in &self.labels {
+            symbols += format!("{:03X} {}\n", addr, name).as_str();
+        }
+        symbols
+    }
+    fn target(&self, addr: u16) -> String {
+        match self.labels.get(&addr) {
+            Some(name) => name.clone(),
+            None => hex_addr(addr),
+        }
+    }
+}
+
+// a line of the listing. code is a word and its mnemonic, data a run of bytes
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+pub struct ListingLine {
+    pub addr: u16,
+    pub label: Option<String>, // the label on this address, if it has one
+    pub word: Option<u16>,
+    pub mnemonic: Option<String>,
+    pub data: Option<Vec<u8>>,
+}
+
+// the labelled listing: reachable code as instructions, everything else as data bytes.
+// `jump_x` decodes BNNN as SUPER-CHIP's BXNN
+pub fn listing(rom: &[u8], analysis: &Analysis, jump_x: bool) -> Vec<ListingLine> {
+    let mut lines = Vec::new();
+    let mut i = 0;
+    while i < rom.len() {
+        let addr = ROM_START + i as u16;
+        let mut line = ListingLine {
+            addr,
+            label: analysis.labels.get(&addr).cloned(),
+            word: None,
+            mnemonic: None,
+            data: None,
+        };
+        if analysis.code[i] && i + 1 < rom.len() {
+            let word = word_at(rom, i);
+            line.word = Some(word);
+            line.mnemonic = Some(instructions::mnemonic(word, jump_x, false, |addr| {
+                analysis.target(addr)
+            }));
+            i += 2;
+        } else {
+            // a run of data, up to 8 bytes or the next label / code byte
+            let mut bytes = Vec::new();
+            while i < rom.len() && bytes.len() < 8 && !(analysis.code[i] && i + 1 < rom.len()) {
+                if !bytes.is_empty() && analysis.labels.contains_key(&(ROM_START + i as u16)) {
+                    break;
+                }
+                bytes.push(rom[i]);
+                i += 1;
+            }
+            line.data = Some(bytes);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+// the listing as text, each label on a line of its own
+fn listing_text(lines: &[ListingLine]) -> String {
+    let mut listing = String::new();
+    for line in lines {
+        if let Some(name) = &line.label {
+            listing += format!("{}:\n", name).as_str();
+        }
+        match (line.word, &line.mnemonic, &line.data) {
+            (Some(word), Some(mnemonic), _) => {
+                listing += format!("  {:03X}  {:04X}  {}\n", line.addr, word, mnemonic).as_str();
+            }
+            (_, _, Some(data)) => {
+                let bytes: Vec<String> = data.iter().map(|byte| format!("{:02X}", byte)).collect();
+                listing += format!("  {:03X}  db {}\n", line.addr, bytes.join(" ")).as_str();
+            }
+            _ => {}
+        }
+    }
+    listing
+}
+
+pub fn run(args: DisasmArgs, json: bool) -> bool {
+    // no bigger than fits at 0x200, which the address math below counts on
+    let rom = match rom_source::read(&args.rom_file.to_string_lossy()) {
+        Ok(rom) => rom,
+        Err(e) => {
+            output::error(json, &format!("Failed to read ROM file {}: {}", args.rom_file.display(), e));
+            return false;
+        }
+    };
+    let mut analysis = analyze(&rom);
+    if let Some(path) = &args.symbols {
+        match regions::load_symbols(path) {
+            Ok(symbols) => analysis.name(symbols.labels),
+            Err(e) => {
+                output::error(json, &format!("Failed to load symbols from {}: {}", path, e));
+                return false;
+            }
+        }
+    }
+    let lines = listing(&rom, &analysis, args.quirk_jump_x);
+    if json {
+        output::print_json(&lines);
+    } else {
+        print!("{}", listing_text(&lines));
+    }
+    if let Some(path) = &args.emit_symbols {
+        if let Err(e) = fs::write(path, analysis.symbol_file()) {
+            output::error(json, &format!("Failed to write symbol file to {}: {}", path, e));
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_rom_that_fills_memory_ends_at_0xfff() {
+        // JMP FFE, and a skip there that would go past the end
+        let mut rom = vec![0; rom_source::MAX_ROM_SIZE];
+        rom[..2].copy_from_slice(&[0x1F, 0xFE]);
+        rom[0xDFE..].copy_from_slice(&[0x30, 0x00]);
+        let analysis = analyze(&rom);
+        let lines = listing(&rom, &analysis, false);
+        let last = lines.last().unwrap();
+        assert_eq!((last.addr, last.word), (0xFFE, Some(0x3000)));
+    }
+
+    #[test]
+    fn a_rom_too_big_for_memory_is_refused() {
+        let name = format!("chip8rust-disasm-{}.ch8", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, vec![0; rom_source::MAX_ROM_SIZE + 1]).unwrap();
+        let args = DisasmArgs {
+            rom_file: path.clone(),
+            emit_symbols: None,
+            symbols: None,
+            quirk_jump_x: false,
+        };
+        assert!(!run(args, false));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn labels_the_demo_rom() {
+        let analysis = analyze(crate::demo::DEMO_ROM);
+        let symbols = "200 start\n234 loc_234\n238 loc_238\n258 dat_258\n25D dat_25D\n\
+                       262 dat_262\n267 dat_267\n26C dat_26C\n";
+        assert_eq!(analysis.symbol_file(), symbols);
+    }
+
+    #[test]
+    fn the_symbol_file_reads_back_as_the_same_labels() {
+        let analysis = analyze(crate::demo::DEMO_ROM);
+        let symbols = regions::parse_symbols(&analysis.symbol_file()).unwrap();
+        assert_eq!(symbols.labels, analysis.labels);
+        assert_eq!(symbols.regions, []);
+    }
+
+    #[test]
+    fn a_symbol_file_renames_the_labels_in_the_listing() {
+        let mut analysis = analyze(crate::demo::DEMO_ROM);
+        analysis.name(regions::parse_symbols("234 bounce\n").unwrap().labels);
+        let lines = listing(crate::demo::DEMO_ROM, &analysis, false);
+        let at = |addr| lines.iter().find(|line| line.addr == addr).unwrap();
+        assert_eq!(at(0x234).label.as_deref(), Some("bounce"));
+        assert_eq!(at(0x256).mnemonic.as_deref(), Some("JMP bounce"));
+    }
+
+    #[test]
+    fn the_json_listing_reads_back() {
+        let lines = listing(crate::demo::DEMO_ROM, &analyze(crate::demo::DEMO_ROM), false);
+        let json = serde_json::to_string_pretty(&lines).unwrap();
+        assert_eq!(serde_json::from_str::<Vec<ListingLine>>(&json).unwrap(), lines);
+    }
+}
@@ -0,0 +1,182 @@
+use std::cmp::Ordering;
+use std::fs;
+
+#[cfg(feature = "frontend")]
+use olc_pge as olc;
+
+use crate::status::NOTICE_TIME;
+use crate::Emulator;
+
+// the longest capture, a pixel-wide column a frame across the timeline
+pub const MAX_FRAMES: u32 = 368;
+// the draws kept from one capture. a ROM that draws more than this is missing the rest
+const MAX_DRAWS: usize = 16384;
+// the timeline: a column per frame under the title, a mark per draw down it
+#[cfg(feature = "frontend")]
+const TOP: i32 = 20;
+#[cfg(feature = "frontend")]
+const MARK_HEIGHT: i32 = 3;
+#[cfg(feature = "frontend")]
+const MAX_MARKS: usize = 40;
+
+// a DXYN during a capture, on the frame (from the start of the capture) and cycle it ran
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct DrawEvent {
+    pub frame: u32,
+    pub cycle: u64,
+    pub x: u8, // where the sprite went, once wrapped
+    pub y: u8,
+    pub height: u8,
+    pub drawn: u32, // pixels it turned on
+    pub erased: u32, // and off
+}
+impl DrawEvent {
+    // whether it drew more than it erased, on balance
+    fn net(&self) -> Ordering {
+        self.drawn.cmp(&self.erased)
+    }
+}
+
+// --capture-frames: every draw over a window of frames, to see the order a ROM erases and
+// redraws its sprites in, which is where flicker comes from
+pub struct DrawCapture {
+    frames: u32,
+    frame: u32, // the frames done so far
+    draws: Vec<DrawEvent>,
+    dropped: u64, // past MAX_DRAWS
+}
+impl DrawCapture {
+    pub fn new(frames: u32) -> DrawCapture {
+        DrawCapture {
+            frames,
+            frame: 0,
+            draws: Vec::new(),
+            dropped: 0,
+        }
+    }
+    pub fn is_done(&self) -> bool {
+        self.frame >= self.frames
+    }
+    // how many draws went into each frame
+    #[cfg(feature = "frontend")]
+    fn per_frame(&self) -> Vec<&[DrawEvent]> {
+        let mut frames = Vec::new();
+        let mut rest = self.draws.as_slice();
+        for frame in 0..self.frames {
+            let count = rest.iter().take_while(|draw| draw.frame == frame).count();
+            let (this, next) = rest.split_at(count);
+            frames.push(this);
+            rest = next;
+        }
+        frames
+    }
+    // a row a draw, net being drew, erased or even
+    pub fn csv(&self) -> String {
+        let mut csv = String::from("frame,cycle,x,y,height,drawn,erased,net\n");
+        for draw in &self.draws {
+            let net = match draw.net() {
+                Ordering::Greater => "drew",
+                Ordering::Less => "erased",
+                Ordering::Equal => "even",
+            };
+            csv += format!(
+                "{},{},{},{},{},{},{},{}\n",
+                draw.frame, draw.cycle, draw.x, draw.y, draw.height, draw.drawn, draw.erased, net
+            )
+            .as_str();
+        }
+        csv
+    }
+}
+
+impl Emulator {
+    // from DXYN, with what it changed
+    pub fn log_draw(&mut self, (x, y): (u8, u8), height: u8, drawn: u32, erased: u32) {
+        let cycle = self.stats.instructions;
+        let Some(capture) = self.draw_capture.as_mut().filter(|capture| !capture.is_done()) else {
+            return;
+        };
+        if capture.draws.len() == MAX_DRAWS {
+            capture.dropped += 1;
+            return;
+        }
+        let frame = capture.frame;
+        capture.draws.push(DrawEvent { frame, cycle, x, y, height, drawn, erased });
+    }
+    // from the timer tick, which ends a frame
+    pub fn draw_capture_frame(&mut self) {
+        let Some(capture) = self.draw_capture.as_mut().filter(|capture| !capture.is_done()) else {
+            return;
+        };
+        capture.frame += 1;
+        if !capture.is_done() {
+            return;
+        }
+        if capture.dropped > 0 {
+            log::warn!("Draw capture: {} draws past the first {} dropped", capture.dropped, MAX_DRAWS);
+        }
+        let message = format!("Captured {} draws over {} frames", capture.draws.len(), capture.frames);
+        self.notify(message, NOTICE_TIME);
+    }
+    pub fn start_draw_capture(&mut self) {
+        self.draw_capture = Some(DrawCapture::new(self.capture_frames));
+        self.notify(format!("Capturing draws for {} frames", self.capture_frames), NOTICE_TIME);
+    }
+    // the C key in the timeline
+    pub fn export_draw_capture(&mut self) {
+        let Some(capture) = &self.draw_capture else {
+            return;
+        };
+        let path = self.capture_csv.clone();
+        match fs::write(&path, capture.csv()) {
+            Ok(()) => self.notify(format!("Draws written to {}", path), NOTICE_TIME),
+            Err(e) => {
+                log::error!("Failed to write the draw capture to {}: {}", path, e);
+                self.notify_error(format!("Failed to write {}", path), NOTICE_TIME);
+            }
+        }
+    }
+}
+
+// a column per frame of the capture, a mark per draw from the top down in the order they
+// ran: green drew more than it erased, red erased more, grey neither
+#[cfg(feature = "frontend")]
+pub fn render(emulator: &Emulator, pge: &mut olc::PixelGameEngine) {
+    let y = TOP + MAX_MARKS as i32 * MARK_HEIGHT + 4;
+    let hint = format!("r: capture {} frames, c: save csv, esc: close", emulator.capture_frames);
+    pge.draw_string(8, y + 10, &hint, olc::GREY);
+    let Some(capture) = &emulator.draw_capture else {
+        pge.draw_string(8, 8, &String::from("NO DRAWS CAPTURED"), olc::WHITE);
+        return;
+    };
+    let title = if capture.is_done() {
+        format!("DRAWS OVER {} FRAMES", capture.frames)
+    } else {
+        format!("DRAWS, {} OF {} FRAMES SO FAR", capture.frame, capture.frames)
+    };
+    pge.draw_string(8, 8, &title, olc::WHITE);
+
+    let column = (MAX_FRAMES / capture.frames).clamp(1, 8);
+    let frames = capture.per_frame();
+    for (frame, draws) in frames.iter().enumerate() {
+        let x = 8 + frame as i32 * column as i32;
+        for (i, draw) in draws.iter().take(MAX_MARKS).enumerate() {
+            let color = match draw.net() {
+                Ordering::Greater => olc::GREEN,
+                Ordering::Less => olc::RED,
+                Ordering::Equal => olc::GREY,
+            };
+            let mark_y = TOP + i as i32 * MARK_HEIGHT;
+            pge.fill_rect(x, mark_y, column.max(2) - 1, MARK_HEIGHT as u32 - 1, color);
+        }
+    }
+    let busiest = frames.iter().map(|draws| draws.len()).max().unwrap_or(0);
+    let erasing = capture.draws.iter().filter(|draw| draw.net() == Ordering::Less).count();
+    let line = format!(
+        "{} DRAWS, {} ERASING, UP TO {} A FRAME",
+        capture.draws.len(),
+        erasing,
+        busiest
+    );
+    pge.draw_string(8, y, &line, olc::GREY);
+}
@@ -0,0 +1,144 @@
+// the C API, behind the ffi feature: include/chip8rust.h declares it, and examples/c/ uses
+// it. every call is wrapped in catch_unwind, so a panic comes back as CHIP8_ERR_PANIC
+// rather than unwinding into the caller
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::hires::HIRES_H;
+use crate::{Emulator, RunMode, SCR_W};
+
+pub const CHIP8_OK: i32 = 0;
+pub const CHIP8_ERR_NULL: i32 = -1; // a null machine or buffer
+pub const CHIP8_ERR_LOAD: i32 = -2; // the ROM doesn't fit at 0x200
+pub const CHIP8_ERR_PANIC: i32 = -3;
+
+// chip8_set_quirks bits, each one away from the default
+pub const CHIP8_QUIRK_JUMP_X: u32 = 1; // BNNN is SUPER-CHIP's BXNN
+pub const CHIP8_QUIRK_CLIP_X: u32 = 2; // sprites are cut off at the right edge, not wrapped
+pub const CHIP8_QUIRK_CLIP_Y: u32 = 4; // and at the bottom
+
+// the most chip8_framebuffer writes, for a hires ROM: a byte a pixel, row by row. a number
+// of its own, so the header doesn't need SCR_W and HIRES_H
+pub const CHIP8_FRAMEBUFFER_SIZE: usize = 64 * 64;
+const _: () = assert!(CHIP8_FRAMEBUFFER_SIZE == SCR_W * HIRES_H);
+
+// opaque to C, made by chip8_new and given back to chip8_free
+pub struct Chip8 {
+    emulator: Emulator,
+    cycles_per_frame: u32,
+}
+
+// runs `call` on the machine behind `chip8`, turning a null pointer or a panic into an
+// error code
+unsafe fn with_chip8(chip8: *mut Chip8, call: impl FnOnce(&mut Chip8) -> i32) -> i32 {
+    let Some(chip8) = chip8.as_mut() else {
+        return CHIP8_ERR_NULL;
+    };
+    panic::catch_unwind(AssertUnwindSafe(|| call(chip8))).unwrap_or(CHIP8_ERR_PANIC)
+}
+
+/// A machine with nothing loaded, in Play, or null if making one panicked.
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    let made = panic::catch_unwind(|| {
+        let mut emulator = Emulator::new();
+        emulator.state = RunMode::Play.into();
+        let cycles_per_frame = Emulator::cycles_per_frame(1.0 / emulator.time_per_cycle);
+        Box::new(Chip8 {
+            emulator,
+            cycles_per_frame,
+        })
+    });
+    made.map_or(ptr::null_mut(), Box::into_raw)
+}
+
+/// Frees a machine from chip8_new.
+///
+/// # Safety
+/// `chip8` is null or from chip8_new, and isn't used again.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Resets the machine and loads `len` bytes of ROM at 0x200. On CHIP8_ERR_LOAD nothing is.
+///
+/// # Safety
+/// `chip8` is null or from chip8_new, and `rom` points at `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, rom: *const u8, len: usize) -> i32 {
+    if rom.is_null() {
+        return CHIP8_ERR_NULL;
+    }
+    let rom = slice::from_raw_parts(rom, len);
+    with_chip8(chip8, |chip8| {
+        chip8.emulator.reset();
+        match chip8.emulator.load_rom_bytes(rom) {
+            Ok(()) => CHIP8_OK,
+            Err(_) => CHIP8_ERR_LOAD,
+        }
+    })
+}
+
+/// One 60Hz frame with `keys` held, bit N for key N.
+///
+/// # Safety
+/// `chip8` is null or from chip8_new.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step_frame(chip8: *mut Chip8, keys: u16) -> i32 {
+    with_chip8(chip8, |chip8| {
+        for (key, held) in chip8.emulator.keys.iter_mut().enumerate() {
+            *held = keys & (1 << key) != 0;
+        }
+        chip8.emulator.step_frame(chip8.cycles_per_frame);
+        CHIP8_OK
+    })
+}
+
+/// Writes the display to `out`, a byte a pixel (0 or 1) row by row, 64 pixels a row.
+/// Returns the number of rows, 32 or 64 for a hires ROM.
+///
+/// # Safety
+/// `chip8` is null or from chip8_new, and `out` has room for CHIP8_FRAMEBUFFER_SIZE bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *mut Chip8, out: *mut u8) -> i32 {
+    if out.is_null() {
+        return CHIP8_ERR_NULL;
+    }
+    let out = slice::from_raw_parts_mut(out, CHIP8_FRAMEBUFFER_SIZE);
+    with_chip8(chip8, |chip8| {
+        let rows = chip8.emulator.screen_height();
+        let display = chip8.emulator.display();
+        let pixels = (0..rows).flat_map(|y| display.iter().map(move |column| column[y]));
+        for (byte, pixel) in out.iter_mut().zip(pixels) {
+            *byte = u8::from(pixel);
+        }
+        rows as i32
+    })
+}
+
+/// 1 while the sound timer runs, 0 when it doesn't.
+///
+/// # Safety
+/// `chip8` is null or from chip8_new.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound_active(chip8: *mut Chip8) -> i32 {
+    with_chip8(chip8, |chip8| i32::from(chip8.emulator.sound_active()))
+}
+
+/// The CHIP8_QUIRK_ bits that are set, the defaults for the rest.
+///
+/// # Safety
+/// `chip8` is null or from chip8_new.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_quirks(chip8: *mut Chip8, quirks: u32) -> i32 {
+    with_chip8(chip8, |chip8| {
+        chip8.emulator.jump_x = quirks & CHIP8_QUIRK_JUMP_X != 0;
+        chip8.emulator.wrap_x = quirks & CHIP8_QUIRK_CLIP_X == 0;
+        chip8.emulator.wrap_y = quirks & CHIP8_QUIRK_CLIP_Y == 0;
+        CHIP8_OK
+    })
+}
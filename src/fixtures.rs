@@ -0,0 +1,300 @@
+// Tiny hand-assembled ROMs with known outcomes, for tests and for poking at by hand
+// with `--demo <name>` (only with the test-roms feature, cargo test always has them).
+// Every ROM ends in a jump-to-self, so "halted" below means PC sits on that jump.
+
+pub struct Fixture {
+    pub name: &'static str,
+    pub rom: &'static [u8],
+}
+
+pub const FIXTURES: [Fixture; 5] = [
+    Fixture {
+        name: "ibm",
+        rom: &IBM,
+    },
+    Fixture {
+        name: "alu",
+        rom: &ALU,
+    },
+    Fixture {
+        name: "keypad",
+        rom: &KEYPAD,
+    },
+    Fixture {
+        name: "stack",
+        rom: &STACK,
+    },
+    Fixture {
+        name: "detect",
+        rom: &DETECT,
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Fixture> {
+    FIXTURES.iter().find(|fixture| fixture.name == name)
+}
+
+// IBM-logo-style draw test: draws "IBM" as three 8x5 sprites at (8, 8), (16, 8) and (24, 8).
+// Expected: halted at 0x216, V0 = 0x18, V1 = 0x08, VF = 0 (no collisions), I = 0x222,
+// and exactly the sprite pixels lit.
+//
+//   0x200  00E0   CLS
+//   0x202  A218   I = 0x218        ; "I"
+//   0x204  6008   V0 = 8
+//   0x206  6108   V1 = 8
+//   0x208  D015   DRAW 5 @ V0,V1
+//   0x20A  7008   V0 += 8
+//   0x20C  A21D   I = 0x21D        ; "B"
+//   0x20E  D015   DRAW 5 @ V0,V1
+//   0x210  7008   V0 += 8
+//   0x212  A222   I = 0x222        ; "M"
+//   0x214  D015   DRAW 5 @ V0,V1
+//   0x216  1216   JMP 0x216
+//   0x218  7C 10 10 10 7C
+//   0x21D  FC 82 FC 82 FC
+//   0x222  82 C6 AA 92 82
+const IBM: [u8; 39] = [
+    0x00, 0xE0, 0xA2, 0x18, 0x60, 0x08, 0x61, 0x08, 0xD0, 0x15, 0x70, 0x08, 0xA2, 0x1D, 0xD0,
+    0x15, 0x70, 0x08, 0xA2, 0x22, 0xD0, 0x15, 0x12, 0x16, 0x7C, 0x10, 0x10, 0x10, 0x7C, 0xFC,
+    0x82, 0xFC, 0x82, 0xFC, 0x82, 0xC6, 0xAA, 0x92, 0x82,
+];
+
+// ALU torture loop: 32 passes through every 8XYN operation with a walking bit in V1.
+// Expected: halted at 0x230 with
+//   V0 = 0xFC, V1 = 0x01, V2 = 0x20, V4 = 0x00, V5 = 0x03, V6 = 0x1D, V7 = 0x1D, VA = 0x01, VF = 0x01
+//
+//   0x200  6000   V0 = 0
+//   0x202  6101   V1 = 1           ; walking bit
+//   0x204  6200   V2 = 0           ; pass counter
+//   0x206  6400   V4 = 0
+//   0x208  65FF   V5 = 0xFF
+//   0x20A  6600   V6 = 0
+//   0x20C  6700   V7 = 0
+//   loop:
+//   0x20E  8014   V0 += V1
+//   0x210  8403   V4 ^= V0
+//   0x212  8515   V5 -= V1
+//   0x214  8AF0   VA = VF          ; the SUB's no-borrow flag
+//   0x216  87A4   V7 += VA         ; count passes without a borrow
+//   0x218  811E   V1 <<= 1
+//   0x21A  3100   SKIPIF V1 == 0
+//   0x21C  1220   JMP 0x220
+//   0x21E  6101   V1 = 1           ; restart the walking bit
+//   0x220  8640   V6 = V4
+//   0x222  8606   V6 >>= 1
+//   0x224  8657   V6 = V5 - V6
+//   0x226  8612   V6 &= V1
+//   0x228  8671   V6 |= V7
+//   0x22A  7201   V2 += 1
+//   0x22C  3220   SKIPIF V2 == 32
+//   0x22E  120E   JMP loop
+//   0x230  1230   JMP 0x230
+const ALU: [u8; 50] = [
+    0x60, 0x00, 0x61, 0x01, 0x62, 0x00, 0x64, 0x00, 0x65, 0xFF, 0x66, 0x00, 0x67, 0x00, 0x80,
+    0x14, 0x84, 0x03, 0x85, 0x15, 0x8A, 0xF0, 0x87, 0xA4, 0x81, 0x1E, 0x31, 0x00, 0x12, 0x20,
+    0x61, 0x01, 0x86, 0x40, 0x86, 0x06, 0x86, 0x57, 0x86, 0x12, 0x86, 0x71, 0x72, 0x01, 0x32,
+    0x20, 0x12, 0x0E, 0x12, 0x30,
+];
+
+// Keypad echo: waits for a key and draws its hex digit at (24, 12), forever.
+// Expected with no input: waiting for a key into V0 at PC 0x202 with a blank screen.
+// After pressing key K: the font digit K drawn at (24, 12), waiting again.
+//
+//   0x200  F00A   KEYBLOCK => V0
+//   0x202  00E0   CLS
+//   0x204  F029   I = DGT(V0)
+//   0x206  6118   V1 = 24
+//   0x208  620C   V2 = 12
+//   0x20A  D125   DRAW 5 @ V1,V2
+//   0x20C  1200   JMP 0x200
+const KEYPAD: [u8; 14] = [
+    0xF0, 0x0A, 0x00, 0xE0, 0xF0, 0x29, 0x61, 0x18, 0x62, 0x0C, 0xD1, 0x25, 0x12, 0x00,
+];
+
+// Stack depth test: a subroutine that recurses until it is 12 calls deep, then unwinds.
+// Expected: halted at 0x206 with V0 = 0x0C (deepest level), V1 = 0x0C (returns taken)
+// and an empty call stack again, after peaking at 12 entries.
+//
+//   0x200  6000   V0 = 0           ; depth
+//   0x202  6100   V1 = 0           ; returns
+//   0x204  2208   CALL 0x208
+//   0x206  1206   JMP 0x206
+//   sub:
+//   0x208  7001   V0 += 1
+//   0x20A  300C   SKIPIF V0 == 12
+//   0x20C  2208   CALL sub
+//   0x20E  7101   V1 += 1
+//   0x210  00EE   RET
+const STACK: [u8; 18] = [
+    0x60, 0x00, 0x61, 0x00, 0x22, 0x08, 0x12, 0x06, 0x70, 0x01, 0x30, 0x0C, 0x22, 0x08, 0x71,
+    0x01, 0x00, 0xEE,
+];
+
+// Interpreter detection, the way ROMs that configure themselves do it: checks the machine
+// started clean (V0-VF zero, I at the font, 0x1FF zero), then tells the shift quirk apart
+// by what 8XY6 shifts.
+// Expected: halted at 0x23E with VA = 0x01 (started clean) and VB = 0x48, the branch for
+// an interpreter that shifts VX (VB = 0x01 on one that shifts VY, like the VIP and
+// --quirk-shift-vy), plus
+// V0 = 0x40, V1 = 0x02, VF = 0x00 and I = 0x1FF. (VA = 0x00 with --font-style rounded, whose
+// 0 doesn't start with 0xF0.)
+//
+//   0x200  8011   V0 |= V1         ; ... through V0 |= VF at 0x21C
+//   0x21E  6A01   VA = 1           ; clean, unless a check below says otherwise
+//   0x220  3000   SKIPIF V0 == 0
+//   0x222  6A00   VA = 0           ; a register didn't start at zero
+//   0x224  F065   LOAD V0          ; from I, which starts on the font's first byte
+//   0x226  30F0   SKIPIF V0 == 0xF0
+//   0x228  6A00   VA = 0
+//   0x22A  A1FF   I = 0x1FF        ; the byte before the ROM
+//   0x22C  F065   LOAD V0
+//   0x22E  3000   SKIPIF V0 == 0
+//   0x230  6A00   VA = 0
+//   0x232  6080   V0 = 0x80
+//   0x234  6102   V1 = 2
+//   0x236  8016   V0 >>= 1         ; 0x40 shifting VX, 0x01 shifting VY
+//   0x238  6B48   VB = 0x48
+//   0x23A  4001   SKIPIF V0 != 1
+//   0x23C  6B01   VB = 1
+//   0x23E  123E   JMP 0x23E
+const DETECT: [u8; 64] = [
+    0x80, 0x11, 0x80, 0x21, 0x80, 0x31, 0x80, 0x41, 0x80, 0x51, 0x80, 0x61, 0x80, 0x71, 0x80,
+    0x81, 0x80, 0x91, 0x80, 0xA1, 0x80, 0xB1, 0x80, 0xC1, 0x80, 0xD1, 0x80, 0xE1, 0x80, 0xF1,
+    0x6A, 0x01, 0x30, 0x00, 0x6A, 0x00, 0xF0, 0x65, 0x30, 0xF0, 0x6A, 0x00, 0xA1, 0xFF, 0xF0,
+    0x65, 0x30, 0x00, 0x6A, 0x00, 0x60, 0x80, 0x61, 0x02, 0x80, 0x16, 0x6B, 0x48, 0x40, 0x01,
+    0x6B, 0x01, 0x12, 0x3E,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::{self, FontStyle};
+    use crate::headless::RunStopReason;
+    use crate::Emulator;
+
+    fn emulator(name: &str) -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(find(name).unwrap().rom).unwrap();
+        emulator
+    }
+
+    // the registers, V0 first
+    fn registers(emulator: &Emulator) -> [u8; 16] {
+        emulator.registers.as_slice().try_into().unwrap()
+    }
+
+    // the 8x5 sprite at `addr` in RAM is what's lit at (x, y)
+    fn drawn_at(emulator: &Emulator, addr: u16, x: usize, y: usize) -> bool {
+        (0..5).all(|row| {
+            let byte = emulator.ram.get(addr + row as u16);
+            (0..8).all(|bit| emulator.display[x + bit][y + row] == (byte & 0x80 >> bit != 0))
+        })
+    }
+
+    fn lit(emulator: &Emulator) -> usize {
+        emulator.display.iter().flatten().filter(|&&on| on).count()
+    }
+
+    #[test]
+    fn ibm() {
+        let mut emulator = emulator("ibm");
+        assert_eq!(emulator.run_cycles(100), RunStopReason::Finished);
+        assert!(emulator.is_halted());
+        assert_eq!(emulator.program_counter, 0x216);
+        let v = registers(&emulator);
+        assert_eq!((v[0x0], v[0x1], v[0xF]), (0x18, 0x08, 0x00));
+        assert_eq!(emulator.stack_pointer, 0x222);
+        assert!(drawn_at(&emulator, 0x218, 8, 8));
+        assert!(drawn_at(&emulator, 0x21D, 16, 8));
+        assert!(drawn_at(&emulator, 0x222, 24, 8));
+        let sprite_pixels: u32 = IBM[0x18..].iter().map(|byte| byte.count_ones()).sum();
+        assert_eq!(lit(&emulator), sprite_pixels as usize);
+    }
+
+    #[test]
+    fn alu() {
+        let mut emulator = emulator("alu");
+        emulator.run_cycles(1000);
+        assert!(emulator.is_halted());
+        assert_eq!(emulator.program_counter, 0x230);
+        let v = registers(&emulator);
+        assert_eq!((v[0x0], v[0x1], v[0x2], v[0x4]), (0xFC, 0x01, 0x20, 0x00));
+        assert_eq!((v[0x5], v[0x6], v[0x7], v[0xA], v[0xF]), (0x03, 0x1D, 0x1D, 0x01, 0x01));
+    }
+
+    #[test]
+    fn keypad() {
+        let mut emulator = emulator("keypad");
+        assert_eq!(emulator.run_cycles(10), RunStopReason::KeyWait(1));
+        assert_eq!((emulator.program_counter, emulator.key_block), (0x202, 0x0));
+        assert_eq!(lit(&emulator), 0);
+
+        emulator.keys[0xA] = true;
+        emulator.run_cycles(1);
+        emulator.keys[0xA] = false; // FX0A takes it once it's let go
+        // CLS to the JMP, and the FX0A that waits again
+        assert_eq!(emulator.run_cycles(10), RunStopReason::KeyWait(7));
+        assert_eq!(registers(&emulator)[0], 0xA);
+        let font_a = emulator.stack_pointer;
+        assert_eq!(font_a, 5 * 0xA);
+        assert!(drawn_at(&emulator, font_a, 24, 12));
+        assert!(emulator.is_waiting_for_key());
+    }
+
+    #[test]
+    fn stack() {
+        let mut emulator = emulator("stack");
+        emulator.run_cycles(100);
+        assert!(emulator.is_halted());
+        assert_eq!(emulator.program_counter, 0x206);
+        let v = registers(&emulator);
+        assert_eq!((v[0x0], v[0x1]), (0x0C, 0x0C));
+        assert!(emulator.call_stack.is_empty());
+        assert_eq!(emulator.stats.peak_stack_depth, 12);
+    }
+
+    #[test]
+    fn detect() {
+        let mut emulator = emulator("detect");
+        emulator.run_cycles(100);
+        assert!(emulator.is_halted());
+        assert_eq!(emulator.program_counter, 0x23E);
+        let v = registers(&emulator);
+        assert_eq!((v[0xA], v[0xB]), (0x01, 0x48));
+        assert_eq!((v[0x0], v[0x1], v[0xF]), (0x40, 0x02, 0x00));
+        assert_eq!(emulator.stack_pointer, 0x1FF);
+    }
+
+    #[test]
+    fn detect_takes_the_shift_vy_branch_on_the_vip_quirk() {
+        let mut emulator = emulator("detect");
+        emulator.shift_vy = true;
+        emulator.run_cycles(100);
+        assert_eq!(emulator.program_counter, 0x23E);
+        let v = registers(&emulator);
+        assert_eq!((v[0xA], v[0xB]), (0x01, 0x01));
+        assert_eq!((v[0x0], v[0xF]), (0x01, 0x00)); // V1's 2 shifted
+    }
+
+    #[test]
+    fn detect_sees_a_register_that_didnt_start_at_zero() {
+        let mut emulator = emulator("detect");
+        emulator.registers.set(0x7, 0x20);
+        emulator.run_cycles(100);
+        assert_eq!(emulator.program_counter, 0x23E);
+        let v = registers(&emulator);
+        assert_eq!((v[0xA], v[0xB]), (0x00, 0x48));
+    }
+
+    #[test]
+    fn detect_sees_a_font_that_doesnt_start_with_f0() {
+        let mut emulator = emulator("detect");
+        emulator.font_style = FontStyle::Rounded;
+        font::load(&mut emulator.ram, FontStyle::Rounded); // as main does for --font-style
+        assert_ne!(emulator.ram.get(0x000), 0xF0);
+        emulator.run_cycles(100);
+        assert_eq!(emulator.program_counter, 0x23E);
+        let v = registers(&emulator);
+        assert_eq!((v[0xA], v[0xB]), (0x00, 0x48));
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs;
+
+use crate::components::Ram;
+
+// 16 digits, 5 bytes each, at the bottom of RAM where FX29 points
+pub const FONT_SIZE: u16 = 0x50;
+
+// the digits FX29 draws, all built in so the binary runs on its own. standard is the font
+// most interpreters use, from system/font.bin
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+pub enum FontStyle {
+    Standard,
+    Cosmac,  // the COSMAC VIP's own, from its interpreter ROM
+    Rounded, // softer corners, easier to read small
+    Bold,    // the left strokes doubled
+    #[value(skip)]
+    Custom([u8; FONT_SIZE as usize]), // --font
+}
+impl FontStyle {
+    fn glyphs(&self) -> &[u8; FONT_SIZE as usize] {
+        match self {
+            FontStyle::Standard => STANDARD,
+            FontStyle::Cosmac => &COSMAC,
+            FontStyle::Rounded => &ROUNDED,
+            FontStyle::Bold => &BOLD,
+            FontStyle::Custom(glyphs) => glyphs,
+        }
+    }
+}
+
+// --font: a file of exactly the 80 bytes that go at 0x000, laid out like system/font.bin
+pub fn read(path: &str) -> Result<FontStyle, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    let glyphs = data.try_into().map_err(|data: Vec<u8>| {
+        format!("{} bytes, a font is {} (5 for each of 16 digits)", data.len(), FONT_SIZE)
+    })?;
+    Ok(FontStyle::Custom(glyphs))
+}
+
+const STANDARD: &[u8; FONT_SIZE as usize] = include_bytes!("../system/font.bin");
+const COSMAC: [u8; FONT_SIZE as usize] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x60, 0x20, 0x20, 0x20, 0x70, // 0 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0, 0x10, 0xF0, 0x10, 0xF0, // 2 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, 0xF0, 0x80, 0xF0, 0x10, 0xF0, // 4 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x10, 0x10, 0x10, // 6 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0, 0x10, 0xF0, // 8 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, 0xF0, 0x50, 0x70, 0x50, 0xF0, // A B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, 0xF0, 0x50, 0x50, 0x50, 0xF0, // C D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80, // E F
+];
+const ROUNDED: [u8; FONT_SIZE as usize] = [
+    0x60, 0x90, 0x90, 0x90, 0x60, 0x20, 0x60, 0x20, 0x20, 0x70, // 0 1
+    0xE0, 0x10, 0x60, 0x80, 0xF0, 0xE0, 0x10, 0x60, 0x10, 0xE0, // 2 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xE0, 0x10, 0xE0, // 4 5
+    0x60, 0x80, 0xE0, 0x90, 0x60, 0xF0, 0x10, 0x20, 0x40, 0x40, // 6 7
+    0x60, 0x90, 0x60, 0x90, 0x60, 0x60, 0x90, 0x70, 0x10, 0x60, // 8 9
+    0x60, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0, // A B
+    0x70, 0x80, 0x80, 0x80, 0x70, 0xE0, 0x90, 0x90, 0x90, 0xE0, // C D
+    0xF0, 0x80, 0xE0, 0x80, 0xF0, 0xF0, 0x80, 0xE0, 0x80, 0x80, // E F
+];
+const BOLD: [u8; FONT_SIZE as usize] = [
+    0xF0, 0xD0, 0xD0, 0xD0, 0xF0, 0x60, 0xE0, 0x60, 0x60, 0xF0, // 0 1
+    0xF0, 0x30, 0xF0, 0xC0, 0xF0, 0xF0, 0x30, 0x70, 0x30, 0xF0, // 2 3
+    0xD0, 0xD0, 0xF0, 0x30, 0x30, 0xF0, 0xC0, 0xF0, 0x30, 0xF0, // 4 5
+    0xF0, 0xC0, 0xF0, 0xD0, 0xF0, 0xF0, 0x30, 0x30, 0x60, 0x60, // 6 7
+    0xF0, 0xD0, 0xF0, 0xD0, 0xF0, 0xF0, 0xD0, 0xF0, 0x30, 0xF0, // 8 9
+    0xF0, 0xD0, 0xF0, 0xD0, 0xD0, 0xE0, 0xD0, 0xE0, 0xD0, 0xE0, // A B
+    0xF0, 0xC0, 0xC0, 0xC0, 0xF0, 0xE0, 0xD0, 0xD0, 0xD0, 0xE0, // C D
+    0xF0, 0xC0, 0xF0, 0xC0, 0xF0, 0xF0, 0xC0, 0xF0, 0xC0, 0xC0, // E F
+];
+
+// writes `style` into the font area
+pub fn load(ram: &mut Ram, style: FontStyle) {
+    match style {
+        FontStyle::Custom(_) => log::debug!("Loading a custom font"),
+        _ => log::debug!("Loading the {:?} font", style),
+    }
+    ram.load_bytes(0x000, style.glyphs()).expect("the font fits below 0x200");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Emulator;
+
+    #[test]
+    fn a_new_machine_has_the_standard_font_at_0x000() {
+        let mut emulator = Emulator::new();
+        let font = &emulator.ram.as_slice()[..FONT_SIZE as usize];
+        assert_eq!(font, STANDARD);
+        assert_eq!(font[..5], [0xF0, 0x90, 0x90, 0x90, 0xF0]); // 0
+        assert_eq!(font[75..], [0xF0, 0x80, 0xF0, 0x80, 0x80]); // F
+        assert_eq!(emulator.ram.get(FONT_SIZE), 0x00);
+        // where FX29 points
+        emulator.load_rom_bytes(&[0x60, 0x0A, 0xF0, 0x29]).unwrap();
+        emulator.run_cycles(2);
+        assert_eq!(emulator.stack_pointer, 5 * 0xA);
+    }
+
+    #[test]
+    fn a_font_file_has_to_be_80_bytes() {
+        let path = std::env::temp_dir().join(format!("chip8rust-font-{}.bin", std::process::id()));
+        fs::write(&path, [0xFF; 79]).unwrap();
+        let error = read(&path.to_string_lossy()).unwrap_err();
+        assert_eq!(error, "79 bytes, a font is 80 (5 for each of 16 digits)");
+        fs::write(&path, [0xFF; 80]).unwrap();
+        assert_eq!(read(&path.to_string_lossy()), Ok(FontStyle::Custom([0xFF; 80])));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,192 @@
+use std::time::Instant;
+
+use crate::run_state::RunState;
+
+// what some host time adds up to. only one of frames and cycles is ever set
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub struct Due {
+    pub frames: u32,      // frame-locked: frames of a fixed cycle count and a timer tick each
+    pub cycles: u32,      // free-running: cycles, one at a time
+    pub timer_ticks: u32, // the timers going on their own, when they aren't run in frames
+    pub capped: bool,     // more was owed than the cap let through
+}
+impl Due {
+    // a 60Hz frame has gone by, so there's a new one to show
+    pub fn present(&self) -> bool {
+        self.frames > 0 || self.timer_ticks > 0
+    }
+}
+
+// when the machine's work falls due: cycles at the cycle speed, or with --ipf whole 60Hz
+// frames, and the timers at 60Hz. it only keeps the time, running what's due is up to the
+// frontend, so the window, the terminal and anything else that has a loop all pace the same
+#[derive(Debug, Clone)]
+pub struct FrameDriver {
+    time_per_cycle: f32, // seconds, 0 for as many as the cap allows
+    frame_locked: bool,
+    pub max_delta: f32, // seconds
+    pub catch_up: bool, // make up frames that ran late, when frame-locked
+    cycle_time: f32,
+    timer_time: f32,
+    last: Option<Instant>, // for advance_to
+}
+impl FrameDriver {
+    pub fn new(time_per_cycle: f32, frame_locked: bool) -> FrameDriver {
+        FrameDriver {
+            time_per_cycle,
+            frame_locked,
+            max_delta: 0.1,
+            catch_up: true,
+            cycle_time: 0.0,
+            timer_time: 0.0,
+            last: None,
+        }
+    }
+    // the same limits, counting from nothing in 60Hz frames, for the frontends that run a
+    // fixed number of cycles a frame
+    pub fn in_frames(&self) -> FrameDriver {
+        FrameDriver {
+            max_delta: self.max_delta,
+            catch_up: self.catch_up,
+            ..FrameDriver::new(self.time_per_cycle, true)
+        }
+    }
+    pub fn reset(&mut self) {
+        self.cycle_time = 0.0;
+        self.timer_time = 0.0;
+    }
+    // a stalled frame (dragging the window, say) comes back as one huge delta
+    pub fn clamp(&self, delta: f32) -> f32 {
+        delta.min(self.max_delta)
+    }
+    // `delta` seconds more of host time, `cap` being as many frames or cycles as fit in one
+    // host frame. what's owed past the cap is dropped instead of piling up
+    pub fn advance(&mut self, delta: f32, state: &RunState, cap: u32) -> Due {
+        let delta = self.clamp(delta);
+        let mut due = Due::default();
+        if self.frame_locked && state.runs_cycles() {
+            // whole frames of the cycles and one timer tick, so how long the host's frames
+            // take only changes when they run
+            self.timer_time += delta;
+            let owed = (self.timer_time * 60.0) as u32;
+            if owed > 0 {
+                due.frames = if self.catch_up { owed.min(cap) } else { 1 };
+                due.capped = owed > cap;
+                self.timer_time -= due.frames as f32 / 60.0;
+                if owed > due.frames {
+                    self.timer_time = 0.0; // frames that were missed stay missed
+                }
+            }
+            return due;
+        }
+        if state.ticks_timers() {
+            self.timer_time += delta;
+            if self.timer_time >= 1.0 / 60.0 {
+                due.timer_ticks = 1;
+                self.timer_time = 0.0;
+            }
+        }
+        if !self.frame_locked && state.runs_cycles() {
+            self.cycle_time += delta;
+            let owed = (self.cycle_time / self.time_per_cycle) as u32;
+            due.cycles = owed.min(cap);
+            due.capped = owed > cap;
+            if due.capped {
+                self.cycle_time = 0.0;
+            } else {
+                self.cycle_time -= owed as f32 * self.time_per_cycle;
+            }
+        }
+        due
+    }
+    // the same, timed from the last call (the first one is owed nothing)
+    pub fn advance_to(&mut self, now: Instant, state: &RunState, cap: u32) -> Due {
+        let delta = self.last.map_or(0.0, |last| now.duration_since(last).as_secs_f32());
+        self.last = Some(now);
+        self.advance(delta, state, cap)
+    }
+    // the cycles that were due didn't all run (a halt, a key wait): the rest aren't owed
+    pub fn stopped(&mut self) {
+        self.cycle_time = 0.0;
+    }
+    // stepping: a timer tick every 10 steps, 600 steps a second's worth
+    pub fn step_timer(&mut self) -> bool {
+        self.timer_time += 1.0 / 600.0;
+        if self.timer_time < 1.0 / 60.0 {
+            return false;
+        }
+        self.timer_time = 0.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Emulator, RunMode};
+
+    const PLAY: RunState = RunState::Play;
+
+    // --ipf: however the host's frames fall, a frame is that many cycles and a timer tick
+    #[test]
+    fn frame_locked_runs_whole_frames_of_cycles() {
+        let mut emulator = Emulator::new();
+        emulator.state = RunMode::Play.into();
+        emulator.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap(); // V0 += 1 forever
+        emulator.sound_timer = 0xFF;
+        let mut driver = FrameDriver::new(0.0, true);
+        let mut frames = Vec::new();
+        for delta in [0.005, 0.02, 0.0, 0.03, 0.001, 0.02] {
+            let due = driver.advance(delta, &PLAY, u32::MAX);
+            assert_eq!((due.cycles, due.timer_ticks), (0, 0)); // all of it in the frames
+            for _ in 0..due.frames {
+                emulator.step_frame(7);
+            }
+            frames.push(due.frames);
+        }
+        assert_eq!(frames, [0, 1, 0, 2, 0, 1]);
+        assert_eq!(emulator.stats.instructions, 4 * 7);
+        assert_eq!(emulator.sound_timer, 0xFF - 4);
+    }
+
+    #[test]
+    fn a_long_stall_is_clamped() {
+        let mut locked = FrameDriver::new(0.0, true);
+        assert_eq!(locked.advance(5.0, &PLAY, u32::MAX).frames, 6); // max_delta, 0.1s
+        let mut free = FrameDriver::new(1.0 / 1000.0, false);
+        let due = free.advance(5.0, &PLAY, u32::MAX);
+        assert!((99..=100).contains(&due.cycles), "{} cycles", due.cycles);
+        assert_eq!(due.timer_ticks, 1); // not 300
+        assert!(!due.capped);
+    }
+
+    #[test]
+    fn without_catch_up_a_late_frame_is_only_one() {
+        let mut driver = FrameDriver::new(0.0, true);
+        driver.catch_up = false;
+        assert_eq!(driver.advance(0.09, &PLAY, u32::MAX).frames, 1);
+        assert_eq!(driver.advance(0.0, &PLAY, u32::MAX).frames, 0); // the rest are dropped
+    }
+
+    #[test]
+    fn past_the_cap_is_dropped() {
+        let mut locked = FrameDriver::new(0.0, true);
+        let due = locked.advance(0.09, &PLAY, 2);
+        assert_eq!((due.frames, due.capped), (2, true));
+        assert_eq!(locked.advance(0.0, &PLAY, 2).frames, 0);
+
+        let mut free = FrameDriver::new(1.0 / 1000.0, false);
+        let due = free.advance(0.05, &PLAY, 10);
+        assert_eq!((due.cycles, due.capped), (10, true));
+        assert_eq!(free.advance(0.0, &PLAY, 10).cycles, 0);
+    }
+
+    #[test]
+    fn only_the_timers_go_on_through_a_key_wait() {
+        let waiting = RunState::WaitingForKey(RunMode::Play);
+        let mut driver = FrameDriver::new(1.0 / 1000.0, false);
+        let due = driver.advance(0.02, &waiting, u32::MAX);
+        assert_eq!((due.cycles, due.timer_ticks), (0, 1));
+        assert_eq!(driver.advance(0.02, &RunState::Step, u32::MAX), Due::default());
+    }
+}
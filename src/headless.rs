@@ -0,0 +1,468 @@
+use crate::pause_at::PauseAt;
+use crate::run_state::{Event, Halt};
+use crate::state_hash::Fnv;
+use crate::{Emulator, RunMode, SCR_W};
+
+// why run_cycles stopped, with how many cycles it got through when it stopped early
+#[derive(PartialEq, Debug)]
+pub enum RunStopReason {
+    Finished,
+    Halted(Halt, u32),
+    KeyWait(u32), // an FX0A with no key held
+}
+
+// what happened on a frame that ran to its end
+#[derive(PartialEq, Debug, Default)]
+pub struct FrameEnd {
+    pub key_wait_expired: bool,     // a key wait ran past --keywait-timeout on this frame
+    pub paused_at: Option<PauseAt>, // what cut it short, the machine back in Step
+    pub display_changed: bool,      // the mode isn't the one the last frame ended in
+}
+
+// how far step_frame_budgeted got
+#[derive(PartialEq, Debug)]
+pub enum FrameProgress {
+    Done(FrameEnd),
+    Remaining(u32), // cycles of the frame still to run, on the next call
+}
+
+impl Emulator {
+    // how many cycles make up one 60Hz frame at the given speed
+    pub fn cycles_per_frame(cycle_speed: f32) -> u32 {
+        (cycle_speed / 60.0).round().max(1.0) as u32
+    }
+    // run one 60Hz frame without a window: a frame's worth of cycles, then one timer tick
+    pub fn step_frame(&mut self, cycles_per_frame: u32) -> FrameEnd {
+        match self.step_frame_budgeted(cycles_per_frame, u32::MAX) {
+            FrameProgress::Done(end) => end,
+            FrameProgress::Remaining(_) => unreachable!("a frame has fewer cycles than that"),
+        }
+    }
+    // step_frame running at most `budget` cycles, for a host that can't give a whole frame
+    // the time in one go. a frame cut short picks up where it stopped on the next call, and
+    // its timer tick comes once, when it's done. --pause-at going off ends the frame there
+    pub fn step_frame_budgeted(&mut self, cycles_per_frame: u32, budget: u32) -> FrameProgress {
+        let mut ran = 0;
+        while self.frame_cycles < cycles_per_frame {
+            if ran == budget {
+                return FrameProgress::Remaining(cycles_per_frame - self.frame_cycles);
+            }
+            if !self.resolve_key_block() {
+                break; // the rest of the frame is spent waiting for a key
+            }
+            self.cycle();
+            self.frame_cycles += 1;
+            ran += 1;
+            if self.halt.is_some() || self.paused_at.is_some() || self.display_waiting {
+                break; // a display wait spends the rest of the frame waiting
+            }
+        }
+        self.frame_cycles = 0;
+        self.tick_timers();
+        self.stats.frames += 1;
+        let paused_at = self.paused_at.take();
+        if paused_at.is_some() {
+            self.state = self.state.clone().on(Event::SetMode(RunMode::Step));
+        }
+        FrameProgress::Done(FrameEnd {
+            key_wait_expired: self.track_key_wait(1.0 / 60.0),
+            paused_at,
+            display_changed: self.take_display_change(),
+        })
+    }
+    // up to n cycles at the stepping pace (a timer tick every 10), stopping early on a halt
+    // or a key wait. a step under a display wait only moves the timers on. the summary is the
+    // last cycle's
+    pub fn run_cycles(&mut self, n: u32) -> RunStopReason {
+        for ran in 0..n {
+            if !self.resolve_key_block() {
+                return RunStopReason::KeyWait(ran);
+            }
+            self.step_timers();
+            if self.display_waiting {
+                continue;
+            }
+            self.summary = self.cycle().1;
+            if let Some(halt) = self.halt {
+                return RunStopReason::Halted(halt, ran + 1);
+            }
+        }
+        RunStopReason::Finished
+    }
+    // times the pending FX0A, returns true the moment it's been waiting longer than the
+    // timeout. the timer starts again with the next wait
+    pub fn track_key_wait(&mut self, delta: f32) -> bool {
+        if !self.is_waiting_for_key() {
+            self.key_wait_time = 0.0;
+            self.key_wait_expired = false;
+            return false;
+        }
+        self.key_wait_time += delta;
+        let timeout = self.keywait_timeout.unwrap_or(f32::INFINITY);
+        if self.key_wait_expired || self.key_wait_time < timeout {
+            return false;
+        }
+        self.key_wait_expired = true;
+        log::info!(
+            "No key for R{:1X} after {:.1}s at {:#05X}",
+            self.key_block,
+            self.key_wait_time,
+            self.program_counter
+        );
+        true
+    }
+    // a program that jumps to itself (1NNN with NNN == PC) is done for good, as is
+    // one that stopped on a halt
+    pub fn is_halted(&mut self) -> bool {
+        if self.halt.is_some() {
+            return true;
+        }
+        self.ram.get_u16(self.program_counter) == 0x1000 | self.program_counter
+    }
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_block != 0x10
+    }
+    // the display row by row, 8 pixels per byte (MSB = leftmost), all 64 rows for hires
+    pub fn packed_display(&self) -> Vec<u8> {
+        let mut packed = vec![0u8; SCR_W * self.screen_height() / 8];
+        for y in 0..self.screen_height() {
+            for x in 0..SCR_W {
+                if self.display[x][y] {
+                    packed[(y * SCR_W + x) / 8] |= 1 << (7 - x % 8);
+                }
+            }
+        }
+        packed
+    }
+    // the display as text, a line per row with # for a lit pixel, for printing or comparing
+    pub fn text_display(&self) -> String {
+        let mut text = String::new();
+        for y in 0..self.screen_height() {
+            text.extend((0..SCR_W).map(|x| if self.display[x][y] { '#' } else { '.' }));
+            text.push('\n');
+        }
+        text
+    }
+    // display_hash, worked out again only after the display changes
+    pub fn frame_hash(&mut self) -> u64 {
+        match self.frame_hash {
+            Some(hash) => hash,
+            None => {
+                let hash = self.display_hash();
+                self.frame_hash = Some(hash);
+                hash
+            }
+        }
+    }
+    // FNV-1a over the packed display, so the value stays the same across platforms and versions
+    pub fn display_hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write(&self.packed_display());
+        hash.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code_guard::{CodeGuard, GuardPc};
+    use crate::hires::{DisplayMode, Platform};
+
+    const CYCLES_PER_FRAME: u32 = 100;
+    // V0 = 5, V0 += 1, a draw, a key check, then a jump to itself
+    const ROM: [u8; 10] = [0x60, 0x05, 0x70, 0x01, 0xD0, 0x05, 0xE0, 0x9E, 0x12, 0x08];
+
+    // the frame stops where the trigger went off, in Step, and the next runs a whole frame
+    fn pauses(trigger: PauseAt, at: u16) {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&ROM).unwrap();
+        emulator.arm_pause_at(trigger);
+        let end = emulator.step_frame(CYCLES_PER_FRAME);
+        assert_eq!(end.paused_at, Some(trigger));
+        assert_eq!(emulator.program_counter, at);
+        assert_eq!(emulator.state.mode(), RunMode::Step);
+        assert_eq!(emulator.paused_at, None);
+
+        let before = emulator.stats.instructions;
+        assert_eq!(emulator.step_frame(CYCLES_PER_FRAME), FrameEnd::default());
+        assert_eq!(emulator.stats.instructions - before, CYCLES_PER_FRAME as u64);
+    }
+
+    #[test]
+    fn pauses_at_the_first_draw() {
+        pauses(PauseAt::FirstDraw, 0x206);
+    }
+
+    #[test]
+    fn pauses_at_the_first_input() {
+        pauses(PauseAt::FirstInput, 0x208);
+    }
+
+    #[test]
+    fn pauses_at_an_address() {
+        pauses(PauseAt::Addr(0x204), 0x204);
+    }
+
+    #[test]
+    fn pauses_at_a_cycle() {
+        pauses(PauseAt::Cycle(3), 0x206);
+    }
+
+    #[test]
+    fn pauses_at_a_write_over_the_code() {
+        // I = 0x206, and V0 stored there, just ahead of the PC
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[0xA2, 0x06, 0xF0, 0x55, 0x12, 0x04]).unwrap();
+        emulator.code_guard = Some(CodeGuard::new(GuardPc::Pause, 4));
+        let end = emulator.step_frame(CYCLES_PER_FRAME);
+        assert_eq!(end.paused_at, Some(PauseAt::CodeWrite { by: 0x202, addr: 0x206 }));
+        assert_eq!(emulator.program_counter, 0x204);
+        assert_eq!(emulator.state.mode(), RunMode::Step);
+    }
+
+    #[test]
+    fn the_frame_a_rom_changes_the_display_on_says_so() {
+        let mut emulator = Emulator::new();
+        emulator.platform = Platform::HiresChip8;
+        assert_eq!(emulator.display_size(), (64, 32));
+        assert!(!emulator.step_frame(CYCLES_PER_FRAME).display_changed);
+
+        emulator.load_rom_bytes(&[0x12, 0x60]).unwrap(); // the hires signature
+        assert_eq!(emulator.display_mode(), DisplayMode::TwoPage);
+        assert_eq!(emulator.display_size(), (64, 64));
+        assert!(emulator.step_frame(CYCLES_PER_FRAME).display_changed);
+        assert!(!emulator.step_frame(CYCLES_PER_FRAME).display_changed);
+
+        emulator.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        assert_eq!(emulator.display_mode(), DisplayMode::Lores);
+        assert_eq!(emulator.display_size(), (64, 32));
+        assert!(emulator.step_frame(CYCLES_PER_FRAME).display_changed);
+        assert!(!emulator.step_frame(CYCLES_PER_FRAME).display_changed);
+    }
+
+    #[test]
+    fn without_a_trigger_the_frame_runs_to_the_end() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&ROM).unwrap();
+        emulator.pause_at = Some(PauseAt::FirstDraw); // not armed, so the machine is in Step
+        assert_eq!(emulator.step_frame(CYCLES_PER_FRAME), FrameEnd::default());
+        assert_eq!(emulator.stats.instructions, CYCLES_PER_FRAME as u64);
+    }
+
+    #[test]
+    fn a_frame_split_three_ways_ends_where_a_whole_one_does() {
+        // DT = 5, then V1 += 1 forever
+        let rom = [0x60, 0x05, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04];
+        let mut whole = Emulator::new();
+        let mut split = Emulator::new();
+        for emulator in [&mut whole, &mut split] {
+            emulator.load_rom_bytes(&rom).unwrap();
+        }
+        whole.step_frame(CYCLES_PER_FRAME);
+
+        let progress = split.step_frame_budgeted(CYCLES_PER_FRAME, 40);
+        assert_eq!(progress, FrameProgress::Remaining(60));
+        assert_eq!(split.step_frame_budgeted(CYCLES_PER_FRAME, 40), FrameProgress::Remaining(20));
+        assert_eq!(split.timer, 5); // no tick until the frame's done
+        assert!(matches!(split.step_frame_budgeted(CYCLES_PER_FRAME, 40), FrameProgress::Done(_)));
+
+        assert_eq!(split.timer, 4); // and then only the one
+        assert_eq!(split.state_eq(&whole), Ok(()));
+        assert_eq!(split.stats.instructions, whole.stats.instructions);
+        assert_eq!(split.stats.frames, 1);
+    }
+
+    #[test]
+    fn run_cycles_finishes_all_n() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap(); // V0 += 1 forever
+        assert_eq!(emulator.run_cycles(25), RunStopReason::Finished);
+        assert_eq!(emulator.stats.instructions, 25);
+        assert_eq!(emulator.registers.get(0x0), 13);
+    }
+
+    #[test]
+    fn run_cycles_stops_on_a_key_wait() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[0x60, 0x01, 0xF1, 0x0A, 0x12, 0x00]).unwrap();
+        // the FX0A counts, it ran and started the wait
+        assert_eq!(emulator.run_cycles(10), RunStopReason::KeyWait(2));
+        assert_eq!(emulator.run_cycles(10), RunStopReason::KeyWait(0));
+        assert_eq!(emulator.stats.instructions, 2);
+    }
+
+    #[test]
+    fn run_cycles_stops_on_a_halt() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[0x60, 0x01, 0x70, 0x01, 0x00, 0xEE]).unwrap();
+        let stopped = emulator.run_cycles(10);
+        assert_eq!(stopped, RunStopReason::Halted(Halt::StackUnderflow(0x204), 3));
+
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[0x00, 0xFD]).unwrap();
+        assert_eq!(emulator.run_cycles(10), RunStopReason::Halted(Halt::Exit(0x200), 1));
+    }
+
+    // pinned, so a change to the hash or the packing shows up here before it does in every
+    // batch report compared against
+    #[test]
+    fn the_display_hash_is_pinned() {
+        let mut emulator = Emulator::new();
+        assert_eq!(emulator.display_hash(), 0xD80A_C658_736B_B725); // 256 zero bytes
+        emulator.load_rom_bytes(crate::fixtures::find("ibm").unwrap().rom).unwrap();
+        emulator.run_cycles(100);
+        assert_eq!(emulator.display_hash(), 0x951C_B69A_3973_1067);
+        assert_eq!(emulator.frame_hash(), emulator.display_hash());
+    }
+
+    #[test]
+    fn a_delay_of_n_is_n_frames() {
+        for n in [1, 2, 5, 60] {
+            // DT = n, then wait on it, then jump to itself at 0x20A
+            let rom = [0x60, n, 0xF0, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x04, 0x12, 0x0A];
+            let mut emulator = Emulator::new();
+            emulator.load_rom_bytes(&rom).unwrap();
+            let mut frames = 0;
+            loop {
+                emulator.step_frame(CYCLES_PER_FRAME);
+                frames += 1;
+                if emulator.timer == 0 {
+                    break;
+                }
+                assert_eq!(emulator.timer, n - frames);
+            }
+            assert_eq!(frames, n);
+            // the next frame reads the 0 and gets out
+            assert_ne!(emulator.program_counter, 0x20A);
+            emulator.step_frame(CYCLES_PER_FRAME);
+            assert_eq!(emulator.program_counter, 0x20A);
+        }
+    }
+
+    // a 3, then FX0A, then the key's digit beside it, then a jump to itself
+    const DRAW_AND_WAIT: [u8; 16] = [
+        0x61, 0x03, 0xF1, 0x29, 0xD0, 0x05, 0xF2, 0x0A, 0xF2, 0x29, 0x63, 0x08, 0xD3, 0x05, 0x12,
+        0x0E,
+    ];
+
+    // A held on frame 3 and let go on 4, for FX0A to take
+    fn hold_a(emulator: &mut Emulator, frame: usize) {
+        emulator.keys[0xA] = frame == 3;
+    }
+
+    #[test]
+    fn stepping_through_a_display_wait_and_a_key_wait_ends_where_playing_does() {
+        let machine = || {
+            let mut emulator = Emulator::new();
+            emulator.display_wait = true;
+            emulator.load_rom_bytes(&DRAW_AND_WAIT).unwrap();
+            emulator
+        };
+        let mut played = machine();
+        played.state = RunMode::Play.into();
+        for frame in 0..8 {
+            hold_a(&mut played, frame);
+            played.step_frame(10);
+            if frame == 0 {
+                assert_eq!(played.program_counter, 0x206); // the draw ended the frame
+            }
+        }
+
+        let mut stepped = machine();
+        for frame in 0..8 {
+            hold_a(&mut stepped, frame);
+            for _ in 0..10 {
+                stepped.run_cycles(1); // a Tab press
+            }
+        }
+        assert_eq!(stepped.program_counter, 0x20E);
+        assert_eq!(stepped.registers.as_slice()[2], 0xA);
+        assert_eq!(stepped.state_eq(&played), Ok(()));
+    }
+
+    // once for each FX0A that goes unanswered, however long it then goes on
+    #[test]
+    fn the_key_wait_timeout_fires_once_a_wait() {
+        let mut emulator = Emulator::new();
+        emulator.state = RunMode::Play.into();
+        emulator.keywait_timeout = Some(0.5);
+        emulator.load_rom_bytes(&[0xF3, 0x0A, 0x12, 0x00]).unwrap();
+        let expired = |emulator: &mut Emulator| {
+            let ends: Vec<bool> =
+                (0..90).map(|_| emulator.step_frame(10).key_wait_expired).collect();
+            (ends.iter().filter(|&&end| end).count(), ends.iter().position(|&end| end))
+        };
+        let (times, first) = expired(&mut emulator);
+        assert_eq!(times, 1);
+        assert!(matches!(first, Some(29..=31)), "after {:?} frames", first);
+        assert!(emulator.key_wait_expired);
+
+        // a key, and the loop back to the FX0A waits all over again
+        emulator.keys[0x7] = true;
+        emulator.step_frame(10);
+        emulator.keys[0x7] = false;
+        emulator.step_frame(10);
+        assert_eq!(emulator.registers.get(0x3), 0x7);
+        assert_eq!(expired(&mut emulator).0, 1);
+    }
+
+    #[test]
+    fn without_a_timeout_a_key_wait_doesnt_expire() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[0xF3, 0x0A]).unwrap();
+        emulator.run_cycles(1);
+        assert!(!emulator.track_key_wait(1e6));
+        emulator.keywait_timeout = Some(0.5);
+        assert!(emulator.track_key_wait(0.25)); // the wait so far counts
+        assert!(!emulator.track_key_wait(0.25));
+    }
+
+    // two key waits, into V5 and V6, then a jump to itself
+    const TWO_WAITS: [u8; 6] = [0xF5, 0x0A, 0xF6, 0x0A, 0x12, 0x04];
+
+    // a frame for each set of keys held, the wait still going on after
+    fn waits_through(frames: &[&[usize]]) -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.state = RunMode::Play.into();
+        emulator.load_rom_bytes(&TWO_WAITS).unwrap();
+        for held in frames {
+            emulator.keys = [false; 0x10];
+            for &key in *held {
+                emulator.keys[key] = true;
+            }
+            emulator.step_frame(CYCLES_PER_FRAME);
+        }
+        emulator
+    }
+
+    #[test]
+    fn a_key_held_when_the_wait_starts_has_to_be_pressed_again() {
+        let mut emulator = waits_through(&[&[3], &[]]);
+        assert_eq!(emulator.program_counter, 0x202); // letting go of it isn't a press
+        emulator = waits_through(&[&[3], &[], &[3], &[]]);
+        assert_eq!(emulator.program_counter, 0x204);
+        assert_eq!(emulator.registers.get(0x5), 3);
+    }
+
+    #[test]
+    fn the_first_key_let_go_is_taken_not_the_lowest_held() {
+        let mut emulator = waits_through(&[&[], &[2], &[2, 9], &[2]]);
+        assert_eq!(emulator.registers.get(0x5), 9);
+        assert!(emulator.is_waiting_for_key());
+        // 2 was down when the second wait began, so letting go of it doesn't count
+        emulator = waits_through(&[&[], &[2], &[2, 9], &[2], &[]]);
+        assert!(emulator.is_waiting_for_key());
+        emulator = waits_through(&[&[], &[2], &[2, 9], &[2], &[], &[2], &[]]);
+        assert!(!emulator.is_waiting_for_key());
+        assert_eq!(emulator.registers.get(0x6), 2);
+    }
+
+    #[test]
+    fn keys_let_go_together_go_to_the_lowest() {
+        let mut emulator = waits_through(&[&[], &[9], &[9, 2], &[]]);
+        assert_eq!(emulator.registers.get(0x5), 2);
+        // the wait resolved once, and 9 going up with it doesn't answer the next one
+        assert!(emulator.is_waiting_for_key());
+        assert_eq!(emulator.registers.get(0x6), 0);
+    }
+}
@@ -0,0 +1,65 @@
+use olc_pge as olc;
+
+use crate::hotkeys::{key_name, Hotkeys};
+use crate::version::VersionInfo;
+use crate::window::{DRAW_BIGGER_PIXELS, KEYS};
+use crate::{Emulator, SCR_H};
+
+// the keypad as it's laid out on the original hardware
+const KEYPAD: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+const LINE_HEIGHT: i32 = 8;
+
+// the help overlay's text, from the bindings actually in use. a pad key left out of
+// --available-keys has no keyboard key, since pressing it does nothing
+pub fn help_lines(
+    rom_name: &str,
+    keys: &[olc::Key; 0x10],
+    available: &[bool; 0x10],
+    hotkeys: &Hotkeys,
+) -> Vec<String> {
+    let version = VersionInfo::current();
+    let mut lines = vec![format!("ROM: {}", rom_name), version.summary()];
+    for (i, features) in version.feature_lines(3).iter().enumerate() {
+        let label = if i == 0 { "FEATURES:" } else { "" };
+        lines.push(format!("{:9} {}", label, features));
+    }
+    lines.push(String::new());
+    lines.push(String::from("KEYPAD    KEYBOARD"));
+    for row in KEYPAD {
+        let keypad: Vec<String> = row.iter().map(|key| format!("{:X}", key)).collect();
+        let keyboard: Vec<String> = row
+            .iter()
+            .map(|key| if available[*key] { key_name(keys[*key]) } else { String::from("-") })
+            .collect();
+        lines.push(format!("{}   {}", keypad.join(" "), keyboard.join(" ")));
+    }
+    lines.push(String::new());
+    let bindings = hotkeys.bindings();
+    let width = bindings.iter().map(|(keys, _)| keys.len()).max().unwrap_or(0);
+    for (keys, action) in bindings {
+        lines.push(format!("{:width$}  {}", keys, action, width = width));
+    }
+    lines
+}
+
+// the help modal, drawn over the dimmed window. lines that don't fit above the status
+// line carry on in another column
+pub fn render(emulator: &Emulator, pge: &mut olc::PixelGameEngine) {
+    let available = &emulator.available_keys;
+    let lines = help_lines(&emulator.rom_name, &KEYS, available, &emulator.hotkeys);
+    let bottom = (SCR_H + SCR_H / 2) as i32 * DRAW_BIGGER_PIXELS - 8 - LINE_HEIGHT;
+    let per_column = ((bottom - 8) / LINE_HEIGHT + 1) as usize;
+    let mut x = 8;
+    for column in lines.chunks(per_column) {
+        for (i, line) in column.iter().enumerate() {
+            pge.draw_string(x, 8 + i as i32 * LINE_HEIGHT, line, olc::WHITE);
+        }
+        let width = column.iter().map(|line| line.len()).max().unwrap_or(0);
+        x += (width as i32 + 2) * 8;
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{Emulator, SCR_H, SCR_W};
+
+// the VIP's two-page hires interpreter: a ROM starting with a jump to 0x260 (its patch to
+// the interpreter) gets a 64x64 display, and runs from 0x2C0 with 0230 clearing the screen
+pub const HIRES_SIGNATURE: u16 = 0x1260;
+pub const HIRES_START: u16 = 0x2C0;
+pub const HIRES_H: usize = 64;
+
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+pub enum Platform {
+    Chip8,
+    HiresChip8, // the 64x64 two-page ROMs, not SUPER-CHIP's hires
+}
+
+// what the display is right now, which a frontend sizes its view by
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DisplayMode {
+    Lores,   // 64x32
+    TwoPage, // the 64x64 hires one
+}
+
+pub fn has_signature(rom: &[u8]) -> bool {
+    rom.len() >= 2 && u16::from_be_bytes([rom[0], rom[1]]) == HIRES_SIGNATURE
+}
+
+impl Emulator {
+    // from load, which ROMs get the tall display
+    pub fn detect_hires(&mut self, rom: &[u8]) {
+        let signature = has_signature(rom);
+        self.hires = signature && self.platform == Platform::HiresChip8;
+        match self.platform {
+            Platform::Chip8 if signature => {
+                log::info!("This looks like a 64x64 hires ROM, try --platform hires-chip8");
+            }
+            Platform::HiresChip8 if !signature => {
+                log::warn!("No {:04X} at 0x200, running it as a 64x32 ROM", HIRES_SIGNATURE);
+            }
+            _ => {}
+        }
+        self.program_counter = self.start_address();
+    }
+    pub fn start_address(&self) -> u16 {
+        if self.hires {
+            HIRES_START
+        } else {
+            0x200
+        }
+    }
+    // rows on the display, the window keeps the same size by halving the pixels' height
+    pub fn screen_height(&self) -> usize {
+        if self.hires {
+            HIRES_H
+        } else {
+            SCR_H
+        }
+    }
+    pub fn display_mode(&self) -> DisplayMode {
+        if self.hires {
+            DisplayMode::TwoPage
+        } else {
+            DisplayMode::Lores
+        }
+    }
+    // width and height, in pixels
+    pub fn display_size(&self) -> (usize, usize) {
+        (SCR_W, self.screen_height())
+    }
+    // whether the mode is different from the last time this was asked, for a frontend to
+    // resize its view. step_frame asks at the end of every frame
+    pub fn take_display_change(&mut self) -> bool {
+        let changed = self.display_mode() != self.reported_mode;
+        self.reported_mode = self.display_mode();
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the signature, then from 0x2C0 a 0 drawn on the lower page and one across the bottom
+    // edge, and a 0230 to clear it all
+    fn two_page_rom() -> Vec<u8> {
+        let mut rom = vec![0; (HIRES_START - 0x200) as usize];
+        rom[..2].copy_from_slice(&HIRES_SIGNATURE.to_be_bytes());
+        let program = [
+            0x61, 0x28, 0xD0, 0x15, 0x61, 0x3E, 0x62, 0x08, 0xD2, 0x15, 0x02, 0x30, 0x12, 0xCC,
+        ];
+        rom.extend_from_slice(&program);
+        rom
+    }
+
+    fn lit_rows(emulator: &Emulator, x: usize) -> Vec<usize> {
+        (0..HIRES_H).filter(|&y| emulator.display[x][y]).collect()
+    }
+
+    #[test]
+    fn only_the_hires_platform_takes_the_signature() {
+        assert!(has_signature(&[0x12, 0x60, 0x00]) && !has_signature(&[0x12]));
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&two_page_rom()).unwrap();
+        assert_eq!((emulator.screen_height(), emulator.program_counter), (SCR_H, 0x200));
+        emulator.platform = Platform::HiresChip8;
+        emulator.load_rom_bytes(&two_page_rom()).unwrap();
+        assert_eq!((emulator.screen_height(), emulator.program_counter), (HIRES_H, HIRES_START));
+    }
+
+    #[test]
+    fn draws_go_down_both_pages_and_0230_clears_them() {
+        let mut emulator = Emulator::new();
+        emulator.platform = Platform::HiresChip8;
+        emulator.load_rom_bytes(&two_page_rom()).unwrap();
+        emulator.run_cycles(5);
+        assert_eq!(lit_rows(&emulator, 0), [40, 41, 42, 43, 44]);
+        assert_eq!(lit_rows(&emulator, 8), [0, 1, 2, 62, 63]); // wrapped at 64, not 32
+        assert_eq!(emulator.registers.get(0xF), 0);
+        emulator.run_cycles(1);
+        assert!(emulator.display.iter().flatten().all(|&on| !on));
+    }
+}
@@ -0,0 +1,201 @@
+use std::fmt;
+use std::fs;
+use std::time::SystemTime;
+
+use crate::rom_source;
+use crate::status::NOTICE_TIME;
+use crate::Emulator;
+
+// seconds between looks at the ROM file's modification time
+const POLL_TIME: f32 = 0.5;
+
+// why a rebuilt ROM can't just be patched in, and gets a full reset instead
+#[derive(PartialEq, Debug)]
+pub enum Fallback {
+    Resized { from: usize, to: usize },
+    UnderPc(u16), // the instruction at the PC is one that changed
+}
+impl fmt::Display for Fallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fallback::Resized { from, to } => {
+                write!(f, "the ROM went from {} to {} bytes", from, to)
+            }
+            Fallback::UnderPc(pc) => write!(f, "the code at the PC ({:#05X}) changed", pc),
+        }
+    }
+}
+
+// the addresses of the bytes that differ between the ROM as it was loaded and as it was
+// rebuilt, when they can be patched in under a program sitting at `pc`
+pub fn changed(old: &[u8], new: &[u8], pc: u16) -> Result<Vec<u16>, Fallback> {
+    if old.len() != new.len() {
+        return Err(Fallback::Resized {
+            from: old.len(),
+            to: new.len(),
+        });
+    }
+    let changed: Vec<u16> = old
+        .iter()
+        .zip(new)
+        .enumerate()
+        .filter(|(_, (old, new))| old != new)
+        .map(|(i, _)| 0x200 + i as u16)
+        .collect();
+    if changed.iter().any(|addr| *addr == pc || *addr == pc.wrapping_add(1)) {
+        return Err(Fallback::UnderPc(pc));
+    }
+    Ok(changed)
+}
+
+// --hot-patch: watches the ROM file, and when it's rebuilt patches the bytes that changed
+// into RAM, leaving the registers, timers and display as they are, like Octo's reload
+pub struct HotPatch {
+    source: String, // the ROM as rom_source reads it
+    path: String,   // the file that's watched, the archive for an entry in one
+    modified: Option<SystemTime>,
+    pending: Option<SystemTime>, // a change from the last poll, left a poll to finish writing
+    since_poll: f32,             // seconds
+}
+impl HotPatch {
+    pub fn new(source: &str) -> HotPatch {
+        let path = source.split_once('#').map_or(source, |(path, _)| path).to_string();
+        let mut hot_patch = HotPatch {
+            source: source.to_string(),
+            path,
+            modified: None,
+            pending: None,
+            since_poll: 0.0,
+        };
+        hot_patch.modified = hot_patch.modified();
+        hot_patch
+    }
+    fn modified(&self) -> Option<SystemTime> {
+        fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok()
+    }
+    // after `delta` more seconds, whether the file changed and then stayed that way a poll
+    fn poll(&mut self, delta: f32) -> bool {
+        self.since_poll += delta;
+        if self.since_poll < POLL_TIME {
+            return false;
+        }
+        self.since_poll = 0.0;
+        let modified = self.modified();
+        if modified == self.modified {
+            self.pending = None;
+            return false;
+        }
+        if self.pending != modified {
+            self.pending = modified;
+            return false;
+        }
+        self.modified = modified;
+        self.pending = None;
+        true
+    }
+}
+
+impl Emulator {
+    // from the window every frame. returns whether the machine was reset and needs redrawing
+    pub fn update_hot_patch(&mut self, delta: f32) -> bool {
+        let Some(hot_patch) = &mut self.hot_patch else {
+            return false;
+        };
+        if !hot_patch.poll(delta) {
+            return false;
+        }
+        let source = hot_patch.source.clone();
+        let rom = match rom_source::read(&source) {
+            Ok(rom) => rom,
+            Err(e) => {
+                log::error!("Failed to reload ROM {}: {}", source, e);
+                self.notify_error(format!("Failed to reload ROM: {}", e), NOTICE_TIME);
+                return false;
+            }
+        };
+        match changed(&self.rom, &rom, self.program_counter) {
+            Ok(addrs) => {
+                for addr in &addrs {
+                    self.ram.set(*addr, rom[(*addr - 0x200) as usize]);
+                }
+                self.rom = rom;
+                log::info!("Patched {} changed bytes in from {}", addrs.len(), source);
+                self.notify(format!("Patched {} bytes", addrs.len()), NOTICE_TIME);
+                false
+            }
+            Err(fallback) => {
+                log::info!("Reloading {} with a reset, {}", source, fallback);
+                self.rom = rom;
+                self.hard_reset();
+                self.notify(format!("Reset, {}", fallback), NOTICE_TIME);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_bytes_that_differ_are_patched() {
+        let old = [0x60, 0x01, 0x12, 0x02, 0xAA, 0xBB];
+        assert_eq!(changed(&old, &old, 0x202), Ok(vec![]));
+        let new = [0x60, 0x05, 0x12, 0x02, 0xAA, 0xBC];
+        assert_eq!(changed(&old, &new, 0x202), Ok(vec![0x201, 0x205]));
+    }
+
+    #[test]
+    fn a_change_under_the_pc_or_in_size_resets() {
+        let old = [0x60, 0x01, 0x12, 0x02];
+        let under_pc = Err(Fallback::UnderPc(0x202));
+        assert_eq!(changed(&old, &[0x60, 0x01, 0x13, 0x02], 0x202), under_pc);
+        assert_eq!(changed(&old, &[0x60, 0x01, 0x12, 0x04], 0x202), under_pc); // its second byte
+        let resized = changed(&old, &[0x60, 0x01], 0x202).unwrap_err();
+        assert_eq!(resized, Fallback::Resized { from: 4, to: 2 });
+        assert_eq!(resized.to_string(), "the ROM went from 4 to 2 bytes");
+    }
+
+    // VA = 7 into the delay timer, then sitting on a jump to itself over a byte of data
+    const ROM: [u8; 7] = [0x6A, 0x07, 0xFA, 0x15, 0x12, 0x04, 0x11];
+
+    // the ROM file rewritten, and a modification time that's plainly later
+    fn rebuild(path: &std::path::Path, rom: &[u8], seconds: u64) {
+        fs::write(path, rom).unwrap();
+        let later = SystemTime::now() + std::time::Duration::from_secs(seconds);
+        fs::File::options().write(true).open(path).unwrap().set_modified(later).unwrap();
+    }
+
+    #[test]
+    fn a_rebuild_keeps_the_machine_going_unless_it_has_to_reset() {
+        let file = format!("chip8rust-hot-{}.ch8", std::process::id());
+        let path = std::env::temp_dir().join(file);
+        fs::write(&path, ROM).unwrap();
+        let mut emulator = Emulator::new();
+        emulator.load_rom(&path.to_string_lossy()).unwrap();
+        emulator.hot_patch = Some(HotPatch::new(&path.to_string_lossy()));
+        emulator.run_cycles(3);
+        assert_eq!((emulator.registers.get(0xA), emulator.timer), (7, 7));
+
+        let mut patched = ROM;
+        patched[6] = 0x22;
+        rebuild(&path, &patched, 10);
+        // seen on one poll, and taken once it's stayed that way for the next
+        assert!(!emulator.update_hot_patch(POLL_TIME));
+        assert_eq!(emulator.ram.get(0x206), 0x11);
+        assert!(!emulator.update_hot_patch(POLL_TIME));
+        assert_eq!(emulator.ram.get(0x206), 0x22);
+        assert_eq!((emulator.registers.get(0xA), emulator.timer), (7, 7));
+        assert_eq!(emulator.program_counter, 0x204);
+        assert_eq!(emulator.status.current(), Some(("Patched 1 bytes", false)));
+
+        patched[5] = 0x00; // the jump the PC is on
+        rebuild(&path, &patched, 20);
+        emulator.update_hot_patch(POLL_TIME);
+        assert!(emulator.update_hot_patch(POLL_TIME));
+        assert_eq!((emulator.registers.get(0xA), emulator.timer), (0, 0));
+        assert_eq!((emulator.program_counter, emulator.ram.get(0x205)), (0x200, 0x00));
+        fs::remove_file(path).unwrap();
+    }
+}
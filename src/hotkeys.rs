@@ -0,0 +1,84 @@
+use olc_pge::Key;
+
+// the emulator's own keys in the window, kept apart from the keypad in KEYS
+pub struct Hotkeys {
+    pub quit: Key,
+    pub play_step: Key,
+    pub step: Key,
+    pub step_count: Key, // prompts for how many
+    pub step_frame: Key,
+    pub step_diff: Key,
+    pub waveform: Key,
+    pub palette: Key,
+    pub coverage: Key,
+    pub reset: Key, // with shift held, a soft reset
+    pub memory_map: Key,
+    pub sprites: Key,
+    pub calls: Key,
+    pub quirks: Key,
+    pub draws: Key,
+    #[cfg(feature = "file-dialog")]
+    pub open_rom: Key, // with control held
+    pub help: [Key; 2],
+}
+impl Hotkeys {
+    pub fn new() -> Hotkeys {
+        Hotkeys {
+            quit: Key::Escape,
+            play_step: Key::Space,
+            step: Key::Tab,
+            step_count: Key::G,
+            step_frame: Key::Period,
+            step_diff: Key::F6,
+            waveform: Key::F7,
+            palette: Key::P,
+            coverage: Key::F9,
+            reset: Key::F5,
+            memory_map: Key::M,
+            sprites: Key::I,
+            calls: Key::T,
+            quirks: Key::F8,
+            draws: Key::F10,
+            #[cfg(feature = "file-dialog")]
+            open_rom: Key::O,
+            help: [Key::F1, Key::H],
+        }
+    }
+    // every binding with what it does, in the order the help lists them
+    pub fn bindings(&self) -> Vec<(String, &'static str)> {
+        let mut bindings = vec![
+            (key_name(self.play_step), "play / step"),
+            (key_name(self.step), "step once"),
+            (key_name(self.step_count), "step a number of times"),
+            (key_name(self.step_frame), "step a whole frame"),
+            (key_name(self.step_diff), "show what each step drew"),
+            (key_name(self.waveform), "next beep waveform"),
+            (key_name(self.palette), "next palette"),
+            (key_name(self.coverage), "print coverage"),
+            (key_name(self.reset), "reset"),
+            (format!("SHIFT+{}", key_name(self.reset)), "soft reset (keeps RAM)"),
+            (key_name(self.memory_map), "memory map"),
+            (key_name(self.sprites), "sprite at I"),
+            (key_name(self.calls), "call timeline"),
+            (key_name(self.quirks), "toggle quirks"),
+            (key_name(self.draws), "draw capture"),
+        ];
+        #[cfg(feature = "file-dialog")]
+        bindings.push((format!("CTRL+{}", key_name(self.open_rom)), "open a ROM"));
+        bindings.push((
+            format!("{} / {}", key_name(self.help[0]), key_name(self.help[1])),
+            "this help",
+        ));
+        bindings.push((key_name(self.quit), "quit"));
+        bindings
+    }
+}
+
+// what's printed on the key, near enough: K1 is 1, Escape is ESCAPE
+pub fn key_name(key: Key) -> String {
+    let name = format!("{:?}", key).to_uppercase();
+    match name.strip_prefix('K') {
+        Some(digit) if digit.len() == 1 => digit.to_string(),
+        _ => name,
+    }
+}
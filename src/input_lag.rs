@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+
+#[cfg(feature = "frontend")]
+use olc_pge as olc;
+
+use crate::Emulator;
+
+// the presses the on-screen average is over
+const RECENT_PRESSES: usize = 8;
+
+// --input-lag: how long after a key goes down the ROM first looks at it with EX9E, EXA1 or
+// FX0A, plus the frame time and cycles per frame that latency comes out of. times are on
+// the emulator's own clock, in seconds
+pub struct InputLag {
+    now: f64,
+    pressed_at: [Option<f64>; 0x10], // presses nothing has looked at yet
+    recent: VecDeque<f32>,
+    frame_time: f32,
+    cycles: u32,      // this frame so far
+    last_cycles: u32, // the whole of the last frame
+}
+impl InputLag {
+    pub fn new() -> InputLag {
+        InputLag {
+            now: 0.0,
+            pressed_at: [None; 0x10],
+            recent: VecDeque::new(),
+            frame_time: 0.0,
+            cycles: 0,
+            last_cycles: 0,
+        }
+    }
+    // the start of a host frame, `delta` seconds after the last one
+    pub fn frame(&mut self, delta: f32) {
+        self.now += delta as f64;
+        self.frame_time = delta;
+        self.last_cycles = self.cycles;
+        self.cycles = 0;
+    }
+    pub fn cycled(&mut self) {
+        self.cycles += 1;
+    }
+    // timestamps the keys that went down between `before` and `after`
+    pub fn key_edges(&mut self, before: &[bool; 0x10], after: &[bool; 0x10]) {
+        for key in 0..0x10 {
+            if after[key] && !before[key] {
+                self.pressed_at[key] = Some(self.now);
+            }
+        }
+    }
+    // an instruction read `key` while it was down, returns the latency if it's the first
+    pub fn observed(&mut self, key: usize) -> Option<f32> {
+        let latency = (self.now - self.pressed_at[key].take()?) as f32;
+        if self.recent.len() == RECENT_PRESSES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(latency);
+        Some(latency)
+    }
+    pub fn average(&self) -> Option<f32> {
+        if self.recent.is_empty() {
+            return None;
+        }
+        Some(self.recent.iter().sum::<f32>() / self.recent.len() as f32)
+    }
+}
+
+impl Emulator {
+    // EX9E / EXA1 / FX0A looked at a key, counted if it's down
+    pub fn key_observed(&mut self, key: usize) {
+        let Some(input_lag) = &mut self.input_lag else {
+            return;
+        };
+        if !self.keys[key] {
+            return;
+        }
+        if let Some(latency) = input_lag.observed(key) {
+            self.stats.latency_total += latency as f64;
+            self.stats.latency_samples += 1;
+        }
+    }
+    // a line over the top of the window, redrawn every frame
+    #[cfg(feature = "frontend")]
+    pub fn draw_input_lag(&self, pge: &mut olc::PixelGameEngine) {
+        let Some(input_lag) = &self.input_lag else {
+            return;
+        };
+        let latency = match input_lag.average() {
+            Some(average) => format!("{:.1}ms", average * 1000.0),
+            None => String::from("--"),
+        };
+        let line = format!(
+            "LAG {} FRAME {:.1}ms CYC {}",
+            latency,
+            input_lag.frame_time * 1000.0,
+            input_lag.last_cycles
+        );
+        pge.fill_rect(0, 0, line.len() as u32 * 8, 8, olc::BLACK);
+        pge.draw_string(0, 0, &line, olc::YELLOW);
+    }
+}
@@ -1,40 +1,63 @@
-use crate::Emulator;
+use rand::Rng;
+
+use crate::lints::QuirkUse;
+use crate::run_state::Halt;
+use crate::timer_hooks::Timer;
+use crate::{Emulator, RunMode};
+
+// how long --show-collisions keeps the erased pixels lit, in seconds
+const COLLISION_FLASH_TIME: f32 = 0.25;
 
 pub struct Instruction {
-    opcode: u8,
-    x: u8,
-    y: u8,
-    n: u8,
-    byte: u8,
-    addr: u16,
-    full: u16,
+    pub opcode: u8,
+    pub x: u8,
+    pub y: u8,
+    pub n: u8,
+    pub byte: u8,
+    pub addr: u16,
+    pub full: u16,
 }
 impl Instruction {
-    pub fn from(msb: u8, lsb: u8) -> Instruction {
-        let combined = ((msb as u16) << 8) | lsb as u16;
+    pub fn from(combined: u16) -> Instruction {
         Instruction {
             full: combined,
             opcode: ((combined & 0xF000) >> 12) as u8, // first nibble
             x: ((combined & 0x0F00) >> 8) as u8,       // second nibble
             y: ((combined & 0x00F0) >> 4) as u8,       // third nibble
             n: (combined & 0x000F) as u8,              // fourth nibble
-            byte: lsb,                                 // second byte of instruction
+            byte: combined as u8,                      // second byte of instruction
             addr: (combined & 0x0FFF), // 16 bit address (really 12 bit, but whatever)
         }
     }
 }
 
+// the PC past a word. code wraps at 4KB whatever --out-of-range-read says, so a debug build
+// doesn't panic where a release build would carry on
+fn next_word(pc: u16) -> u16 {
+    pc.wrapping_add(2) & 0xFFF
+}
+
 impl Emulator {
+    // a taken skip: the PC past the next word, which is handed back without counting as a
+    // fetch. it's always the one word, an F000 included
+    fn skip_next(&mut self) -> u16 {
+        let word = self.ram.get_u16(self.program_counter);
+        self.program_counter = next_word(self.program_counter);
+        word
+    }
     // implement all of the instruction code here, to keep main less cluttered
     pub fn cycle(&mut self) -> (bool, String) {
-        let mut summary = String::new();
         let mut redraw = false;
+        let mut skipped = None; // the word a taken skip jumped over
 
-        let instruction = Instruction::from(
-            self.ram.get(self.program_counter),
-            self.ram.get(self.program_counter + 1),
-        );
-        self.program_counter += 2;
+        let instruction_addr = self.program_counter;
+        let instruction = Instruction::from(self.ram.fetch_u16(self.program_counter));
+        self.program_counter = next_word(self.program_counter);
+        self.stats.instructions += 1;
+        self.history.push(instruction_addr, instruction.full);
+        if let Some(input_lag) = &mut self.input_lag {
+            input_lag.cycled();
+        }
 
         let x_reg = instruction.x;
         let y_reg = instruction.y;
@@ -44,74 +67,84 @@ impl Emulator {
         let byte = instruction.byte;
         let addr = instruction.addr;
 
-        summary += format!("{:#6X} => ", instruction.full).as_str();
-
+        // what ran, then what came of it
+        let target = |addr| self.label_or_hex(addr);
+        let mut summary = mnemonic(instruction.full, self.jump_x, self.hires, target);
         match instruction.opcode {
             0x0 => {
                 match byte {
-                    0xE0 => {
-                        // CLS
-                        for x in 0..64 {
-                            for y in 0..32 {
-                                self.display[x][y] = false;
-                            }
-                        }
+                    0xE0 | 0x30 if byte == 0xE0 || (self.hires && instruction.full == 0x0230) => {
+                        // CLS, or the hires interpreter's own 0230
+                        self.clear_display();
                         redraw = true;
-                        summary += "CLS";
                     }
                     0xEE => {
                         // RET
-                        self.program_counter = self.call_stack.pop().unwrap();
-                        summary += format!("RET {:#5X}", self.program_counter).as_str();
+                        match self.call_stack.pop() {
+                            Ok(addr) => {
+                                self.program_counter = addr;
+                                self.log_return();
+                                summary += format!(" {:03X}", self.program_counter).as_str();
+                            }
+                            Err(_) => {
+                                // stays on the RET, so running on just halts again
+                                self.program_counter = instruction_addr;
+                                self.halt = Some(Halt::StackUnderflow(instruction_addr));
+                                summary += " (empty stack)";
+                            }
+                        }
+                    }
+                    0xFD if instruction.full == 0x00FD => {
+                        // EXIT (SUPER-CHIP), the program is done
+                        self.program_counter = instruction_addr;
+                        self.halt = Some(Halt::Exit(instruction_addr));
                     }
                     _ => {
-                        summary += "???";
+                        // SYS addr, machine code on the original hardware, so it's skipped
+                        if self.ram.exec_count(instruction_addr) == 1 {
+                            log::warn!("Ignoring SYS {:#05X} at {:#05X}", addr, instruction_addr);
+                        }
                     }
                 }
             }
             0x1 => {
                 // JMP addr
                 self.program_counter = addr;
-                summary += format!("JMP {:#5X}", self.program_counter).as_str();
             }
             0x2 => {
                 // CALL addr
-                self.call_stack.push(self.program_counter);
-                self.program_counter = addr;
-                summary += format!("CALL {:#5X}", self.program_counter).as_str();
+                if self.call_stack.push(self.program_counter).is_err() {
+                    // stays on the CALL, like an empty RET
+                    self.program_counter = instruction_addr;
+                    self.halt = Some(Halt::StackOverflow(instruction_addr));
+                    summary += " (full stack)";
+                } else {
+                    let depth = self.call_stack.len();
+                    self.stats.peak_stack_depth = self.stats.peak_stack_depth.max(depth);
+                    self.log_call(addr);
+                    self.program_counter = addr;
+                }
             }
             0x3 => {
                 // SKIPIF RX == byte
-                if x == byte {
-                    self.program_counter += 2;
-                }
-                summary += format!("SKIPIF R{:1X} == {:#4X}", x_reg, byte).as_str();
+                skipped = (x == byte).then(|| self.skip_next());
             }
             0x4 => {
                 // SKIPIF RX != byte
-                if x != byte {
-                    self.program_counter += 2
-                }
-                summary += format!("SKIPIF R{:1X} != {:#4X}", x_reg, byte).as_str();
+                skipped = (x != byte).then(|| self.skip_next());
             }
             0x5 => {
                 // SKIPIF RX == RY
-                if x == y {
-                    self.program_counter += 2
-                }
-                summary += format!("SKIPIF R{:1X} == R{:1X}", x_reg, y_reg).as_str();
+                skipped = (x == y).then(|| self.skip_next());
             }
             0x6 => {
                 // LOAD byte => RX
                 self.registers.set(x_reg, byte);
-                summary += format!("IMM {:#4X} => R{:1X}", byte, x_reg).as_str();
             }
             0x7 => {
                 // IMM ADD RX + byte => RX
                 let (result, _overflow) = x.overflowing_add(byte);
                 self.registers.set(x_reg, result);
-                summary +=
-                    format!("IMM ADD R{:1X} + {:#4X} => R{:1X}", x_reg, byte, x_reg).as_str();
             }
             0x8 => {
                 // ALU stuff
@@ -119,135 +152,158 @@ impl Emulator {
                     0x0 => {
                         // COPY RY => RX
                         self.registers.set(x_reg, y);
-                        summary += format!("COPY R{:1X} => R{:1X}", y_reg, x_reg).as_str();
                     }
                     0x1 => {
                         // OR RX | RY => RX
                         self.registers.set(x_reg, x | y);
-                        summary +=
-                            format!("OR R{:1X} | R{:1X} => R{:1X}", x_reg, y_reg, x_reg).as_str();
                     }
                     0x2 => {
                         // AND RX & RY => RX
                         self.registers.set(x_reg, x & y);
-                        summary +=
-                            format!("AND R{:1X} & R{:1X} => R{:1X}", x_reg, y_reg, x_reg).as_str();
                     }
                     0x3 => {
                         // XOR RX ^ RY => RX
                         self.registers.set(x_reg, x ^ y);
-                        summary +=
-                            format!("XOR R{:1X} ^ R{:1X} => R{:1X}", x_reg, y_reg, x_reg).as_str();
                     }
                     0x4 => {
                         // ADD RX + RY => RX (sets overflow flag)
                         let (result, overflow) = x.overflowing_add(y);
                         self.registers.set(0xF, u8::from(overflow));
                         self.registers.set(x_reg, result);
-                        summary +=
-                            format!("ADD R{:1X} + R{:1X} => R{:1X}", x_reg, y_reg, x_reg).as_str();
                     }
                     0x5 => {
                         // SUB RX - RY => RX (sets !overflow flag)
                         let (result, overflow) = x.overflowing_sub(y);
                         self.registers.set(0xF, u8::from(!overflow));
                         self.registers.set(x_reg, result);
-                        summary +=
-                            format!("SUB R{:1X} - R{:1X} => R{:1X}", x_reg, y_reg, x_reg).as_str();
                     }
                     0x6 => {
-                        // SHR RX >> 1 => RX (sets overflow flag)
-                        self.registers.set(0xF, x & 1);
-                        self.registers.set(x_reg, x >> 1);
-                        summary += format!("SHR R{:1X} >> 1 => R{:1X}", x_reg, x_reg).as_str();
+                        // SHR RX >> 1 => RX (sets overflow flag), RY with shift_vy
+                        self.quirk_used(QuirkUse::Shift);
+                        let source = if self.shift_vy { y } else { x };
+                        self.registers.set(0xF, source & 1);
+                        self.registers.set(x_reg, source >> 1);
                     }
                     0x7 => {
                         // SUB RY - RX => RX (sets !overflow flag)
                         let (result, overflow) = y.overflowing_sub(x);
                         self.registers.set(0xF, u8::from(!overflow));
                         self.registers.set(x_reg, result);
-                        summary +=
-                            format!("SUB R{:1X} - R{:1X} => R{:1X}", y_reg, x_reg, x_reg).as_str();
                     }
                     0xE => {
-                        // SHL RX << 1 => RX (sets overflow flag)
-                        self.registers.set(0xF, (x & (1 << 7)) >> 7);
-                        self.registers.set(x_reg, x << 1);
-                        summary += format!("SHL R{:1X} << 1 => R{:1X}", x_reg, x_reg).as_str();
-                    }
-                    _ => {
-                        summary += "???";
+                        // SHL RX << 1 => RX (sets overflow flag), RY with shift_vy
+                        self.quirk_used(QuirkUse::Shift);
+                        let source = if self.shift_vy { y } else { x };
+                        self.registers.set(0xF, (source & (1 << 7)) >> 7);
+                        self.registers.set(x_reg, source << 1);
                     }
+                    _ => {}
                 }
             }
             0x9 => {
                 // SKIPIF RX != RY
-                if x != y {
-                    self.program_counter += 2
-                }
-                summary += format!("SKIPIF R{:1X} != R{:1X}", x_reg, y_reg).as_str();
+                skipped = (x != y).then(|| self.skip_next());
             }
             0xA => {
                 // stack pointer = addr
                 self.stack_pointer = addr;
-                summary += format!("Set stack pointer to {:#5X}", addr).as_str();
             }
             0xB => {
-                // jump to addr + R0
-                self.stack_pointer = addr + self.registers.get(0) as u16;
-                summary += format!("JMPP {:#5X} + R0", addr).as_str();
+                // jump to addr + R0, or with --quirk-jump-x SUPER-CHIP's BXNN, XNN + RX
+                self.quirk_used(QuirkUse::OffsetJump);
+                let offset_reg = if self.jump_x { x_reg } else { 0 };
+                let offset = self.registers.get(offset_reg) as u16;
+                self.program_counter = (addr + offset) & 0xFFF;
+                summary += format!(" => {:03X}", self.program_counter).as_str();
             }
             0xC => {
                 // RAND & byte => RX
-                let random: u8 = rand::random();
+                let random: u8 = self.rng.gen();
                 self.registers.set(x_reg, random & byte);
-                summary += format!("RAND & {:#4X} => R{:1X}", byte, x_reg).as_str();
             }
             0xD => {
                 // DRAW
                 redraw = true;
-                summary += format!("DRAW {} bytes @ ({}, {})", n, x, y).as_str();
+                self.display_waiting = self.display_wait;
                 let mut bytes = Vec::new();
                 let mut collision: u8 = 0;
+                let (mut drawn, mut erased) = (0, 0);
+                let record = self.show_collisions || self.state.mode() == RunMode::Step;
+                let rows = self.screen_height(); // 64 for hires, where VY runs down both pages
+                self.collisions.clear(); // reused, so keeping them doesn't allocate every draw
                 for i in 0..n {
-                    bytes.push(self.ram.get(self.stack_pointer + i as u16));
+                    bytes.push(self.ram.read(self.stack_pointer.wrapping_add(i as u16)));
                 }
                 for (y_off, byte) in bytes.iter().enumerate().take(n as usize) {
+                    // the sprite starts on screen either way, its pixels past an edge wrap
+                    // around or are cut off
+                    let y_pos = y as usize % rows + y_off;
+                    if y_pos >= rows && !self.wrap_y {
+                        break;
+                    }
+                    let y_pos = y_pos % rows;
                     let bools = byte_to_bools(*byte);
                     for (x_off, bit) in bools.iter().enumerate().take(8) {
-                        let x_pos = (x as usize + x_off) % 64;
-                        let y_pos = (y as usize + y_off) % 32;
+                        let x_pos = x as usize % 64 + x_off;
+                        if x_pos >= 64 && !self.wrap_x {
+                            break;
+                        }
+                        let x_pos = x_pos % 64;
                         if *bit {
                             if self.display[x_pos][y_pos] {
                                 collision = 1;
+                                if record {
+                                    self.collisions.push((x_pos as u8, y_pos as u8));
+                                }
                                 self.display[x_pos][y_pos] = false;
+                                erased += 1;
                             } else {
                                 self.display[x_pos][y_pos] = true;
+                                drawn += 1;
                             }
                         }
                     }
                 }
                 self.registers.set(0xF, collision);
+                if x as usize % 64 + 8 > 64 || y as usize % rows + n as usize > rows {
+                    self.quirk_used(QuirkUse::EdgeDraw);
+                }
+                self.log_draw((x % 64, y % rows as u8), n, drawn, erased);
+                // the registers used, then where the sprite really went once wrapped
+                summary += format!(" ({}, {})", x % 64, y % rows as u8).as_str();
+                if let Some((hit_x, hit_y)) = self.collisions.first() {
+                    summary += format!(
+                        " COLLISION x{} @ ({}, {})",
+                        self.collisions.len(),
+                        hit_x,
+                        hit_y
+                    )
+                    .as_str();
+                } else if collision == 1 {
+                    summary += " COLLISION";
+                }
+                if collision == 1 && self.show_collisions {
+                    self.collision_flash = COLLISION_FLASH_TIME;
+                }
             }
             0xE => {
+                let key = (x & 0xF) as usize; // the VIP only looks at the low nibble
                 match byte {
                     0x9E => {
                         // SKIPIF KEY == RX
-                        if self.keys[x as usize] {
-                            self.program_counter += 2;
+                        if self.rom_keys()[key] {
+                            skipped = Some(self.skip_next());
                         }
-                        summary += format!("SKIPIF KEY == R{:1X}", x_reg).as_str();
+                        self.key_observed(key);
                     }
                     0xA1 => {
                         // SKIPIF KEY != RX
-                        if !self.keys[x as usize] {
-                            self.program_counter += 2;
+                        if !self.rom_keys()[key] {
+                            skipped = Some(self.skip_next());
                         }
-                        summary += format!("SKIPIF KEY != R{:1X}", x_reg).as_str();
-                    }
-                    _ => {
-                        summary += "???";
+                        self.key_observed(key);
                     }
+                    _ => {}
                 }
             }
             0xF => {
@@ -255,70 +311,158 @@ impl Emulator {
                     0x07 => {
                         // TIMER => RX
                         self.registers.set(x_reg, self.timer);
-                        summary += format!("TIMER => R{:1X}", x_reg).as_str();
+                        self.timer_read(Timer::Delay, self.timer, instruction_addr);
                     }
                     0x0A => {
                         // KEYBLOCK => RX
                         self.key_block = x_reg;
-                        summary += format!("KEYBLOCK => R{:1X}", x_reg).as_str();
+                        self.key_wait_down = self.rom_keys(); // held now doesn't count
+                        self.key_wait_pressed = [false; 0x10];
                     }
                     0x15 => {
                         // RX => TIMER
                         self.timer = x;
-                        summary += format!("R{:1X} => TIMER", x_reg).as_str();
+                        self.timer_written(Timer::Delay, x, instruction_addr);
                     }
                     0x18 => {
                         // RX => SOUND
+                        if self.sound_timer == 0 && x > 0 {
+                            self.stats.sound_activations += 1;
+                        }
                         self.sound_timer = x;
-                        summary += format!("R{:1X} => SOUND", x_reg).as_str();
+                        self.timer_written(Timer::Sound, x, instruction_addr);
                     }
                     0x1E => {
                         // STKP += RX
-                        self.stack_pointer += x as u16;
-                        summary += format!("STKP += R{:1X}", x_reg).as_str();
+                        self.stack_pointer = self.stack_pointer.wrapping_add(x as u16);
                     }
                     0x29 => {
                         // STKP = DGT(RX)
                         self.stack_pointer = (x as u16) * 5;
-                        summary += format!("STKP = DGT(R{:1X})", x_reg).as_str();
                     }
                     0x33 => {
                         // STORE DEC(RX)
-                        self.ram.set(self.stack_pointer, x / 100);
-                        self.ram.set(self.stack_pointer + 1, (x / 10) % 10);
-                        self.ram.set(self.stack_pointer + 2, x % 10);
-                        summary += format!("STORE DEC(R{:1X})", x_reg).as_str();
+                        self.store(self.stack_pointer, x / 100);
+                        self.store(self.stack_pointer.wrapping_add(1), (x / 10) % 10);
+                        self.store(self.stack_pointer.wrapping_add(2), x % 10);
                     }
                     0x55 => {
                         // STORE R0..RX
+                        self.quirk_used(QuirkUse::LoadStore);
                         for i in 0..=x_reg as usize {
-                            self.ram
-                                .set(self.stack_pointer + i as u16, self.registers.get(i as u8));
+                            let val = self.registers.get(i as u8);
+                            self.store(self.stack_pointer.wrapping_add(i as u16), val);
                         }
-                        summary += format!("STORE R0..R{:1X}", x_reg).as_str();
                     }
                     0x65 => {
                         // LOAD R0..RX
+                        self.quirk_used(QuirkUse::LoadStore);
                         for i in 0..=x_reg as usize {
-                            self.registers
-                                .set(i as u8, self.ram.get(self.stack_pointer + i as u16));
+                            let val = self.ram.read(self.stack_pointer.wrapping_add(i as u16));
+                            self.registers.set(i as u8, val);
                         }
-                        summary += format!("STORE R0..R{:1X}", x_reg).as_str();
-                    }
-                    _ => {
-                        summary += "???";
                     }
+                    _ => {}
                 }
             }
-            _ => {
-                summary += "???";
-            }
+            _ => {}
         }
 
+        if let Some(word) = skipped {
+            summary += format!("  [skipped {:#06X}]", word).as_str();
+        }
+        let summary = step_line(instruction_addr, instruction.full, &summary);
+        log::trace!("{}", summary);
+        if redraw {
+            self.frame_hash = None; // the display changed
+            self.mirror_display();
+        }
+        self.lint(instruction_addr, &instruction);
+        self.check_pause_at(instruction.full);
+        #[cfg(feature = "scripting")]
+        self.run_script_cycle_hooks(redraw);
         (redraw, summary)
     }
 }
 
+// how a step is shown everywhere, from the step panel to the trace log: the address, the
+// instruction and what it did, in columns
+pub fn step_line(addr: u16, word: u16, mnemonic: &str) -> String {
+    format!("{:04X}  {:04X}  {}", addr, word, mnemonic)
+}
+
+// an address as the mnemonics show one without a label
+pub fn hex_addr(addr: u16) -> String {
+    format!("{:03X}", addr)
+}
+
+// the instruction as text, decoded the way cycle runs it: the step panel, the trace log,
+// the crash trace and the disassembly all get it from here. `target` names the address a
+// jump, call or STKP goes to, so the disassembly can put its labels in
+pub fn mnemonic(word: u16, jump_x: bool, hires: bool, target: impl Fn(u16) -> String) -> String {
+    let Instruction {
+        opcode,
+        x,
+        y,
+        n,
+        byte,
+        addr,
+        ..
+    } = Instruction::from(word);
+    match opcode {
+        0x0 => match byte {
+            0xE0 => String::from("CLS"),
+            0x30 if hires && word == 0x0230 => String::from("CLS"),
+            0xEE => String::from("RET"),
+            0xFD if word == 0x00FD => String::from("EXIT"),
+            _ => format!("SYS {:03X}", addr),
+        },
+        0x1 => format!("JMP {}", target(addr)),
+        0x2 => format!("CALL {}", target(addr)),
+        0x3 => format!("SKIPIF R{:1X} == {:02X}", x, byte),
+        0x4 => format!("SKIPIF R{:1X} != {:02X}", x, byte),
+        0x5 => format!("SKIPIF R{:1X} == R{:1X}", x, y),
+        0x6 => format!("IMM {:02X} => R{:1X}", byte, x),
+        0x7 => format!("IMM ADD R{:1X} + {:02X} => R{:1X}", x, byte, x),
+        0x8 => match n {
+            0x0 => format!("COPY R{:1X} => R{:1X}", y, x),
+            0x1 => format!("OR R{:1X} | R{:1X} => R{:1X}", x, y, x),
+            0x2 => format!("AND R{:1X} & R{:1X} => R{:1X}", x, y, x),
+            0x3 => format!("XOR R{:1X} ^ R{:1X} => R{:1X}", x, y, x),
+            0x4 => format!("ADD R{:1X} + R{:1X} => R{:1X}", x, y, x),
+            0x5 => format!("SUB R{:1X} - R{:1X} => R{:1X}", x, y, x),
+            0x6 => format!("SHR R{:1X} >> 1 => R{:1X}", x, x),
+            0x7 => format!("SUB R{:1X} - R{:1X} => R{:1X}", y, x, x),
+            0xE => format!("SHL R{:1X} << 1 => R{:1X}", x, x),
+            _ => String::from("???"),
+        },
+        0x9 => format!("SKIPIF R{:1X} != R{:1X}", x, y),
+        0xA => format!("STKP = {}", target(addr)),
+        // with --quirk-jump-x, SUPER-CHIP's BXNN
+        0xB => format!("JMPP {} + R{:1X}", target(addr), if jump_x { x } else { 0 }),
+        0xC => format!("RAND & {:02X} => R{:1X}", byte, x),
+        0xD => format!("DRAW {} @ R{:1X},R{:1X}", n, x, y),
+        0xE => match byte {
+            0x9E => format!("SKIPIF KEY == R{:1X}", x),
+            0xA1 => format!("SKIPIF KEY != R{:1X}", x),
+            _ => String::from("???"),
+        },
+        0xF => match byte {
+            0x07 => format!("TIMER => R{:1X}", x),
+            0x0A => format!("KEYBLOCK => R{:1X}", x),
+            0x15 => format!("R{:1X} => TIMER", x),
+            0x18 => format!("R{:1X} => SOUND", x),
+            0x1E => format!("STKP += R{:1X}", x),
+            0x29 => format!("STKP = DGT(R{:1X})", x),
+            0x33 => format!("STORE DEC(R{:1X})", x),
+            0x55 => format!("STORE R0..R{:1X}", x),
+            0x65 => format!("LOAD R0..R{:1X}", x),
+            _ => String::from("???"),
+        },
+        _ => String::from("???"),
+    }
+}
+
 fn byte_to_bools(byte: u8) -> [bool; 8] {
     let mut bools = [false; 8];
     for (i, value) in bools.iter_mut().enumerate() {
@@ -327,3 +471,257 @@ fn byte_to_bools(byte: u8) -> [bool; 8] {
 
     bools
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::OutOfRangeRead;
+
+    // a machine with `rom` at 0x200
+    fn emulator(rom: &[u8]) -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(rom).unwrap();
+        emulator
+    }
+
+    #[test]
+    fn cls_clears_the_display() {
+        let mut emulator = emulator(&[]);
+        emulator.ram.set_u16(0x200, 0x00E0);
+        emulator.display[0][0] = true;
+        emulator.display[63][31] = true;
+        emulator.display[10][5] = true;
+        emulator.cycle();
+        assert!(emulator.display().iter().flatten().all(|pixel| !pixel));
+    }
+
+    #[test]
+    fn a_sprite_on_the_corner_wraps_or_clips_by_axis() {
+        // two rows of 0xFF at (62, 31), the bottom-right corner
+        let rom = [0x60, 0x3E, 0x61, 0x1F, 0xA2, 0x0A, 0xD0, 0x12, 0x12, 0x08, 0xFF, 0xFF];
+        // wrap_x, wrap_y, pixels lit, and which of (0, 31), (62, 0) and (0, 0) are
+        for (wrap_x, wrap_y, lit, corners) in [
+            (true, true, 16, [true, true, true]),
+            (true, false, 8, [true, false, false]),
+            (false, true, 4, [false, true, false]),
+            (false, false, 2, [false, false, false]),
+        ] {
+            let mut emulator = emulator(&rom);
+            emulator.wrap_x = wrap_x;
+            emulator.wrap_y = wrap_y;
+            emulator.run_cycles(4);
+            let display = emulator.display();
+            let count = display.iter().flatten().filter(|&&on| on).count();
+            assert_eq!(count, lit, "wrap_x {}, wrap_y {}", wrap_x, wrap_y);
+            assert_eq!([display[0][31], display[62][0], display[0][0]], corners);
+            assert!(display[63][31]);
+        }
+    }
+
+    #[test]
+    fn bnnn_jumps_to_nnn_plus_v0() {
+        let mut emulator = emulator(&[0x60, 0x12, 0xA4, 0x56, 0xB3, 0x00]);
+        emulator.run_cycles(3);
+        assert_eq!(emulator.program_counter, 0x312);
+        assert_eq!(emulator.stack_pointer, 0x456);
+    }
+
+    #[test]
+    fn bxnn_jumps_to_xnn_plus_vx_with_quirk_jump_x() {
+        let mut emulator = emulator(&[0x60, 0x12, 0x63, 0x04, 0xB3, 0x00]);
+        emulator.jump_x = true;
+        emulator.run_cycles(3);
+        assert_eq!(emulator.program_counter, 0x304);
+    }
+
+    #[test]
+    fn shifts_vy_into_vx_with_quirk_shift_vy() {
+        // V0 = 0x11, V1 = 0x81, then V0 = V1 >> 1 and V2 = V1 << 1
+        let mut emulator = emulator(&[0x60, 0x11, 0x61, 0x81, 0x80, 0x16, 0x82, 0x1E]);
+        emulator.shift_vy = true;
+        emulator.run_cycles(3);
+        assert_eq!((emulator.registers.get(0x0), emulator.registers.get(0xF)), (0x40, 1));
+        emulator.run_cycles(1);
+        assert_eq!((emulator.registers.get(0x2), emulator.registers.get(0xF)), (0x02, 1));
+        assert_eq!(emulator.registers.get(0x1), 0x81);
+    }
+
+    #[test]
+    fn ex9e_looks_at_the_low_nibble_of_vx() {
+        // found by tests/differential.rs: VX = DA indexed the 16 keys with 218
+        let mut emulator = emulator(&[0x61, 0xDA, 0xE1, 0x9E]);
+        emulator.keys[0xA] = true;
+        emulator.run_cycles(2);
+        assert_eq!(emulator.program_counter, 0x206);
+    }
+
+    // the address math that used to overflow, and panic in a debug build
+
+    #[test]
+    fn fx55_wraps_past_0xfff() {
+        let mut emulator = emulator(&[
+            0x60, 0x11, 0x61, 0x22, 0x62, 0x33, 0x63, 0x44, 0xAF, 0xFE, 0xF3, 0x55,
+        ]);
+        emulator.run_cycles(6);
+        let stored = [0xFFE, 0xFFF, 0x000, 0x001].map(|addr| emulator.ram.get(addr));
+        assert_eq!(stored, [0x11, 0x22, 0x33, 0x44]);
+    }
+
+    // I = 0xFFE, then a 4-row sprite at (0, 0) over a lit pixel on its third row, so rows
+    // 2 and 3 come from past the end. what's drawn, row by row, and VF
+    fn draw_past_the_end(read: OutOfRangeRead) -> ([u8; 4], u8) {
+        let mut emulator = emulator(&[0xAF, 0xFE, 0xD0, 0x04]);
+        emulator.ram.policy.read = read;
+        emulator.ram.set(0xFFE, 0xF0);
+        emulator.ram.set(0xFFF, 0x0F);
+        emulator.display[0][2] = true;
+        emulator.run_cycles(2);
+        let row = |y: usize| (0..8).fold(0, |byte, x| byte << 1 | emulator.display[x][y] as u8);
+        ([0, 1, 2, 3].map(row), emulator.registers.get(0xF))
+    }
+
+    #[test]
+    fn a_sprite_past_0xfff_wraps_into_the_font() {
+        // the font's 0 is F0 90 ..., and its first pixel erases the lit one
+        assert_eq!(draw_past_the_end(OutOfRangeRead::Wrap), ([0xF0, 0x0F, 0x70, 0x90], 1));
+    }
+
+    #[test]
+    fn a_sprite_past_0xfff_reads_zeros() {
+        // the lit pixel stays, with nothing drawn over it
+        assert_eq!(draw_past_the_end(OutOfRangeRead::Zero), ([0xF0, 0x0F, 0x80, 0x00], 0));
+    }
+
+    #[test]
+    fn a_sprite_past_0xfff_reads_ff() {
+        assert_eq!(draw_past_the_end(OutOfRangeRead::Ff), ([0xF0, 0x0F, 0x7F, 0xFF], 1));
+    }
+
+    #[test]
+    fn fetch_at_0xfff_takes_its_low_byte_from_0x000() {
+        let mut emulator = emulator(&[0x1F, 0xFF]);
+        emulator.ram.set(0xFFF, 0x12);
+        emulator.ram.set(0x000, 0x04);
+        emulator.run_cycles(2);
+        assert_eq!(emulator.program_counter, 0x204);
+    }
+
+    #[test]
+    fn fx1e_wraps_i() {
+        let mut emulator = emulator(&[0x60, 0x02, 0xF0, 0x1E]);
+        emulator.stack_pointer = 0xFFFF;
+        emulator.run_cycles(2);
+        assert_eq!(emulator.stack_pointer, 0x0001);
+    }
+
+    #[test]
+    fn skip_at_0xffe_wraps_the_pc() {
+        let mut emulator = emulator(&[0x1F, 0xFE]);
+        emulator.ram.set_u16(0xFFE, 0x3000); // SKIPIF R0 == 00, taken
+        emulator.run_cycles(2);
+        assert_eq!(emulator.program_counter, 0x002);
+    }
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(mnemonic(0xD455, false, false, hex_addr), "DRAW 5 @ R4,R5");
+        assert_eq!(mnemonic(0x3F05, false, false, hex_addr), "SKIPIF RF == 05");
+        assert_eq!(mnemonic(0x121F, false, false, hex_addr), "JMP 21F");
+        assert_eq!(mnemonic(0xA005, false, false, hex_addr), "STKP = 005");
+        assert_eq!(mnemonic(0xB312, false, false, hex_addr), "JMPP 312 + R0");
+        assert_eq!(mnemonic(0xB312, true, false, hex_addr), "JMPP 312 + R3");
+        assert_eq!(mnemonic(0x0230, false, false, hex_addr), "SYS 230");
+        assert_eq!(mnemonic(0x0230, false, true, hex_addr), "CLS");
+        assert_eq!(mnemonic(0x2300, false, false, |_| String::from("sub")), "CALL sub");
+    }
+
+    #[test]
+    fn step_lines() {
+        let mut emulator = emulator(&[0x64, 0x03, 0xD4, 0x55, 0x30, 0x00, 0x00, 0x00, 0x12, 0x00]);
+        let lines: Vec<String> = (0..4).map(|_| emulator.cycle().1).collect();
+        assert_eq!(
+            lines,
+            [
+                "0200  6403  IMM 03 => R4",
+                "0202  D455  DRAW 5 @ R4,R5 (3, 0)",
+                "0204  3000  SKIPIF R0 == 00  [skipped 0x0000]",
+                "0208  1200  JMP 200",
+            ]
+        );
+    }
+
+    #[test]
+    fn the_trace_names_a_target_by_its_label() {
+        let mut emulator = emulator(&[0x22, 0x04, 0x12, 0x02, 0x00, 0xEE]);
+        emulator.labels.insert(0x204, String::from("sub_draw"));
+        assert_eq!(emulator.cycle().1, "0200  2204  CALL sub_draw");
+    }
+
+    // values nowhere near the indices, and past the screen so the wrapped place shows too
+    #[test]
+    fn draw_names_its_registers_not_their_values() {
+        let rom = [0x6A, 0x4A, 0x6B, 0x47, 0xDA, 0xB1, 0xDA, 0xB1, 0xF3, 0x65];
+        let mut emulator = emulator(&rom);
+        emulator.state = RunMode::Step.into(); // where the collisions are kept
+        let lines: Vec<String> = (0..5).map(|_| emulator.cycle().1).collect();
+        assert_eq!(
+            lines,
+            [
+                "0200  6A4A  IMM 4A => RA",
+                "0202  6B47  IMM 47 => RB",
+                "0204  DAB1  DRAW 1 @ RA,RB (10, 7)",
+                "0206  DAB1  DRAW 1 @ RA,RB (10, 7) COLLISION x4 @ (10, 7)", // the 0 font row, F0
+                "0208  F365  LOAD R0..R3",
+            ]
+        );
+    }
+
+    // the 0 glyph's top two rows, F0 and 90, at (2, 3) and again two pixels to the right,
+    // where only the top rows overlap
+    const OVERLAPPING: [u8; 10] = [0x60, 0x02, 0x61, 0x03, 0xD0, 0x12, 0x60, 0x04, 0xD0, 0x12];
+
+    #[test]
+    fn the_erased_pixels_are_kept_while_stepping() {
+        let mut emulator = emulator(&OVERLAPPING);
+        emulator.run_cycles(4);
+        assert!(emulator.last_collisions().is_empty());
+        assert_eq!(emulator.cycle().1, "0208  D012  DRAW 2 @ R0,R1 (4, 3) COLLISION x2 @ (4, 3)");
+        assert_eq!(emulator.last_collisions(), [(4, 3), (5, 3)]);
+        assert_eq!(emulator.collision_flash, 0.0); // only with --show-collisions
+    }
+
+    #[test]
+    fn play_only_keeps_them_with_show_collisions() {
+        for show in [false, true] {
+            let mut emulator = emulator(&OVERLAPPING);
+            emulator.state = RunMode::Play.into();
+            emulator.show_collisions = show;
+            emulator.run_cycles(5);
+            assert_eq!(emulator.registers.get(0xF), 1);
+            let kept: &[(u8, u8)] = if show { &[(4, 3), (5, 3)] } else { &[] };
+            assert_eq!(emulator.last_collisions(), kept);
+            assert_eq!(emulator.collision_flash > 0.0, show);
+        }
+    }
+
+    #[test]
+    fn a_taken_skip_names_the_word_it_skipped() {
+        let rom = [
+            0x63, 0x10, 0x33, 0x10, 0x61, 0x05, 0x43, 0x10, 0x61, 0x06, 0x93, 0x00, 0xF0, 0x00,
+            0xE3, 0xA1, 0x00, 0xE0,
+        ];
+        let mut emulator = emulator(&rom);
+        let lines: Vec<String> = (0..6).map(|_| emulator.cycle().1).collect();
+        assert_eq!(
+            lines,
+            [
+                "0200  6310  IMM 10 => R3",
+                "0202  3310  SKIPIF R3 == 10  [skipped 0x6105]",
+                "0206  4310  SKIPIF R3 != 10",
+                "0208  6106  IMM 06 => R1",
+                "020A  9300  SKIPIF R3 != R0  [skipped 0xF000]",
+                "020E  E3A1  SKIPIF KEY != R3  [skipped 0x00E0]",
+            ]
+        );
+    }
+}
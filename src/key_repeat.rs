@@ -0,0 +1,209 @@
+use clap::ValueEnum;
+
+use crate::Emulator;
+
+// how a pad key being held down turns into what the ROM sees
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+pub enum KeyShape {
+    Pass,   // held for as long as it's down
+    Single, // held for one frame per press, for menus that would skip on a held key
+    Repeat, // held for one frame per press, then again at --key-repeat-rate while it's down
+}
+// --input-mode, from before there was --key-repeat
+#[derive(PartialEq, Debug, Clone, clap::ValueEnum)]
+pub enum InputMode {
+    Once,
+    Hold,
+}
+impl From<InputMode> for KeyShape {
+    // what --input-mode used to mean
+    fn from(mode: InputMode) -> KeyShape {
+        match mode {
+            InputMode::Hold => KeyShape::Pass,
+            InputMode::Once => KeyShape::Single,
+        }
+    }
+}
+
+// --key-repeat-key: a pad key in hex, then its shape, like "5=repeat"
+pub fn parse_key_shape(text: &str) -> Result<(usize, KeyShape), String> {
+    let (key, shape) = text.split_once('=').ok_or("expected KEY=SHAPE")?;
+    let key = match usize::from_str_radix(key.trim(), 16) {
+        Ok(key) if key < 0x10 => key,
+        _ => return Err(format!("\"{}\" isn't a key from 0 to F", key)),
+    };
+    let shape = KeyShape::from_str(shape.trim(), true)
+        .map_err(|_| format!("\"{}\" isn't pass, single or repeat", shape))?;
+    Ok((key, shape))
+}
+
+// --available-keys: pad keys in hex, like "0,1,2,3,4,5,6,7" for hardware that only wires up 8
+pub fn parse_key_set(text: &str) -> Result<[bool; 0x10], String> {
+    let mut keys = [false; 0x10];
+    for key in text.split(',') {
+        match usize::from_str_radix(key.trim(), 16) {
+            Ok(key) if key < 0x10 => keys[key] = true,
+            _ => return Err(format!("\"{}\" isn't a key from 0 to F", key)),
+        }
+    }
+    Ok(keys)
+}
+
+pub struct KeyRepeat {
+    shapes: [KeyShape; 0x10],
+    period: f32,                   // seconds between repeats
+    down_for: [Option<f32>; 0x10], // seconds since each key went down, or its last repeat
+}
+impl KeyRepeat {
+    pub fn new(shape: KeyShape, rate: f32) -> KeyRepeat {
+        KeyRepeat {
+            shapes: [shape; 0x10],
+            period: 1.0 / rate,
+            down_for: [None; 0x10],
+        }
+    }
+    pub fn set(&mut self, key: usize, shape: KeyShape) {
+        self.shapes[key] = shape;
+    }
+    // a host frame of `delta` seconds with each key `down` or not, and what the ROM gets to
+    // see. stepping always passes them through, one frame at a time would be hard to hit
+    pub fn shape(&mut self, down: [bool; 0x10], delta: f32, stepping: bool) -> [bool; 0x10] {
+        let mut held = [false; 0x10];
+        for key in 0..0x10 {
+            let pressed = down[key] && self.down_for[key].is_none();
+            let repeated = match &mut self.down_for[key] {
+                Some(time) if down[key] => {
+                    *time += delta;
+                    if *time >= self.period {
+                        *time -= self.period;
+                        true
+                    } else {
+                        false
+                    }
+                }
+                _ => false,
+            };
+            self.down_for[key] = if down[key] { self.down_for[key].or(Some(0.0)) } else { None };
+            held[key] = match self.shapes[key] {
+                _ if stepping => down[key],
+                KeyShape::Pass => down[key],
+                KeyShape::Single => pressed,
+                KeyShape::Repeat => pressed || repeated,
+            };
+        }
+        held
+    }
+}
+
+impl Emulator {
+    // the keys as EX9E, EXA1 and FX0A see them, with the ones left out of --available-keys
+    // never down whatever the keyboard, a script or the server says
+    pub fn rom_keys(&self) -> [bool; 0x10] {
+        let mut keys = self.keys;
+        for (key, available) in keys.iter_mut().zip(self.available_keys) {
+            *key &= available;
+        }
+        keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAME: f32 = 0.125; // half a repeat at 4 a second
+
+    // key 5 held for the frames marked #, as the ROM sees it
+    fn shaped(shape: KeyShape, pattern: &str, stepping: bool) -> String {
+        let mut repeat = KeyRepeat::new(shape, 4.0);
+        let held = pattern.chars().map(|c| {
+            let mut down = [false; 0x10];
+            down[5] = c == '#';
+            let keys = repeat.shape(down, FRAME, stepping);
+            assert!(keys.iter().enumerate().all(|(key, &held)| key == 5 || !held));
+            if keys[5] { '#' } else { '.' }
+        });
+        held.collect()
+    }
+
+    #[test]
+    fn pass_is_held_while_down() {
+        assert_eq!(shaped(KeyShape::Pass, "##..#####.", false), "##..#####.");
+    }
+
+    #[test]
+    fn single_is_one_frame_a_press() {
+        assert_eq!(shaped(KeyShape::Single, "##..#####.#", false), "#...#.....#");
+    }
+
+    #[test]
+    fn repeat_goes_on_at_the_rate() {
+        assert_eq!(shaped(KeyShape::Repeat, "#########.#", false), "#.#.#.#.#.#");
+        assert_eq!(shaped(KeyShape::Repeat, "#.#.", false), "#.#.", "a press starts afresh");
+    }
+
+    #[test]
+    fn stepping_passes_every_shape_through() {
+        for shape in [KeyShape::Single, KeyShape::Repeat] {
+            assert_eq!(shaped(shape, "###..#", true), "###..#");
+        }
+    }
+
+    #[test]
+    fn a_key_can_have_its_own_shape() {
+        let mut repeat = KeyRepeat::new(KeyShape::Pass, 4.0);
+        repeat.set(0xA, KeyShape::Single);
+        let mut down = [false; 0x10];
+        (down[0x1], down[0xA]) = (true, true);
+        assert_eq!(repeat.shape(down, FRAME, false), down);
+        let second = repeat.shape(down, FRAME, false);
+        assert!(second[0x1] && !second[0xA]);
+    }
+
+    #[test]
+    fn input_modes_and_settings() {
+        assert_eq!(KeyShape::from(InputMode::Hold), KeyShape::Pass);
+        assert_eq!(KeyShape::from(InputMode::Once), KeyShape::Single);
+        assert_eq!(parse_key_shape("a=Repeat"), Ok((0xA, KeyShape::Repeat)));
+        assert_eq!(parse_key_shape("10=pass"), Err(String::from("\"10\" isn't a key from 0 to F")));
+        assert!(parse_key_shape("5").is_err() && parse_key_shape("5=hold").is_err());
+    }
+
+    fn eight_keys(rom: &[u8]) -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.available_keys = parse_key_set("0,1,2,3,4,5,6,7").unwrap();
+        emulator.load_rom_bytes(rom).unwrap();
+        emulator
+    }
+
+    #[test]
+    fn a_key_left_out_is_never_down_to_the_rom() {
+        // SKIPIF KEY == R0 and SKIPIF KEY != R0 with key 9 held, then key 3
+        for (key, skips) in [(0x9, [false, true]), (0x3, [true, false])] {
+            let mut emulator = eight_keys(&[0x60, key, 0xE0, 0x9E, 0x00, 0xE0, 0xE0, 0xA1]);
+            emulator.keys[key as usize] = true;
+            emulator.run_cycles(2);
+            assert_eq!(emulator.program_counter == 0x206, skips[0], "EX9E, key {:X}", key);
+            emulator.program_counter = 0x206;
+            emulator.run_cycles(1);
+            assert_eq!(emulator.program_counter == 0x20A, skips[1], "EXA1, key {:X}", key);
+        }
+    }
+
+    #[test]
+    fn a_key_left_out_doesnt_answer_a_key_wait() {
+        let mut emulator = eight_keys(&[0xF5, 0x0A]);
+        emulator.run_cycles(1);
+        for key in [0x9, 0x2] {
+            emulator.keys[key] = true;
+            emulator.resolve_key_block();
+            emulator.keys[key] = false;
+            emulator.resolve_key_block();
+        }
+        assert!(!emulator.is_waiting_for_key());
+        assert_eq!(emulator.registers.get(0x5), 0x2);
+        let keys = parse_key_set("0,8,F").unwrap();
+        assert_eq!(keys.iter().filter(|&&on| on).count(), 3);
+        assert!(parse_key_set("0,10").is_err());
+    }
+}
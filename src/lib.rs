@@ -0,0 +1,599 @@
+// the interpreter, for the chip8rust binary and anything else that wants to run CHIP-8
+// without a window. the window, terminal and --compare-rom frontends are behind the
+// frontend feature, which is all that pulls in olc_pge
+
+// the machine's parts are made with new(), none of them has a Default worth having
+#![allow(clippy::new_without_default)]
+
+use std::collections::BTreeMap;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+pub mod audio;
+pub mod batch;
+mod call_log;
+pub mod code_guard;
+#[cfg(feature = "frontend")]
+pub mod compare;
+pub mod components;
+mod coverage;
+pub mod crash;
+#[cfg(feature = "frontend")]
+mod debug_panel;
+pub mod demo;
+pub mod disasm;
+pub mod draw_log;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(any(test, feature = "test-roms"))]
+mod fixtures;
+pub mod font;
+pub mod frame_driver;
+pub mod headless;
+#[cfg(feature = "frontend")]
+mod help;
+pub mod hires;
+pub mod hot_patch;
+#[cfg(feature = "frontend")]
+mod hotkeys;
+pub mod input_lag;
+pub mod key_repeat;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+pub mod instructions;
+pub mod lints;
+pub mod logging;
+#[cfg(feature = "frontend")]
+mod memory_map;
+#[cfg(feature = "frontend")]
+mod modal;
+pub mod output;
+pub mod pacing;
+pub mod palette;
+pub mod pause_at;
+mod quirks;
+pub mod regions;
+pub mod rom_source;
+pub mod run_state;
+pub mod save_ram;
+#[cfg(feature = "scripting")]
+mod scripting;
+#[cfg(feature = "sdl2")]
+pub mod sdl;
+#[cfg(feature = "serve")]
+pub mod serve;
+mod sprites;
+pub mod stats;
+mod timer_hooks;
+pub mod state_hash;
+pub mod status;
+mod step_diff;
+#[cfg(feature = "frontend")]
+pub mod terminal;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod version;
+mod vip_areas;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "frontend")]
+pub mod window;
+pub mod write_log;
+
+pub use components::{Ram, Registers};
+pub use instructions::Instruction;
+
+use audio::{
+    BeepStyle, Beeper, Envelope, FrequencySetting, NullBeeper, Recording, Waveform, WaveformSetting,
+    BEEP_VOLUME,
+};
+use call_log::CallLog;
+use code_guard::CodeGuard;
+use components::{ArrayStack, CALL_STACK_SIZE, MAX_CALL_STACK_SIZE};
+use coverage::Coverage;
+use crash::History;
+#[cfg(feature = "frontend")]
+use debug_panel::PanelValues;
+use draw_log::DrawCapture;
+use font::FontStyle;
+use frame_driver::FrameDriver;
+use hires::{DisplayMode, Platform, HIRES_H};
+use hot_patch::HotPatch;
+#[cfg(feature = "frontend")]
+use hotkeys::Hotkeys;
+use input_lag::InputLag;
+use key_repeat::{KeyRepeat, KeyShape};
+use lints::Lints;
+use pacing::Pacing;
+use palette::Palette;
+use pause_at::PauseAt;
+use regions::Region;
+use rom_source::LoadError;
+use run_state::{Event, Halt, RunState};
+use save_ram::SaveRam;
+use stats::RunStats;
+use status::StatusLine;
+use write_log::WriteLog;
+
+pub const SCR_W: usize = 64;
+pub const SCR_H: usize = 32;
+const START_RUN_MODE: RunMode = RunMode::Step;
+
+#[derive(PartialEq, Debug, Clone, Copy, clap::ValueEnum)]
+pub enum RunMode {
+    Play,
+    Step,
+}
+impl RunMode {
+    pub fn toggled(self) -> RunMode {
+        match self {
+            RunMode::Play => RunMode::Step,
+            RunMode::Step => RunMode::Play,
+        }
+    }
+}
+
+pub struct Emulator {
+    pub time_per_cycle: f32,
+    pub ipf: Option<u32>, // instructions per frame, which takes over from time_per_cycle
+    pub driver: FrameDriver, // when cycles, frames and timer ticks are due
+    pub pacing: Pacing,
+    pub font_style: FontStyle,
+    pub reserve_vip_areas: bool, // the display mirrored into 0xF00-0xFFF
+    pub jump_x: bool,            // BXNN adds VX
+    pub shift_vy: bool,          // 8XY6 and 8XYE shift VY into VX, as on the VIP
+    pub display_wait: bool,      // DXYN waits for the next frame, as on the VIP
+    pub display_waiting: bool,   // a DXYN under display_wait, until the frame is over
+    pub platform: Platform,
+    pub hires: bool, // a two-page ROM on --platform hires-chip8, with 64 rows
+    reported_mode: DisplayMode, // the mode a frontend was last told about
+    pub wrap_x: bool, // sprites past the right edge come back on the left, or are cut off
+    pub wrap_y: bool,
+    pub display: [[bool; HIRES_H]; SCR_W],
+    pub palettes: Vec<Palette>,
+    pub palette: usize, // which of palettes is in use
+    pub ram: Ram,
+    pub timer: u8,
+    pub sound_timer: u8,
+    pub registers: Registers,
+    pub program_counter: u16,
+    pub stack_pointer: u16,
+    pub call_stack: ArrayStack<u16, MAX_CALL_STACK_SIZE>, // CALL_STACK_SIZE deep unless set
+    pub call_log: CallLog,
+    pub write_log: WriteLog,
+    pub history: History, // for a crash dump
+    pub draw_capture: Option<DrawCapture>,
+    pub user_regions: Vec<Region>, // from --symbols
+    pub labels: BTreeMap<u16, String>, // from --symbols too
+    pub capture_frames: u32,
+    pub capture_csv: String,
+    pub key_block: u8,
+    pub key_wait_down: [bool; 0x10],    // the keys down the last time a pending FX0A looked
+    pub key_wait_pressed: [bool; 0x10], // the keys that went down since it started waiting
+    pub keys: [bool; 0x10],
+    pub key_repeat: KeyRepeat, // what the ROM sees of the keys being held
+    pub available_keys: [bool; 0x10], // the rest always read as up, see rom_keys
+    pub state: RunState,
+    pub halt: Option<Halt>, // set by the cycle that stopped the machine
+    pub summary: String,    // what the last cycle did, for the step panel
+    pub beeper: Box<dyn Beeper>, // set up by main, silent until then
+    pub envelope: Envelope,
+    pub beep_wave: WaveformSetting,
+    pub beep_freq: f32,
+    pub beep_style: BeepStyle,
+    pub beep_pitch: FrequencySetting, // what's playing, from the frequency and style
+    pub recording: Option<Recording>,
+    pub speaker_shown: bool,
+    #[cfg(feature = "frontend")]
+    pub last_panel: PanelValues,
+    pub status: StatusLine,
+    pub status_shown: bool, // whether there's anything on the status line to clear
+    pub key_wait_time: f32, // seconds the pending FX0A has been waiting
+    pub keywait_timeout: Option<f32>,
+    pub key_wait_expired: bool, // the pending FX0A ran past keywait_timeout
+    pub frame_cycles: u32, // how far step_frame_budgeted got into a frame its budget cut short
+    pub pause_at: Option<PauseAt>,  // --pause-at, until it goes off
+    pub code_guard: Option<CodeGuard>,
+    pub paused_at: Option<PauseAt>, // it went off, for the frontend to stop
+    pub collisions: Vec<(u8, u8)>, // the pixels the last DXYN erased, if they're being kept
+    pub show_collisions: bool,
+    pub collision_flash: f32, // seconds left showing them
+    pub save_ram: Option<SaveRam>,
+    pub hot_patch: Option<HotPatch>, // with --hot-patch
+    pub lints: Option<Lints>, // with --lint
+    pub show_step_diff: bool,
+    pub frame_hash: Option<u64>, // the display's hash, None when it changed since
+    pub show_hash: bool,
+    pub trace_display: bool,
+    pub trace_timers: bool,
+    pub input_lag: Option<InputLag>, // with --input-lag
+    pub step_changes: Vec<(u8, u8, bool)>, // the pixels the last step changed, and if they're lit
+    pub rng: StdRng,
+    pub rom: Vec<u8>, // as it was loaded, for resets
+    pub rom_size: usize,
+    pub rom_name: String,
+    #[cfg(feature = "frontend")]
+    pub hotkeys: Hotkeys,
+    pub print_coverage: bool,
+    pub coverage_map: Option<String>,
+    pub stats: RunStats,
+    pub print_stats: bool,
+    pub json: bool,
+    #[cfg(feature = "scripting")]
+    pub script: Option<scripting::Script>,
+}
+impl Emulator {
+    pub fn new() -> Emulator {
+        let mut ram = Ram::new();
+        font::load(&mut ram, FontStyle::Standard);
+
+        Emulator {
+            time_per_cycle: 1.0/600.0,
+            ipf: None,
+            driver: FrameDriver::new(1.0/600.0, false),
+            pacing: Pacing::new(0.008),
+            font_style: FontStyle::Standard,
+            reserve_vip_areas: false,
+            jump_x: false,
+            shift_vy: false,
+            display_wait: false,
+            display_waiting: false,
+            platform: Platform::Chip8,
+            hires: false,
+            reported_mode: DisplayMode::Lores,
+            wrap_x: true,
+            wrap_y: true,
+            display: [[false; HIRES_H]; SCR_W], // x, y format, only the top SCR_H rows unless hires
+            palettes: palette::builtins(),
+            palette: 0,
+            ram, // using RAM rather than a Vec because it encapsulates ROM loading
+            timer: 0x00,     // basic timer
+            sound_timer: 0x00, // sound timer, plays a beep while > 0
+            registers: Registers::new(), // registers 0 through F
+            program_counter: 0x200, // programs always start at location 0x200 in RAM
+            stack_pointer: 0x000, // doesn't matter where this starts, programs will modify it
+            call_stack: ArrayStack::with_limit(CALL_STACK_SIZE),
+            call_log: CallLog::new(),
+            write_log: WriteLog::new(0, false),
+            history: History::new(),
+            draw_capture: None,
+            user_regions: Vec::new(),
+            labels: BTreeMap::new(),
+            capture_frames: 60,
+            capture_csv: String::from("draw_capture.csv"),
+            key_block: 0x10,
+            key_wait_down: [false; 0x10],
+            key_wait_pressed: [false; 0x10],
+            keys: [false; 0x10],
+            key_repeat: KeyRepeat::new(KeyShape::Pass, 10.0),
+            available_keys: [true; 0x10],
+            state: START_RUN_MODE.into(),
+            halt: None,
+            summary: String::new(),
+            beeper: Box::new(NullBeeper),
+            envelope: Envelope::new(),
+            beep_wave: WaveformSetting::new(Waveform::Square),
+            beep_freq: 440.0,
+            beep_style: BeepStyle::Plain,
+            beep_pitch: FrequencySetting::new(440.0),
+            recording: None,
+            speaker_shown: false,
+            #[cfg(feature = "frontend")]
+            last_panel: PanelValues::default(),
+            status: StatusLine::new(),
+            status_shown: false,
+            key_wait_time: 0.0,
+            keywait_timeout: None,
+            key_wait_expired: false,
+            frame_cycles: 0,
+            pause_at: None,
+            code_guard: None,
+            paused_at: None,
+            collisions: Vec::new(),
+            show_collisions: false,
+            collision_flash: 0.0,
+            save_ram: None,
+            hot_patch: None,
+            lints: None,
+            show_step_diff: true,
+            frame_hash: None,
+            show_hash: false,
+            trace_display: false,
+            trace_timers: false,
+            input_lag: None,
+            step_changes: Vec::new(),
+            rng: StdRng::from_entropy(),
+            rom: Vec::new(),
+            rom_size: 0,
+            rom_name: String::new(),
+            #[cfg(feature = "frontend")]
+            hotkeys: Hotkeys::new(),
+            print_coverage: false,
+            coverage_map: None,
+            stats: RunStats::new(),
+            print_stats: false,
+            json: false,
+            #[cfg(feature = "scripting")]
+            script: None,
+        }
+    }
+    // anything rom_source can read: a file, a zip archive (entry), or a URL
+    pub fn load_rom(&mut self, rom_file: &str) -> Result<(), LoadError> {
+        let rom = rom_source::read(rom_file)?;
+        self.load_rom_bytes(&rom)?;
+        // the file name, or the entry name in an archive
+        self.rom_name = rom_file.rsplit(['/', '#']).next().unwrap_or(rom_file).to_string();
+        Ok(())
+    }
+    pub fn load_demo(&mut self) {
+        self.load_rom_bytes(demo::DEMO_ROM).expect("the demos fit in RAM");
+        self.rom_name = format!("demo {}", demo::DEFAULT_DEMO);
+    }
+    // an empty ROM is fine, it runs 0000s. one too big for 0x200-0xFFF leaves RAM as it was
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), LoadError> {
+        log::debug!("Loading a {} byte ROM at 0x200", rom.len());
+        self.ram.load_bytes(0x200, rom)?;
+        self.rom_size = rom.len();
+        self.rom = rom.to_vec();
+        self.detect_hires(rom);
+        self.check_vip_areas();
+        Ok(())
+    }
+    // back to the power-on state, keeping the settings. the ROM has to be loaded again
+    // how every machine starts, which ROMs that work out the interpreter from its state lean
+    // on: RAM all zero but the font at 0x000 and the ROM at 0x200, V0-VF, I, the timers and
+    // the display zero, nothing on the stack, and the PC at 0x200 (0x2C0 for hires)
+    pub fn reset(&mut self) {
+        self.flush_save_ram(); // before the RAM it's in goes
+        let policy = self.ram.policy;
+        self.ram = Ram::new();
+        self.ram.policy = policy;
+        font::load(&mut self.ram, self.font_style);
+        self.rom_size = 0;
+        self.lints = self.lints.take().map(|_| Lints::new()); // the new ROM gets its own reports
+        self.reset_machine();
+    }
+    // every pixel off, both pages of a hires display included
+    fn clear_display(&mut self) {
+        self.display = [[false; HIRES_H]; SCR_W];
+    }
+    // the pixels as x, y, for a frontend to draw. only the top screen_height() rows are used
+    pub fn display(&self) -> &[[bool; HIRES_H]; SCR_W] {
+        &self.display
+    }
+    // whether the beeper should be sounding, for a frontend doing its own audio
+    pub fn sound_active(&self) -> bool {
+        self.state.beeps() && self.sound_timer > 0
+    }
+    // the CPU, timers and display back to how they start, leaving RAM alone
+    fn reset_machine(&mut self) {
+        self.driver.reset();
+        self.frame_cycles = 0;
+        self.clear_display();
+        self.frame_hash = None;
+        self.mirror_display();
+        self.timer = 0;
+        self.sound_timer = 0;
+        self.registers = Registers::new();
+        self.program_counter = self.start_address();
+        self.stack_pointer = 0x000;
+        self.call_stack.clear();
+        self.call_log = CallLog::new();
+        self.write_log.clear();
+        self.history.clear();
+        self.key_block = 0x10;
+        self.keys = [false; 0x10];
+        self.halt = None;
+        self.state = self.state.clone().on(Event::Reset);
+    }
+    // F5: as if the machine was switched off and on, with the same ROM going back in
+    pub fn hard_reset(&mut self) {
+        let rom = std::mem::take(&mut self.rom);
+        self.reset();
+        self.load_rom_bytes(&rom).expect("the ROM went in before");
+        self.restore_save_ram();
+    }
+    // shift+F5: the machine restarts but RAM past the ROM keeps what the last run left there,
+    // high scores included. the ROM image itself goes back in, bar any of it --persist-ram keeps
+    pub fn soft_reset(&mut self) {
+        self.reset_machine();
+        for (i, byte) in self.rom.iter().enumerate() {
+            let addr = 0x200 + i as u16;
+            if !self.is_persisted(addr) {
+                self.ram.set(addr, *byte);
+            }
+        }
+    }
+    // where the last DXYN erased pixels. only kept while stepping or with --show-collisions,
+    // so it's empty otherwise
+    pub fn last_collisions(&self) -> &[(u8, u8)] {
+        &self.collisions
+    }
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+    // a timer tick every 10 steps, 600 steps a second's worth
+    pub fn step_timers(&mut self) {
+        if self.driver.step_timer() {
+            self.tick_timers();
+        }
+    }
+    pub fn tick_timers(&mut self) {
+        if let Some(recording) = &mut self.recording {
+            let frequency = audio::beep_frequency(self.beep_style, self.beep_freq, self.sound_timer);
+            recording.frame(self.sound_timer > 0, self.beep_wave.get(), frequency);
+        }
+        self.timer = self.timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.display_waiting = false;
+        self.stats.timer_ticks += 1;
+        self.draw_capture_frame();
+        self.keep_post_mortem();
+        if self.trace_display {
+            let hash = self.frame_hash();
+            log::trace!(target: logging::DISPLAY_TRACE, "frame {} {:016x}", self.stats.timer_ticks, hash);
+        }
+        #[cfg(feature = "scripting")]
+        self.run_script_hook("on_frame");
+    }
+    // hands a key to a pending FX0A, returns false while the machine is still blocked. like
+    // the VIP, a key has to be pressed and then released during the wait: one that was
+    // already held (from the last FX0A, say) doesn't count until it's let go and pressed
+    // again, and of several released together the lowest wins
+    pub fn resolve_key_block(&mut self) -> bool {
+        #[cfg(feature = "scripting")]
+        self.apply_script_keys();
+        if self.key_block != 0x10 {
+            let (keys, mut released) = (self.rom_keys(), None);
+            for (i, &down) in keys.iter().enumerate() {
+                let was_down = self.key_wait_down[i];
+                if down && !was_down {
+                    self.key_wait_pressed[i] = true;
+                    self.key_observed(i); // the ROM sees the press, even if it waits on
+                }
+                if was_down && !down && self.key_wait_pressed[i] && released.is_none() {
+                    released = Some(i);
+                }
+            }
+            self.key_wait_down = keys;
+            if let Some(i) = released {
+                self.registers.set(self.key_block, i as u8);
+                self.key_block = 0x10;
+                // the next FX0A can start waiting in the same frame, and gets timed afresh
+                self.key_wait_time = 0.0;
+                self.key_wait_expired = false;
+            }
+        }
+        self.key_block == 0x10
+    }
+    pub fn coverage(&mut self) -> Coverage {
+        Coverage::measure(&mut self.ram, 0x200, self.rom_size)
+    }
+    // every way out of the emulator ends up here
+    pub fn shutdown(&mut self) {
+        self.flush_save_ram();
+        self.stats.finish();
+        if self.print_stats && self.json {
+            output::print_json(&self.stats.report());
+        } else if self.print_stats {
+            println!("{}", self.stats);
+        }
+        self.report_coverage();
+        if let Some(recording) = &self.recording {
+            if let Err(e) = recording.write() {
+                log::error!("Failed to write audio recording to {}: {}", recording.path(), e);
+            }
+        }
+    }
+    fn report_coverage(&mut self) {
+        if !self.print_coverage && self.coverage_map.is_none() {
+            return;
+        }
+        let coverage = self.coverage();
+        if self.print_coverage {
+            println!("{}", coverage);
+        }
+        if let Some(path) = &self.coverage_map {
+            if let Err(e) = coverage.write_map(path) {
+                log::error!("Failed to write coverage map to {}: {}", path, e);
+            }
+        }
+    }
+    // the beeper plays while the sound timer runs, if the state lets it
+    pub fn update_beeper(&mut self, delta: f32) {
+        let on = self.sound_active();
+        let level = self.envelope.update(on, delta);
+        if on {
+            // left alone while silent, so the release keeps the beep's last pitch
+            let frequency = audio::beep_frequency(self.beep_style, self.beep_freq, self.sound_timer);
+            self.beep_pitch.set(frequency);
+        }
+        self.beeper.set_level(BEEP_VOLUME * level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a high score to 0xE00, over its own byte at 0x20E, the delay timer, a call and a draw
+    const LEAVES_STATE: [u8; 20] = [
+        0xAE, 0x00, 0x60, 0x42, 0xF0, 0x55, 0xA2, 0x0E, 0xF0, 0x55, 0xF0, 0x15, 0x22, 0x10,
+        0x00, 0x00, 0xD0, 0x15, 0x12, 0x12,
+    ];
+
+    fn ran() -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&LEAVES_STATE).unwrap();
+        emulator.run_cycles(20);
+        assert_eq!((emulator.ram.get(0xE00), emulator.ram.get(0x20E)), (0x42, 0x42));
+        emulator
+    }
+
+    fn assert_restarted(emulator: &Emulator) {
+        assert_eq!(emulator.registers.as_slice(), [0; 16]);
+        assert_eq!((emulator.program_counter, emulator.stack_pointer), (0x200, 0x000));
+        assert_eq!((emulator.timer, emulator.sound_timer), (0, 0));
+        assert_eq!(emulator.call_stack.len(), 0);
+        assert!(emulator.display.iter().flatten().all(|&on| !on));
+        assert_eq!(emulator.halt, None);
+    }
+
+    #[test]
+    fn a_soft_reset_keeps_ram_past_the_rom() {
+        let mut emulator = ran();
+        emulator.soft_reset();
+        assert_restarted(&emulator);
+        assert_eq!(emulator.ram.get(0xE00), 0x42);
+        assert_eq!(emulator.ram.get(0x20E), 0x00); // the ROM image goes back in
+    }
+
+    #[test]
+    fn a_hard_reset_starts_ram_over() {
+        let mut emulator = ran();
+        emulator.hard_reset();
+        assert_restarted(&emulator);
+        assert_eq!((emulator.ram.get(0xE00), emulator.ram.get(0x20E)), (0x00, 0x00));
+        assert_eq!(&emulator.ram.as_slice()[0x200..0x214], LEAVES_STATE);
+        assert_eq!(emulator.ram.get(0x000), 0xF0); // the font's back
+    }
+
+    // the persisted range is left as it is, even where it's over the ROM
+    #[test]
+    fn a_soft_reset_leaves_the_persisted_range_alone() {
+        let mut emulator = ran();
+        let rom = std::env::temp_dir().join(format!("chip8rust-soft-{}.ch8", std::process::id()));
+        emulator.save_ram = Some(SaveRam::new(0x20E..=0x20F, &rom.to_string_lossy()));
+        emulator.soft_reset();
+        assert_eq!(emulator.ram.get(0x20E), 0x42);
+    }
+
+    #[test]
+    fn a_rom_one_byte_too_big_is_turned_away() {
+        use rom_source::MAX_ROM_SIZE;
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[0x12, 0x00]).unwrap();
+        let error = emulator.load_rom_bytes(&vec![0xAA; MAX_ROM_SIZE + 1]).unwrap_err();
+        assert_eq!(error.to_string(), "ROM too large (3585 bytes, max 3584)");
+        assert_eq!(&emulator.ram.as_slice()[0x200..0x203], [0x12, 0x00, 0x00]); // left as it was
+
+        emulator.load_rom_bytes(&vec![0xAA; MAX_ROM_SIZE]).unwrap();
+        assert_eq!((emulator.ram.get(0x200), emulator.ram.get(0xFFF)), (0xAA, 0xAA));
+        assert_eq!(emulator.rom_size, MAX_ROM_SIZE);
+    }
+
+    #[test]
+    fn an_empty_rom_or_a_missing_file() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[]).unwrap();
+        assert_eq!((emulator.rom_size, emulator.program_counter), (0, 0x200));
+        let missing = std::env::temp_dir().join(format!("chip8rust-{}.none", std::process::id()));
+        let error = emulator.load_rom(&missing.to_string_lossy()).unwrap_err();
+        assert_eq!(error.to_string(), "file not found");
+        // and at 0x000, where the font goes
+        emulator.ram.load_bytes(0x000, &[0x01, 0x02]).unwrap();
+        assert_eq!((emulator.ram.get(0x000), emulator.ram.get(0x001)), (0x01, 0x02));
+        assert!(emulator.ram.load_bytes(0xFFF, &[0x01, 0x02]).is_err());
+    }
+}
@@ -0,0 +1,547 @@
+// a libretro core, so RetroArch and the other libretro frontends can run CHIP-8: build the
+// cdylib with the libretro feature and load it as a core. retro_run is one 60Hz step_frame,
+// drawn as XRGB8888 and beeped at 44.1kHz, with the joypad or the keyboard on the 16 keys.
+// libretro calls a core from one thread, so everything lives in a thread-local
+use std::cell::{Cell, RefCell};
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::rc::Rc;
+use std::slice;
+
+use crate::audio::{Beeper, Tone, SAMPLE_RATE};
+use crate::hires::{Platform, HIRES_H};
+use crate::palette::Rgb;
+use crate::{Emulator, RunMode, SCR_H, SCR_W};
+
+const API_VERSION: c_uint = 1;
+const REGION_NTSC: c_uint = 0;
+
+const ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+const ENVIRONMENT_SET_GEOMETRY: c_uint = 37;
+const PIXEL_FORMAT_XRGB8888: c_uint = 1;
+
+pub const DEVICE_JOYPAD: c_uint = 1;
+pub const DEVICE_KEYBOARD: c_uint = 3;
+
+// RETRO_DEVICE_ID_JOYPAD_*, in order from B to R3, and the key each one presses. the
+// d-pad is 2/4/6/8 and A is 5, the keys most ROMs move and fire with
+const JOYPAD_KEYS: [usize; 16] = [
+    0x0, // B
+    0xB, // Y
+    0xE, // select
+    0xF, // start
+    0x2, // up
+    0x8, // down
+    0x4, // left
+    0x6, // right
+    0x5, // A
+    0xA, // X
+    0x1, // L
+    0x3, // R
+    0x7, // L2
+    0x9, // R2
+    0xC, // L3
+    0xD, // R3
+];
+// the keyboard the way the window has it, 1234 / QWER / ASDF / ZXCV, indexed by key. the
+// RETROK_ codes of letters and digits are their lowercase ASCII
+const KEYBOARD_KEYS: &[u8; 16] = b"x123qweasdzc4rfv";
+
+const CYCLE_SPEED: &CStr = c"chip8rust_cycle_speed";
+const QUIRKS: &CStr = c"chip8rust_quirks";
+const PALETTE: &CStr = c"chip8rust_palette";
+// the frontend shows the first value as the default
+const CYCLE_SPEEDS: &CStr =
+    c"Cycle speed (instructions a second); 600|300|420|540|720|900|1200|1800|3000";
+const PROFILES: &CStr = c"Quirks (hires takes a restart); chip8|vip|schip|hires";
+const PALETTES: &CStr = c"Palette; white|gray|green|classic-green|amber|lcd|paper-white|ice|candy|\
+high-contrast|deuteranopia|protanopia|tritanopia";
+
+pub type EnvironmentFn = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+pub type VideoRefreshFn =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+pub type AudioSampleFn = unsafe extern "C" fn(left: i16, right: i16);
+pub type AudioSampleBatchFn = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+pub type InputPollFn = unsafe extern "C" fn();
+pub type InputStateFn =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[repr(C)]
+pub struct SystemInfo {
+    pub library_name: *const c_char,
+    pub library_version: *const c_char,
+    pub valid_extensions: *const c_char,
+    pub need_fullpath: bool,
+    pub block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    pub base_width: c_uint,
+    pub base_height: c_uint,
+    pub max_width: c_uint,
+    pub max_height: c_uint,
+    pub aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    pub fps: f64,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    pub geometry: GameGeometry,
+    pub timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct Variable {
+    pub key: *const c_char,
+    pub value: *const c_char,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    pub path: *const c_char,
+    pub data: *const c_void,
+    pub size: usize,
+    pub meta: *const c_char,
+}
+
+// chip8rust_quirks, the quirks of a few interpreters as one setting
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Profile {
+    Chip8,     // the defaults: sprites wrap, BNNN adds V0
+    Vip,       // the COSMAC VIP's: sprites cut off at the edges, its RAM areas reserved
+    SuperChip, // sprites cut off, BXNN adds VX
+    Hires,     // the 64x64 two-page ROMs, from the next load or restart
+}
+impl Profile {
+    fn parse(value: &str) -> Profile {
+        match value {
+            "vip" => Profile::Vip,
+            "schip" => Profile::SuperChip,
+            "hires" => Profile::Hires,
+            _ => Profile::Chip8,
+        }
+    }
+    fn apply(self, emulator: &mut Emulator) {
+        let clip = matches!(self, Profile::Vip | Profile::SuperChip);
+        emulator.wrap_x = !clip;
+        emulator.wrap_y = !clip;
+        emulator.jump_x = self == Profile::SuperChip;
+        emulator.reserve_vip_areas = self == Profile::Vip;
+        emulator.display_wait = self == Profile::Vip;
+        emulator.shift_vy = self == Profile::Vip;
+        emulator.platform = match self {
+            Profile::Hires => Platform::HiresChip8,
+            _ => Platform::Chip8,
+        };
+        emulator.mirror_display();
+    }
+}
+
+// the beeper's level, for retro_run to turn into samples
+struct Level(Rc<Cell<f32>>);
+impl Beeper for Level {
+    fn set_level(&mut self, level: f32) {
+        self.0.set(level);
+    }
+}
+
+struct Game {
+    emulator: Emulator,
+    cycles_per_frame: u32,
+    on: u32, // the palette's colors as XRGB8888
+    off: u32,
+    level: Rc<Cell<f32>>,
+    tone: Tone,
+    framebuffer: Vec<u32>,
+    samples: Vec<i16>, // a frame of them, left and right
+}
+
+#[derive(Default)]
+struct Core {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+    game: Option<Game>,
+}
+
+thread_local! {
+    static CORE: RefCell<Core> = RefCell::new(Core::default());
+}
+
+fn xrgb(color: Rgb) -> u32 {
+    (color.r as u32) << 16 | (color.g as u32) << 8 | color.b as u32
+}
+
+fn geometry(height: usize) -> GameGeometry {
+    GameGeometry {
+        base_width: SCR_W as c_uint,
+        base_height: height as c_uint,
+        max_width: SCR_W as c_uint,
+        max_height: HIRES_H as c_uint,
+        aspect_ratio: 2.0, // hires halves the rows, the screen stays the shape it was
+    }
+}
+
+impl Core {
+    unsafe fn environment(&self, cmd: c_uint, data: *mut c_void) -> bool {
+        self.environment.is_some_and(|environment| environment(cmd, data))
+    }
+    // a core option's value, or None if the frontend has none
+    unsafe fn variable(&self, key: &CStr) -> Option<String> {
+        let mut variable = Variable {
+            key: key.as_ptr(),
+            value: ptr::null(),
+        };
+        let data = &mut variable as *mut Variable as *mut c_void;
+        if !self.environment(ENVIRONMENT_GET_VARIABLE, data) || variable.value.is_null() {
+            return None;
+        }
+        Some(CStr::from_ptr(variable.value).to_string_lossy().into_owned())
+    }
+    // the core options, onto the game. the cycle speed and the palette change straight away,
+    // as do quirks bar the platform, which is decided when the ROM goes in
+    unsafe fn apply_options(&mut self) {
+        let speed = self.variable(CYCLE_SPEED).and_then(|speed| speed.parse().ok());
+        let profile = Profile::parse(self.variable(QUIRKS).as_deref().unwrap_or_default());
+        let wanted = self.variable(PALETTE);
+        let Some(game) = &mut self.game else {
+            return;
+        };
+        game.cycles_per_frame = Emulator::cycles_per_frame(speed.unwrap_or(600.0));
+        profile.apply(&mut game.emulator);
+        let palettes = &game.emulator.palettes;
+        let chosen = palettes.iter().find(|palette| Some(&palette.name) == wanted.as_ref());
+        let chosen = chosen.unwrap_or(&palettes[0]);
+        (game.on, game.off) = (xrgb(chosen.on()), xrgb(chosen.off()));
+    }
+    unsafe fn read_input(&mut self) {
+        let (Some(poll), Some(state), Some(game)) =
+            (self.input_poll, self.input_state, &mut self.game)
+        else {
+            return;
+        };
+        poll();
+        let mut down = [false; 0x10];
+        for (id, key) in JOYPAD_KEYS.iter().enumerate() {
+            down[*key] |= state(0, DEVICE_JOYPAD, 0, id as c_uint) != 0;
+        }
+        for (key, code) in KEYBOARD_KEYS.iter().enumerate() {
+            down[key] |= state(0, DEVICE_KEYBOARD, 0, *code as c_uint) != 0;
+        }
+        let emulator = &mut game.emulator;
+        emulator.keys = emulator.key_repeat.shape(down, 1.0 / 60.0, false);
+    }
+    unsafe fn run(&mut self) {
+        let mut updated = false;
+        let data = &mut updated as *mut bool as *mut c_void;
+        if self.environment(ENVIRONMENT_GET_VARIABLE_UPDATE, data) && updated {
+            self.apply_options();
+        }
+        self.read_input();
+        let Some(game) = &mut self.game else {
+            return;
+        };
+        let end = game.emulator.step_frame(game.cycles_per_frame);
+        if let Some(halt) = game.emulator.halt {
+            log::info!("Halted: {}", halt);
+        }
+
+        let (_, height) = game.emulator.display_size();
+        if end.display_changed {
+            let mut geometry = geometry(height);
+            let data = &mut geometry as *mut GameGeometry as *mut c_void;
+            if let Some(environment) = self.environment {
+                environment(ENVIRONMENT_SET_GEOMETRY, data);
+            }
+        }
+        for (x, column) in game.emulator.display().iter().enumerate() {
+            for (y, lit) in column[..height].iter().enumerate() {
+                game.framebuffer[y * SCR_W + x] = if *lit { game.on } else { game.off };
+            }
+        }
+        if let Some(video_refresh) = self.video_refresh {
+            let data = game.framebuffer.as_ptr() as *const c_void;
+            video_refresh(data, SCR_W as c_uint, height as c_uint, SCR_W * 4);
+        }
+
+        // the same envelope and tone the window's beeper has, a sample at a time
+        game.samples.clear();
+        for _ in 0..SAMPLE_RATE / 60 {
+            game.emulator.update_beeper(1.0 / SAMPLE_RATE as f32);
+            let sample = game.tone.next().unwrap_or(0.0) * game.level.get();
+            let sample = (sample * i16::MAX as f32) as i16;
+            game.samples.extend([sample, sample]);
+        }
+        if let Some(audio_sample_batch) = self.audio_sample_batch {
+            audio_sample_batch(game.samples.as_ptr(), game.samples.len() / 2);
+        }
+    }
+}
+
+// runs `call` on the core. a panic in there is caught rather than unwinding into the
+// frontend, and the game it came from is dropped
+fn with_core<T>(fallback: T, call: impl FnOnce(&mut Core) -> T) -> T {
+    CORE.with(|core| {
+        let mut core = core.borrow_mut();
+        match panic::catch_unwind(AssertUnwindSafe(|| call(&mut core))) {
+            Ok(result) => result,
+            Err(_) => {
+                log::error!("The core panicked, unloading the game");
+                core.game = None;
+                fallback
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    API_VERSION
+}
+
+/// # Safety
+/// `environment` is the frontend's, and good for as long as the core is loaded.
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(environment: Option<EnvironmentFn>) {
+    with_core((), |core| {
+        core.environment = environment;
+        let variables = [
+            Variable {
+                key: CYCLE_SPEED.as_ptr(),
+                value: CYCLE_SPEEDS.as_ptr(),
+            },
+            Variable {
+                key: QUIRKS.as_ptr(),
+                value: PROFILES.as_ptr(),
+            },
+            Variable {
+                key: PALETTE.as_ptr(),
+                value: PALETTES.as_ptr(),
+            },
+            Variable {
+                key: ptr::null(),
+                value: ptr::null(),
+            },
+        ];
+        core.environment(ENVIRONMENT_SET_VARIABLES, variables.as_ptr() as *mut c_void);
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(video_refresh: Option<VideoRefreshFn>) {
+    with_core((), |core| core.video_refresh = video_refresh)
+}
+
+// the one-sample callback goes unused, a frame's samples go to the batch one
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_audio_sample: Option<AudioSampleFn>) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: Option<AudioSampleBatchFn>) {
+    with_core((), |core| core.audio_sample_batch = audio_sample_batch)
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(input_poll: Option<InputPollFn>) {
+    with_core((), |core| core.input_poll = input_poll)
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(input_state: Option<InputStateFn>) {
+    with_core((), |core| core.input_state = input_state)
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    with_core((), |core| *core = Core::default())
+}
+
+/// # Safety
+/// `info` points at a retro_system_info to fill in.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    let Some(info) = info.as_mut() else {
+        return;
+    };
+    *info = SystemInfo {
+        library_name: c"chip8rust".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: c"ch8|c8|rom".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+/// `info` points at a retro_system_av_info to fill in.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    let Some(info) = info.as_mut() else {
+        return;
+    };
+    let size = |game: &Game| game.emulator.display_size().1;
+    let height = with_core(SCR_H, |core| core.game.as_ref().map_or(SCR_H, size));
+    *info = SystemAvInfo {
+        geometry: geometry(height),
+        timing: SystemTiming {
+            fps: 60.0,
+            sample_rate: SAMPLE_RATE as f64,
+        },
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_core((), |core| {
+        if let Some(game) = &mut core.game {
+            game.emulator.hard_reset();
+        }
+    })
+}
+
+/// # Safety
+/// Only after retro_load_game, from the thread that loaded it.
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    with_core((), |core| core.run())
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    0 // no save states
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize(_data: *mut c_void, _size: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unserialize(_data: *const c_void, _size: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// # Safety
+/// `game` points at a retro_game_info whose data is `size` bytes, the ROM.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    let Some(game) = game.as_ref().filter(|game| !game.data.is_null()) else {
+        return false; // no game, or one only given as a path: need_fullpath is off
+    };
+    let rom = slice::from_raw_parts(game.data as *const u8, game.size);
+    with_core(false, |core| {
+        let mut format = PIXEL_FORMAT_XRGB8888;
+        let data = &mut format as *mut c_uint as *mut c_void;
+        if !core.environment(ENVIRONMENT_SET_PIXEL_FORMAT, data) {
+            log::error!("The frontend can't take XRGB8888");
+            return false;
+        }
+        let level = Rc::new(Cell::new(0.0));
+        let mut emulator = Emulator::new();
+        emulator.state = RunMode::Play.into();
+        emulator.beeper = Box::new(Level(level.clone()));
+        let tone = Tone::new(emulator.beep_pitch.clone(), emulator.beep_wave.clone());
+        core.game = Some(Game {
+            emulator,
+            cycles_per_frame: 0,
+            on: 0,
+            off: 0,
+            level,
+            tone,
+            framebuffer: vec![0; SCR_W * HIRES_H],
+            samples: Vec::with_capacity(2 * (SAMPLE_RATE / 60) as usize),
+        });
+        core.apply_options(); // the platform first, it decides where the ROM starts
+        let game = core.game.as_mut().unwrap();
+        if let Err(e) = game.emulator.load_rom_bytes(rom) {
+            log::error!("Failed to load the ROM: {}", e);
+            core.game = None;
+            return false;
+        }
+        // retro_get_system_av_info gives the frontend this size, it's no change to it
+        game.emulator.take_display_change();
+        true
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const GameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    with_core((), |core| {
+        if let Some(mut game) = core.game.take() {
+            game.emulator.shutdown();
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_palette_option_lists_every_builtin() {
+        let options = PALETTES.to_str().unwrap().split_once("; ").unwrap().1;
+        let names: Vec<String> = crate::palette::builtins().into_iter().map(|p| p.name).collect();
+        assert_eq!(options, names.join("|"));
+    }
+
+    #[test]
+    fn a_profile_sets_the_quirks() {
+        let mut emulator = Emulator::new();
+        Profile::parse("schip").apply(&mut emulator);
+        assert!(!emulator.wrap_x && !emulator.wrap_y && emulator.jump_x);
+        assert!(!emulator.display_wait && !emulator.shift_vy);
+        Profile::parse("vip").apply(&mut emulator);
+        assert!(emulator.reserve_vip_areas && emulator.display_wait && !emulator.jump_x);
+        assert!(emulator.shift_vy);
+        Profile::parse("hires").apply(&mut emulator);
+        assert!(emulator.wrap_x && emulator.platform == Platform::HiresChip8);
+    }
+}
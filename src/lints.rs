@@ -0,0 +1,271 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::instructions::Instruction;
+use crate::status::NOTICE_TIME;
+use crate::Emulator;
+
+// FX07s at one address, with nothing ever setting the timer, before it counts as a spin
+const TIMER_SPIN_READS: u32 = 100;
+// deeper than the original interpreter's stack went
+const MAX_CALL_DEPTH: usize = 12;
+// uses of one quirk-sensitive instruction before its hint goes up
+const QUIRK_HINT_USES: u64 = 100;
+
+// mistakes that are easy to make writing a ROM, which run without complaint but are
+// almost never what was meant
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum Lint {
+    TimerSpin,    // waiting on the delay timer when nothing has set it
+    FontDraw,     // a sprite taller than a font digit, drawn from the font area
+    DeepCalls,    // more calls on the stack than MAX_CALL_DEPTH
+    SkipIntoLong, // a skip that lands in the middle of XO-CHIP's 4-byte F000 NNNN
+    SpritePastEnd, // a sprite whose rows run past 0xFFF, which interpreters read differently
+}
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::TimerSpin => write!(f, "reads the delay timer in a loop, but nothing sets it"),
+            Lint::FontDraw => write!(f, "draws more than 5 rows with I in the font area"),
+            Lint::DeepCalls => write!(f, "calls more than {} deep", MAX_CALL_DEPTH),
+            Lint::SkipIntoLong => write!(f, "skips into the middle of an F000 NNNN"),
+            Lint::SpritePastEnd => write!(f, "draws a sprite with rows past 0xFFF"),
+        }
+    }
+}
+
+// --lint: what's been seen so far. each lint is reported once per address
+pub struct Lints {
+    reported: HashSet<(Lint, u16)>,
+    timer_reads: HashMap<u16, u32>, // FX07s at each address, until something sets the timer
+    timer_set: bool,
+}
+impl Lints {
+    pub fn new() -> Lints {
+        Lints {
+            reported: HashSet::new(),
+            timer_reads: HashMap::new(),
+            timer_set: false,
+        }
+    }
+    // the lints `instruction` at `addr` sets off, from I and the call depth after it ran
+    // and whether it skipped over an F000
+    fn check(
+        &mut self,
+        addr: u16,
+        instruction: &Instruction,
+        i: u16,
+        call_depth: usize,
+        skipped_long: bool,
+    ) -> Vec<Lint> {
+        let mut found = Vec::new();
+        match (instruction.opcode, instruction.byte) {
+            (0xF, 0x07) if !self.timer_set => {
+                let reads = self.timer_reads.entry(addr).or_insert(0);
+                *reads += 1;
+                if *reads >= TIMER_SPIN_READS {
+                    found.push(Lint::TimerSpin);
+                }
+            }
+            (0xF, 0x15) => {
+                self.timer_set = true;
+                self.timer_reads.clear();
+            }
+            (0xD, _) => {
+                if instruction.n > 5 && i < 0x200 {
+                    found.push(Lint::FontDraw);
+                }
+                if i as usize + instruction.n as usize > 0x1000 {
+                    found.push(Lint::SpritePastEnd);
+                }
+            }
+            (0x2, _) if call_depth > MAX_CALL_DEPTH => found.push(Lint::DeepCalls),
+            (0x3 | 0x4 | 0x5 | 0x9, _) | (0xE, 0x9E | 0xA1) if skipped_long => {
+                found.push(Lint::SkipIntoLong)
+            }
+            _ => {}
+        }
+        found.retain(|lint| self.reported.insert((*lint, addr)));
+        found
+    }
+}
+
+// instructions interpreters disagree about, counted on every run, lint or not
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum QuirkUse {
+    Shift,      // 8XY6 and 8XYE
+    LoadStore,  // FX55 and FX65
+    OffsetJump, // BNNN
+    EdgeDraw,   // a DXYN running past the right or bottom edge
+}
+
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct QuirkUses {
+    pub shifts: u64,
+    pub load_stores: u64,
+    pub offset_jumps: u64,
+    pub edge_draws: u64,
+}
+impl QuirkUses {
+    fn count(&mut self, kind: QuirkUse) -> u64 {
+        let uses = match kind {
+            QuirkUse::Shift => &mut self.shifts,
+            QuirkUse::LoadStore => &mut self.load_stores,
+            QuirkUse::OffsetJump => &mut self.offset_jumps,
+            QuirkUse::EdgeDraw => &mut self.edge_draws,
+        };
+        *uses += 1;
+        *uses
+    }
+    pub fn merge(&mut self, other: &QuirkUses) {
+        self.shifts += other.shifts;
+        self.load_stores += other.load_stores;
+        self.offset_jumps += other.offset_jumps;
+        self.edge_draws += other.edge_draws;
+    }
+}
+
+impl Emulator {
+    // counts a quirk-sensitive instruction, and on its QUIRK_HINT_USES'th use says
+    // how this emulator runs it, or which flag changes that
+    pub fn quirk_used(&mut self, kind: QuirkUse) {
+        if self.stats.quirk_uses.count(kind) != QUIRK_HINT_USES {
+            return;
+        }
+        let flip = |on: bool| if on { "false" } else { "true" };
+        let hint = match kind {
+            QuirkUse::Shift if self.shift_vy => {
+                String::from("Uses 8XY6/8XYE a lot: here they shift VY, not VX")
+            }
+            QuirkUse::Shift => {
+                String::from("Uses 8XY6/8XYE a lot: here they shift VX, --quirk-shift-vy VY")
+            }
+            QuirkUse::LoadStore => String::from("Uses FX55/FX65 a lot: here they leave I alone"),
+            QuirkUse::OffsetJump if self.jump_x => {
+                String::from("Uses BXNN a lot: here it adds VX, not V0")
+            }
+            QuirkUse::OffsetJump => {
+                String::from("Uses BNNN a lot: here it adds V0, --quirk-jump-x VX")
+            }
+            QuirkUse::EdgeDraw => format!(
+                "Draws on the edges a lot, if it looks wrong try --wrap-x {} or --wrap-y {}",
+                flip(self.wrap_x),
+                flip(self.wrap_y)
+            ),
+        };
+        log::info!("{}", hint);
+        self.notify(hint, NOTICE_TIME * 2.0);
+    }
+    // after every cycle with --lint on, warning about anything new
+    pub fn lint(&mut self, addr: u16, instruction: &Instruction) {
+        let Some(lints) = &mut self.lints else {
+            return;
+        };
+        // only skips move the PC on by 4
+        let skipped = self.program_counter == addr.wrapping_add(4) & 0xFFF;
+        let skipped_long = skipped && self.ram.get_u16(addr.wrapping_add(2)) == 0xF000;
+        let (i, call_depth) = (self.stack_pointer, self.call_stack.len());
+        for lint in lints.check(addr, instruction, i, call_depth, skipped_long) {
+            log::warn!("Lint: {:#05X} {}", addr, lint);
+            self.notify(format!("Lint: {:#05X} {}", addr, lint), NOTICE_TIME);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // what --lint reported on `cycles` of `rom`
+    fn linted(rom: &[u8], cycles: u32) -> HashSet<(Lint, u16)> {
+        let mut emulator = Emulator::new();
+        emulator.lints = Some(Lints::new());
+        emulator.load_rom_bytes(rom).unwrap();
+        emulator.run_cycles(cycles);
+        emulator.lints.unwrap().reported
+    }
+
+    #[test]
+    fn a_spin_on_a_timer_nothing_sets() {
+        assert_eq!(linted(&[0xF0, 0x07, 0x12, 0x00], 400), [(Lint::TimerSpin, 0x200)].into());
+        // the same loop is fine once the timer's been set
+        assert!(linted(&[0x60, 0x05, 0xF0, 0x15, 0xF0, 0x07, 0x12, 0x04], 400).is_empty());
+    }
+
+    #[test]
+    fn a_tall_sprite_from_the_font() {
+        let font_draws = [0xA0, 0x00, 0xD0, 0x16, 0xD0, 0x1F, 0xD0, 0x15, 0x12, 0x00];
+        let found = linted(&font_draws, 40);
+        assert_eq!(found, [(Lint::FontDraw, 0x202), (Lint::FontDraw, 0x204)].into());
+    }
+
+    #[test]
+    fn a_sprite_past_the_end_of_ram() {
+        let found = linted(&[0xAF, 0xFE, 0xD0, 0x13, 0xD0, 0x12, 0x12, 0x02], 40);
+        assert_eq!(found, [(Lint::SpritePastEnd, 0x202)].into());
+    }
+
+    #[test]
+    fn calls_too_deep() {
+        // calling itself until the stack runs out
+        assert_eq!(linted(&[0x22, 0x00], 20), [(Lint::DeepCalls, 0x200)].into());
+        let calls: Vec<u8> = (0..MAX_CALL_DEPTH as u16)
+            .flat_map(|depth| (0x2000 | (0x202 + 2 * depth)).to_be_bytes())
+            .chain([0x00, 0xFD])
+            .collect();
+        assert!(linted(&calls, 20).is_empty()); // as deep as it goes
+    }
+
+    #[test]
+    fn a_skip_into_a_long_instruction() {
+        // SKIPIF R0 == 00, taken, over the F000 and into its NNNN
+        let found = linted(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x00], 40);
+        assert_eq!(found, [(Lint::SkipIntoLong, 0x200)].into());
+        assert!(linted(&[0x30, 0x01, 0x00, 0xE0, 0x12, 0x00], 40).is_empty());
+    }
+
+    #[test]
+    fn a_new_rom_starts_over() {
+        let mut emulator = Emulator::new();
+        emulator.lints = Some(Lints::new());
+        emulator.load_rom_bytes(&[0xA0, 0x00, 0xD0, 0x16]).unwrap();
+        emulator.run_cycles(2);
+        assert_eq!(emulator.lints.as_ref().unwrap().reported.len(), 1);
+        emulator.reset();
+        assert!(emulator.lints.unwrap().reported.is_empty());
+    }
+
+    // 8XY6 round and round: the hint goes up on its 100th use, and never again
+    #[test]
+    fn a_quirk_hint_shows_once() {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&[0x80, 0x06, 0x12, 0x00]).unwrap();
+        emulator.run_cycles(2 * QUIRK_HINT_USES as u32 - 2);
+        assert_eq!(emulator.status.current(), None);
+        emulator.run_cycles(1);
+        let hint = "Uses 8XY6/8XYE a lot: here they shift VX, --quirk-shift-vy VY";
+        assert_eq!(emulator.status.current(), Some((hint, false)));
+        emulator.status.update(NOTICE_TIME * 2.0);
+        emulator.run_cycles(2000);
+        assert_eq!(emulator.status.current(), None);
+        assert_eq!(emulator.stats.quirk_uses.shifts, QUIRK_HINT_USES + 1000);
+        assert_eq!(emulator.stats.quirk_uses.load_stores, 0);
+    }
+
+    #[test]
+    fn each_quirk_is_counted_on_its_own() {
+        let mut emulator = Emulator::new();
+        emulator.jump_x = true;
+        // 8X0E, FX55, FX65, a BXNN to the next word, and a draw off the bottom edge
+        let rom = [
+            0x80, 0x0E, 0xF0, 0x55, 0xF0, 0x65, 0xB2, 0x08, 0x6A, 0x3E, 0xD0, 0xA5, 0x00, 0xFD,
+        ];
+        emulator.load_rom_bytes(&rom).unwrap();
+        emulator.run_cycles(10);
+        let uses = emulator.stats.quirk_uses;
+        let counts = (uses.shifts, uses.load_stores, uses.offset_jumps, uses.edge_draws);
+        assert_eq!(counts, (1, 2, 1, 1));
+    }
+}
@@ -0,0 +1,71 @@
+use std::fs::File;
+use std::io::{self, Write};
+
+use env_logger::{Builder, Target};
+use log::LevelFilter;
+
+// --trace-display logs each frame's display hash here, whatever the level
+pub const DISPLAY_TRACE: &str = "chip8rust::display";
+// and --trace-timers every FX07, FX15 and FX18
+pub const TIMER_TRACE: &str = "chip8rust::timers";
+
+#[derive(PartialEq, Debug, Clone, clap::ValueEnum)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace, // every instruction executed
+}
+impl LogLevel {
+    fn filter(&self) -> LevelFilter {
+        match self {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+// log lines go to stderr and, with --log-file, to the file as well
+struct Tee {
+    file: File,
+}
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        io::stderr().write_all(buf)?;
+        self.file.write_all(buf)?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()?;
+        self.file.flush()
+    }
+}
+
+pub fn init(
+    level: &LogLevel,
+    log_file: Option<&str>,
+    trace_display: bool,
+    trace_timers: bool,
+) -> io::Result<()> {
+    let mut builder = Builder::new();
+    builder.filter_module("chip8rust", level.filter());
+    if trace_display {
+        builder.filter_module(DISPLAY_TRACE, LevelFilter::Trace);
+    }
+    if trace_timers {
+        builder.filter_module(TIMER_TRACE, LevelFilter::Trace);
+    }
+    if let Some(path) = log_file {
+        builder.target(Target::Pipe(Box::new(Tee {
+            file: File::create(path)?,
+        })));
+    }
+    builder.init();
+    Ok(())
+}
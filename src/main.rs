@@ -1,47 +1,29 @@
-use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
+use std::path::Path;
 
+use chip8rust::audio::{self, BeepStyle, Recording, Waveform};
+use chip8rust::code_guard::{CodeGuard, GuardPc};
+use chip8rust::components::{ArrayStack, CALL_STACK_SIZE, MAX_CALL_STACK_SIZE};
+use chip8rust::components::{OutOfRangeRead, OutOfRangeWrite, RamPolicy};
+use chip8rust::font::{self, FontStyle};
+use chip8rust::frame_driver::FrameDriver;
+use chip8rust::hires::Platform;
+use chip8rust::hot_patch::HotPatch;
+use chip8rust::input_lag::InputLag;
+use chip8rust::key_repeat::{self, InputMode, KeyRepeat, KeyShape};
+use chip8rust::lints::Lints;
+use chip8rust::logging::{self, LogLevel};
+use chip8rust::pacing::{self, Pacing};
+use chip8rust::pause_at::{self, PauseAt};
+use chip8rust::save_ram::{self, SaveRam};
+#[cfg(feature = "serve")]
+use chip8rust::serve;
+#[cfg(feature = "sdl2")]
+use chip8rust::sdl;
+use chip8rust::status::NOTICE_TIME;
+use chip8rust::write_log::WriteLog;
+use chip8rust::{batch, compare, crash, demo, disasm, draw_log, output, palette, regions};
+use chip8rust::{terminal, version, window, Emulator, RunMode};
 use clap::Parser;
-use olc_pge as olc;
-use olc_pge::Key;
-use rodio::{Decoder, OutputStream, Sink, Source};
-
-mod components;
-mod instructions;
-
-use components::{Ram, Registers};
-
-const SCR_W: usize = 64;
-const SCR_H: usize = 32;
-const DRAW_BIGGER_PIXELS: i32 = 4;
-const START_RUN_MODE: RunMode = RunMode::Step;
-
-const KEYS: [Key; 0x10] = [
-    Key::X,
-    Key::K1,
-    Key::K2,
-    Key::K3,
-    Key::Q,
-    Key::W,
-    Key::E,
-    Key::A,
-    Key::S,
-    Key::D,
-    Key::Z,
-    Key::C,
-    Key::K4,
-    Key::R,
-    Key::F,
-    Key::V,
-];
-
-
-#[derive(PartialEq, Debug, Clone, clap::ValueEnum)]
-enum RunMode {
-    Play,
-    Step,
-}
 
 #[derive(PartialEq, Debug, Clone, clap::ValueEnum)]
 enum ColorMode {
@@ -49,279 +31,479 @@ enum ColorMode {
     Gray,
     White,
 }
+impl ColorMode {
+    // each mode is one of the built-in palettes
+    fn palette_name(&self) -> &'static str {
+        match self {
+            ColorMode::Green => "green",
+            ColorMode::Gray => "gray",
+            ColorMode::White => "white",
+        }
+    }
+}
 
 #[derive(PartialEq, Debug, Clone, clap::ValueEnum)]
-enum InputMode {
-    Once,
-    Hold,
+enum Backend {
+    Window,
+    Terminal,
+    #[cfg(feature = "sdl2")]
+    Sdl2,
 }
 
 #[derive(Debug, Parser)]
+#[clap(args_conflicts_with_subcommands = true)]
 struct Args {
-    /// The ROM file to load
+    #[clap(subcommand)]
+    command: Option<Command>,
+    /// The ROM file to load (the built-in demo runs if none is given)
     #[clap(short, long, value_parser)]
-    rom_file: String,
-    /// The target execution speed for the processor (in cycles per second)
-    #[clap(short, long, default_value_t = 600.0)]
+    rom_file: Option<String>,
+    /// Run a built-in demo ROM, even if a ROM file is given
+    #[clap(long, value_name = "NAME", num_args = 0..=1, default_missing_value = demo::DEFAULT_DEMO)]
+    demo: Option<String>,
+    /// The target execution speed for the processor (in cycles per second), or max to run as
+    /// many as fit in --cycle-budget
+    #[clap(short, long, default_value_t = 600.0, value_parser = pacing::parse_cycle_speed)]
     cycle_speed: f32,
+    /// Run exactly this many instructions per 60Hz frame instead, whatever the host's timing
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    ipf: Option<u32>,
     /// Whether to start the program paused or not
     #[clap(value_enum, short = 'm', long, default_value_t = RunMode::Play)]
     run_mode: RunMode,
     /// The color mode to use for the display
     #[clap(value_enum, long, default_value_t = ColorMode::White)]
     color_mode: ColorMode,
-    /// The mode for the input keys (press once / hold)
+    /// A palette by name, built in or from palettes/ (P cycles through them), over --color-mode
+    #[clap(long, value_name = "NAME")]
+    palette: Option<String>,
+    /// The mode for the input keys (press once / hold), the same as --key-repeat single / pass
     #[clap(value_enum, long, default_value_t = InputMode::Hold)]
     input_mode: InputMode,
+    /// How holding a key down reaches the ROM: pass (held while down), single (one frame per
+    /// press) or repeat (one frame per press, then again at --key-repeat-rate)
+    #[clap(value_enum, long, value_name = "SHAPE")]
+    key_repeat: Option<KeyShape>,
+    /// One pad key's own --key-repeat, like 5=repeat (can be given for several keys)
+    #[clap(long, value_name = "KEY=SHAPE", value_parser = key_repeat::parse_key_shape)]
+    key_repeat_key: Vec<(usize, KeyShape)>,
+    /// How many times a second a repeat key comes back while it's held
+    #[clap(long, value_name = "HZ", default_value_t = 10.0, value_parser = pacing::parse_rate)]
+    key_repeat_rate: f32,
+    /// The only pad keys the ROM can see, like 0,1,2,3,4,5,6,7 (the rest always read as up)
+    #[clap(long, value_name = "KEYS", value_parser = key_repeat::parse_key_set)]
+    available_keys: Option<[bool; 0x10]>,
+    /// Print a ROM coverage report on exit (F9 prints one at any time)
+    #[clap(long)]
+    coverage: bool,
+    /// Write an annotated hex map of ROM coverage to this file on exit
+    #[clap(long, value_parser)]
+    coverage_map: Option<String>,
+    /// How many frames a draw capture (F10, then R) records every DXYN for
+    #[clap(long, value_name = "N", default_value_t = 60,
+           value_parser = clap::value_parser!(u32).range(1..=draw_log::MAX_FRAMES as i64))]
+    capture_frames: u32,
+    /// Where C in the draw capture writes it, as CSV
+    #[clap(long, value_parser, default_value = "draw_capture.csv")]
+    capture_csv: String,
+    /// Named ranges of RAM for the memory map and dumps (`name: START..END` lines) and labels
+    /// for the trace (`ADDR name` lines, as `disasm --emit-symbols` writes them)
+    #[clap(long, value_name = "FILE", value_parser)]
+    symbols: Option<String>,
+    /// Print run statistics on exit
+    #[clap(long)]
+    stats: bool,
+    /// Where to show the emulator (the terminal backend draws with half-block characters, sdl2
+    /// is a resizable window, when built with it)
+    #[clap(value_enum, long, default_value_t = Backend::Window)]
+    backend: Backend,
+    /// The pitch of the beep (in Hz)
+    #[clap(long, default_value_t = 440.0)]
+    beep_freq: f32,
+    /// The shape of the beep (F7 cycles through them)
+    #[clap(value_enum, long, default_value_t = Waveform::Square)]
+    beep_wave: Waveform,
+    /// Plain beeps keep one pitch, pitch beeps sweep down as the sound timer runs out
+    #[clap(value_enum, long, default_value_t = BeepStyle::Plain)]
+    beep_style: BeepStyle,
+    /// Record the beeper to this WAV file, written on exit
+    #[clap(long, value_name = "FILE.wav")]
+    record_audio: Option<String>,
+    /// Drop into step mode when an FX0A key wait lasts longer than this (in seconds)
+    #[clap(long, value_name = "SECS")]
+    keywait_timeout: Option<f32>,
+    /// Start in play mode and drop into step mode the first time this happens:
+    /// first-draw, first-input (EX9E, EXA1 or FX0A), addr:0xNNN or cycle:N
+    #[clap(long, value_name = "TRIGGER", value_parser = pause_at::parse,
+           conflicts_with = "run_mode")]
+    pause_at: Option<PauseAt>,
+    /// Warn about writes landing just ahead of the PC, once per instruction (log) or by
+    /// dropping into step mode (pause)
+    #[clap(value_enum, long, value_name = "MODE")]
+    guard_pc: Option<GuardPc>,
+    /// How many bytes past the writing instruction --guard-pc watches
+    #[clap(long, value_name = "N", default_value_t = 4)]
+    guard_pc_bytes: u16,
+    /// Experimental: when the ROM file is rebuilt, in the window, patch the bytes that changed
+    /// into RAM and keep running, or reset if its size or the instruction at the PC changed
+    #[clap(long)]
+    hot_patch: bool,
+    /// Log the program's memory writes from the start, for the memory map (M) to say what
+    /// last wrote the selected byte. W in the map turns it on and off
+    #[clap(long)]
+    write_log: bool,
+    /// How many writes the write log keeps, the oldest going first
+    #[clap(long, value_name = "N", default_value_t = 4096)]
+    write_log_size: usize,
+    /// Flash the pixels each sprite collision erased, in red
+    #[clap(long)]
+    show_collisions: bool,
+    /// Keep this RAM range (like 0xE00..0xFFF) in <rom>.sav, for ROMs that save high scores
+    #[clap(long, value_name = "START..END", value_parser = save_ram::parse_range)]
+    persist_ram: Option<std::ops::RangeInclusive<u16>>,
+    /// Run this ROM next to the first one in lockstep, stopping on the first frame they differ
+    #[clap(long, value_name = "ROM", conflicts_with = "backend")]
+    compare_rom: Option<String>,
+    /// Show the display hash in the step panel
+    #[clap(long)]
+    show_hash: bool,
+    /// Log every frame's display hash, at any log level, to compare runs across versions
+    #[clap(long)]
+    trace_display: bool,
+    /// Log every FX07, FX15 and FX18 (the timer, the value and the PC), at any log level
+    #[clap(long)]
+    trace_timers: bool,
+    /// Show how long the ROM takes to notice key presses, the frame time and cycles per frame
+    #[clap(long)]
+    input_lag: bool,
+    /// The digits FX29 points at: the standard font, the COSMAC VIP's, or a rounded or bold set
+    #[clap(value_enum, long, value_name = "STYLE", default_value_t = FontStyle::Standard)]
+    font_style: FontStyle,
+    /// A font file to use instead: 80 bytes, 5 rows for each of the 16 digits
+    #[clap(long, value_name = "PATH", conflicts_with = "font_style")]
+    font: Option<String>,
+    /// Whether sprites wrap around to the left edge (false cuts them off at the right)
+    #[clap(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    wrap_x: bool,
+    /// Whether sprites wrap around to the top (false cuts them off at the bottom)
+    #[clap(long, value_name = "BOOL", default_value_t = true, action = clap::ArgAction::Set)]
+    wrap_y: bool,
+    /// Keep the display in 0xF00-0xFFF as well, where the COSMAC VIP had it, for ROMs that
+    /// read it back from there
+    #[clap(long)]
+    reserve_vip_areas: bool,
+    /// BXNN jumps to XNN plus VX, as on SUPER-CHIP, instead of NNN plus V0
+    #[clap(long)]
+    quirk_jump_x: bool,
+    /// DXYN waits for the next 60Hz frame before the ROM goes on, as on the COSMAC VIP
+    #[clap(long)]
+    quirk_display_wait: bool,
+    /// 8XY6 and 8XYE shift VY into VX, as on the COSMAC VIP, instead of shifting VX
+    #[clap(long)]
+    quirk_shift_vy: bool,
+    /// How many return addresses the call stack holds before a CALL halts with an overflow
+    #[clap(long, value_name = "N", default_value_t = CALL_STACK_SIZE as u32,
+           value_parser = clap::value_parser!(u32).range(1..=MAX_CALL_STACK_SIZE as i64))]
+    stack_depth: u32,
+    /// The machine to be: plain CHIP-8, or the VIP's 64x64 two-page hires for ROMs starting
+    /// with 1260
+    #[clap(value_enum, long, value_name = "PLATFORM", default_value_t = Platform::Chip8)]
+    platform: Platform,
+    /// What reading past 0xFFF gets: wrapped back to 0x000, 0, or 0xFF
+    #[clap(value_enum, long, value_name = "POLICY", default_value_t = OutOfRangeRead::Wrap)]
+    out_of_range_read: OutOfRangeRead,
+    /// What writing past 0xFFF does: wraps back to 0x000, or nothing
+    #[clap(value_enum, long, value_name = "POLICY", default_value_t = OutOfRangeWrite::Wrap)]
+    out_of_range_write: OutOfRangeWrite,
+    /// The longest a frame can count as (in ms), so a stall doesn't come back as a burst of
+    /// catch-up frames
+    #[clap(long, value_name = "MS", default_value_t = 100.0)]
+    max_delta: f32,
+    /// With --ipf, drop frames the host fell behind on instead of running them late, which
+    /// keeps input and sound current but makes music slow down under load
+    #[clap(long)]
+    no_catchup: bool,
+    /// How much of each host frame (in ms) running cycles can take. Cycles that don't fit are
+    /// dropped and OVERLOAD shows, so a speed the host can't manage doesn't pile up
+    #[clap(long, value_name = "MS", default_value_t = 8.0)]
+    cycle_budget: f32,
+    /// Warn about common ROM bugs as they happen (timer spins, tall font draws, deep calls...)
+    #[clap(long)]
+    lint: bool,
+    /// How much to log to stderr (trace logs every instruction)
+    #[clap(value_enum, long, global = true, default_value_t = LogLevel::Warn)]
+    log_level: LogLevel,
+    /// Also write the log to this file
+    #[clap(long, global = true, value_parser)]
+    log_file: Option<String>,
+    /// Print the subcommands' output and --stats as JSON, and errors as {"error": ...}
+    #[clap(long, global = true)]
+    json: bool,
+    /// Print the version, the commit it was built from, its features and its quirks
+    #[clap(short = 'V', long)]
+    version: bool,
+    /// A rhai script with on_frame / on_draw / on_halt callbacks
+    #[cfg(feature = "scripting")]
+    #[clap(long, value_parser)]
+    script: Option<String>,
+    /// Run headless and serve the display and controls to browsers on this port, until the
+    /// last browser leaves or Ctrl-C
+    #[cfg(feature = "serve")]
+    #[clap(long, value_name = "PORT")]
+    serve: Option<u16>,
+    /// The address --serve listens on. Only this machine can connect by default, 0.0.0.0 lets
+    /// the network in
+    #[cfg(feature = "serve")]
+    #[clap(long, value_name = "ADDR", default_value = "127.0.0.1")]
+    serve_bind: std::net::IpAddr,
 }
 
-impl olc::PGEApplication for Emulator {
-    const APP_NAME: &'static str = "Chip8 Emulator - Rust";
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Run every ROM in a directory headlessly and write a JSON report
+    Batch(batch::BatchArgs),
+    /// Disassemble a ROM, labelling the code and data found by static analysis
+    Disasm(disasm::DisasmArgs),
+    /// Print the version, the commit it was built from, its features and its quirks
+    Info,
+}
+
+// a startup error: printed as --json has it, and the process exits
+fn fail(json: bool, message: &str) -> ! {
+    output::error(json, message);
+    std::process::exit(1);
+}
 
-    fn on_user_create(&mut self, _pge: &mut olc::PixelGameEngine) -> bool {
-        true
+fn main() {
+    let args = Args::parse();
+    let log_file = args.log_file.as_deref();
+    if let Err(e) = logging::init(&args.log_level, log_file, args.trace_display, args.trace_timers) {
+        fail(args.json, &format!("Failed to open log file: {}", e));
     }
 
-    fn on_user_update(&mut self, pge: &mut olc::PixelGameEngine, delta: f32) -> bool {
-        for (i, key) in KEYS.iter().enumerate() {
-            match self.input_mode {
-                InputMode::Hold => { self.keys[i] = pge.get_key(*key).held; },
-                InputMode::Once => {
-                    if self.run_mode == RunMode::Step { // it's still hold mode for stepping, otherwise it'd be hard
-                        self.keys[i] = pge.get_key(*key).held;
-                    } else {
-                        self.keys[i] = pge.get_key(*key).pressed;
-                    }
-                },
+    let memory = RamPolicy {
+        read: args.out_of_range_read,
+        write: args.out_of_range_write,
+    };
+    if let Some(command) = args.command {
+        let success = match command {
+            Command::Batch(batch_args) => batch::run(batch_args, args.json),
+            Command::Disasm(disasm_args) => disasm::run(disasm_args, args.json),
+            Command::Info => {
+                version::print(args.json, memory);
+                true
             }
+        };
+        if !success {
+            std::process::exit(1);
         }
+        return;
+    }
+    if args.version {
+        version::print(args.json, memory);
+        return;
+    }
 
-        if self.key_block != 0x10 {
-            for (i, key) in self.keys.iter().enumerate() {
-                if *key {
-                    self.registers.set(self.key_block, i as u8);
-                    self.key_block = 0x10;
-                    break;
-                }
+    crash::install();
+    let mut emulator = Emulator::new();
+    emulator.reserve_vip_areas = args.reserve_vip_areas;
+    emulator.jump_x = args.quirk_jump_x;
+    emulator.display_wait = args.quirk_display_wait;
+    emulator.shift_vy = args.quirk_shift_vy;
+    emulator.call_stack = ArrayStack::with_limit(args.stack_depth as usize);
+    emulator.wrap_x = args.wrap_x;
+    emulator.wrap_y = args.wrap_y;
+    emulator.platform = args.platform;
+    emulator.ram.policy = memory;
+    emulator.stats.memory = memory;
+    let font_style = match &args.font {
+        Some(path) => match font::read(path) {
+            Ok(style) => style,
+            Err(e) => {
+                fail(args.json, &format!("Failed to load font {}: {}", path, e));
             }
-            if self.key_block != 0x10 {
-                return true;
+        },
+        None => args.font_style,
+    };
+    if font_style != FontStyle::Standard {
+        emulator.font_style = font_style;
+        font::load(&mut emulator.ram, font_style);
+    }
+    // where the ROM came from, when there's a file to keep a save next to
+    let rom_path = match (&args.demo, &args.rom_file) {
+        (Some(name), _) => match demo::find(name) {
+            Some(rom) => {
+                emulator.load_rom_bytes(rom).expect("the demos fit in RAM");
+                emulator.rom_name = format!("demo {}", name);
+                None
             }
-        }
-        if self.run_mode == RunMode::Play {
-            // run continuously at 600 CPS
-            //decrement the timers
-            self.cycle_time += delta;
-            self.timer_time += delta;
-            if self.timer_time >= 1.0 / 60.0 {
-                self.timer = if self.timer == 0 { 0 } else { self.timer - 1 };
-                self.sound_timer = if self.sound_timer == 0 {
-                    0
-                } else {
-                    self.sound_timer - 1
-                };
-                self.timer_time = 0.0;
+            None => {
+                let names = demo::names().join(", ");
+                fail(args.json, &format!("Unknown demo \"{}\" (available: {})", name, names));
             }
-            // run the beeper if the sound timer is > 0
-            if self.sound_timer > 0 {
-                self.beeper.play();
-            } else {
-                self.beeper.pause();
+        },
+        (None, Some(rom_file)) => {
+            if let Err(e) = emulator.load_rom(rom_file) {
+                fail(args.json, &format!("Failed to load ROM {}: {}", rom_file, e));
             }
-
-            // run once if the cycle time is full
-            if self.cycle_time >= self.time_per_cycle {
-                let (redraw, summary) = self.cycle();
-                if redraw {
-                    self.draw(pge);
+            Some(rom_file.clone())
+        }
+        #[cfg(feature = "file-dialog")]
+        (None, None) => match window::pick_rom() {
+            Some(rom_file) => {
+                if let Err(e) = emulator.load_rom(&rom_file.to_string_lossy()) {
+                    let message = format!("Failed to load ROM {}: {}", rom_file.display(), e);
+                    fail(args.json, &message);
                 }
-                self.draw_debug(pge, summary);
-                self.cycle_time = 0.0;
+                Some(rom_file.to_string_lossy().to_string())
             }
-            if pge.get_key(olc::Key::Space).pressed {
-                self.run_mode = RunMode::Step
+            None => {
+                emulator.load_demo();
+                None
             }
-        } else {
-            // run step-by-step
-            self.beeper.pause(); // it'd be annoying if this kept going
-            if pge.get_key(olc::Key::Tab).pressed {
-                self.timer_time += 1.0 / 600.0;
-                if self.timer_time >= 1.0 / 60.0 {
-                    self.timer = if self.timer == 0 { 0 } else { self.timer - 1 };
-                    self.sound_timer = if self.sound_timer == 0 {
-                        0
-                    } else {
-                        self.sound_timer - 1
-                    };
-                    self.timer_time = 0.0
-                }
-                let (_redraw, summary) = self.cycle();
-                self.draw(pge);
-                self.draw_debug(pge, summary);
+        },
+        #[cfg(not(feature = "file-dialog"))]
+        (None, None) => {
+            emulator.load_demo();
+            None
+        }
+    };
+    if let Some(range) = args.persist_ram {
+        let Some(rom_path) = &rom_path else {
+            fail(args.json, "--persist-ram needs a ROM file to keep the save next to");
+        };
+        emulator.save_ram = Some(SaveRam::new(range, rom_path));
+        emulator.restore_save_ram();
+    }
+    if args.hot_patch {
+        let is_url = |path: &&str| path.starts_with("http://") || path.starts_with("https://");
+        let Some(rom_path) = rom_path.as_deref().filter(|path| !is_url(path)) else {
+            fail(args.json, "--hot-patch needs a ROM file to watch");
+        };
+        emulator.hot_patch = Some(HotPatch::new(rom_path));
+    }
+    let windowed = args.compare_rom.is_none() && args.backend == Backend::Window;
+    #[cfg(feature = "serve")]
+    let windowed = windowed && args.serve.is_none();
+    if args.cycle_speed.is_infinite() && args.ipf.is_none() && !windowed {
+        fail(args.json, "--cycle-speed max only works in the window, on its own");
+    }
+    emulator.time_per_cycle = 1.0 / args.cycle_speed;
+    emulator.ipf = args.ipf;
+    emulator.driver = FrameDriver::new(emulator.time_per_cycle, args.ipf.is_some());
+    emulator.driver.max_delta = args.max_delta / 1000.0;
+    emulator.driver.catch_up = !args.no_catchup;
+    emulator.pacing = Pacing::new(args.cycle_budget / 1000.0);
+    emulator.state = args.run_mode.into();
+    emulator.palettes = palette::load(Path::new(palette::PALETTE_DIR));
+    let palette_name = args.palette.as_deref().unwrap_or(args.color_mode.palette_name());
+    match emulator.palettes.iter().position(|palette| palette.name == palette_name) {
+        Some(palette) => emulator.palette = palette,
+        None => {
+            let names: Vec<&str> =
+                emulator.palettes.iter().map(|palette| palette.name.as_str()).collect();
+            let names = names.join(", ");
+            let message = format!("Unknown palette \"{}\" (available: {})", palette_name, names);
+            fail(args.json, &message);
+        }
+    }
+    let contrast = emulator.palettes[emulator.palette].contrast();
+    if contrast < palette::MIN_CONTRAST {
+        log::warn!(
+            "Palette {} has a contrast of only {:.1}:1, --palette high-contrast is easier to read",
+            palette_name,
+            contrast
+        );
+        emulator.notify("Low contrast, try --palette high-contrast", NOTICE_TIME * 2.0);
+    }
+    emulator.key_repeat = KeyRepeat::new(
+        args.key_repeat.unwrap_or(args.input_mode.into()),
+        args.key_repeat_rate,
+    );
+    for (key, shape) in args.key_repeat_key {
+        emulator.key_repeat.set(key, shape);
+    }
+    if let Some(keys) = args.available_keys {
+        emulator.available_keys = keys;
+    }
+    emulator.print_coverage = args.coverage;
+    emulator.coverage_map = args.coverage_map;
+    if let Some(path) = &args.symbols {
+        match regions::load_symbols(path) {
+            Ok(symbols) => {
+                emulator.user_regions = symbols.regions;
+                emulator.labels = symbols.labels;
             }
-            if pge.get_key(olc::Key::Space).pressed {
-                self.run_mode = RunMode::Play
+            Err(e) => {
+                fail(args.json, &format!("Failed to load symbols from {}: {}", path, e));
             }
         }
-        true
     }
-}
-
-fn main() {
-    let args = Args::parse();
-
-    // set up audio (rodio audio setup only works in main)
-    let mut emulator = Emulator::new();
-    emulator.load_rom(&args.rom_file);
-    emulator.time_per_cycle = 1.0 / args.cycle_speed;
-    emulator.run_mode = args.run_mode;
-    emulator.color_mode = args.color_mode;
-    emulator.input_mode = args.input_mode;
-
-    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let file = File::open("system/square.ogg").unwrap();
-    let new_sound = stream_handle.play_once(BufReader::new(file)).unwrap();
-    new_sound.set_volume(0.2);
-
-    let looping_source = Decoder::new(File::open("system/square.ogg").unwrap()).unwrap();
-    let looping_source = Source::repeat_infinite(looping_source);
-    new_sound.append(looping_source);
-
-    new_sound.pause();
-
-    emulator.beeper = new_sound;
-
-    // run the olc::pge application
-    olc::PixelGameEngine::construct(
-        emulator,
-        (SCR_W + (SCR_W / 2)) * DRAW_BIGGER_PIXELS as usize,
-        (SCR_H + (SCR_H / 2)) * DRAW_BIGGER_PIXELS as usize,
-        2,
-        2,
-    )
-    .start();
-}
-
-struct Emulator {
-    time_per_cycle: f32,
-    cycle_time: f32,
-    timer_time: f32,
-    display: [[bool; SCR_H]; SCR_W],
-    color_mode: ColorMode,
-    ram: Ram,
-    timer: u8,
-    sound_timer: u8,
-    registers: Registers,
-    program_counter: u16,
-    stack_pointer: u16,
-    call_stack: Vec<u16>,
-    key_block: u8,
-    keys: [bool; 0x10],
-    input_mode: InputMode,
-    run_mode: RunMode,
-    beeper: Sink,
-}
-impl Emulator {
-    fn new() -> Emulator {
-        let mut ram = Ram::new();
-        ram.load_from_rom(0x000, PathBuf::from("system/font.bin"));
+    emulator.capture_frames = args.capture_frames;
+    emulator.capture_csv = args.capture_csv;
+    emulator.print_stats = args.stats;
+    emulator.json = args.json;
+    emulator.beep_wave.set(args.beep_wave);
+    emulator.beep_freq = args.beep_freq;
+    emulator.beep_style = args.beep_style;
+    emulator.recording = args.record_audio.map(Recording::new);
+    emulator.show_collisions = args.show_collisions;
+    emulator.keywait_timeout = args.keywait_timeout;
+    if let Some(trigger) = args.pause_at {
+        emulator.arm_pause_at(trigger);
+    }
+    emulator.code_guard = args.guard_pc.map(|mode| CodeGuard::new(mode, args.guard_pc_bytes));
+    emulator.lints = args.lint.then(Lints::new);
+    emulator.write_log = WriteLog::new(args.write_log_size, args.write_log);
+    emulator.show_hash = args.show_hash;
+    emulator.trace_display = args.trace_display;
+    emulator.trace_timers = args.trace_timers;
+    emulator.input_lag = args.input_lag.then(InputLag::new);
+    log::info!(
+        "Running a {} byte ROM at {} cycles/s ({:?} mode, {:?} backend)",
+        emulator.rom_size,
+        args.cycle_speed,
+        emulator.state.mode(),
+        args.backend
+    );
+    #[cfg(feature = "scripting")]
+    if let Some(path) = &args.script {
+        if let Err(e) = emulator.load_script(path) {
+            fail(args.json, &format!("Failed to load script {}: {}", path, e));
+        }
+    }
 
-        Emulator {
-            time_per_cycle: 1.0/600.0,
-            timer_time: 0.0,
-            cycle_time: 0.0,
-            display: [[false; SCR_H]; SCR_W], // x, y format
-            color_mode: ColorMode::White,
-            ram, // using RAM rather than a Vec because it encapsulates ROM loading
-            timer: 0x00,     // basic timer
-            sound_timer: 0x00, // sound timer, plays a beep while > 0
-            registers: Registers::new(), // registers 0 through F
-            program_counter: 0x200, // programs always start at location 0x200 in RAM
-            stack_pointer: 0x000, // doesn't matter where this starts, programs will modify it
-            call_stack: Vec::new(),
-            key_block: 0x10,
-            keys: [false; 0x10],
-            input_mode: InputMode::Hold,
-            run_mode: START_RUN_MODE,
-            beeper: Sink::try_new(&OutputStream::try_default().unwrap().1).unwrap(),
+    if let Some(other) = &args.compare_rom {
+        let mut right = Emulator::new();
+        right.platform = emulator.platform; // before the ROM, which it decides the start of
+        if let Err(e) = right.load_rom(other) {
+            fail(args.json, &format!("Failed to load ROM {}: {}", other, e));
         }
+        compare::run(emulator, right, args.cycle_speed, rand::random());
+        return;
     }
-    fn load_rom(&mut self, rom_file: &str) {
-        self.ram.load_from_rom(0x200, PathBuf::from(rom_file));
+    #[cfg(feature = "sdl2")]
+    if args.backend == Backend::Sdl2 {
+        if let Err(e) = sdl::run(emulator, args.cycle_speed) {
+            fail(args.json, &format!("SDL error: {}", e));
+        }
+        return;
     }
-    fn draw(&mut self, pge: &mut olc::PixelGameEngine) {
-        let bigger_draw = if self.run_mode == RunMode::Step {
-            DRAW_BIGGER_PIXELS
-        } else {
-            DRAW_BIGGER_PIXELS + (DRAW_BIGGER_PIXELS / 2)
-        };
-        let color_on = match self.color_mode {
-            ColorMode::White => olc::WHITE,
-            ColorMode::Gray => olc::DARK_GREY,
-            ColorMode::Green => olc::GREEN,
-        };
-        let color_off = match self.color_mode {
-            ColorMode::White => olc::BLACK,
-            ColorMode::Gray => olc::GREY,
-            ColorMode::Green => olc::VERY_DARK_GREEN,
-        };
-        pge.clear(olc::BLACK);
-        for x in 0..64 {
-            for y in 0..32 {
-                for xs in 0..bigger_draw {
-                    for ys in 0..bigger_draw {
-                        let pixel = if self.display[x][y] {
-                            color_on
-                        } else {
-                            color_off
-                        };
-                        pge.draw(
-                            x as i32 * bigger_draw + xs,
-                            y as i32 * bigger_draw + ys,
-                            pixel,
-                        );
-                    }
-                }
-            }
+    if args.backend == Backend::Terminal {
+        if let Err(e) = terminal::run(emulator, args.cycle_speed) {
+            fail(args.json, &format!("Terminal error: {}", e));
         }
+        return;
     }
-    fn draw_debug(&mut self, pge: &mut olc::PixelGameEngine, summary: String) {
-        if self.run_mode == RunMode::Step {
-            for i in 0..0x8 {
-                let mut string = String::new();
-                string += format!("R{:1X}:", i).as_str();
-                string += format!("{:2X}", self.registers.get(i as u8)).as_str();
-                pge.draw_string(
-                    64 * DRAW_BIGGER_PIXELS + 4,
-                    4 + (i * 8),
-                    &string,
-                    olc::WHITE,
-                );
-            }
-            for i in 0..0x8 {
-                let mut string = String::new();
-                string += format!("R{:1X}:", 0x8 + i).as_str();
-                string += format!("{:2X}", self.registers.get(0x8 + i as u8)).as_str();
-                pge.draw_string(
-                    64 * DRAW_BIGGER_PIXELS + 64,
-                    4 + (i * 8),
-                    &string,
-                    olc::GREY,
-                );
-            }
-            let (stringa, stringb) = summary.split_once(" => ").unwrap();
-            pge.draw_string(
-                4,
-                32 * DRAW_BIGGER_PIXELS + 8,
-                &stringa.to_string(),
-                olc::WHITE,
-            );
-            pge.draw_string(
-                4,
-                32 * DRAW_BIGGER_PIXELS + 16,
-                &stringb.to_string(),
-                olc::WHITE,
-            );
+    #[cfg(feature = "serve")]
+    if let Some(port) = args.serve {
+        if let Err(e) = serve::run(emulator, args.cycle_speed, args.serve_bind, port) {
+            fail(args.json, &format!("Server error: {}", e));
         }
+        return;
     }
+
+    emulator.use_beeper(audio::init(emulator.beep_pitch.clone(), emulator.beep_wave.clone()));
+
+    window::run(emulator);
 }
@@ -0,0 +1,160 @@
+use olc_pge as olc;
+use olc_pge::Key;
+
+use crate::components::Ram;
+use crate::font::FONT_SIZE;
+use crate::status::NOTICE_TIME;
+use crate::Emulator;
+
+// the map is 64 cells a row, 64 rows, one cell a byte
+const CELL: i32 = 2;
+const ROW: u16 = 64;
+const TEXT_X: i32 = 4 + ROW as i32 * CELL + 8;
+const REGION_EDGE: olc::Pixel = olc::DARK_MAGENTA;
+
+// what a byte of RAM has been used for so far, the later ones winning
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum MemoryClass {
+    Untouched,
+    Font,
+    Rom,          // loaded with the ROM, not run (yet)
+    Written,      // stored to by the program
+    Executed,     // fetched as an instruction
+    SelfModified, // both, the program ran code it wrote
+}
+impl MemoryClass {
+    const ALL: [MemoryClass; 6] = [
+        MemoryClass::Untouched,
+        MemoryClass::Font,
+        MemoryClass::Rom,
+        MemoryClass::Written,
+        MemoryClass::Executed,
+        MemoryClass::SelfModified,
+    ];
+    fn color(&self) -> olc::Pixel {
+        match self {
+            MemoryClass::Untouched => olc::VERY_DARK_GREY,
+            MemoryClass::Font => olc::DARK_BLUE,
+            MemoryClass::Rom => olc::DARK_GREEN,
+            MemoryClass::Written => olc::YELLOW,
+            MemoryClass::Executed => olc::GREEN,
+            MemoryClass::SelfModified => olc::RED,
+        }
+    }
+    fn name(&self) -> &'static str {
+        match self {
+            MemoryClass::Untouched => "UNTOUCHED",
+            MemoryClass::Font => "FONT",
+            MemoryClass::Rom => "ROM",
+            MemoryClass::Written => "WRITTEN",
+            MemoryClass::Executed => "EXECUTED",
+            MemoryClass::SelfModified => "SELF-MODIFIED",
+        }
+    }
+}
+
+pub fn classify(ram: &Ram, addr: u16, rom_size: usize) -> MemoryClass {
+    let rom = 0x200..0x200 + rom_size as u16;
+    match (ram.exec_count(addr) > 0, ram.was_written(addr)) {
+        (true, true) => MemoryClass::SelfModified,
+        (true, false) => MemoryClass::Executed,
+        (false, true) => MemoryClass::Written,
+        _ if addr < FONT_SIZE => MemoryClass::Font,
+        _ if rom.contains(&addr) => MemoryClass::Rom,
+        _ => MemoryClass::Untouched,
+    }
+}
+
+// the arrows move the selection a cell or a row, wrapping around the 4KB
+pub fn move_selection(selected: u16, key: Key) -> u16 {
+    let step = match key {
+        Key::Left => 0xFFF,
+        Key::Right => 1,
+        Key::Up => 0x1000 - ROW,
+        Key::Down => ROW,
+        _ => 0,
+    };
+    (selected + step) & 0xFFF
+}
+
+// the address of the cell under the mouse, if it's over the map
+pub fn cell_at(x: i32, y: i32) -> Option<u16> {
+    let (column, row) = ((x - 4) / CELL, (y - 4) / CELL);
+    let inside = x >= 4 && y >= 4 && column < ROW as i32 && row < ROW as i32;
+    inside.then_some(row as u16 * ROW + column as u16)
+}
+
+impl Emulator {
+    // the D key in the map: all of RAM on stdout, each row with the regions it's in
+    pub fn print_dump(&mut self) {
+        println!("{}", self.ram.generate_dump(0x000, 0x1000, &self.regions()));
+        self.notify("Memory dump printed", NOTICE_TIME);
+    }
+}
+
+// the map on the left with PC (white) and I (cyan) marked, a legend and the selected
+// byte's row of memory on the right
+pub fn render(emulator: &Emulator, pge: &mut olc::PixelGameEngine, selected: u16) {
+    let ram = &emulator.ram;
+    let cell_pos = |addr: u16| {
+        let (column, row) = ((addr % ROW) as i32, (addr / ROW) as i32);
+        (4 + column * CELL, 4 + row * CELL)
+    };
+    for addr in 0..0x1000 {
+        let (x, y) = cell_pos(addr);
+        let color = classify(ram, addr, emulator.rom_size).color();
+        pge.fill_rect(x, y, CELL as u32, CELL as u32, color);
+    }
+    let marks = [(emulator.stack_pointer, olc::CYAN), (emulator.program_counter, olc::WHITE)];
+    for (addr, color) in marks {
+        let (x, y) = cell_pos(addr & 0xFFF);
+        pge.fill_rect(x - 1, y - 1, CELL as u32 + 2, CELL as u32 + 2, color);
+    }
+    // the first and last byte of each named region
+    let regions = emulator.regions();
+    for addr in (0..0x1000).filter(|addr| regions.is_boundary(*addr)) {
+        let (x, y) = cell_pos(addr);
+        pge.fill_rect(x, y, CELL as u32, CELL as u32, REGION_EDGE);
+    }
+    let (x, y) = cell_pos(selected);
+    pge.draw_rect(x - 2, y - 2, CELL as u32 + 3, CELL as u32 + 3, olc::MAGENTA);
+
+    for (i, class) in MemoryClass::ALL.iter().enumerate() {
+        let y = 4 + i as i32 * 10;
+        pge.fill_rect(TEXT_X, y, 7, 7, class.color());
+        pge.draw_string(TEXT_X + 10, y, &class.name().to_string(), olc::WHITE);
+    }
+    let y = 4 + MemoryClass::ALL.len() as i32 * 10;
+    pge.fill_rect(TEXT_X, y, 7, 7, REGION_EDGE);
+    pge.draw_string(TEXT_X + 10, y, &String::from("REGION EDGE"), olc::WHITE);
+    let marks = format!("PC:{:03X} I:{:03X}", emulator.program_counter, emulator.stack_pointer);
+    pge.draw_string(TEXT_X, y + 14, &marks, olc::WHITE);
+
+    let y = y + 28;
+    let class = classify(ram, selected, emulator.rom_size);
+    let line = format!("{:03X} {}", selected, class.name());
+    pge.draw_string(TEXT_X, y, &line, olc::MAGENTA);
+    let region = regions.at(selected).map_or(String::from("-"), |region| region.name.clone());
+    pge.draw_string(TEXT_X, y + 10, &format!("IN {}", region.to_uppercase()), olc::GREY);
+    let y = y + 10;
+    let start = selected & !0x7;
+    for half in 0..2 {
+        let row_start = (start + half * 8) & 0xFFF;
+        let bytes: Vec<String> = (row_start..row_start + 8)
+            .map(|addr| format!("{:02X}", ram.get(addr & 0xFFF)))
+            .collect();
+        let line = format!("{:03X}: {}", row_start, bytes.join(" "));
+        pge.draw_string(TEXT_X, y + 10 + half as i32 * 10, &line, olc::GREY);
+    }
+    let last_write = match emulator.writes_to(selected).next() {
+        Some(write) => {
+            format!("BY {:03X} {:02X}>{:02X} CYCLE {}", write.pc, write.old, write.new, write.cycle)
+        }
+        None if emulator.write_log.on => String::from("NO WRITES LOGGED"),
+        None => String::from("WRITE LOG OFF"),
+    };
+    pge.draw_string(TEXT_X, y + 34, &last_write, olc::MAGENTA);
+    let hint = String::from("arrows/click: select, d: dump");
+    pge.draw_string(TEXT_X, y + 48, &hint, olc::GREY);
+    pge.draw_string(TEXT_X, y + 58, &String::from("w: write log, esc: close"), olc::GREY);
+}
@@ -0,0 +1,218 @@
+use olc_pge as olc;
+use olc_pge::Key;
+
+use crate::call_log;
+use crate::draw_log;
+use crate::help;
+use crate::memory_map;
+use crate::quirks::{self, Quirk, QUIRKS};
+use crate::sprites;
+use crate::hotkeys::{key_name, Hotkeys};
+use crate::run_state::{Event, Modal, RunState};
+use crate::window::DRAW_BIGGER_PIXELS;
+use crate::{Emulator, SCR_H, SCR_W};
+
+// the keys a modal screen can be handed. pge has no list of what was pressed, so these
+// are polled every frame a modal is up
+const UI_KEYS: [Key; 60] = [
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J,
+    Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T,
+    Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::K0, Key::K1, Key::K2, Key::K3, Key::K4, Key::K5, Key::K6, Key::K7, Key::K8, Key::K9,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10,
+    Key::F11, Key::F12,
+    Key::Up, Key::Down, Key::Left, Key::Right,
+    Key::Space, Key::Tab, Key::Back, Key::Delete, Key::Escape, Key::Return, Key::Enter,
+    Key::Period,
+];
+
+pub enum ModalAction {
+    Stay,
+    Close,
+    StepCount(u32), // closes, then steps this many times
+    PrintSprite,    // prints the sprite viewer's bytes
+    PrintDump,      // prints all of RAM, with its regions
+    ToggleQuirk(Quirk),
+    CaptureDraws, // closes, then starts a draw capture
+    ToggleWriteLog,
+    ExportDraws,
+}
+
+// the longest count that can be typed, 0x included
+const COUNT_DIGITS: usize = 8;
+
+impl Modal {
+    pub fn handle_key(&mut self, key: Key, hotkeys: &Hotkeys) -> ModalAction {
+        match self {
+            Modal::Help if key == Key::Escape || hotkeys.help.contains(&key) => ModalAction::Close,
+            Modal::Help => ModalAction::Stay,
+            Modal::StepCount(_) if key == Key::Escape => ModalAction::Close,
+            Modal::StepCount(text) if key == Key::Return || key == Key::Enter => {
+                match parse_count(text) {
+                    Some(count) => ModalAction::StepCount(count),
+                    None => ModalAction::Stay, // nothing to run yet
+                }
+            }
+            Modal::MemoryMap(_) if key == Key::Escape || key == hotkeys.memory_map => {
+                ModalAction::Close
+            }
+            Modal::MemoryMap(_) if key == Key::D => ModalAction::PrintDump,
+            Modal::MemoryMap(_) if key == Key::W => ModalAction::ToggleWriteLog,
+            Modal::MemoryMap(selected) => {
+                *selected = memory_map::move_selection(*selected, key);
+                ModalAction::Stay
+            }
+            Modal::Calls if key == Key::Escape || key == hotkeys.calls => ModalAction::Close,
+            Modal::Calls => ModalAction::Stay,
+            Modal::Draws if key == Key::Escape || key == hotkeys.draws => ModalAction::Close,
+            Modal::Draws if key == Key::R => ModalAction::CaptureDraws,
+            Modal::Draws if key == Key::C => ModalAction::ExportDraws,
+            Modal::Draws => ModalAction::Stay,
+            Modal::Quirks(_) if key == Key::Escape || key == hotkeys.quirks => ModalAction::Close,
+            Modal::Quirks(selected) if key == Key::Space || key == Key::Return => {
+                ModalAction::ToggleQuirk(QUIRKS[*selected])
+            }
+            Modal::Quirks(selected) => {
+                *selected = quirks::move_selection(*selected, key);
+                ModalAction::Stay
+            }
+            Modal::Sprites(_) if key == Key::Escape || key == hotkeys.sprites => ModalAction::Close,
+            Modal::Sprites(_) if key == Key::C => ModalAction::PrintSprite,
+            Modal::Sprites(view) => {
+                view.handle_key(key);
+                ModalAction::Stay
+            }
+            Modal::StepCount(text) => {
+                if key == Key::Back {
+                    text.pop();
+                } else if let Some(c) = count_char(key) {
+                    if text.len() < COUNT_DIGITS {
+                        text.push(c);
+                    }
+                }
+                ModalAction::Stay
+            }
+        }
+    }
+    // a left click at x, y in the window, returns whether it changed anything
+    pub fn handle_click(&mut self, x: i32, y: i32) -> bool {
+        match self {
+            Modal::MemoryMap(selected) => match memory_map::cell_at(x, y) {
+                Some(addr) => {
+                    *selected = addr;
+                    true
+                }
+                None => false,
+            },
+            _ => false,
+        }
+    }
+    pub fn render(&self, emulator: &Emulator, pge: &mut olc::PixelGameEngine) {
+        dim(pge);
+        match self {
+            Modal::Help => help::render(emulator, pge),
+            Modal::StepCount(text) => {
+                pge.draw_string(8, 8, &format!("STEP HOW MANY? {}_", text), olc::WHITE);
+                let hint = String::from("decimal or 0x hex, enter: step, esc: cancel");
+                pge.draw_string(8, 18, &hint, olc::GREY);
+            }
+            Modal::MemoryMap(selected) => memory_map::render(emulator, pge, *selected),
+            Modal::Sprites(view) => sprites::render(emulator, pge, view),
+            Modal::Calls => call_log::render(emulator, pge),
+            Modal::Quirks(selected) => quirks::render(emulator, pge, *selected),
+            Modal::Draws => draw_log::render(emulator, pge),
+        }
+    }
+}
+
+// what a key types into the count prompt: digits, and the x and A-F of hex
+fn count_char(key: Key) -> Option<char> {
+    let name = key_name(key);
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_hexdigit() => Some(c),
+        (Some('X'), None) => Some('x'),
+        _ => None,
+    }
+}
+
+// a step count, 25 or 0x19, which has to be at least 1
+fn parse_count(text: &str) -> Option<u32> {
+    let count = match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => text.parse().ok()?,
+    };
+    (count > 0).then_some(count)
+}
+
+// modals go over whatever's on screen, darkened so they stand out
+fn dim(pge: &mut olc::PixelGameEngine) {
+    let width = (SCR_W + SCR_W / 2) as u32 * DRAW_BIGGER_PIXELS as u32;
+    let height = (SCR_H + SCR_H / 2) as u32 * DRAW_BIGGER_PIXELS as u32;
+    pge.set_pixel_mode(olc::PixelMode::Alpha);
+    pge.fill_rect(0, 0, width, height, olc::Pixel::rgba(0, 0, 0, 208));
+    pge.set_pixel_mode(olc::PixelMode::Normal);
+}
+
+impl Emulator {
+    pub fn open_modal(&mut self, pge: &mut olc::PixelGameEngine, modal: Modal) {
+        self.keys = [false; 0x10]; // nothing held carries through to the ROM
+        self.transition(pge, Event::OpenModal(modal));
+    }
+    // hands the modal this frame's key presses, returns false if there isn't one up
+    pub fn update_modal(&mut self, pge: &mut olc::PixelGameEngine) -> bool {
+        let RunState::Modal(modal, _) = &mut self.state else {
+            return false;
+        };
+        let mut action = ModalAction::Stay;
+        let mut typed = false;
+        for key in UI_KEYS {
+            if pge.get_key(key).pressed {
+                typed = true;
+                action = modal.handle_key(key, &self.hotkeys);
+                if !matches!(action, ModalAction::Stay) {
+                    break;
+                }
+            }
+        }
+        if pge.get_mouse(0).pressed {
+            typed |= modal.handle_click(pge.get_mouse_x(), pge.get_mouse_y());
+        }
+        match action {
+            ModalAction::Stay if typed => self.draw(pge), // the modal may have changed
+            ModalAction::Stay => {}
+            ModalAction::Close => self.transition(pge, Event::CloseModal),
+            ModalAction::StepCount(count) => {
+                self.transition(pge, Event::CloseModal);
+                self.step_count(pge, count);
+            }
+            ModalAction::CaptureDraws => {
+                self.transition(pge, Event::CloseModal);
+                self.start_draw_capture();
+            }
+            ModalAction::ExportDraws => {
+                self.export_draw_capture();
+                self.draw(pge);
+            }
+            ModalAction::ToggleQuirk(quirk) => {
+                self.toggle_quirk(quirk);
+                self.draw(pge);
+            }
+            ModalAction::PrintDump => {
+                self.print_dump();
+                self.draw(pge);
+            }
+            ModalAction::ToggleWriteLog => {
+                self.toggle_write_log();
+                self.draw(pge);
+            }
+            ModalAction::PrintSprite => {
+                if let RunState::Modal(Modal::Sprites(view), _) = self.state.clone() {
+                    self.print_sprite(&view);
+                    self.draw(pge);
+                }
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+// --json: what the subcommands and --stats print goes to stdout as JSON instead, and
+// errors to stderr as {"error": "..."}
+
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+pub struct ErrorOutput {
+    pub error: String,
+}
+
+pub fn error(json: bool, message: &str) {
+    if json {
+        let error = ErrorOutput {
+            error: message.to_string(),
+        };
+        eprintln!("{}", serde_json::to_string(&error).expect("a string serializes"));
+    } else {
+        eprintln!("{}", message);
+    }
+}
+
+pub fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(e) => error(true, &format!("Failed to serialize output: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_error_reads_back() {
+        let error = ErrorOutput {
+            error: String::from("Failed to load ROM a.ch8: No such file or directory"),
+        };
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(json, r#"{"error":"Failed to load ROM a.ch8: No such file or directory"}"#);
+        assert_eq!(serde_json::from_str::<ErrorOutput>(&json).unwrap(), error);
+    }
+}
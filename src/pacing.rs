@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+#[cfg(feature = "frontend")]
+use olc_pge as olc;
+
+#[cfg(feature = "frontend")]
+use crate::status::NOTICE_TIME;
+#[cfg(feature = "frontend")]
+use crate::window::DRAW_BIGGER_PIXELS;
+use crate::Emulator;
+#[cfg(feature = "frontend")]
+use crate::SCR_W;
+
+// what a cycle is taken to cost until one's been timed
+const FIRST_CYCLE_COST: f32 = 1e-6;
+// how long OVERLOAD stays up after the last frame that hit the cap
+const OVERLOAD_SHOWN: f32 = 1.0;
+// the rate that was managed is measured over this long, and logged at most this often
+const RATE_WINDOW: f32 = 5.0;
+
+// a cycle speed, which has to be above zero
+pub fn parse_rate(text: &str) -> Result<f32, String> {
+    match text.parse::<f32>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        Ok(_) => Err(String::from("has to be more than 0")),
+        Err(_) => Err(format!("\"{}\" isn't a number", text)),
+    }
+}
+
+// the window's --cycle-speed, which can also be "max": as fast as --cycle-budget lets it,
+// kept as an infinite speed so time_per_cycle comes out as 0
+pub fn parse_cycle_speed(text: &str) -> Result<f32, String> {
+    if text.eq_ignore_ascii_case("max") {
+        return Ok(f32::INFINITY);
+    }
+    parse_rate(text).map_err(|e| format!("{} (or max)", e))
+}
+
+// --cycle-budget: how many cycles one host frame gets to run, from how long they've been
+// taking. whatever doesn't fit is dropped instead of being owed to the next frame, where
+// on a host that can't keep up it would only pile up
+pub struct Pacing {
+    budget: f32,     // seconds of host time per frame
+    cycle_cost: f32, // seconds, a running average
+    overload_shown: f32,
+    window: f32, // seconds into the current RATE_WINDOW
+    window_cycles: u64,
+    window_capped: bool,
+}
+impl Pacing {
+    pub fn new(budget: f32) -> Pacing {
+        Pacing {
+            budget,
+            cycle_cost: FIRST_CYCLE_COST,
+            overload_shown: 0.0,
+            window: 0.0,
+            window_cycles: 0,
+            window_capped: false,
+        }
+    }
+    // how many batches of `per` cycles (an --ipf frame, or just the one) fit in a frame, at
+    // least one so it never stops altogether
+    pub fn cap(&self, per: u32) -> u32 {
+        let batches = self.budget / (self.cycle_cost * per as f32);
+        (batches as u32).max(1)
+    }
+    // a frame ran `cycles` in `took`, `capped` if more were owed than the cap let it run
+    pub fn ran(&mut self, cycles: u64, took: Duration, capped: bool) {
+        if cycles > 0 {
+            let cost = took.as_secs_f32() / cycles as f32;
+            self.cycle_cost += (cost - self.cycle_cost) * 0.1;
+        }
+        self.window_cycles += cycles;
+        if capped {
+            self.overload_shown = OVERLOAD_SHOWN;
+            self.window_capped = true;
+        }
+    }
+    // the host frame's time passing. at the end of a window, the cycles per second it managed
+    // and whether any of its frames were cut short by the cap
+    pub fn frame(&mut self, delta: f32) -> Option<(f32, bool)> {
+        self.overload_shown = (self.overload_shown - delta).max(0.0);
+        self.window += delta;
+        if self.window < RATE_WINDOW {
+            return None;
+        }
+        let achieved = (self.window_cycles as f32 / self.window, self.window_capped);
+        self.window = 0.0;
+        self.window_cycles = 0;
+        self.window_capped = false;
+        Some(achieved)
+    }
+    pub fn overloaded(&self) -> bool {
+        self.overload_shown > 0.0
+    }
+}
+
+impl Emulator {
+    // --cycle-speed max
+    pub fn unlimited(&self) -> bool {
+        self.ipf.is_none() && self.time_per_cycle == 0.0
+    }
+    #[cfg(feature = "frontend")]
+    fn requested_rate(&self) -> f32 {
+        match self.ipf {
+            Some(ipf) => ipf as f32 * 60.0,
+            None => 1.0 / self.time_per_cycle,
+        }
+    }
+    #[cfg(feature = "frontend")]
+    pub fn update_pacing(&mut self, pge: &mut olc::PixelGameEngine, delta: f32) {
+        let was_overloaded = self.pacing.overloaded();
+        match self.pacing.frame(delta) {
+            Some((achieved, _)) if self.unlimited() && self.state.runs_cycles() => {
+                log::info!("Running at {:.0} cycles/s", achieved);
+                self.notify(format!("{:.0} cycles/s", achieved), NOTICE_TIME);
+            }
+            Some((achieved, true)) => {
+                let requested = self.requested_rate();
+                log::warn!("Can't keep up: {:.0} cycles/s of the {:.0} asked for", achieved, requested);
+            }
+            _ => {}
+        }
+        if was_overloaded && !self.pacing.overloaded() {
+            self.redraw(pge); // takes the indicator back off
+        }
+    }
+    pub fn pacing_ran(&mut self, cycles: u64, took: Duration, capped: bool) {
+        self.pacing.ran(cycles, took, capped);
+        self.stats.paced_frames += 1;
+        if capped {
+            self.stats.capped_frames += 1;
+        }
+    }
+    // next to the speaker while frames are hitting the cap, redrawn every frame
+    #[cfg(feature = "frontend")]
+    pub fn draw_overload(&self, pge: &mut olc::PixelGameEngine) {
+        if !self.pacing.overloaded() {
+            return;
+        }
+        let text = String::from("OVERLOAD");
+        let x = (SCR_W + SCR_W / 2) as i32 * DRAW_BIGGER_PIXELS - 20 - text.len() as i32 * 8;
+        pge.fill_rect(x - 1, 1, text.len() as u32 * 8 + 2, 10, olc::BLACK);
+        pge.draw_string(x, 2, &text, olc::RED);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_speeds() {
+        assert_eq!(parse_cycle_speed("600"), Ok(600.0));
+        assert_eq!(parse_cycle_speed("MAX"), Ok(f32::INFINITY));
+        assert_eq!(parse_rate("0"), Err(String::from("has to be more than 0")));
+        let not_a_number = String::from("\"fast\" isn't a number (or max)");
+        assert_eq!(parse_cycle_speed("fast"), Err(not_a_number));
+    }
+
+    // slow cycles, as if each one took 10us: the cap comes down as the cost is measured
+    #[test]
+    fn the_cap_follows_what_cycles_cost() {
+        let mut pacing = Pacing::new(0.01);
+        assert_eq!((pacing.cap(1), pacing.cap(10)), (10_000, 1_000));
+        for _ in 0..100 {
+            pacing.ran(1000, Duration::from_millis(10), false);
+        }
+        assert_eq!((pacing.cap(1), pacing.cap(10)), (1_000, 100));
+        assert!(!pacing.overloaded());
+        pacing.ran(1000, Duration::from_secs(100), false);
+        assert_eq!(pacing.cap(10_000), 1); // never none at all
+    }
+
+    #[test]
+    fn overload_shows_for_a_second_after_the_cap_is_hit() {
+        let mut pacing = Pacing::new(0.01);
+        pacing.ran(1000, Duration::from_millis(10), true);
+        assert!(pacing.overloaded());
+        pacing.frame(0.5);
+        assert!(pacing.overloaded());
+        pacing.frame(0.5);
+        assert!(!pacing.overloaded());
+    }
+
+    #[test]
+    fn the_rate_is_measured_over_a_window() {
+        let mut pacing = Pacing::new(0.01);
+        pacing.ran(2000, Duration::from_millis(10), true);
+        assert_eq!(pacing.frame(2.5), None);
+        pacing.ran(3000, Duration::from_millis(10), false);
+        assert_eq!(pacing.frame(2.5), Some((1000.0, true)));
+        assert_eq!(pacing.frame(RATE_WINDOW), Some((0.0, false))); // counted afresh
+    }
+
+    #[test]
+    fn capped_frames_are_counted() {
+        let mut emulator = Emulator::new();
+        for capped in [false, true, true] {
+            emulator.pacing_ran(100, Duration::from_millis(1), capped);
+        }
+        assert_eq!((emulator.stats.paced_frames, emulator.stats.capped_frames), (3, 2));
+        assert!(emulator.pacing.overloaded());
+    }
+}
@@ -0,0 +1,185 @@
+use std::fs;
+use std::path::Path;
+
+#[cfg(feature = "frontend")]
+use olc_pge as olc;
+
+// where palette files are looked for, next to system/
+pub const PALETTE_DIR: &str = "palettes";
+// below this the foreground is hard to make out, projected especially. WCAG asks 3:1 of
+// large text, which 4x pixels are
+pub const MIN_CONTRAST: f32 = 3.0;
+
+// a color, without the window's Pixel so frontends other than olc can draw with it
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+#[cfg(feature = "frontend")]
+impl From<Rgb> for olc::Pixel {
+    fn from(color: Rgb) -> olc::Pixel {
+        olc::Pixel::rgb(color.r, color.g, color.b)
+    }
+}
+
+// background first, then the foreground. XO-CHIP's two extra plane colors can follow,
+// they're checked and kept but nothing draws with them yet
+pub struct Palette {
+    pub name: String,
+    pub colors: Vec<Rgb>,
+}
+impl Palette {
+    pub fn off(&self) -> Rgb {
+        self.colors[0]
+    }
+    pub fn on(&self) -> Rgb {
+        self.colors[1]
+    }
+    pub fn contrast(&self) -> f32 {
+        contrast(self.on(), self.off())
+    }
+}
+
+// WCAG's relative luminance, from sRGB
+fn luminance(color: Rgb) -> f32 {
+    let linear = |channel: u8| {
+        let c = channel as f32 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(color.r) + 0.7152 * linear(color.g) + 0.0722 * linear(color.b)
+}
+
+// from 1 (the same) to 21 (black and white), whichever way round they are
+pub fn contrast(a: Rgb, b: Rgb) -> f32 {
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
+// the three --color-mode colors come first, so the modes are just palettes. the
+// deuteranopia, protanopia and tritanopia ones keep to colors those kinds of color
+// blindness still tell apart
+const BUILTINS: [(&str, &[u32]); 13] = [
+    ("white", &[0x000000, 0xFFFFFF]),
+    ("gray", &[0xC0C0C0, 0x808080]),
+    ("green", &[0x004000, 0x00FF00]),
+    ("classic-green", &[0x0A1A0A, 0x33FF33, 0x1F8F1F, 0xB3FFB3]),
+    ("amber", &[0x1A0E00, 0xFFB000, 0x8F5F00, 0xFFE0A0]),
+    ("lcd", &[0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F]),
+    ("paper-white", &[0xF4F1E8, 0x222222, 0x8A8578, 0xC9C3B3]),
+    ("ice", &[0x0B1E2D, 0xA8E6FF, 0x3C7FA6, 0xFFFFFF]),
+    ("candy", &[0x2B0A3D, 0xFF6FB5, 0x8AE8FF, 0xFFF07A]),
+    ("high-contrast", &[0x000000, 0xFFFF00, 0x00FFFF, 0xFFFFFF]),
+    ("deuteranopia", &[0x0B1A33, 0xF0C419, 0x0072B2, 0xE69F00]),
+    ("protanopia", &[0x101020, 0x56B4E9, 0x0072B2, 0xF0E442]),
+    ("tritanopia", &[0x1A0A0A, 0xFF6B6B, 0x009E73, 0xF4F4F4]),
+];
+
+fn rgb(color: u32) -> Rgb {
+    Rgb {
+        r: (color >> 16) as u8,
+        g: (color >> 8) as u8,
+        b: color as u8,
+    }
+}
+
+pub fn builtins() -> Vec<Palette> {
+    BUILTINS
+        .iter()
+        .map(|(name, colors)| Palette {
+            name: name.to_string(),
+            colors: colors.iter().map(|color| rgb(*color)).collect(),
+        })
+        .collect()
+}
+
+// a name on the first line, then one #RRGGBB color per line, background first.
+// blank lines and lines starting with ; are skipped
+pub fn parse(text: &str) -> Result<Palette, String> {
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with(';'));
+    let name = lines.next().ok_or("no name")?.to_string();
+    let mut colors = Vec::new();
+    for line in lines {
+        let hex = line.strip_prefix('#').unwrap_or(line);
+        let color = match u32::from_str_radix(hex, 16) {
+            Ok(color) if hex.len() == 6 => rgb(color),
+            _ => return Err(format!("bad color \"{}\"", line)),
+        };
+        if colors.contains(&color) {
+            return Err(format!("{} is in there twice", line));
+        }
+        colors.push(color);
+    }
+    if !(2..=4).contains(&colors.len()) {
+        return Err(format!("needs 2 to 4 colors, not {}", colors.len()));
+    }
+    Ok(Palette { name, colors })
+}
+
+// the built-ins plus every palette file in `dir`, in file name order. a file with a built-in's
+// name replaces it, broken files are skipped with a warning
+pub fn load(dir: &Path) -> Vec<Palette> {
+    let mut palettes = builtins();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return palettes; // no palettes directory is fine
+    };
+    let mut paths: Vec<_> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+    for path in paths.iter().filter(|path| path.is_file()) {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string());
+        let palette = match text.and_then(|text| parse(&text)) {
+            Ok(palette) => palette,
+            Err(e) => {
+                log::warn!("Skipping palette {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        log::debug!("Loaded palette {} from {}", palette.name, path.display());
+        match palettes.iter_mut().find(|builtin| builtin.name == palette.name) {
+            Some(builtin) => *builtin = palette,
+            None => palettes.push(palette),
+        }
+    }
+    palettes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK: Rgb = Rgb { r: 0, g: 0, b: 0 };
+    const WHITE: Rgb = Rgb { r: 0xFF, g: 0xFF, b: 0xFF };
+
+    fn builtin(name: &str) -> Palette {
+        builtins().into_iter().find(|palette| palette.name == name).unwrap()
+    }
+
+    #[test]
+    fn contrast_runs_from_1_to_21() {
+        assert!((contrast(BLACK, WHITE) - 21.0).abs() < 1e-4);
+        assert_eq!(contrast(WHITE, WHITE), 1.0);
+        assert_eq!(contrast(WHITE, BLACK), contrast(BLACK, WHITE));
+        // WCAG's own example, #777777 on white just under 4.5
+        let gray = rgb(0x777777);
+        assert!((contrast(gray, WHITE) - 4.48).abs() < 0.01, "{}", contrast(gray, WHITE));
+    }
+
+    #[test]
+    fn the_presets_meant_to_be_read_are_readable() {
+        let readable = ["white", "high-contrast", "deuteranopia", "protanopia", "tritanopia"];
+        for name in readable {
+            let contrast = builtin(name).contrast();
+            assert!(contrast >= MIN_CONTRAST, "{} is only {:.2}:1", name, contrast);
+        }
+        assert!(builtin("high-contrast").contrast() > 19.0);
+        assert!(builtin("gray").contrast() < MIN_CONTRAST); // which gets the warning
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+use crate::run_state::Event;
+use crate::save_ram::parse_addr;
+use crate::{Emulator, RunMode};
+
+// --pause-at: what drops the machine from Play to Step, the first time it happens. it's
+// only checked while it plays, and once it's gone off it's gone
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PauseAt {
+    FirstDraw,  // the first DXYN
+    FirstInput, // the first EX9E, EXA1 or FX0A
+    Addr(u16),  // the PC getting here, before the instruction runs
+    Cycle(u64), // this many cycles in
+    CodeWrite { by: u16, addr: u16 }, // --guard-pc pause, not a --pause-at
+}
+impl fmt::Display for PauseAt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PauseAt::FirstDraw => write!(f, "the first draw"),
+            PauseAt::FirstInput => write!(f, "the first input check"),
+            PauseAt::Addr(addr) => write!(f, "{:#05X}", addr),
+            PauseAt::Cycle(cycle) => write!(f, "cycle {}", cycle),
+            PauseAt::CodeWrite { by, addr } => {
+                write!(f, "{:#05X} writing over the code at {:#05X}", by, addr)
+            }
+        }
+    }
+}
+
+// first-draw, first-input, addr:0xNNN or cycle:N
+pub fn parse(text: &str) -> Result<PauseAt, String> {
+    match text.split_once(':') {
+        None if text == "first-draw" => Ok(PauseAt::FirstDraw),
+        None if text == "first-input" => Ok(PauseAt::FirstInput),
+        Some(("addr", addr)) => parse_addr(addr).map(PauseAt::Addr),
+        Some(("cycle", cycle)) => match cycle.parse() {
+            Ok(cycle) if cycle > 0 => Ok(PauseAt::Cycle(cycle)),
+            _ => Err(format!("\"{}\" isn't a cycle count above 0", cycle)),
+        },
+        _ => Err(String::from("expected first-draw, first-input, addr:0xNNN or cycle:N")),
+    }
+}
+
+impl Emulator {
+    // it's only looked for while the machine plays, so this sets it playing too
+    pub fn arm_pause_at(&mut self, trigger: PauseAt) {
+        self.pause_at = Some(trigger);
+        self.state = self.state.clone().on(Event::SetMode(RunMode::Play));
+    }
+    // from cycle, after `word` ran
+    pub fn check_pause_at(&mut self, word: u16) {
+        let Some(trigger) = self.pause_at else {
+            return;
+        };
+        if !self.state.runs_cycles() {
+            return;
+        }
+        let hit = match trigger {
+            PauseAt::FirstDraw => word & 0xF000 == 0xD000,
+            PauseAt::FirstInput => matches!(word & 0xF0FF, 0xE09E | 0xE0A1 | 0xF00A),
+            PauseAt::Addr(addr) => self.program_counter == addr,
+            PauseAt::Cycle(cycle) => self.stats.instructions >= cycle,
+            PauseAt::CodeWrite { .. } => false,
+        };
+        if hit {
+            log::info!("Pausing at {}", trigger);
+            self.pause_at = None;
+            self.paused_at = Some(trigger);
+        }
+    }
+}
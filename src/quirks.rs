@@ -0,0 +1,113 @@
+#[cfg(feature = "frontend")]
+use olc_pge as olc;
+
+use crate::status::NOTICE_TIME;
+use crate::Emulator;
+
+// the behaviors that differ between interpreters and can be flipped while a ROM runs, from
+// the quirks screen. the machine is frozen while it's up, so a change lands between cycles
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Quirk {
+    WrapX,
+    WrapY,
+    VipAreas,
+    JumpX,
+    DisplayWait,
+    ShiftVy,
+}
+pub const QUIRKS: [Quirk; 6] = [
+    Quirk::WrapX,
+    Quirk::WrapY,
+    Quirk::VipAreas,
+    Quirk::JumpX,
+    Quirk::DisplayWait,
+    Quirk::ShiftVy,
+];
+
+impl Quirk {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quirk::WrapX => "wrap sprites left-right",
+            Quirk::WrapY => "wrap sprites top-bottom",
+            Quirk::VipAreas => "reserve the VIP areas",
+            Quirk::JumpX => "BXNN jumps by VX",
+            Quirk::DisplayWait => "DXYN waits for the frame",
+            Quirk::ShiftVy => "8XY6/8XYE shift VY",
+        }
+    }
+}
+
+// up and down pick a quirk
+#[cfg(feature = "frontend")]
+pub fn move_selection(selected: usize, key: olc::Key) -> usize {
+    match key {
+        olc::Key::Up => selected.saturating_sub(1),
+        olc::Key::Down => (selected + 1).min(QUIRKS.len() - 1),
+        _ => selected,
+    }
+}
+
+impl Emulator {
+    pub fn quirk(&self, quirk: Quirk) -> bool {
+        match quirk {
+            Quirk::WrapX => self.wrap_x,
+            Quirk::WrapY => self.wrap_y,
+            Quirk::VipAreas => self.reserve_vip_areas,
+            Quirk::JumpX => self.jump_x,
+            Quirk::DisplayWait => self.display_wait,
+            Quirk::ShiftVy => self.shift_vy,
+        }
+    }
+    pub fn toggle_quirk(&mut self, quirk: Quirk) {
+        let on = !self.quirk(quirk);
+        match quirk {
+            Quirk::WrapX => self.wrap_x = on,
+            Quirk::WrapY => self.wrap_y = on,
+            Quirk::VipAreas => {
+                self.reserve_vip_areas = on;
+                self.mirror_display(); // so it's there before the next draw
+            }
+            Quirk::JumpX => self.jump_x = on,
+            Quirk::DisplayWait => {
+                self.display_wait = on;
+                self.display_waiting = false;
+            }
+            Quirk::ShiftVy => self.shift_vy = on,
+        }
+        log::info!("Quirk {:?} turned {}", quirk, if on { "on" } else { "off" });
+        self.notify(format!("{}: {}", quirk.name(), if on { "on" } else { "off" }), NOTICE_TIME);
+    }
+    // the quirks in force, short enough for the debug panel
+    pub fn quirk_summary(&self) -> String {
+        let axis = |on: bool, name| if on { name } else { '-' };
+        let mut summary = format!("WRAP:{}{}", axis(self.wrap_x, 'X'), axis(self.wrap_y, 'Y'));
+        if self.reserve_vip_areas {
+            summary += " VIP";
+        }
+        if self.jump_x {
+            summary += " JMPX";
+        }
+        if self.display_wait {
+            summary += " DWAIT";
+        }
+        if self.shift_vy {
+            summary += " SHVY";
+        }
+        summary
+    }
+}
+
+#[cfg(feature = "frontend")]
+pub fn render(emulator: &Emulator, pge: &mut olc::PixelGameEngine, selected: usize) {
+    pge.draw_string(8, 8, &String::from("QUIRKS"), olc::WHITE);
+    for (i, quirk) in QUIRKS.iter().enumerate() {
+        let on = emulator.quirk(*quirk);
+        let marker = if i == selected { '>' } else { ' ' };
+        let line = format!("{} [{}] {}", marker, if on { 'X' } else { ' ' }, quirk.name());
+        let color = if i == selected { olc::YELLOW } else { olc::WHITE };
+        pge.draw_string(8, 24 + i as i32 * 10, &line.to_uppercase(), color);
+    }
+    let y = 24 + QUIRKS.len() as i32 * 10 + 6;
+    let hint = String::from("up/down: pick, space: toggle, esc: close");
+    pge.draw_string(8, y, &hint, olc::GREY);
+}
@@ -0,0 +1,201 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::RangeInclusive;
+
+use crate::font::FONT_SIZE;
+use crate::instructions::hex_addr;
+use crate::save_ram;
+use crate::vip_areas::{DISPLAY_AREA, WORK_AREA};
+use crate::Emulator;
+
+// a named stretch of RAM, for the memory map and dumps to say what's where
+#[derive(PartialEq, Debug, Clone)]
+pub struct Region {
+    pub name: String,
+    pub range: RangeInclusive<u16>,
+}
+
+// the regions that apply to a machine, kept in order of where they start. they can
+// overlap: a byte belongs to the narrowest one it's in
+#[derive(PartialEq, Debug)]
+pub struct Regions {
+    regions: Vec<Region>,
+}
+impl Regions {
+    pub fn new(mut regions: Vec<Region>) -> Regions {
+        regions.sort_by_key(|region| (*region.range.start(), *region.range.end()));
+        Regions { regions }
+    }
+    pub fn at(&self, addr: u16) -> Option<&Region> {
+        self.regions
+            .iter()
+            .take_while(|region| *region.range.start() <= addr)
+            .filter(|region| region.range.contains(&addr))
+            .min_by_key(|region| region.range.end() - region.range.start())
+    }
+    // the regions with any of `range` in them
+    pub fn overlapping(&self, range: RangeInclusive<u16>) -> impl Iterator<Item = &Region> {
+        self.regions.iter().filter(move |region| {
+            region.range.start() <= range.end() && range.start() <= region.range.end()
+        })
+    }
+    pub fn is_boundary(&self, addr: u16) -> bool {
+        self.regions
+            .iter()
+            .any(|region| *region.range.start() == addr || *region.range.end() == addr)
+    }
+}
+
+// what a --symbols file names: ranges for the memory map and dumps, labels for the
+// addresses the trace and the disassembly show
+#[derive(PartialEq, Debug, Default)]
+pub struct Symbols {
+    pub regions: Vec<Region>,
+    pub labels: BTreeMap<u16, String>,
+}
+
+pub fn load_symbols(path: &str) -> Result<Symbols, String> {
+    let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    parse_symbols(&text)
+}
+
+// a line each, either a range `name: START..END` with both ends included or a label
+// `ADDR name`, the way disasm --emit-symbols writes them. the two can be mixed
+pub fn parse_symbols(text: &str) -> Result<Symbols, String> {
+    let mut symbols = Symbols::default();
+    for (i, line) in text.lines().enumerate() {
+        let on_line = |e: String| format!("line {}: {}", i + 1, e);
+        let line = line.trim();
+        if let Some((name, range)) = line.split_once(':') {
+            symbols.regions.push(Region {
+                name: name.trim().to_string(),
+                range: save_ram::parse_range(range).map_err(on_line)?,
+            });
+        } else if let Some((addr, name)) = line.split_once(char::is_whitespace) {
+            let addr = save_ram::parse_addr(addr).map_err(on_line)?;
+            symbols.labels.insert(addr, name.trim().to_string());
+        } else if !line.is_empty() {
+            let e = format!("\"{}\" is neither `name: START..END` nor `ADDR name`", line);
+            return Err(on_line(e));
+        }
+    }
+    Ok(symbols)
+}
+
+impl Emulator {
+    // an address as the trace shows it, by its --symbols label if it has one
+    pub fn label_or_hex(&self, addr: u16) -> String {
+        self.labels.get(&addr).cloned().unwrap_or_else(|| hex_addr(addr))
+    }
+    // the built-in regions that apply right now, and --symbols
+    pub fn regions(&self) -> Regions {
+        let mut regions = vec![Region {
+            name: String::from("font"),
+            range: 0x000..=FONT_SIZE - 1,
+        }];
+        if self.rom_size > 0 {
+            let end = (0x200 + self.rom_size - 1).min(0xFFF) as u16;
+            regions.push(Region {
+                name: String::from("rom"),
+                range: 0x200..=end,
+            });
+        }
+        if let Some(save) = &self.save_ram {
+            regions.push(Region {
+                name: String::from("persisted"),
+                range: save.range(),
+            });
+        }
+        if self.reserve_vip_areas {
+            regions.push(Region {
+                name: String::from("vip work area"),
+                range: WORK_AREA,
+            });
+            regions.push(Region {
+                name: String::from("vip display"),
+                range: DISPLAY_AREA,
+            });
+        }
+        regions.extend(self.user_regions.iter().cloned());
+        Regions::new(regions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_labels_disasm_writes() {
+        let symbols = parse_symbols("200 start\n23A loc_23A\n\n").unwrap();
+        assert_eq!(symbols.labels.get(&0x23A).map(String::as_str), Some("loc_23A"));
+        assert_eq!(symbols.labels.len(), 2);
+    }
+
+    #[test]
+    fn a_line_that_is_neither_is_an_error() {
+        let e = parse_symbols("200 start\nbuffer\n").unwrap_err();
+        assert_eq!(e, "line 2: \"buffer\" is neither `name: START..END` nor `ADDR name`");
+    }
+
+    #[test]
+    fn labels_and_ranges_go_in_the_same_file() {
+        let symbols = parse_symbols("200 start\nbuffer: 0x300..0x33F\n\n2A0 sub_draw\n").unwrap();
+        let buffer = Region {
+            name: String::from("buffer"),
+            range: 0x300..=0x33F,
+        };
+        assert_eq!(symbols.regions, [buffer]);
+        let labels: Vec<(u16, &str)> =
+            symbols.labels.iter().map(|(addr, name)| (*addr, name.as_str())).collect();
+        assert_eq!(labels, [(0x200, "start"), (0x2A0, "sub_draw")]);
+    }
+
+    fn regions() -> Regions {
+        let region = |name: &str, range| Region {
+            name: String::from(name),
+            range,
+        };
+        // out of order, the way a symbol file might have them
+        Regions::new(vec![
+            region("sprites", 0x300..=0x3FF),
+            region("rom", 0x200..=0x4FF),
+            region("ball", 0x310..=0x313),
+        ])
+    }
+
+    #[test]
+    fn a_byte_belongs_to_the_narrowest_region_it_is_in() {
+        let regions = regions();
+        let at = |addr| regions.at(addr).map(|region| region.name.as_str());
+        assert_eq!(at(0x1FF), None);
+        assert_eq!(at(0x200), Some("rom"));
+        assert_eq!(at(0x300), Some("sprites"));
+        assert_eq!(at(0x311), Some("ball"));
+        assert_eq!(at(0x313), Some("ball"));
+        assert_eq!(at(0x314), Some("sprites"));
+        assert_eq!(at(0x400), Some("rom"));
+        assert_eq!(at(0x500), None);
+    }
+
+    #[test]
+    fn overlapping_finds_every_region_touching_the_range() {
+        let regions = regions();
+        let names = |range| -> Vec<&str> {
+            regions.overlapping(range).map(|region| region.name.as_str()).collect()
+        };
+        assert_eq!(names(0x100..=0x1FF), Vec::<&str>::new());
+        assert_eq!(names(0x1F0..=0x200), ["rom"]);
+        assert_eq!(names(0x300..=0x30F), ["rom", "sprites"]);
+        assert_eq!(names(0x310..=0x31F), ["rom", "sprites", "ball"]);
+        assert_eq!(names(0x4FF..=0x4FF), ["rom"]);
+    }
+
+    #[test]
+    fn both_ends_of_a_region_are_boundaries() {
+        let regions = regions();
+        assert!(regions.is_boundary(0x200) && regions.is_boundary(0x4FF));
+        assert!(regions.is_boundary(0x310) && regions.is_boundary(0x313));
+        assert!(!regions.is_boundary(0x311));
+    }
+}
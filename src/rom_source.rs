@@ -0,0 +1,226 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Cursor, Read};
+
+use zip::ZipArchive;
+
+// everything above the interpreter area is free for the program
+pub const MAX_ROM_SIZE: usize = 0x1000 - 0x200;
+// archives can hold more than one ROM, so downloads get more room than a single ROM
+#[cfg(feature = "net")]
+const MAX_DOWNLOAD_SIZE: u64 = 4 * 1024 * 1024;
+const ROM_ENTRY_EXTENSIONS: [&str; 2] = ["ch8", "c8"];
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Zip(zip::result::ZipError),
+    NoRomInArchive,
+    MissingEntry(String),
+    Download(String),
+    TooBig { size: usize, max: usize }, // bytes, and the most there's room for where it goes
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(e) if e.kind() == io::ErrorKind::NotFound => write!(f, "file not found"),
+            LoadError::Io(e) => write!(f, "{}", e),
+            LoadError::Zip(e) => write!(f, "bad zip archive: {}", e),
+            LoadError::NoRomInArchive => write!(f, "no .ch8 or .c8 file in the archive"),
+            LoadError::MissingEntry(name) => write!(f, "no entry named {} in the archive", name),
+            LoadError::Download(e) => write!(f, "download failed: {}", e),
+            LoadError::TooBig { size, max } => {
+                write!(f, "ROM too large ({} bytes, max {})", size, max)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> LoadError {
+        LoadError::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for LoadError {
+    fn from(e: zip::result::ZipError) -> LoadError {
+        LoadError::Zip(e)
+    }
+}
+
+// reads the ROM named by `source`: a file path, `game.zip` (its first ROM entry),
+// `game.zip#brix.ch8` (a named entry), or with the net feature an http(s):// URL to either
+pub fn read(source: &str) -> Result<Vec<u8>, LoadError> {
+    let (location, entry) = split_entry(source);
+    log::debug!("Reading ROM from {}", location);
+    let data = if location.starts_with("http://") || location.starts_with("https://") {
+        download(location)?
+    } else {
+        fs::read(location)?
+    };
+    let rom = if location.to_ascii_lowercase().ends_with(".zip") {
+        from_zip(&data, entry)?
+    } else {
+        data
+    };
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(LoadError::TooBig {
+            size: rom.len(),
+            max: MAX_ROM_SIZE,
+        });
+    }
+    Ok(rom)
+}
+
+// `#` only picks an entry after a .zip, anywhere else it's part of the file name
+fn split_entry(source: &str) -> (&str, Option<&str>) {
+    match source.to_ascii_lowercase().find(".zip#") {
+        Some(i) => (&source[..i + 4], Some(&source[i + 5..])),
+        None => (source, None),
+    }
+}
+
+fn from_zip(data: &[u8], entry: Option<&str>) -> Result<Vec<u8>, LoadError> {
+    let mut archive = ZipArchive::new(Cursor::new(data))?;
+    let mut index = None;
+    for i in 0..archive.len() {
+        let file = archive.by_index(i)?;
+        let name = file.name()?;
+        // a named entry matches the full path in the archive, or just the file name
+        let matches = match entry {
+            Some(entry) => name == entry || name.rsplit('/').next() == Some(entry),
+            None => {
+                let name = name.to_ascii_lowercase();
+                ROM_ENTRY_EXTENSIONS
+                    .iter()
+                    .any(|extension| name.ends_with(&format!(".{}", extension)))
+            }
+        };
+        if matches && file.is_file() {
+            index = Some(i);
+            break;
+        }
+    }
+    let index = match (index, entry) {
+        (Some(index), _) => index,
+        (None, Some(entry)) => return Err(LoadError::MissingEntry(entry.to_string())),
+        (None, None) => return Err(LoadError::NoRomInArchive),
+    };
+    let file = archive.by_index(index)?;
+    log::debug!("Using archive entry {}", file.name()?);
+    if file.size() > MAX_ROM_SIZE as u64 {
+        return Err(LoadError::TooBig {
+            size: file.size() as usize,
+            max: MAX_ROM_SIZE,
+        });
+    }
+    let mut rom = Vec::new();
+    file.take(MAX_ROM_SIZE as u64 + 1).read_to_end(&mut rom)?;
+    Ok(rom)
+}
+
+#[cfg(feature = "net")]
+fn download(url: &str) -> Result<Vec<u8>, LoadError> {
+    let mut response = ureq::get(url)
+        .call()
+        .map_err(|e| LoadError::Download(e.to_string()))?;
+    // links to a page about the ROM rather than the ROM itself come back as HTML
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+    if content_type.starts_with("text/html") {
+        return Err(LoadError::Download(format!(
+            "{} is a web page, not a ROM",
+            url
+        )));
+    }
+    response
+        .body_mut()
+        .with_config()
+        .limit(MAX_DOWNLOAD_SIZE)
+        .read_to_vec()
+        .map_err(|e| LoadError::Download(e.to_string()))
+}
+
+#[cfg(not(feature = "net"))]
+fn download(url: &str) -> Result<Vec<u8>, LoadError> {
+    Err(LoadError::Download(format!(
+        "{} is a URL, loading from URLs needs the net feature",
+        url
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::{SimpleFileOptions, ZipWriter};
+
+    // an archive of (name, contents), in that order
+    fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(contents).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn the_first_rom_in_an_archive_is_taken() {
+        let data = archive(&[("README.txt", b"docs"), ("brix.ch8", &[0x12, 0x00])]);
+        assert_eq!(from_zip(&data, None).unwrap(), [0x12, 0x00]);
+    }
+
+    #[test]
+    fn an_entry_is_picked_by_name_or_path() {
+        let data = archive(&[
+            ("games/pong.ch8", &[0x01]),
+            ("games/brix.ch8", &[0x02]),
+            ("tank.C8", &[0x03]),
+        ]);
+        assert_eq!(from_zip(&data, None).unwrap(), [0x01]);
+        assert_eq!(from_zip(&data, Some("brix.ch8")).unwrap(), [0x02]);
+        assert_eq!(from_zip(&data, Some("games/brix.ch8")).unwrap(), [0x02]);
+        assert_eq!(from_zip(&data, Some("tank.C8")).unwrap(), [0x03]);
+        let missing = from_zip(&data, Some("ufo.ch8")).unwrap_err();
+        assert_eq!(missing.to_string(), "no entry named ufo.ch8 in the archive");
+    }
+
+    #[test]
+    fn an_archive_without_a_rom_says_so() {
+        let data = archive(&[("README.txt", b"docs")]);
+        assert!(matches!(from_zip(&data, None), Err(LoadError::NoRomInArchive)));
+        assert!(matches!(from_zip(b"not a zip", None), Err(LoadError::Zip(_))));
+    }
+
+    #[test]
+    fn an_entry_too_big_for_ram_is_refused() {
+        let fits = vec![0xAA; MAX_ROM_SIZE];
+        let too_big = vec![0xAA; MAX_ROM_SIZE + 1];
+        let data = archive(&[("fits.ch8", &fits), ("big.ch8", &too_big)]);
+        assert_eq!(from_zip(&data, Some("fits.ch8")).unwrap().len(), MAX_ROM_SIZE);
+        let error = from_zip(&data, Some("big.ch8")).unwrap_err();
+        assert!(matches!(error, LoadError::TooBig { size, max: MAX_ROM_SIZE } if size == 0xE01));
+    }
+
+    #[test]
+    fn the_entry_follows_the_zip_name() {
+        assert_eq!(split_entry("games.zip#brix.ch8"), ("games.zip", Some("brix.ch8")));
+        assert_eq!(split_entry("GAMES.ZIP#a/b.ch8"), ("GAMES.ZIP", Some("a/b.ch8")));
+        assert_eq!(split_entry("odd#name.ch8"), ("odd#name.ch8", None));
+    }
+
+    #[test]
+    fn read_goes_through_the_archive() {
+        let path = std::env::temp_dir().join(format!("chip8rust-{}.zip", std::process::id()));
+        fs::write(&path, archive(&[("a.ch8", &[0x0A]), ("b.ch8", &[0x0B])])).unwrap();
+        let path = path.to_string_lossy().to_string();
+        assert_eq!(read(&path).unwrap(), [0x0A]);
+        assert_eq!(read(&format!("{}#b.ch8", path)).unwrap(), [0x0B]);
+        fs::remove_file(path).unwrap();
+    }
+}
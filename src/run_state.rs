@@ -0,0 +1,225 @@
+use std::fmt;
+
+use crate::sprites::SpriteView;
+use crate::RunMode;
+
+// why the machine stopped, it stays stopped until a reset or another ROM
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Halt {
+    Exit(u16),           // 00FD at this address
+    StackUnderflow(u16), // RET with nothing on the call stack, at this address
+    StackOverflow(u16),  // CALL with the call stack full, at this address
+}
+impl Halt {
+    // a ROM exiting is a way of finishing, the rest are bugs in the ROM (or the emulator)
+    pub fn is_error(&self) -> bool {
+        !matches!(self, Halt::Exit(_))
+    }
+}
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Halt::Exit(addr) => write!(f, "EXIT at {:#05X}", addr),
+            Halt::StackUnderflow(addr) => write!(f, "RET with an empty stack at {:#05X}", addr),
+            Halt::StackOverflow(addr) => write!(f, "CALL with a full stack at {:#05X}", addr),
+        }
+    }
+}
+
+// what the emulator is doing, which decides what each frame of a frontend does:
+//
+//   state                cycles    timers     beeper    space   tab
+//   Play                 run       tick       plays     yes     -
+//   Step                 on tab    per step   -         yes     yes
+//   Paused(mode)         -         -          -         -       -
+//   WaitingForKey(mode)  -         in play    in play   yes     -
+//   Halted(why)          -         -          -         -       -
+//   Modal(modal, under)  -         -          -         -       -
+//
+// escape, F7, F9 and ctrl+O work in every state but Modal, where the modal gets every key
+#[derive(PartialEq, Debug, Clone)]
+pub enum RunState {
+    Play,
+    Step,
+    Paused(RunMode),        // the window lost focus, it picks up in the same mode
+    WaitingForKey(RunMode), // an FX0A wants a key, then it's back to the same mode
+    Halted(Halt),
+    Modal(Modal, Box<RunState>), // a modal screen is up, over whatever state it closes back to
+}
+
+// an in-window screen (see modal.rs) that takes over the keyboard. while one is up the machine is
+// frozen (see RunState::Modal), and closing it puts back the state it opened over
+#[derive(PartialEq, Debug, Clone)]
+pub enum Modal {
+    Help,
+    StepCount(String), // what's been typed so far
+    MemoryMap(u16),    // the selected address
+    Sprites(SpriteView),
+    Calls,
+    Quirks(usize), // the selected quirk
+    Draws,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Event {
+    SetMode(RunMode), // space, or a remote pause / resume
+    FocusLost,
+    FocusGained,
+    KeyWait,    // an FX0A started waiting
+    KeyPressed, // ...and got its key
+    Halt(Halt),
+    OpenModal(Modal),
+    CloseModal,
+    Reset, // a reset or another ROM
+}
+
+impl From<RunMode> for RunState {
+    fn from(mode: RunMode) -> RunState {
+        match mode {
+            RunMode::Play => RunState::Play,
+            RunMode::Step => RunState::Step,
+        }
+    }
+}
+
+impl RunState {
+    // the transitions. anything not listed leaves the state as it is, so a halt can only
+    // be left with a reset, and switching modes while paused or waiting just changes the
+    // mode that comes back
+    pub fn on(self, event: Event) -> RunState {
+        use RunState::*;
+        match (self, event) {
+            // a modal goes over any state and leaves it as it was, resets aside
+            (Modal(_, under), Event::CloseModal) => *under,
+            (Modal(modal, under), Event::Reset) => Modal(modal, Box::new(under.on(Event::Reset))),
+            (state @ Modal(..), _) => state,
+            (state, Event::OpenModal(modal)) => Modal(modal, Box::new(state)),
+
+            (Halted(_), Event::Reset) => Play,
+            (Halted(halt), _) => Halted(halt), // the first halt is the one that counts
+            (_, Event::Halt(halt)) => Halted(halt),
+
+            (Play | Step, Event::SetMode(mode)) => mode.into(),
+            (Paused(_), Event::SetMode(mode)) => Paused(mode),
+            (WaitingForKey(_), Event::SetMode(mode)) => WaitingForKey(mode),
+
+            // a key wait that's still pending when focus comes back is picked up again
+            (state @ (Play | Step | WaitingForKey(_)), Event::FocusLost) => Paused(state.mode()),
+            (Paused(mode), Event::FocusGained) => mode.into(),
+
+            (state @ (Play | Step), Event::KeyWait) => WaitingForKey(state.mode()),
+            (WaitingForKey(mode), Event::KeyPressed) => mode.into(),
+            (Paused(mode) | WaitingForKey(mode), Event::Reset) => mode.into(),
+
+            (state, _) => state,
+        }
+    }
+    // the mode the state plays out in, which picks the layout. a halt shows the
+    // step panel so the machine can be looked over
+    pub fn mode(&self) -> RunMode {
+        match self {
+            RunState::Play => RunMode::Play,
+            RunState::Step | RunState::Halted(_) => RunMode::Step,
+            RunState::Paused(mode) | RunState::WaitingForKey(mode) => *mode,
+            RunState::Modal(_, under) => under.mode(),
+        }
+    }
+    pub fn runs_cycles(&self) -> bool {
+        *self == RunState::Play
+    }
+    pub fn steps(&self) -> bool {
+        *self == RunState::Step
+    }
+    // FX0A doesn't stop the clock, only the CPU
+    pub fn ticks_timers(&self) -> bool {
+        matches!(self, RunState::Play | RunState::WaitingForKey(RunMode::Play))
+    }
+    // not while stepping (it'd be annoying)
+    pub fn beeps(&self) -> bool {
+        self.ticks_timers()
+    }
+    pub fn switches_mode(&self) -> bool {
+        matches!(self, RunState::Play | RunState::Step | RunState::WaitingForKey(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXIT: Halt = Halt::Exit(0x204);
+
+    fn after(state: RunState, events: &[Event]) -> RunState {
+        events.iter().cloned().fold(state, RunState::on)
+    }
+
+    #[test]
+    fn space_switches_between_play_and_step() {
+        assert_eq!(RunState::Play.on(Event::SetMode(RunMode::Step)), RunState::Step);
+        assert_eq!(RunState::Step.on(Event::SetMode(RunMode::Play)), RunState::Play);
+    }
+
+    #[test]
+    fn losing_focus_pauses_in_the_same_mode() {
+        let paused = RunState::Step.on(Event::FocusLost);
+        assert_eq!(paused, RunState::Paused(RunMode::Step));
+        // space while paused only changes what comes back
+        let paused = paused.on(Event::SetMode(RunMode::Play));
+        assert_eq!(paused, RunState::Paused(RunMode::Play));
+        assert_eq!(paused.on(Event::FocusGained), RunState::Play);
+    }
+
+    #[test]
+    fn a_key_wait_goes_back_to_its_mode_on_a_key() {
+        let waiting = RunState::Play.on(Event::KeyWait);
+        assert_eq!(waiting, RunState::WaitingForKey(RunMode::Play));
+        assert!(waiting.ticks_timers() && !waiting.runs_cycles());
+        assert_eq!(waiting.clone().on(Event::KeyPressed), RunState::Play);
+        // focus coming back plays on, and the FX0A waits again on its next cycle
+        let refocused = after(waiting, &[Event::FocusLost, Event::FocusGained]);
+        assert_eq!(refocused, RunState::Play);
+    }
+
+    // nothing but a reset gets out of a halt, and a second halt doesn't replace the first
+    #[test]
+    fn a_halt_lasts_until_a_reset() {
+        let halted = RunState::Play.on(Event::Halt(EXIT));
+        let events = [
+            Event::SetMode(RunMode::Play),
+            Event::FocusLost,
+            Event::FocusGained,
+            Event::KeyWait,
+            Event::KeyPressed,
+            Event::Halt(Halt::StackUnderflow(0x300)),
+        ];
+        for event in events {
+            assert_eq!(halted.clone().on(event), RunState::Halted(EXIT));
+        }
+        assert!(!halted.runs_cycles() && !halted.ticks_timers() && !halted.switches_mode());
+        assert_eq!(halted.mode(), RunMode::Step);
+        assert_eq!(halted.on(Event::Reset), RunState::Play);
+    }
+
+    #[test]
+    fn a_modal_freezes_and_closes_back_to_the_state_under_it() {
+        let modal = RunState::Play.on(Event::OpenModal(Modal::Help));
+        assert_eq!(modal.mode(), RunMode::Play);
+        assert!(!modal.runs_cycles());
+        let under = after(modal.clone(), &[Event::Halt(EXIT), Event::SetMode(RunMode::Step)]);
+        assert_eq!(under, modal);
+        assert_eq!(modal.on(Event::CloseModal), RunState::Play);
+
+        // a reset goes through to the state under it
+        let halted = RunState::Halted(EXIT).on(Event::OpenModal(Modal::Calls));
+        let reset = halted.on(Event::Reset);
+        assert_eq!(reset, RunState::Modal(Modal::Calls, Box::new(RunState::Play)));
+    }
+
+    #[test]
+    fn halts_say_what_went_wrong() {
+        assert!(!EXIT.is_error());
+        assert!(Halt::StackOverflow(0x2FE).is_error());
+        assert_eq!(EXIT.to_string(), "EXIT at 0x204");
+        assert_eq!(Halt::StackUnderflow(0x300).to_string(), "RET with an empty stack at 0x300");
+    }
+}
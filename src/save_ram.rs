@@ -0,0 +1,213 @@
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+
+use crate::status::NOTICE_TIME;
+use crate::Emulator;
+
+// how long the range has to be left alone before it's saved, so a score written a byte
+// at a time goes out once
+const SAVE_DELAY: f32 = 1.0;
+
+// --persist-ram: a range of RAM kept in a file next to the ROM, for homebrew that saves
+// its high scores into memory and expects them to still be there next time
+pub struct SaveRam {
+    range: RangeInclusive<u16>,
+    path: PathBuf,
+    since_write: Option<f32>, // seconds since the range was last written, while it's unsaved
+}
+impl SaveRam {
+    // `<rom>.sav`, or for a download, the file name in the working directory
+    pub fn new(range: RangeInclusive<u16>, rom_file: &str) -> SaveRam {
+        let rom = match rom_file.split_once("://") {
+            Some(_) => rom_file.rsplit(['/', '#']).next().unwrap_or(rom_file),
+            None => rom_file,
+        };
+        SaveRam {
+            range,
+            path: PathBuf::from(format!("{}.sav", rom)),
+            since_write: None,
+        }
+    }
+    pub fn range(&self) -> RangeInclusive<u16> {
+        self.range.clone()
+    }
+    fn len(&self) -> usize {
+        self.range.len()
+    }
+}
+
+// "0xE00..0xFFF", both ends included (the 0x is optional)
+pub fn parse_range(text: &str) -> Result<RangeInclusive<u16>, String> {
+    let (start, end) = text.split_once("..").ok_or("expected START..END")?;
+    let (start, end) = (parse_addr(start)?, parse_addr(end)?);
+    if start > end {
+        return Err(format!("{:#05X} comes after {:#05X}", start, end));
+    }
+    Ok(start..=end)
+}
+
+// hex, with or without the 0x
+pub fn parse_addr(text: &str) -> Result<u16, String> {
+    let hex = text.trim().trim_start_matches("0x").trim_start_matches("0X");
+    match u16::from_str_radix(hex, 16) {
+        Ok(addr) if addr <= 0xFFF => Ok(addr),
+        _ => Err(format!("\"{}\" isn't an address from 0x000 to 0xFFF", text)),
+    }
+}
+
+impl Emulator {
+    // puts the saved range back over the ROM that was just loaded, if there's a save for it
+    pub fn restore_save_ram(&mut self) {
+        let Some(save) = &self.save_ram else {
+            return;
+        };
+        let range = save.range.clone();
+        match fs::read(&save.path) {
+            Ok(data) if data.len() == save.len() => {
+                let (start, end) = (range.start(), range.end());
+                log::info!("Restoring {:#05X}..{:#05X} from {}", start, end, save.path.display());
+                for (addr, byte) in range.clone().zip(data) {
+                    self.ram.set(addr, byte);
+                }
+            }
+            Ok(data) => log::warn!(
+                "Ignoring {}: {} bytes, the range is {}",
+                save.path.display(),
+                data.len(),
+                save.len()
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {} // nothing saved yet
+            Err(e) => log::warn!("Ignoring {}: {}", save.path.display(), e),
+        }
+        self.ram.take_writes(range); // loading isn't something to save
+    }
+    // once a frame: saves when the range has changed and then been left alone for SAVE_DELAY
+    pub fn update_save_ram(&mut self, delta: f32) {
+        let Some(save) = &mut self.save_ram else {
+            return;
+        };
+        if self.ram.take_writes(save.range.clone()) {
+            save.since_write = Some(0.0);
+        } else if let Some(since_write) = &mut save.since_write {
+            *since_write += delta;
+            if *since_write >= SAVE_DELAY {
+                self.write_save_ram();
+            }
+        }
+    }
+    // whether --persist-ram covers `addr`, which a soft reset then leaves alone
+    pub fn is_persisted(&self, addr: u16) -> bool {
+        self.save_ram.as_ref().is_some_and(|save| save.range.contains(&addr))
+    }
+    // another ROM was loaded, which the save isn't for. it was flushed by the reset
+    #[cfg(any(feature = "serve", feature = "file-dialog"))]
+    pub fn drop_save_ram(&mut self) {
+        if self.save_ram.take().is_some() {
+            log::info!("--persist-ram only covers the ROM it started with, not saving from now on");
+        }
+    }
+    // saves now if there's anything unsaved, for exits and resets
+    pub fn flush_save_ram(&mut self) {
+        let Some(save) = &mut self.save_ram else {
+            return;
+        };
+        if self.ram.take_writes(save.range.clone()) || save.since_write.is_some() {
+            self.write_save_ram();
+        }
+    }
+    fn write_save_ram(&mut self) {
+        let Some(save) = &mut self.save_ram else {
+            return;
+        };
+        save.since_write = None;
+        let range = save.range.clone();
+        let path = save.path.clone();
+        let data: Vec<u8> = range.map(|addr| self.ram.get(addr)).collect();
+        match fs::write(&path, data) {
+            Ok(()) => log::info!("Saved RAM to {}", path.display()),
+            Err(e) => {
+                log::error!("Failed to save RAM to {}: {}", path.display(), e);
+                self.notify_error(format!("Failed to save RAM: {}", e), NOTICE_TIME);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // V0-V2 to 0xE00..0xE02, then round and round
+    const SAVES: [u8; 12] = [
+        0xAE, 0x00, 0x60, 0x11, 0x61, 0x22, 0x62, 0x33, 0xF2, 0x55, 0x12, 0x0A,
+    ];
+
+    fn rom_file(name: &str) -> String {
+        let rom = format!("chip8rust-{}-{}.ch8", name, std::process::id());
+        std::env::temp_dir().join(rom).to_string_lossy().into_owned()
+    }
+
+    fn persisted(rom: &[u8], rom_file: &str) -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(rom).unwrap();
+        emulator.save_ram = Some(SaveRam::new(0xE00..=0xE02, rom_file));
+        emulator.restore_save_ram();
+        emulator
+    }
+
+    #[test]
+    fn a_saved_range_comes_back_next_time() {
+        let file = rom_file("saves");
+        let mut emulator = persisted(&SAVES, &file);
+        emulator.run_cycles(6);
+        emulator.update_save_ram(1.0 / 60.0);
+        emulator.update_save_ram(0.5);
+        assert!(fs::metadata(format!("{}.sav", file)).is_err()); // not left alone long enough
+        emulator.update_save_ram(0.5);
+        assert_eq!(fs::read(format!("{}.sav", file)).unwrap(), [0x11, 0x22, 0x33]);
+
+        let emulator = persisted(&[0x12, 0x00], &file);
+        let restored: Vec<u8> = (0xE00..=0xE02).map(|addr| emulator.ram.get(addr)).collect();
+        assert_eq!(restored, [0x11, 0x22, 0x33]);
+        fs::remove_file(format!("{}.sav", file)).unwrap();
+    }
+
+    #[test]
+    fn nothing_is_saved_until_the_range_is_written() {
+        let file = rom_file("unsaved");
+        let mut emulator = persisted(&[0x12, 0x00], &file);
+        emulator.run_cycles(10);
+        for _ in 0..120 {
+            emulator.update_save_ram(1.0 / 60.0);
+        }
+        emulator.flush_save_ram();
+        assert!(fs::metadata(format!("{}.sav", file)).is_err());
+    }
+
+    #[test]
+    fn a_save_of_the_wrong_size_is_ignored() {
+        let file = rom_file("short");
+        fs::write(format!("{}.sav", file), [0xAA, 0xBB]).unwrap();
+        let emulator = persisted(&[0x12, 0x00], &file);
+        assert_eq!((emulator.ram.get(0xE00), emulator.ram.get(0xE01)), (0, 0));
+        fs::remove_file(format!("{}.sav", file)).unwrap();
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("0xE00..0xFFF"), Ok(0xE00..=0xFFF));
+        assert_eq!(parse_range("e00..E0F"), Ok(0xE00..=0xE0F));
+        assert_eq!(parse_range("0xF00..0xE00"), Err(String::from("0xF00 comes after 0xE00")));
+        assert!(parse_range("0xE00..0x1000").is_err());
+        assert!(parse_range("0xE00").is_err());
+    }
+
+    #[test]
+    fn a_download_saves_in_the_working_directory() {
+        let save = SaveRam::new(0xE00..=0xFFF, "https://example.com/roms/pong.ch8");
+        assert_eq!(save.path, PathBuf::from("pong.ch8.sav"));
+        let save = SaveRam::new(0xE00..=0xFFF, "roms/pong.ch8");
+        assert_eq!(save.path, PathBuf::from("roms/pong.ch8.sav"));
+    }
+}
@@ -0,0 +1,335 @@
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+use rhai::{CallFnOptions, Dynamic, Engine, FuncArgs, Scope, AST, INT};
+
+use crate::hires::HIRES_H;
+use crate::status::NOTICE_TIME;
+use crate::{Emulator, SCR_H, SCR_W};
+
+// what a script changed, applied to the emulator once the callback returns
+enum Write {
+    Register(u8, u8),
+    Ram(u16, u8),
+    Timer(u8),
+    SoundTimer(u8),
+}
+
+// the machine as a script sees it: a copy taken before each callback
+struct Machine {
+    registers: [u8; 0x10],
+    ram: Vec<u8>,
+    display: [[bool; HIRES_H]; SCR_W],
+    rows: usize, // of the display, 64 for hires
+    program_counter: u16,
+    stack_pointer: u16,
+    timer: u8,
+    sound_timer: u8,
+    writes: Vec<Write>,
+    injected_keys: [Option<bool>; 0x10], // None leaves the key to the keyboard
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    machine: Rc<RefCell<Machine>>,
+    halt_reported: bool, // on_halt fires once per halt
+}
+
+impl Script {
+    fn load(path: &str) -> Result<Script, String> {
+        let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let machine = Rc::new(RefCell::new(Machine {
+            registers: [0; 0x10],
+            ram: Vec::new(),
+            display: [[false; HIRES_H]; SCR_W],
+            rows: SCR_H,
+            program_counter: 0,
+            stack_pointer: 0,
+            timer: 0,
+            sound_timer: 0,
+            writes: Vec::new(),
+            injected_keys: [None; 0x10],
+        }));
+        let mut engine = Engine::new();
+        register_api(&mut engine, &machine);
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        Ok(Script {
+            engine,
+            ast,
+            scope: Scope::new(),
+            machine,
+            halt_reported: false,
+        })
+    }
+    fn has(&self, callback: &str, params: usize) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|f| f.name == callback && f.params.len() == params)
+    }
+}
+
+// registers, memory and timers wrap to their real sizes, so scripts can't go out of bounds
+fn register_api(engine: &mut Engine, machine: &Rc<RefCell<Machine>>) {
+    let m = machine.clone();
+    engine.register_fn("reg", move |i: INT| m.borrow().registers[(i & 0xF) as usize] as INT);
+    let m = machine.clone();
+    engine.register_fn("set_reg", move |i: INT, value: INT| {
+        let mut m = m.borrow_mut();
+        m.registers[(i & 0xF) as usize] = value as u8;
+        m.writes.push(Write::Register((i & 0xF) as u8, value as u8));
+    });
+    let m = machine.clone();
+    engine.register_fn("peek", move |addr: INT| m.borrow().ram[(addr & 0xFFF) as usize] as INT);
+    let m = machine.clone();
+    engine.register_fn("poke", move |addr: INT, value: INT| {
+        let mut m = m.borrow_mut();
+        m.ram[(addr & 0xFFF) as usize] = value as u8;
+        m.writes.push(Write::Ram((addr & 0xFFF) as u16, value as u8));
+    });
+    let m = machine.clone();
+    engine.register_fn("pc", move || m.borrow().program_counter as INT);
+    let m = machine.clone();
+    engine.register_fn("stkp", move || m.borrow().stack_pointer as INT);
+    let m = machine.clone();
+    engine.register_fn("timer", move || m.borrow().timer as INT);
+    let m = machine.clone();
+    engine.register_fn("set_timer", move |value: INT| {
+        let mut m = m.borrow_mut();
+        m.timer = value as u8;
+        m.writes.push(Write::Timer(value as u8));
+    });
+    let m = machine.clone();
+    engine.register_fn("sound_timer", move || m.borrow().sound_timer as INT);
+    let m = machine.clone();
+    engine.register_fn("set_sound_timer", move |value: INT| {
+        let mut m = m.borrow_mut();
+        m.sound_timer = value as u8;
+        m.writes.push(Write::SoundTimer(value as u8));
+    });
+    let m = machine.clone();
+    engine.register_fn("press", move |key: INT| {
+        m.borrow_mut().injected_keys[(key & 0xF) as usize] = Some(true);
+    });
+    let m = machine.clone();
+    engine.register_fn("release", move |key: INT| {
+        m.borrow_mut().injected_keys[(key & 0xF) as usize] = None;
+    });
+    let m = machine.clone();
+    engine.register_fn("pixel", move |x: INT, y: INT| {
+        let machine = m.borrow();
+        let x = x.rem_euclid(SCR_W as INT) as usize;
+        let y = y.rem_euclid(machine.rows as INT) as usize;
+        machine.display[x][y]
+    });
+    let m = machine.clone();
+    engine.register_fn("lit_pixels", move || {
+        m.borrow().display.iter().flatten().filter(|pixel| **pixel).count() as INT
+    });
+}
+
+impl Emulator {
+    // loads a script and runs its top-level statements against the loaded ROM
+    pub fn load_script(&mut self, path: &str) -> Result<(), String> {
+        let mut script = Script::load(path)?;
+        self.sync_script(&script);
+        let result = script.engine.run_ast_with_scope(&mut script.scope, &script.ast);
+        self.apply_script(&script);
+        result.map_err(|e| e.to_string())?;
+        self.script = Some(script);
+        Ok(())
+    }
+    pub fn run_script_hook(&mut self, callback: &str) {
+        self.run_script_hook_with(callback, 0, ());
+    }
+    // a callback taking `params` arguments
+    pub fn run_script_hook_with(&mut self, callback: &str, params: usize, args: impl FuncArgs) {
+        let Some(mut script) = self.script.take() else {
+            return;
+        };
+        if !script.has(callback, params) {
+            self.script = Some(script);
+            return;
+        }
+        self.sync_script(&script);
+        let options = CallFnOptions::new().eval_ast(false).rewind_scope(false);
+        let result = script.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut script.scope,
+            &script.ast,
+            callback,
+            args,
+        );
+        self.apply_script(&script);
+        match result {
+            Ok(_) => self.script = Some(script),
+            // keep emulating, a broken script shouldn't take the ROM down with it
+            Err(e) => {
+                log::error!("Script error in {}, scripting disabled: {}", callback, e);
+                self.notify_error("Script error, scripting disabled", NOTICE_TIME);
+            }
+        }
+    }
+    // called after every cycle: on_draw when the display changed, on_halt when the ROM stops
+    pub fn run_script_cycle_hooks(&mut self, redraw: bool) {
+        if self.script.is_none() {
+            return;
+        }
+        if redraw {
+            self.run_script_hook("on_draw");
+        }
+        let halted = self.is_halted();
+        let Some(script) = &mut self.script else {
+            return;
+        };
+        if halted && !script.halt_reported {
+            script.halt_reported = true;
+            self.run_script_hook("on_halt");
+        } else if !halted {
+            script.halt_reported = false;
+        }
+    }
+    // keys pressed by the script override the keyboard until released
+    pub fn apply_script_keys(&mut self) {
+        if let Some(script) = &self.script {
+            for (i, key) in script.machine.borrow().injected_keys.iter().enumerate() {
+                if let Some(held) = key {
+                    self.keys[i] = *held;
+                }
+            }
+        }
+    }
+    fn sync_script(&mut self, script: &Script) {
+        let mut machine = script.machine.borrow_mut();
+        for i in 0..0x10 {
+            machine.registers[i] = self.registers.get(i as u8);
+        }
+        machine.ram = (0..0x1000).map(|addr| self.ram.get(addr)).collect();
+        machine.display = self.display;
+        machine.rows = self.screen_height();
+        machine.program_counter = self.program_counter;
+        machine.stack_pointer = self.stack_pointer;
+        machine.timer = self.timer;
+        machine.sound_timer = self.sound_timer;
+    }
+    fn apply_script(&mut self, script: &Script) {
+        for write in script.machine.borrow_mut().writes.drain(..) {
+            match write {
+                Write::Register(i, value) => self.registers.set(i, value),
+                Write::Ram(addr, value) => self.ram.set(addr, value),
+                Write::Timer(value) => self.timer = value,
+                Write::SoundTimer(value) => self.sound_timer = value,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a machine running `rom` with `source` loaded as its script
+    fn scripted(name: &str, rom: &[u8], source: &str) -> Emulator {
+        let file = format!("chip8rust-{}-{}.rhai", name, std::process::id());
+        let path = std::env::temp_dir().join(file);
+        fs::write(&path, source).unwrap();
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(rom).unwrap();
+        emulator.load_script(&path.to_string_lossy()).unwrap();
+        fs::remove_file(path).unwrap();
+        emulator
+    }
+
+    #[test]
+    fn writes_land_once_the_callback_returns() {
+        let source = "
+            fn on_frame() {
+                set_reg(3, reg(3) + 1);
+                poke(0x3A2, 9);
+                set_timer(30);
+                set_sound_timer(4);
+                if peek(0x3A2) != 9 { throw \"the script doesn't see its own poke\"; }
+            }";
+        let mut emulator = scripted("writes", &[0x12, 0x00], source);
+        emulator.run_script_hook("on_frame");
+        assert!(emulator.script.is_some());
+        assert_eq!(emulator.registers.get(3), 1);
+        assert_eq!(emulator.ram.get(0x3A2), 9);
+        assert_eq!((emulator.timer, emulator.sound_timer), (30, 4));
+
+        // the frame's own tick comes first, then the callback's writes
+        emulator.tick_timers();
+        assert_eq!((emulator.registers.get(3), emulator.timer, emulator.sound_timer), (2, 30, 4));
+    }
+
+    #[test]
+    fn pressed_keys_hold_until_released() {
+        let source = "
+            press(5);
+            fn on_frame() { release(5); }";
+        let mut emulator = scripted("keys", &[0x12, 0x00], source);
+        emulator.apply_script_keys();
+        assert!(emulator.keys[5]);
+        emulator.keys[5] = false; // the keyboard letting go doesn't count
+        emulator.apply_script_keys();
+        assert!(emulator.keys[5]);
+
+        emulator.run_script_hook("on_frame");
+        emulator.keys[5] = false;
+        emulator.apply_script_keys();
+        assert!(!emulator.keys[5]); // back to the keyboard
+    }
+
+    #[test]
+    fn on_halt_fires_once_a_halt() {
+        // counts into 0x400. the ROM starts on a jump to itself, then gets out of it to another
+        let source = "fn on_halt() { poke(0x400, peek(0x400) + 1); }";
+        let mut emulator = scripted("halt", &[0x12, 0x00, 0x00, 0x00, 0x12, 0x04], source);
+        emulator.run_cycles(5);
+        assert_eq!(emulator.ram.get(0x400), 1);
+        emulator.ram.set(0x201, 0x02);
+        emulator.run_cycles(5);
+        assert_eq!(emulator.program_counter, 0x204);
+        assert_eq!(emulator.ram.get(0x400), 2);
+    }
+
+    #[test]
+    fn a_script_error_turns_scripting_off() {
+        let source = "fn on_frame() { poke(0x300, 1); no_such_function(); }";
+        let mut emulator = scripted("error", &[0x12, 0x00], source);
+        emulator.step_frame(10);
+        assert!(emulator.script.is_none());
+        assert_eq!(emulator.ram.get(0x300), 1); // what it did before the error stands
+        emulator.step_frame(10); // and the ROM keeps going without it
+        assert_eq!(emulator.stats.frames, 2);
+    }
+
+    // reads counted at 0x300 and writes at 0x301, with the last of each's pc, timer and value
+    #[test]
+    fn the_timer_hooks_see_every_read_and_write() {
+        let source = "
+            fn on_timer_read(timer, value, pc) {
+                poke(0x300, peek(0x300) + 1);
+                poke(0x302, pc - 0x200);
+                poke(0x303, value);
+                if timer != \"delay\" { throw \"only the delay timer can be read\"; }
+            }
+            fn on_timer_write(timer, value, pc) {
+                poke(0x301, peek(0x301) + 1);
+                poke(0x304, pc - 0x200);
+                poke(0x305, if timer == \"sound\" { value } else { 0xFF });
+            }";
+        // DT = 5, ST = 3, then DT into V1 and V2
+        let rom = [
+            0x60, 0x05, 0xF0, 0x15, 0x63, 0x03, 0xF3, 0x18, 0xF1, 0x07, 0xF2, 0x07, 0x00, 0xFD,
+        ];
+        let mut emulator = scripted("timers", &rom, source);
+        emulator.run_cycles(10);
+        assert!(emulator.script.is_some());
+        let logged: Vec<u8> = (0x300..=0x305).map(|addr| emulator.ram.get(addr)).collect();
+        assert_eq!(logged, [2, 2, 0x0A, 5, 0x06, 3]);
+        assert_eq!((emulator.registers.get(1), emulator.registers.get(2)), (5, 5));
+    }
+}
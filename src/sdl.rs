@@ -0,0 +1,274 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Scancode};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, RenderTarget, Texture};
+use sdl2::video::Window;
+use sdl2::EventPump;
+
+use crate::audio::{Beeper, Tone, SAMPLE_RATE};
+use crate::headless::FrameEnd;
+use crate::run_state::{self, RunState};
+use crate::terminal::key_char;
+use crate::window::KEYS;
+use crate::{Emulator, RunMode, SCR_H, SCR_W};
+
+// the window starts at this many screen pixels a pixel, and can be resized from there
+const START_SCALE: u32 = 10;
+// how much sound is kept queued ahead, in frames: enough not to run dry on a slow frame
+const QUEUED_FRAMES: u32 = 2;
+const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / 60;
+
+// the beep through SDL's audio queue, topped up with the tone at the level of the moment
+struct QueueBeeper {
+    queue: AudioQueue<f32>,
+    tone: Tone,
+}
+impl Beeper for QueueBeeper {
+    fn set_level(&mut self, level: f32) {
+        let queued = self.queue.size() / std::mem::size_of::<f32>() as u32;
+        if queued >= QUEUED_FRAMES * SAMPLES_PER_FRAME {
+            return;
+        }
+        let samples: Vec<f32> = (0..SAMPLES_PER_FRAME)
+            .map(|_| self.tone.next().unwrap_or(0.0) * level)
+            .collect();
+        if let Err(e) = self.queue.queue_audio(&samples) {
+            log::warn!("Failed to queue audio: {}", e);
+        }
+    }
+}
+
+// the pad keys as SDL scancodes, laid out as in the window
+fn pad_scancodes() -> [Option<Scancode>; 0x10] {
+    KEYS.map(|key| Scancode::from_name(&key_char(key)?.to_string()))
+}
+
+// the largest whole number of screen pixels a pixel that fits, centered
+fn fit(width: u32, height: u32, rows: usize) -> Rect {
+    let scale = (width / SCR_W as u32).min(height / rows as u32).max(1);
+    let (w, h) = (SCR_W as u32 * scale, rows as u32 * scale);
+    Rect::new((width as i32 - w as i32) / 2, (height as i32 - h as i32) / 2, w, h)
+}
+
+pub fn run(mut emulator: Emulator, cycle_speed: f32) -> Result<(), String> {
+    let sdl = sdl2::init()?;
+    let window = sdl
+        .video()?
+        .window("Chip8 Emulator - Rust", SCR_W as u32 * START_SCALE, SCR_H as u32 * START_SCALE)
+        .position_centered()
+        .resizable()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let mut canvas = window.into_canvas().build().map_err(|e| e.to_string())?;
+    let mut events = sdl.event_pump()?;
+
+    let desired = AudioSpecDesired {
+        freq: Some(SAMPLE_RATE as i32),
+        channels: Some(1),
+        samples: None,
+    };
+    let tone = Tone::new(emulator.beep_pitch.clone(), emulator.beep_wave.clone());
+    let queue = sdl.audio().and_then(|audio| audio.open_queue::<f32, _>(None, &desired));
+    emulator.use_beeper(queue.map(|queue| {
+        queue.resume();
+        Box::new(QueueBeeper { queue, tone }) as Box<dyn Beeper>
+    }));
+
+    let result = run_loop(&mut canvas, &mut events, &mut emulator, cycle_speed);
+    emulator.shutdown();
+    result
+}
+
+fn run_loop(
+    canvas: &mut Canvas<Window>,
+    events: &mut EventPump,
+    emulator: &mut Emulator,
+    cycle_speed: f32,
+) -> Result<(), String> {
+    let frame_time = Duration::from_secs_f32(1.0 / 60.0);
+    let cycles_per_frame = emulator
+        .ipf
+        .unwrap_or_else(|| Emulator::cycles_per_frame(cycle_speed));
+    let scancodes = pad_scancodes();
+    let creator = canvas.texture_creator();
+    let (_, rows) = emulator.display_size();
+    let mut texture = creator
+        .create_texture_streaming(PixelFormatEnum::RGB24, SCR_W as u32, rows as u32)
+        .map_err(|e| e.to_string())?;
+    let mut title = String::new();
+    let mut driver = emulator.driver.in_frames();
+    let mut last_frame = Instant::now();
+    loop {
+        let frame_start = Instant::now();
+        let delta = emulator.driver.clamp((frame_start - last_frame).as_secs_f32());
+        last_frame = frame_start;
+        let mut step = false;
+
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. } => return Ok(()),
+                Event::KeyDown { keycode: Some(key), repeat: false, .. } => match key {
+                    Keycode::ESCAPE => return Ok(()),
+                    Keycode::SPACE => {
+                        let mode = emulator.state.mode().toggled();
+                        emulator.state = emulator.state.clone().on(run_state::Event::SetMode(mode));
+                    }
+                    Keycode::TAB => step = true,
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        let keyboard = events.keyboard_state();
+        let down = scancodes.map(|scancode| {
+            scancode.is_some_and(|scancode| keyboard.is_scancode_pressed(scancode))
+        });
+        let stepping = emulator.state.mode() == RunMode::Step;
+        emulator.keys = emulator.key_repeat.shape(down, delta, stepping);
+
+        let due = driver.advance_to(frame_start, &emulator.state, u32::MAX);
+        let mut end = FrameEnd::default();
+        let mut display_changed = false;
+        for _ in 0..due.frames {
+            end = emulator.step_frame(cycles_per_frame);
+            display_changed |= end.display_changed;
+            if end.key_wait_expired || end.paused_at.is_some() || emulator.halt.is_some() {
+                break;
+            }
+        }
+        for _ in 0..due.timer_ticks {
+            emulator.tick_timers();
+        }
+        if let Some(trigger) = end.paused_at {
+            // step_frame has it in Step already
+            log::info!("Paused at {}", trigger);
+        } else if end.key_wait_expired {
+            // stuck on a key wait past --keywait-timeout
+            emulator.state = emulator.state.clone().on(run_state::Event::SetMode(RunMode::Step));
+        } else if step && emulator.state.steps() && emulator.resolve_key_block() {
+            emulator.step_timers(); // same timer pacing as stepping in the window
+            if !emulator.display_waiting {
+                emulator.summary = emulator.cycle().1;
+            }
+        }
+        if let Some(halt) = emulator.halt {
+            emulator.state = emulator.state.clone().on(run_state::Event::Halt(halt));
+        }
+        emulator.update_beeper(delta);
+
+        if display_changed {
+            let (_, rows) = emulator.display_size();
+            texture = creator
+                .create_texture_streaming(PixelFormatEnum::RGB24, SCR_W as u32, rows as u32)
+                .map_err(|e| e.to_string())?;
+        }
+        draw(canvas, &mut texture, emulator)?;
+        canvas.present();
+
+        let next_title = window_title(emulator);
+        if next_title != title {
+            canvas.window_mut().set_title(&next_title).map_err(|e| e.to_string())?;
+            title = next_title;
+        }
+
+        emulator.update_save_ram(frame_time.as_secs_f32());
+        let elapsed = frame_start.elapsed();
+        if elapsed < frame_time {
+            thread::sleep(frame_time - elapsed);
+        }
+    }
+}
+
+// the display in the palette's colors, as big as fits in whole pixels, onto a texture with a
+// row for each of its rows
+fn draw<T: RenderTarget>(
+    canvas: &mut Canvas<T>,
+    texture: &mut Texture,
+    emulator: &Emulator,
+) -> Result<(), String> {
+    let palette = &emulator.palettes[emulator.palette];
+    let (on, off) = (palette.on(), palette.off());
+    let rows = emulator.screen_height();
+    texture.with_lock(None, |pixels, pitch| {
+        for y in 0..rows {
+            for x in 0..SCR_W {
+                let color = if emulator.display[x][y] { on } else { off };
+                let at = y * pitch + x * 3;
+                pixels[at..at + 3].copy_from_slice(&[color.r, color.g, color.b]);
+            }
+        }
+    })?;
+    canvas.set_draw_color(Color::RGB(off.r, off.g, off.b));
+    canvas.clear();
+    let (width, height) = canvas.output_size()?;
+    canvas.copy(texture, None, fit(width, height, rows))
+}
+
+// what the ROM is and what it's doing, where the window shows it on the screen
+fn window_title(emulator: &Emulator) -> String {
+    let mode = match &emulator.state {
+        RunState::Halted(halt) => format!("HALTED: {}", halt),
+        _ if emulator.state.mode() == RunMode::Play => String::from("PLAY"),
+        _ => format!("STEP, tab: step  {}", emulator.summary),
+    };
+    format!("{} - {} - Chip8 Emulator - Rust", emulator.rom_name, mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_display_scales_by_whole_pixels() {
+        assert_eq!(fit(640, 320, SCR_H), Rect::new(0, 0, 640, 320));
+        assert_eq!(fit(800, 330, SCR_H), Rect::new(80, 5, 640, 320));
+        assert_eq!(fit(640, 640, 64), Rect::new(0, 0, 640, 640));
+        assert_eq!(fit(10, 10, SCR_H), Rect::new(-27, -11, 64, 32)); // never below 1
+    }
+
+    // the demo's title, drawn onto a 640x400 surface: every pixel is a 10x10 block, centered
+    #[test]
+    fn the_display_is_drawn_as_it_is() {
+        use crate::{demo, RunMode};
+        use sdl2::surface::Surface;
+
+        let mut emulator = Emulator::new();
+        emulator.state = RunMode::Play.into();
+        emulator.load_rom_bytes(demo::find("demo").unwrap()).unwrap();
+        for _ in 0..60 {
+            emulator.step_frame(10);
+        }
+        let surface = Surface::new(640, 400, PixelFormatEnum::RGB24).unwrap();
+        let mut canvas = surface.into_canvas().unwrap();
+        let creator = canvas.texture_creator();
+        let mut texture = creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, SCR_W as u32, SCR_H as u32)
+            .unwrap();
+        draw(&mut canvas, &mut texture, &emulator).unwrap();
+        let pixels = canvas.read_pixels(None, PixelFormatEnum::RGB24).unwrap();
+
+        let lit = |x: usize, y: usize| pixels[(y * 640 + x) * 3] == 0xFF;
+        assert!(!lit(0, 0) && !lit(639, 39) && !lit(0, 360)); // the bars above and below
+        for y in 0..SCR_H {
+            for x in 0..SCR_W {
+                let shown = lit(x * 10 + 5, 40 + y * 10 + 5);
+                assert_eq!(shown, emulator.display[x][y], "the pixel at ({}, {})", x, y);
+            }
+        }
+        assert!(emulator.display.iter().flatten().any(|&on| on));
+    }
+
+    #[test]
+    fn the_keys_are_laid_out_as_in_the_window() {
+        let scancodes = pad_scancodes();
+        assert_eq!(scancodes[0x1], Some(Scancode::Num1));
+        assert_eq!(scancodes[0x0], Some(Scancode::X));
+        assert_eq!(scancodes[0xF], Some(Scancode::V));
+    }
+}
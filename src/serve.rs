@@ -0,0 +1,283 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tungstenite::{Message, WebSocket};
+
+use crate::rom_source::MAX_ROM_SIZE;
+use crate::run_state::Event;
+use crate::{Emulator, RunMode, SCR_H};
+
+const CLIENT_HTML: &str = include_str!("../system/serve.html");
+
+// first byte of every binary frame, followed by the packed display
+const FLAG_BUZZER: u8 = 0x01;
+const FLAG_HIRES: u8 = 0x02; // both pages follow, 64 rows instead of 32
+
+// what the browser client sends, as JSON text messages
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Key { key: u8, down: bool },
+    Pause,
+    Resume,
+    Reset,
+    Rom { data: Vec<u8> },
+}
+
+// [flags, the display packed like `packed_display`: 256 bytes, or 512 for hires]
+fn encode_frame(emulator: &Emulator) -> Vec<u8> {
+    let display = emulator.packed_display();
+    let mut flags = if emulator.sound_timer > 0 { FLAG_BUZZER } else { 0 };
+    if emulator.screen_height() > SCR_H {
+        flags |= FLAG_HIRES;
+    }
+    let mut frame = Vec::with_capacity(1 + display.len());
+    frame.push(flags);
+    frame.extend_from_slice(&display);
+    frame
+}
+
+impl Emulator {
+    // a key goes into `down`, which reaches the ROM through key_repeat once a frame
+    fn handle(&mut self, message: ClientMessage, down: &mut [bool; 0x10]) {
+        match message {
+            ClientMessage::Key { key, down: is_down } => {
+                if let Some(held) = down.get_mut(key as usize) {
+                    *held = is_down;
+                }
+            }
+            ClientMessage::Pause => {
+                self.state = self.state.clone().on(Event::SetMode(RunMode::Step));
+            }
+            ClientMessage::Resume => {
+                self.state = self.state.clone().on(Event::SetMode(RunMode::Play));
+            }
+            ClientMessage::Reset => self.hard_reset(),
+            ClientMessage::Rom { data } => {
+                if data.len() > MAX_ROM_SIZE {
+                    log::warn!("Ignoring uploaded ROM: {} bytes is too big", data.len());
+                    return;
+                }
+                self.reset();
+                self.drop_save_ram();
+                self.load_rom_bytes(&data).expect("it's no bigger than MAX_ROM_SIZE");
+            }
+        }
+    }
+}
+
+// a plain HTTP request gets the client page, a WebSocket upgrade becomes a client. it
+// waits on the client, so it runs on a thread of its own
+fn accept(stream: TcpStream) -> io::Result<Option<WebSocket<TcpStream>>> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut head = [0u8; 2048];
+    let len = stream.peek(&mut head)?;
+    let request = String::from_utf8_lossy(&head[..len]).to_ascii_lowercase();
+    if request.contains("upgrade: websocket") {
+        let websocket = tungstenite::accept(stream)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        websocket.get_ref().set_nonblocking(true)?;
+        return Ok(Some(websocket));
+    }
+
+    let mut stream = stream;
+    let _ = stream.read(&mut head)?;
+    let response = if request.starts_with("get / ") {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            CLIENT_HTML.len(),
+            CLIENT_HTML
+        )
+    } else {
+        String::from("HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+    };
+    stream.write_all(response.as_bytes())?;
+    Ok(None)
+}
+
+// serves until the last browser to connect leaves or Ctrl-C, then shuts the emulator down
+// the way the other frontends do
+pub fn run(mut emulator: Emulator, cycle_speed: f32, bind: IpAddr, port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind((bind, port))?;
+    println!("Serving on http://{}/", listener.local_addr()?);
+    let stop = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(signal_hook::consts::SIGINT, Arc::clone(&stop))?;
+    let result = serve(&mut emulator, listener, cycle_speed, &stop);
+    emulator.shutdown();
+    result
+}
+
+fn serve(
+    emulator: &mut Emulator,
+    listener: TcpListener,
+    cycle_speed: f32,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let frame_time = Duration::from_secs_f32(1.0 / 60.0);
+    let cycles_per_frame = emulator
+        .ipf
+        .unwrap_or_else(|| Emulator::cycles_per_frame(cycle_speed));
+    let mut driver = emulator.driver.in_frames();
+    let mut clients: Vec<WebSocket<TcpStream>> = Vec::new();
+    let (joins, joined_clients) = mpsc::channel();
+    let mut last_frame: Vec<u8> = Vec::new();
+    let mut down = [false; 0x10];
+    let mut served = false; // someone has connected, so the last of them leaving stops it
+    while !stop.load(Ordering::Relaxed) {
+        let frame_start = Instant::now();
+
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let joins = joins.clone();
+                    thread::spawn(move || match accept(stream) {
+                        Ok(Some(client)) => {
+                            let _ = joins.send(client);
+                        }
+                        Ok(None) => {}
+                        Err(e) => log::warn!("Connection failed: {}", e),
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        let mut joined = false;
+        for client in joined_clients.try_iter() {
+            clients.push(client);
+            joined = true;
+            served = true;
+        }
+        if served && clients.is_empty() {
+            println!("The last client left, stopping");
+            return Ok(());
+        }
+
+        // drain every client's messages, dropping the ones that went away
+        clients.retain_mut(|client| loop {
+            match client.read() {
+                Ok(Message::Text(text)) => match serde_json::from_str(&text) {
+                    Ok(message) => emulator.handle(message, &mut down),
+                    Err(e) => log::warn!("Bad client message {}: {}", text, e),
+                },
+                Ok(Message::Close(_)) => return false,
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => return true,
+                Err(_) => return false,
+            }
+        });
+
+        let stepping = emulator.state.mode() == RunMode::Step;
+        emulator.keys = emulator.key_repeat.shape(down, frame_time.as_secs_f32(), stepping);
+        let due = driver.advance_to(frame_start, &emulator.state, u32::MAX);
+        for _ in 0..due.frames {
+            // a --pause-at going off leaves it in Step already
+            if emulator.step_frame(cycles_per_frame).key_wait_expired {
+                // stuck on a key wait past --keywait-timeout
+                emulator.state = emulator.state.clone().on(Event::SetMode(RunMode::Step));
+            }
+            if let Some(halt) = emulator.halt {
+                emulator.state = emulator.state.clone().on(Event::Halt(halt));
+            }
+            if !emulator.state.runs_cycles() {
+                break;
+            }
+        }
+        for _ in 0..due.timer_ticks {
+            emulator.tick_timers();
+        }
+
+        // only send when something changed, or someone new needs a first frame
+        let frame = encode_frame(emulator);
+        if frame != last_frame || joined {
+            clients.retain_mut(|client| match client.send(Message::binary(frame.clone())) {
+                Ok(()) => true,
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => true,
+                Err(_) => false,
+            });
+            last_frame = frame;
+        }
+
+        emulator.update_save_ram(frame_time.as_secs_f32());
+        let elapsed = frame_start.elapsed();
+        if elapsed < frame_time {
+            thread::sleep(frame_time - elapsed);
+        }
+    }
+    for client in &mut clients {
+        let _ = client.close(None);
+        let _ = client.flush();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SCR_W;
+
+    #[test]
+    fn a_frame_is_the_flags_then_the_display() {
+        let mut emulator = Emulator::new();
+        emulator.display[0][0] = true;
+        emulator.display[SCR_W - 1][SCR_H - 1] = true;
+        emulator.sound_timer = 3;
+        let frame = encode_frame(&emulator);
+        assert_eq!(frame.len(), 1 + 256);
+        assert_eq!((frame[0], frame[1], frame[256]), (FLAG_BUZZER, 0x80, 0x01));
+    }
+
+    #[test]
+    fn a_hires_frame_has_both_pages() {
+        let mut emulator = Emulator::new();
+        emulator.hires = true;
+        emulator.display[SCR_W - 1][2 * SCR_H - 1] = true;
+        let frame = encode_frame(&emulator);
+        assert_eq!(frame.len(), 1 + 512);
+        assert_eq!((frame[0], frame[512]), (FLAG_HIRES, 0x01));
+    }
+
+    #[test]
+    fn a_key_waits_for_key_repeat() {
+        let mut emulator = Emulator::new();
+        let mut down = [false; 0x10];
+        emulator.handle(ClientMessage::Key { key: 5, down: true }, &mut down);
+        emulator.handle(ClientMessage::Key { key: 0x10, down: true }, &mut down);
+        assert!(down[5]);
+        assert_eq!(down.iter().filter(|&&key| key).count(), 1);
+        assert_eq!(emulator.keys, [false; 0x10]);
+    }
+
+    #[test]
+    fn serves_a_client_on_loopback_until_it_leaves() {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+        // an Emulator isn't Send, so it's made on the server's thread
+        let server = thread::spawn(move || {
+            let mut emulator = Emulator::new();
+            emulator.display[0][0] = true;
+            serve(&mut emulator, listener, 600.0, &AtomicBool::new(false))
+        });
+
+        let mut page = TcpStream::connect(addr).unwrap();
+        page.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = String::new();
+        page.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(CLIENT_HTML));
+
+        let (mut client, _) = tungstenite::connect(format!("ws://{}/", addr)).unwrap();
+        let frame = client.read().unwrap().into_data();
+        assert_eq!((frame.len(), frame[0], frame[1]), (1 + 256, 0, 0x80));
+        client.close(None).unwrap();
+        while client.read().is_ok() {}
+        server.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+#[cfg(feature = "frontend")]
+use olc_pge as olc;
+
+use crate::status::NOTICE_TIME;
+use crate::Emulator;
+
+// the tallest sprite DXYN can draw
+#[cfg(feature = "frontend")]
+const MAX_HEIGHT: u8 = 15;
+// a sprite pixel in the viewer
+#[cfg(feature = "frontend")]
+const CELL: i32 = 6;
+
+// what the sprite viewer does to the bytes at I before showing them, to catch sprites
+// that were stored the wrong way round
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Transform {
+    None,
+    FlipH,
+    FlipV,
+    Rotate, // a quarter turn clockwise, of the top 8x8
+}
+impl Transform {
+    pub fn apply(&self, rows: &[u8]) -> Vec<u8> {
+        match self {
+            Transform::None => rows.to_vec(),
+            Transform::FlipH => rows.iter().map(|row| flip_h(*row)).collect(),
+            Transform::FlipV => rows.iter().rev().copied().collect(),
+            Transform::Rotate => rotate(rows),
+        }
+    }
+    #[cfg(feature = "frontend")]
+    fn name(&self) -> &'static str {
+        match self {
+            Transform::None => "AS STORED",
+            Transform::FlipH => "FLIPPED LEFT-RIGHT",
+            Transform::FlipV => "FLIPPED UPSIDE DOWN",
+            Transform::Rotate => "ROTATED (8X8)",
+        }
+    }
+}
+
+// the row mirrored left to right
+pub fn flip_h(row: u8) -> u8 {
+    row.reverse_bits()
+}
+
+// the top 8 rows, short ones padded with blank rows, turned clockwise: the left column
+// becomes the top row
+pub fn rotate(rows: &[u8]) -> Vec<u8> {
+    let row = |y: usize| rows.get(y).copied().unwrap_or(0);
+    (0..8)
+        .map(|y| {
+            // the new row y is the old column y, read from the bottom up
+            (0..8).fold(0, |rotated, x| {
+                let bit = (row(7 - x) >> (7 - y)) & 1;
+                rotated | bit << (7 - x)
+            })
+        })
+        .collect()
+}
+
+// ready to paste into a ROM's source
+pub fn as_bytes(rows: &[u8]) -> String {
+    let bytes: Vec<String> = rows.iter().map(|row| format!("0x{:02X}", row)).collect();
+    bytes.join(", ")
+}
+
+// the viewer's settings, kept while it's open
+#[derive(PartialEq, Debug, Clone)]
+pub struct SpriteView {
+    pub height: u8,
+    pub transform: Transform,
+}
+impl SpriteView {
+    pub fn new() -> SpriteView {
+        SpriteView {
+            height: 8,
+            transform: Transform::None,
+        }
+    }
+    // up and down change the height, H, V and R pick a transform (again to go back)
+    #[cfg(feature = "frontend")]
+    pub fn handle_key(&mut self, key: olc::Key) {
+        let transform = match key {
+            olc::Key::Up => {
+                self.height = (self.height - 1).max(1);
+                return;
+            }
+            olc::Key::Down => {
+                self.height = (self.height + 1).min(MAX_HEIGHT);
+                return;
+            }
+            olc::Key::H => Transform::FlipH,
+            olc::Key::V => Transform::FlipV,
+            olc::Key::R => Transform::Rotate,
+            _ => return,
+        };
+        self.transform = if self.transform == transform { Transform::None } else { transform };
+    }
+}
+
+// `rows` as a block of CELL-sized pixels with its bytes down the side
+#[cfg(feature = "frontend")]
+fn draw_sprite(pge: &mut olc::PixelGameEngine, (x, y): (i32, i32), rows: &[u8], title: &str) {
+    pge.draw_string(x, y, &title.to_string(), olc::WHITE);
+    let top = y + 10;
+    pge.fill_rect(x, top, 8 * CELL as u32, rows.len() as u32 * CELL as u32, olc::VERY_DARK_GREY);
+    for (row_y, row) in rows.iter().enumerate() {
+        let py = top + row_y as i32 * CELL;
+        for column in 0..8 {
+            if row & (0x80 >> column) != 0 {
+                pge.fill_rect(x + column * CELL, py, CELL as u32, CELL as u32, olc::WHITE);
+            }
+        }
+        pge.draw_string(x + 8 * CELL + 4, py, &format!("{:02X}", row), olc::GREY);
+    }
+}
+
+impl Emulator {
+    fn sprite_rows(&self, height: u8) -> Vec<u8> {
+        (0..height as u16)
+            .map(|row| self.ram.get(self.stack_pointer.wrapping_add(row) & 0xFFF))
+            .collect()
+    }
+    // the C key in the viewer: the transformed bytes on stdout, to copy from
+    pub fn print_sprite(&mut self, view: &SpriteView) {
+        let rows = view.transform.apply(&self.sprite_rows(view.height));
+        println!("{}", as_bytes(&rows));
+        self.notify(format!("{} bytes printed", rows.len()), NOTICE_TIME);
+    }
+}
+
+// the bytes at I as they're stored, and next to them with the transform
+#[cfg(feature = "frontend")]
+pub fn render(emulator: &Emulator, pge: &mut olc::PixelGameEngine, view: &SpriteView) {
+    let rows = emulator.sprite_rows(view.height);
+    let title = format!("AT I={:03X}", emulator.stack_pointer & 0xFFF);
+    draw_sprite(pge, (8, 8), &rows, &title);
+    let x = 8 + 8 * CELL + 32;
+    draw_sprite(pge, (x, 8), &view.transform.apply(&rows), "PREVIEW:");
+    let color = if view.transform == Transform::None { olc::GREY } else { olc::YELLOW };
+    pge.draw_string(x + 9 * 8, 8, &view.transform.name().to_string(), color);
+    let y = 18 + MAX_HEIGHT as i32 * CELL + 6;
+    let hint = format!("h/v/r: flip or rotate, up/down: rows ({})", view.height);
+    pge.draw_string(8, y, &hint, olc::GREY);
+    let hint = String::from("c: print bytes, esc: close");
+    pge.draw_string(8, y + 10, &hint, olc::GREY);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // bit by bit, the way it'd be done by hand
+    fn mirrored(row: u8) -> u8 {
+        (0..8).fold(0, |out, bit| out | ((row >> bit) & 1) << (7 - bit))
+    }
+
+    #[test]
+    fn flipping_every_byte_mirrors_it() {
+        for row in 0..=0xFF {
+            assert_eq!(flip_h(row), mirrored(row), "{:08b}", row);
+            assert_eq!(flip_h(flip_h(row)), row, "{:08b}", row);
+        }
+    }
+
+    #[test]
+    fn transforms() {
+        let arrow = [0x80, 0xC0, 0xE0];
+        assert_eq!(Transform::None.apply(&arrow), arrow);
+        assert_eq!(Transform::FlipH.apply(&arrow), [0x01, 0x03, 0x07]);
+        assert_eq!(Transform::FlipV.apply(&arrow), [0xE0, 0xC0, 0x80]);
+        // padded to 8 rows, then the left column read bottom up is the top row
+        let rotated = [0x07, 0x06, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00];
+        assert_eq!(Transform::Rotate.apply(&arrow), rotated);
+        let square = [0xF0; 8];
+        let turned: Vec<u8> = (0..4).fold(square.to_vec(), |rows, _| rotate(&rows));
+        assert_eq!(turned, square); // four quarter turns go all the way round
+    }
+
+    #[test]
+    fn the_bytes_are_ready_to_paste() {
+        assert_eq!(as_bytes(&[0xF0, 0x09]), "0xF0, 0x09");
+    }
+}
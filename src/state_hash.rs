@@ -0,0 +1,131 @@
+use std::fmt;
+
+use crate::Emulator;
+
+// the parts of the machine state_hash covers, in the order it hashes them
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Component {
+    Registers,
+    I,
+    ProgramCounter,
+    Timers,
+    CallStack,
+    Ram,
+    Display,
+}
+impl fmt::Display for Component {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Component::Registers => "registers",
+            Component::I => "I",
+            Component::ProgramCounter => "PC",
+            Component::Timers => "timers",
+            Component::CallStack => "call stack",
+            Component::Ram => "RAM",
+            Component::Display => "display",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// FNV-1a, which comes out the same on every platform
+pub struct Fnv(pub u64);
+impl Fnv {
+    pub fn new() -> Fnv {
+        Fnv(0xcbf29ce484222325)
+    }
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+impl Emulator {
+    // everything the program can see, hashed the same on every platform and version so
+    // hashes can be kept and compared later. it's for telling states apart quickly, not
+    // cryptographic: two states with the same hash could still differ, state_eq is certain
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write(self.registers.as_slice());
+        hash.write(&self.stack_pointer.to_le_bytes());
+        hash.write(&self.program_counter.to_le_bytes());
+        hash.write(&[self.timer, self.sound_timer]);
+        hash.write(&[self.call_stack.len() as u8]);
+        for addr in self.call_stack.as_slice() {
+            hash.write(&addr.to_le_bytes());
+        }
+        hash.write(self.ram.as_slice());
+        hash.write(&self.packed_display());
+        hash.0
+    }
+    // the first part of the state that isn't the same as `other`'s
+    pub fn state_eq(&self, other: &Emulator) -> Result<(), Component> {
+        let differs = [
+            (self.registers.as_slice() != other.registers.as_slice(), Component::Registers),
+            (self.stack_pointer != other.stack_pointer, Component::I),
+            (self.program_counter != other.program_counter, Component::ProgramCounter),
+            (
+                (self.timer, self.sound_timer) != (other.timer, other.sound_timer),
+                Component::Timers,
+            ),
+            (self.call_stack.as_slice() != other.call_stack.as_slice(), Component::CallStack),
+            (self.ram.as_slice() != other.ram.as_slice(), Component::Ram),
+            (self.display != other.display, Component::Display),
+        ];
+        match differs.iter().find(|(differs, _)| *differs) {
+            Some((_, component)) => Err(*component),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // V1 = 5, I = the font's 0, draw it, ST = V1, CALL a RET
+    const ROM: [u8; 12] = [0x61, 0x05, 0xF0, 0x29, 0xD0, 0x05, 0xF1, 0x18, 0x22, 0x0A, 0x00, 0xEE];
+
+    fn ran() -> Emulator {
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&ROM).unwrap();
+        emulator.run_cycles(5);
+        emulator
+    }
+
+    #[test]
+    fn the_hash_is_the_same_every_run() {
+        assert_eq!(ran().state_hash(), ran().state_hash());
+        // and from one version to the next, which kept hashes lean on
+        assert_eq!(ran().state_hash(), 0x0BEF_25E1_130F_DC75);
+    }
+
+    #[test]
+    fn any_byte_of_ram_changes_the_hash() {
+        let mut emulator = ran();
+        let hash = emulator.state_hash();
+        for addr in 0..0x1000 {
+            let byte = emulator.ram.get(addr);
+            emulator.ram.set(addr, byte ^ 1);
+            assert_ne!(emulator.state_hash(), hash, "{:03X}", addr);
+            emulator.ram.set(addr, byte);
+        }
+        assert_eq!(emulator.state_hash(), hash);
+    }
+
+    #[test]
+    fn state_eq_names_the_first_part_that_differs() {
+        let (a, mut b) = (ran(), ran());
+        assert_eq!(a.state_eq(&b), Ok(()));
+        b.display[63][31] = true;
+        assert_eq!(a.state_eq(&b), Err(Component::Display));
+        b.ram.set(0xFFF, 1);
+        assert_eq!(a.state_eq(&b), Err(Component::Ram));
+        b.program_counter += 2;
+        assert_eq!(a.state_eq(&b), Err(Component::ProgramCounter));
+        b.registers.set(0xA, 1);
+        assert_eq!(a.state_eq(&b), Err(Component::Registers));
+    }
+}
@@ -0,0 +1,231 @@
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::components::RamPolicy;
+use crate::lints::QuirkUses;
+
+// counters collected over a run, printed when the emulator shuts down
+pub struct RunStats {
+    started: Option<Instant>,  // there's no clock in the browser build, so no wall time there
+    elapsed: Option<Duration>, // frozen once the run is over
+    pub instructions: u64,
+    pub frames: u64,
+    pub timer_ticks: u64,
+    pub sound_activations: u64,
+    pub peak_stack_depth: usize,
+    pub latency_total: f64, // seconds, over latency_samples key presses, with --input-lag
+    pub latency_samples: u64,
+    pub paced_frames: u64,  // window frames that ran cycles
+    pub capped_frames: u64, // the ones of those that hit the --cycle-budget cap
+    pub quirk_uses: QuirkUses,
+    pub memory: RamPolicy, // not a counter, but it changes what a run does
+}
+impl RunStats {
+    pub fn new() -> RunStats {
+        RunStats {
+            started: (!cfg!(target_arch = "wasm32")).then(Instant::now),
+            elapsed: None,
+            instructions: 0,
+            frames: 0,
+            timer_ticks: 0,
+            sound_activations: 0,
+            peak_stack_depth: 0,
+            latency_total: 0.0,
+            latency_samples: 0,
+            paced_frames: 0,
+            capped_frames: 0,
+            quirk_uses: QuirkUses::default(),
+            memory: RamPolicy::new(),
+        }
+    }
+    pub fn finish(&mut self) {
+        if self.elapsed.is_none() {
+            self.elapsed = Some(self.since_started());
+        }
+    }
+    pub fn wall_time(&self) -> Duration {
+        self.elapsed.unwrap_or_else(|| self.since_started())
+    }
+    fn since_started(&self) -> Duration {
+        self.started.map_or(Duration::ZERO, |started| started.elapsed())
+    }
+    pub fn instructions_per_second(&self) -> f64 {
+        let seconds = self.wall_time().as_secs_f64();
+        if seconds > 0.0 {
+            self.instructions as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+    // adds another run's counters into these (the wall time stays this run's own)
+    pub fn merge(&mut self, other: &RunStats) {
+        self.instructions += other.instructions;
+        self.frames += other.frames;
+        self.timer_ticks += other.timer_ticks;
+        self.sound_activations += other.sound_activations;
+        self.peak_stack_depth = self.peak_stack_depth.max(other.peak_stack_depth);
+        self.latency_total += other.latency_total;
+        self.latency_samples += other.latency_samples;
+        self.paced_frames += other.paced_frames;
+        self.capped_frames += other.capped_frames;
+        self.quirk_uses.merge(&other.quirk_uses);
+    }
+}
+
+// the counters as --json prints them
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+pub struct StatsReport {
+    wall_time: f64, // seconds
+    instructions: u64,
+    instructions_per_second: f64,
+    frames: u64,
+    timer_ticks: u64,
+    sound_activations: u64,
+    peak_call_depth: usize,
+    input_latency_ms: Option<f64>,
+    capped_percent: Option<f64>,
+    quirk_uses: QuirkUses,
+    memory: String,
+}
+
+impl RunStats {
+    fn average_latency_ms(&self) -> Option<f64> {
+        (self.latency_samples > 0)
+            .then(|| self.latency_total / self.latency_samples as f64 * 1000.0)
+    }
+    fn capped_percent(&self) -> Option<f64> {
+        (self.paced_frames > 0)
+            .then(|| self.capped_frames as f64 / self.paced_frames as f64 * 100.0)
+    }
+    pub fn report(&self) -> StatsReport {
+        StatsReport {
+            wall_time: self.wall_time().as_secs_f64(),
+            instructions: self.instructions,
+            instructions_per_second: self.instructions_per_second(),
+            frames: self.frames,
+            timer_ticks: self.timer_ticks,
+            sound_activations: self.sound_activations,
+            peak_call_depth: self.peak_stack_depth,
+            input_latency_ms: self.average_latency_ms(),
+            capped_percent: self.capped_percent(),
+            quirk_uses: self.quirk_uses,
+            memory: self.memory.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for RunStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Run statistics:")?;
+        writeln!(f, "  wall-clock time:     {:.2} s", self.wall_time().as_secs_f64())?;
+        writeln!(f, "  instructions:        {}", self.instructions)?;
+        writeln!(f, "  instructions/second: {:.1}", self.instructions_per_second())?;
+        writeln!(f, "  frames drawn:        {}", self.frames)?;
+        writeln!(f, "  timer ticks:         {}", self.timer_ticks)?;
+        writeln!(f, "  sound activations:   {}", self.sound_activations)?;
+        write!(f, "  peak call depth:     {}", self.peak_stack_depth)?;
+        if let Some(average) = self.average_latency_ms() {
+            let samples = self.latency_samples;
+            write!(f, "\n  input latency:       {:.1} ms over {} presses", average, samples)?;
+        }
+        if let Some(capped) = self.capped_percent() {
+            write!(f, "\n  frames at the cap:   {:.1}%", capped)?;
+        }
+        let uses = &self.quirk_uses;
+        write!(f, "\n  quirk-sensitive:     {} shifts, ", uses.shifts)?;
+        write!(f, "{} FX55/FX65, {} BNNN, ", uses.load_stores, uses.offset_jumps)?;
+        write!(f, "{} edge draws", uses.edge_draws)?;
+        write!(f, "\n  memory:              {}", self.memory)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Emulator;
+
+    #[test]
+    fn the_json_reads_back() {
+        let mut stats = RunStats::new();
+        stats.instructions = 1234;
+        stats.frames = 60;
+        stats.latency_samples = 2;
+        stats.latency_total = 0.05;
+        stats.finish();
+        let mut report = stats.report();
+        // the clock's, which needn't come back to the last bit through JSON
+        report.wall_time = 2.5;
+        report.instructions_per_second = 493.5;
+        let json = serde_json::to_string_pretty(&report).unwrap();
+        assert_eq!(serde_json::from_str::<StatsReport>(&json).unwrap(), report);
+    }
+
+    #[test]
+    fn a_run_counts_what_it_did() {
+        // a beep set twice, only the first starting it, then calls two deep and a jump to
+        // itself at 0x208
+        let rom = [
+            0x60, 0x05, 0xF0, 0x18, 0xF0, 0x18, 0x22, 0x0C, 0x12, 0x08, 0x00, 0x00, 0x22, 0x10,
+            0x00, 0xEE, 0x00, 0xEE,
+        ];
+        let mut emulator = Emulator::new();
+        emulator.load_rom_bytes(&rom).unwrap();
+        for _ in 0..2 {
+            emulator.step_frame(10);
+        }
+        let stats = &emulator.stats;
+        assert_eq!((stats.instructions, stats.frames, stats.timer_ticks), (20, 2, 2));
+        assert_eq!((stats.sound_activations, stats.peak_stack_depth), (1, 2));
+    }
+
+    #[test]
+    fn merging_adds_the_counters_and_keeps_the_deepest_stack() {
+        let mut total = RunStats::new();
+        for (instructions, depth) in [(100, 3), (50, 1)] {
+            let mut run = RunStats::new();
+            run.instructions = instructions;
+            run.frames = 10;
+            run.sound_activations = 1;
+            run.peak_stack_depth = depth;
+            total.merge(&run);
+        }
+        assert_eq!((total.instructions, total.frames, total.sound_activations), (150, 20, 2));
+        assert_eq!(total.peak_stack_depth, 3);
+    }
+
+    #[test]
+    fn the_summary_lists_every_counter() {
+        let mut stats = RunStats::new();
+        stats.elapsed = Some(Duration::from_millis(2500));
+        stats.instructions = 1500;
+        stats.frames = 150;
+        stats.timer_ticks = 149;
+        stats.sound_activations = 3;
+        stats.peak_stack_depth = 4;
+        stats.quirk_uses.shifts = 7;
+        let expected = "\
+            Run statistics:\n  \
+            wall-clock time:     2.50 s\n  \
+            instructions:        1500\n  \
+            instructions/second: 600.0\n  \
+            frames drawn:        150\n  \
+            timer ticks:         149\n  \
+            sound activations:   3\n  \
+            peak call depth:     4\n  \
+            quirk-sensitive:     7 shifts, 0 FX55/FX65, 0 BNNN, 0 edge draws\n  \
+            memory:              reads past 0xFFF wrap, writes wrap";
+        assert_eq!(stats.to_string(), expected);
+
+        // and the lines only some runs have
+        stats.latency_total = 0.1;
+        stats.latency_samples = 4;
+        stats.paced_frames = 100;
+        stats.capped_frames = 25;
+        let text = stats.to_string();
+        assert!(text.contains("\n  input latency:       25.0 ms over 4 presses\n"));
+        assert!(text.contains("\n  frames at the cap:   25.0%\n"));
+    }
+}
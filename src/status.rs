@@ -0,0 +1,103 @@
+#[cfg(feature = "frontend")]
+use olc_pge as olc;
+
+#[cfg(feature = "frontend")]
+use crate::window::DRAW_BIGGER_PIXELS;
+use crate::Emulator;
+#[cfg(feature = "frontend")]
+use crate::{RunMode, SCR_H, SCR_W};
+
+// the status line is the last text row of the window, under the step-mode panel
+#[cfg(feature = "frontend")]
+const STATUS_HEIGHT: i32 = 8;
+// how long a message stays up when there's no reason to pick anything else, in seconds
+pub const NOTICE_TIME: f32 = 3.0;
+
+struct Notice {
+    message: String,
+    error: bool,
+    time_left: f32, // seconds
+}
+
+// short messages for the status line. the newest one is shown, and when it runs out
+// whatever older message still has time left comes back
+pub struct StatusLine {
+    notices: Vec<Notice>,
+}
+impl StatusLine {
+    pub fn new() -> StatusLine {
+        StatusLine { notices: Vec::new() }
+    }
+    pub fn push(&mut self, message: String, duration: f32, error: bool) {
+        self.notices.push(Notice {
+            message,
+            error,
+            time_left: duration,
+        });
+    }
+    // counts every message down, dropping the ones that ran out
+    pub fn update(&mut self, delta: f32) {
+        for notice in self.notices.iter_mut() {
+            notice.time_left -= delta;
+        }
+        self.notices.retain(|notice| notice.time_left > 0.0);
+    }
+    // the message on screen and whether it's an error
+    pub fn current(&self) -> Option<(&str, bool)> {
+        self.notices.last().map(|notice| (notice.message.as_str(), notice.error))
+    }
+}
+
+impl Emulator {
+    // shows a message on the status line for `duration` seconds
+    pub fn notify(&mut self, message: impl Into<String>, duration: f32) {
+        self.status.push(message.into(), duration, false);
+    }
+    // the same, in the error color
+    pub fn notify_error(&mut self, message: impl Into<String>, duration: f32) {
+        self.status.push(message.into(), duration, true);
+    }
+    // what the status line says: the newest message, or else a pending FX0A,
+    // pulsing so it doesn't look like a hang
+    #[cfg(feature = "frontend")]
+    fn status_text(&self) -> Option<(String, olc::Pixel)> {
+        if let Some((message, error)) = self.status.current() {
+            let color = if error { olc::RED } else { olc::WHITE };
+            return Some((message.to_string(), color));
+        }
+        if !self.is_waiting_for_key() {
+            return None;
+        }
+        let pulse = (self.key_wait_time * 2.0).fract() < 0.5;
+        let color = if pulse { olc::YELLOW } else { olc::DARK_YELLOW };
+        Some((format!("waiting for key -> R{:1X}", self.key_block), color))
+    }
+    // redraws the status line while there's something on it, and once more to clear it
+    #[cfg(feature = "frontend")]
+    pub fn update_status(&mut self, pge: &mut olc::PixelGameEngine, delta: f32) {
+        self.status.update(delta);
+        if self.status_text().is_some() {
+            self.draw_status(pge);
+        } else if self.status_shown {
+            if self.state.mode() == RunMode::Play {
+                self.draw(pge); // the line sits over the bottom of the display, which needs putting back
+            } else {
+                self.draw_status(pge);
+            }
+        }
+    }
+    #[cfg(feature = "frontend")]
+    pub fn draw_status(&mut self, pge: &mut olc::PixelGameEngine) {
+        let text = self.status_text();
+        self.status_shown = text.is_some();
+        if text.is_none() && self.state.mode() == RunMode::Play {
+            return; // nothing to clear, the display was just drawn over it
+        }
+        let y = (SCR_H + SCR_H / 2) as i32 * DRAW_BIGGER_PIXELS - STATUS_HEIGHT;
+        let width = (SCR_W + SCR_W / 2) as i32 * DRAW_BIGGER_PIXELS;
+        pge.fill_rect(0, y, width as u32, STATUS_HEIGHT as u32, olc::BLACK);
+        if let Some((message, color)) = text {
+            pge.draw_string(0, y, &message, color);
+        }
+    }
+}
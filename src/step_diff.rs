@@ -0,0 +1,51 @@
+#[cfg(feature = "frontend")]
+use olc_pge as olc;
+
+use crate::hires::HIRES_H;
+#[cfg(feature = "frontend")]
+use crate::window::DRAW_BIGGER_PIXELS;
+use crate::{Emulator, SCR_W};
+#[cfg(feature = "frontend")]
+use crate::{RunMode, SCR_H};
+
+pub type Display = [[bool; HIRES_H]; SCR_W];
+
+// every pixel that's different in `after`, and whether it's lit now
+pub fn changed_pixels(before: &Display, after: &Display) -> Vec<(u8, u8, bool)> {
+    let mut changed = Vec::new();
+    for x in 0..SCR_W {
+        for y in 0..HIRES_H {
+            if before[x][y] != after[x][y] {
+                changed.push((x as u8, y as u8, after[x][y]));
+            }
+        }
+    }
+    changed
+}
+
+impl Emulator {
+    // the display to compare the coming step against. only while stepping with the overlay
+    // on, so running never copies it
+    pub fn step_diff_start(&self) -> Option<Display> {
+        (self.show_step_diff && self.state.steps()).then_some(self.display)
+    }
+    pub fn step_diff_end(&mut self, before: Option<Display>) {
+        match before {
+            Some(before) => self.step_changes = changed_pixels(&before, &self.display),
+            None => self.step_changes.clear(),
+        }
+    }
+    // over the display in step mode: pixels the last step lit in cyan, cleared in magenta
+    #[cfg(feature = "frontend")]
+    pub fn draw_step_diff(&self, pge: &mut olc::PixelGameEngine) {
+        if !self.show_step_diff || self.state.mode() != RunMode::Step {
+            return;
+        }
+        let tall = DRAW_BIGGER_PIXELS * SCR_H as i32 / self.screen_height() as i32;
+        for (x, y, lit) in &self.step_changes {
+            let color = if *lit { olc::CYAN } else { olc::MAGENTA };
+            let (x, y) = (*x as i32 * DRAW_BIGGER_PIXELS, *y as i32 * tall);
+            pge.fill_rect(x, y, DRAW_BIGGER_PIXELS as u32, tall as u32, color);
+        }
+    }
+}
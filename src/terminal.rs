@@ -0,0 +1,238 @@
+use std::io::{self, Stdout, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::{cursor, queue, terminal};
+use olc_pge::Key;
+
+use crate::headless::FrameEnd;
+use crate::run_state::{self, RunState};
+use crate::window::KEYS;
+use crate::{Emulator, RunMode, SCR_W};
+
+// terminals only report key presses, so a press counts as held for this many frames
+const HOLD_FRAMES: u32 = 8;
+// the display takes half its rows (two pixels per cell), plus the status and debug lines
+const DEBUG_LINES: usize = 4;
+
+// restores the terminal even if the emulator panics
+struct RawTerminal {
+    stdout: Stdout,
+}
+impl RawTerminal {
+    fn enter() -> io::Result<RawTerminal> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        crossterm::execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(RawTerminal { stdout })
+    }
+}
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(self.stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+// the terminal character for a key in the KEYS layout
+pub fn key_char(key: Key) -> Option<char> {
+    let c = match key {
+        Key::K0 => '0',
+        Key::K1 => '1',
+        Key::K2 => '2',
+        Key::K3 => '3',
+        Key::K4 => '4',
+        Key::K5 => '5',
+        Key::K6 => '6',
+        Key::K7 => '7',
+        Key::K8 => '8',
+        Key::K9 => '9',
+        Key::A => 'a',
+        Key::B => 'b',
+        Key::C => 'c',
+        Key::D => 'd',
+        Key::E => 'e',
+        Key::F => 'f',
+        Key::G => 'g',
+        Key::H => 'h',
+        Key::I => 'i',
+        Key::J => 'j',
+        Key::K => 'k',
+        Key::L => 'l',
+        Key::M => 'm',
+        Key::N => 'n',
+        Key::O => 'o',
+        Key::P => 'p',
+        Key::Q => 'q',
+        Key::R => 'r',
+        Key::S => 's',
+        Key::T => 't',
+        Key::U => 'u',
+        Key::V => 'v',
+        Key::W => 'w',
+        Key::X => 'x',
+        Key::Y => 'y',
+        Key::Z => 'z',
+        _ => return None,
+    };
+    Some(c)
+}
+
+pub fn run(mut emulator: Emulator, cycle_speed: f32) -> io::Result<()> {
+    let mut term = RawTerminal::enter()?;
+    let result = run_loop(&mut term.stdout, &mut emulator, cycle_speed);
+    drop(term); // leave the alternate screen first so the exit report stays visible
+    emulator.shutdown();
+    result
+}
+
+fn run_loop(stdout: &mut Stdout, emulator: &mut Emulator, cycle_speed: f32) -> io::Result<()> {
+    let frame_time = Duration::from_secs_f32(1.0 / 60.0);
+    let cycles_per_frame = emulator
+        .ipf
+        .unwrap_or_else(|| Emulator::cycles_per_frame(cycle_speed));
+    let mut hold = [0u32; 0x10];
+    let mut summary = String::new();
+    let mut driver = emulator.driver.in_frames();
+    loop {
+        let frame_start = Instant::now();
+        let mut step = false;
+        let mut quit = false;
+
+        while event::poll(Duration::ZERO)? {
+            match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => match key.code {
+                    KeyCode::Esc => quit = true,
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        quit = true
+                    }
+                    KeyCode::Char(' ') => {
+                        let mode = emulator.state.mode().toggled();
+                        emulator.state = emulator.state.clone().on(run_state::Event::SetMode(mode));
+                    }
+                    KeyCode::Tab => step = true,
+                    KeyCode::Char(c) => {
+                        let c = c.to_ascii_lowercase();
+                        if let Some(i) = KEYS.iter().position(|key| key_char(*key) == Some(c)) {
+                            hold[i] = HOLD_FRAMES;
+                        }
+                    }
+                    _ => {}
+                },
+                Event::Resize(_, _) => queue!(stdout, terminal::Clear(terminal::ClearType::All))?,
+                _ => {}
+            }
+        }
+        if quit {
+            return Ok(());
+        }
+
+        // the terminal only says when a key goes down, so one counts as held for a while
+        let down = hold.map(|frames| frames > 0);
+        for frames in hold.iter_mut() {
+            *frames = frames.saturating_sub(1);
+        }
+        let stepping = emulator.state.mode() == RunMode::Step;
+        emulator.keys = emulator.key_repeat.shape(down, frame_time.as_secs_f32(), stepping);
+
+        let sound_was_on = emulator.sound_timer > 0;
+        let due = driver.advance_to(frame_start, &emulator.state, u32::MAX);
+        let mut end = FrameEnd::default();
+        for _ in 0..due.frames {
+            end = emulator.step_frame(cycles_per_frame);
+            if end.key_wait_expired || end.paused_at.is_some() || emulator.halt.is_some() {
+                break;
+            }
+        }
+        for _ in 0..due.timer_ticks {
+            emulator.tick_timers();
+        }
+        let stuck = end.key_wait_expired;
+        if let Some(trigger) = end.paused_at {
+            // step_frame has it in Step already
+            summary = format!("paused at {}", trigger);
+        } else if stuck {
+            // stuck on a key wait past --keywait-timeout
+            emulator.state = emulator.state.clone().on(run_state::Event::SetMode(RunMode::Step));
+        } else if step && emulator.state.steps() && emulator.resolve_key_block() {
+            emulator.step_timers(); // same timer pacing as stepping in the window
+            if !emulator.display_waiting {
+                summary = emulator.cycle().1;
+            }
+        }
+        // key waits are handled inside step_frame, only a halt changes the state here
+        if let Some(halt) = emulator.halt {
+            emulator.state = emulator.state.clone().on(run_state::Event::Halt(halt));
+        }
+        if emulator.sound_timer > 0 && !sound_was_on && emulator.state.beeps() {
+            queue!(stdout, Print('\x07'))?;
+        }
+
+        // nothing changes between frames while it runs
+        if due.present() || !emulator.state.runs_cycles() {
+            render(stdout, emulator, &summary)?;
+        }
+        emulator.update_save_ram(frame_time.as_secs_f32());
+        let elapsed = frame_start.elapsed();
+        if elapsed < frame_time {
+            thread::sleep(frame_time - elapsed);
+        }
+    }
+}
+
+fn render(stdout: &mut Stdout, emulator: &mut Emulator, summary: &str) -> io::Result<()> {
+    let (cols, rows) = terminal::size()?;
+    queue!(stdout, cursor::MoveTo(0, 0))?;
+    let display_rows = emulator.screen_height() / 2;
+    let min_rows = display_rows + DEBUG_LINES;
+    if (cols as usize) < SCR_W || (rows as usize) < min_rows {
+        queue!(
+            stdout,
+            terminal::Clear(terminal::ClearType::All),
+            Print(format!("terminal too small, need {}x{}", SCR_W, min_rows))
+        )?;
+        return stdout.flush();
+    }
+
+    for row in 0..display_rows {
+        let mut line = String::with_capacity(SCR_W * 3);
+        for x in 0..SCR_W {
+            line.push(match (emulator.display[x][row * 2], emulator.display[x][row * 2 + 1]) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        queue!(stdout, cursor::MoveTo(0, row as u16), Print(line))?;
+    }
+
+    let mode = match &emulator.state {
+        RunState::Halted(halt) => format!("HALTED: {}  (esc: quit)", halt),
+        _ if emulator.state.mode() == RunMode::Play => String::from("PLAY  (space: pause, esc: quit)"),
+        _ => String::from("STEP  (tab: step, space: run, esc: quit)"),
+    };
+    let sound = if emulator.sound_timer > 0 { "  ♪" } else { "" };
+    let mut lines = vec![format!("{}{}", mode, sound)];
+    if emulator.state.mode() == RunMode::Step {
+        for half in 0..2u8 {
+            let registers: Vec<String> = (0..8u8)
+                .map(|i| format!("R{:1X}:{:02X}", half * 8 + i, emulator.registers.get(half * 8 + i)))
+                .collect();
+            lines.push(registers.join(" "));
+        }
+        lines.push(summary.to_string());
+    }
+    for i in 0..DEBUG_LINES {
+        let line = lines.get(i).map(String::as_str).unwrap_or("");
+        queue!(
+            stdout,
+            cursor::MoveTo(0, (display_rows + i) as u16),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            Print(line)
+        )?;
+    }
+    stdout.flush()
+}
@@ -0,0 +1,267 @@
+// golden-image checks for tests, behind the testing feature. the display goes to a PNG a
+// pixel a pixel, white on black, and is held up against one that's checked in. a mismatch
+// leaves NAME.actual.png and NAME.diff.png next to NAME.png, so the failure can be looked at
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::Emulator;
+
+const ON: u8 = 0xFF;
+const OFF: u8 = 0x00;
+
+// in the diff: lit only in what ran, lit only in what was expected, and either pixel the two
+// agree on, dimmed
+const EXTRA: [u8; 3] = [0xFF, 0x20, 0x20];
+const MISSING: [u8; 3] = [0x20, 0x60, 0xFF];
+const SAME_ON: [u8; 3] = [0x60, 0x60, 0x60];
+const SAME_OFF: [u8; 3] = [0x00, 0x00, 0x00];
+
+// a display, row by row
+#[derive(PartialEq, Debug, Clone)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<bool>,
+}
+
+impl Frame {
+    pub fn new(width: usize, height: usize) -> Frame {
+        Frame {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        }
+    }
+    // the rows the ROM is using, 32 or 64 for hires
+    pub fn of(emulator: &Emulator) -> Frame {
+        let display = emulator.display();
+        let height = emulator.screen_height();
+        let mut frame = Frame::new(display.len(), height);
+        for y in 0..height {
+            for (x, column) in display.iter().enumerate() {
+                frame.set(x, y, column[y]);
+            }
+        }
+        frame
+    }
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * self.width + x]
+    }
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        self.pixels[y * self.width + x] = on;
+    }
+
+    // any grayscale or RGB PNG, a pixel lit if it's brighter than half
+    pub fn load(path: &Path) -> Result<Frame, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let (info, mut reader) = png::Decoder::new(file)
+            .read_info()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let channels = match (info.color_type, info.bit_depth) {
+            (png::ColorType::Grayscale, png::BitDepth::Eight) => 1,
+            (png::ColorType::RGB, png::BitDepth::Eight) => 3,
+            (png::ColorType::RGBA, png::BitDepth::Eight) => 4,
+            other => return Err(format!("{}: a {:?} PNG", path.display(), other)),
+        };
+        let (width, height) = (info.width as usize, info.height as usize);
+        let mut frame = Frame::new(width, height);
+        for (i, pixel) in data.chunks(channels).take(width * height).enumerate() {
+            frame.pixels[i] = pixel[0] > 0x80;
+        }
+        Ok(frame)
+    }
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let bytes: Vec<u8> = self.pixels.iter().map(|&on| if on { ON } else { OFF }).collect();
+        write_png(path, self.width, self.height, png::ColorType::Grayscale, &bytes)
+    }
+}
+
+fn write_png(
+    path: &Path,
+    width: usize,
+    height: usize,
+    color: png::ColorType,
+    data: &[u8],
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| format!("{}: {}", path.display(), e))?;
+    writer.write_image_data(data).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// how far apart two frames are
+#[derive(PartialEq, Debug)]
+pub enum Mismatch {
+    Size {
+        actual: (usize, usize),
+        expected: (usize, usize),
+    },
+    Pixels {
+        extra: usize,   // lit, but not in the expected frame
+        missing: usize, // lit in the expected frame, but not here
+    },
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Mismatch::Size { actual, expected } => {
+                let ((width, height), (want_width, want_height)) = (actual, expected);
+                write!(f, "the display is {}x{}, not {}x{}", width, height, want_width, want_height)
+            }
+            Mismatch::Pixels { extra, missing } => write!(
+                f,
+                "{} pixels differ, {} lit that shouldn't be and {} not that should",
+                extra + missing,
+                extra,
+                missing
+            ),
+        }
+    }
+}
+
+pub fn compare(actual: &Frame, expected: &Frame) -> Option<Mismatch> {
+    if (actual.width, actual.height) != (expected.width, expected.height) {
+        return Some(Mismatch::Size {
+            actual: (actual.width, actual.height),
+            expected: (expected.width, expected.height),
+        });
+    }
+    let pairs = actual.pixels.iter().zip(&expected.pixels);
+    let extra = pairs.clone().filter(|&(&got, &want)| got && !want).count();
+    let missing = pairs.filter(|&(&got, &want)| !got && want).count();
+    (extra + missing > 0).then_some(Mismatch::Pixels { extra, missing })
+}
+
+// the two frames over each other as RGB, the size of `actual`. where `expected` is smaller,
+// what's past it counts as off
+pub fn diff_image(actual: &Frame, expected: &Frame) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(actual.pixels.len() * 3);
+    for y in 0..actual.height {
+        for x in 0..actual.width {
+            let want = x < expected.width && y < expected.height && expected.get(x, y);
+            let colour = match (actual.get(x, y), want) {
+                (true, false) => EXTRA,
+                (false, true) => MISSING,
+                (true, true) => SAME_ON,
+                (false, false) => SAME_OFF,
+            };
+            rgb.extend_from_slice(&colour);
+        }
+    }
+    rgb
+}
+
+// NAME.png => NAME.actual.png
+fn beside(path: &Path, what: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}.png", stem, what))
+}
+
+// panics unless the display is the PNG at `path`. a new image is made by running this
+// without one, checking the NAME.actual.png it writes and renaming it
+pub fn assert_display_matches(emulator: &Emulator, path: &Path) {
+    let actual = Frame::of(emulator);
+    let actual_path = beside(path, "actual");
+    let diff_path = beside(path, "diff");
+    let expected = match Frame::load(path) {
+        Ok(expected) => expected,
+        Err(e) => {
+            actual.save(&actual_path).unwrap();
+            panic!("no golden image ({}), the display is in {}", e, actual_path.display());
+        }
+    };
+    let Some(mismatch) = compare(&actual, &expected) else {
+        // a stale one from an earlier failure would only confuse
+        let _ = std::fs::remove_file(&actual_path);
+        let _ = std::fs::remove_file(&diff_path);
+        return;
+    };
+    actual.save(&actual_path).unwrap();
+    let diff = diff_image(&actual, &expected);
+    write_png(&diff_path, actual.width, actual.height, png::ColorType::RGB, &diff).unwrap();
+    panic!(
+        "the display isn't {}: {}. see {} and {} (red is extra, blue missing)",
+        path.display(),
+        mismatch,
+        actual_path.display(),
+        diff_path.display()
+    );
+}
+
+// assert_display_matches!(emulator, "tests/golden/ibm.png"), the path from the crate root
+#[macro_export]
+macro_rules! assert_display_matches {
+    ($emulator:expr, $path:expr) => {
+        $crate::testing::assert_display_matches(
+            &$emulator,
+            &std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join($path),
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(rows: &[&str]) -> Frame {
+        let mut frame = Frame::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, pixel) in row.chars().enumerate() {
+                frame.set(x, y, pixel == '#');
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn the_same_frame_matches() {
+        let a = frame(&["#..#", ".##."]);
+        assert_eq!(compare(&a, &a.clone()), None);
+    }
+
+    #[test]
+    fn counts_extra_and_missing_pixels() {
+        let actual = frame(&["##..", "...."]);
+        let expected = frame(&["#...", "...#"]);
+        let mismatch = compare(&actual, &expected).unwrap();
+        assert_eq!(mismatch, Mismatch::Pixels { extra: 1, missing: 1 });
+        let summary = "2 pixels differ, 1 lit that shouldn't be and 1 not that should";
+        assert_eq!(mismatch.to_string(), summary);
+    }
+
+    #[test]
+    fn a_different_size_is_a_mismatch() {
+        let mismatch = compare(&Frame::new(64, 64), &Frame::new(64, 32)).unwrap();
+        assert_eq!(mismatch.to_string(), "the display is 64x64, not 64x32");
+    }
+
+    #[test]
+    fn the_diff_colours_each_pixel() {
+        let actual = frame(&["##.."]);
+        let expected = frame(&["#.#."]);
+        let pixels: Vec<[u8; 3]> =
+            diff_image(&actual, &expected).chunks(3).map(|c| [c[0], c[1], c[2]]).collect();
+        assert_eq!(pixels, [SAME_ON, EXTRA, MISSING, SAME_OFF]);
+    }
+
+    #[test]
+    fn a_frame_survives_a_png() {
+        let path = std::env::temp_dir().join(format!("chip8rust-{}.png", std::process::id()));
+        let a = frame(&["#..#", ".##.", "#..."]);
+        a.save(&path).unwrap();
+        assert_eq!(Frame::load(&path), Ok(a));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn beside_the_expected_image() {
+        let path = Path::new("tests/golden/ibm.png");
+        assert_eq!(beside(path, "diff"), Path::new("tests/golden/ibm.diff.png"));
+    }
+}
@@ -0,0 +1,47 @@
+#[cfg(feature = "scripting")]
+use rhai::INT;
+
+use crate::logging;
+use crate::Emulator;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Timer {
+    Delay,
+    Sound,
+}
+impl Timer {
+    fn name(&self) -> &'static str {
+        match self {
+            Timer::Delay => "delay",
+            Timer::Sound => "sound",
+        }
+    }
+}
+
+// every FX07, FX15 and FX18, for ROM authors working out their own timing: traced with
+// --trace-timers, and passed to a script's on_timer_read / on_timer_write(timer, value, pc)
+impl Emulator {
+    pub fn timer_read(&mut self, timer: Timer, value: u8, pc: u16) {
+        self.timer_access("read", timer, value, pc);
+    }
+    pub fn timer_written(&mut self, timer: Timer, value: u8, pc: u16) {
+        self.timer_access("write", timer, value, pc);
+    }
+    fn timer_access(&mut self, access: &str, timer: Timer, value: u8, pc: u16) {
+        if self.trace_timers {
+            log::trace!(
+                target: logging::TIMER_TRACE,
+                "{:#05X} {} {} timer {}",
+                pc,
+                access,
+                timer.name(),
+                value
+            );
+        }
+        #[cfg(feature = "scripting")]
+        if self.script.is_some() {
+            let args = (timer.name().to_string(), value as INT, pc as INT);
+            self.run_script_hook_with(&format!("on_timer_{}", access), 3, args);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::components::RamPolicy;
+use crate::quirks::QUIRKS;
+
+// which build this is, for --version, the info subcommand and the help screen: what to
+// ask for first in a bug report
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub version: String,
+    pub commit: String, // "unknown" without git at build time
+    pub features: Vec<String>,
+    pub quirks: Vec<String>,
+    pub memory: String, // the RAM policy, the default unless the command line picks one
+}
+impl VersionInfo {
+    pub fn current() -> VersionInfo {
+        let features = [
+            ("audio-rodio", cfg!(feature = "audio-rodio")),
+            ("test-roms", cfg!(feature = "test-roms")),
+            ("scripting", cfg!(feature = "scripting")),
+            ("serve", cfg!(feature = "serve")),
+            ("file-dialog", cfg!(feature = "file-dialog")),
+            ("net", cfg!(feature = "net")),
+        ];
+        VersionInfo {
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            commit: String::from(env!("CHIP8RUST_COMMIT")),
+            features: features
+                .iter()
+                .filter(|(_, on)| *on)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            quirks: QUIRKS.iter().map(|quirk| quirk.name().to_string()).collect(),
+            memory: RamPolicy::new().to_string(),
+        }
+    }
+    // the first line of --version, short enough for the help screen
+    pub fn summary(&self) -> String {
+        format!("chip8rust {} ({})", self.version, self.commit)
+    }
+    // a few at a time, for lines that have to stay short
+    pub fn feature_lines(&self, per_line: usize) -> Vec<String> {
+        if self.features.is_empty() {
+            return vec![String::from("none")];
+        }
+        self.features.chunks(per_line).map(|features| features.join(", ")).collect()
+    }
+}
+impl fmt::Display for VersionInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.summary())?;
+        writeln!(f, "features: {}", self.feature_lines(usize::MAX).join(""))?;
+        writeln!(f, "quirks: {}", self.quirks.join(", "))?;
+        write!(f, "memory: {}", self.memory)
+    }
+}
+
+pub fn print(json: bool, memory: RamPolicy) {
+    let mut info = VersionInfo::current();
+    info.memory = memory.to_string();
+    if json {
+        crate::output::print_json(&info);
+    } else {
+        println!("{}", info);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_json_reads_back() {
+        let info = VersionInfo::current();
+        let json = serde_json::to_string_pretty(&info).unwrap();
+        assert_eq!(serde_json::from_str::<VersionInfo>(&json).unwrap(), info);
+    }
+
+    #[test]
+    fn it_says_which_build_this_is() {
+        let info = VersionInfo::current();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        let hex = info.commit.chars().all(|c| c.is_ascii_hexdigit());
+        assert!(info.commit == "unknown" || hex && !info.commit.is_empty(), "{}", info.commit);
+        let rodio = info.features.contains(&String::from("audio-rodio"));
+        assert_eq!(rodio, cfg!(feature = "audio-rodio"));
+        assert_eq!(info.quirks.len(), QUIRKS.len());
+        assert_eq!(info.quirks[0], "wrap sprites left-right");
+    }
+
+    #[test]
+    fn the_text_version() {
+        let info = VersionInfo {
+            version: String::from("1.2.3"),
+            commit: String::from("abc1234"),
+            features: vec![String::from("net"), String::from("serve"), String::from("scripting")],
+            quirks: vec![String::from("BXNN jumps by VX")],
+            memory: String::from("reads past 0xFFF wrap, writes wrap"),
+        };
+        let text = "chip8rust 1.2.3 (abc1234)\n\
+                    features: net, serve, scripting\n\
+                    quirks: BXNN jumps by VX\n\
+                    memory: reads past 0xFFF wrap, writes wrap";
+        assert_eq!(info.to_string(), text);
+        assert_eq!(info.feature_lines(2), ["net, serve", "scripting"]);
+        let none = VersionInfo { features: vec![], ..info };
+        assert_eq!(none.feature_lines(2), ["none"]);
+    }
+}
@@ -0,0 +1,81 @@
+use std::ops::RangeInclusive;
+
+use crate::Emulator;
+
+// what the COSMAC VIP interpreter kept at the top of its 4KB: its stack and variables, then
+// the display, a bit per pixel, which the video chip read straight out of RAM
+pub const WORK_AREA: RangeInclusive<u16> = 0xEA0..=0xEFF;
+pub const DISPLAY_AREA: RangeInclusive<u16> = 0xF00..=0xFFF;
+
+// the reserved areas a ROM of `size` bytes at 0x200 runs into, and what the VIP kept there
+pub fn overlapped(size: usize) -> Vec<(RangeInclusive<u16>, &'static str)> {
+    let end = 0x200 + size; // one past the last byte
+    [(WORK_AREA, "stack and work area"), (DISPLAY_AREA, "display")]
+        .into_iter()
+        .filter(|(area, _)| end > *area.start() as usize)
+        .collect()
+}
+
+impl Emulator {
+    // a ROM that runs into the VIP's reserved areas may have been written for an interpreter
+    // that didn't reserve them, or may expect them reserved. either way it's worth knowing
+    pub fn check_vip_areas(&self) {
+        for (area, used_for) in overlapped(self.rom_size) {
+            log::warn!(
+                "The ROM runs into {:#05X}-{:#05X}, the VIP interpreter's {}",
+                area.start(),
+                area.end(),
+                used_for
+            );
+        }
+        let end = 0x200 + self.rom_size as u16;
+        if self.reserve_vip_areas && end > *DISPLAY_AREA.start() {
+            log::warn!("--reserve-vip-areas will write the display over the end of the ROM");
+        }
+    }
+    // --reserve-vip-areas: the display copied into 0xF00-0xFFF the way the VIP laid it out,
+    // for ROMs that read it back. RAM writes there don't show on the display
+    pub fn mirror_display(&mut self) {
+        if !self.reserve_vip_areas {
+            return;
+        }
+        // a hires display is twice what the area holds, its top page goes in
+        for (i, byte) in self.packed_display().iter().take(DISPLAY_AREA.len()).enumerate() {
+            self.ram.set(DISPLAY_AREA.start() + i as u16, *byte);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_rom_that_runs_into_the_reserved_areas() {
+        let last_free = (*WORK_AREA.start() - 0x200) as usize; // the ROM ends at 0xE9F
+        assert!(overlapped(last_free).is_empty());
+        let work = overlapped(last_free + 1);
+        assert_eq!(work, [(WORK_AREA, "stack and work area")]);
+        let both = overlapped((*DISPLAY_AREA.start() - 0x200) as usize + 1);
+        let areas: Vec<RangeInclusive<u16>> = both.into_iter().map(|(area, _)| area).collect();
+        assert_eq!(areas, [WORK_AREA, DISPLAY_AREA]);
+        assert_eq!(overlapped(crate::rom_source::MAX_ROM_SIZE).len(), 2);
+    }
+
+    // a 0 at (8, 1): its rows land a byte in on each of five display rows of 8 bytes
+    #[test]
+    fn the_display_reads_back_from_0xf00() {
+        let mut emulator = Emulator::new();
+        emulator.reserve_vip_areas = true;
+        emulator.load_rom_bytes(&[0x60, 0x08, 0x61, 0x01, 0xD0, 0x15]).unwrap();
+        emulator.run_cycles(3);
+        let rows: Vec<u8> = (1..6).map(|y| emulator.ram.get(0xF00 + y * 8 + 1)).collect();
+        assert_eq!(rows, [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+        assert_eq!(emulator.ram.get(0xF00), 0x00);
+
+        emulator.reserve_vip_areas = false; // and without it, nothing is
+        emulator.display[0][0] = true;
+        emulator.mirror_display();
+        assert_eq!(emulator.ram.get(0xF00), 0x00);
+    }
+}
@@ -0,0 +1,71 @@
+use wasm_bindgen::prelude::*;
+
+use crate::demo::DEMO_ROM;
+use crate::{Emulator, RunMode, SCR_W};
+
+// the browser build: a machine the page in web/ runs a frame at a time. the ROM comes in as
+// bytes, and there's no sound here either, the page beeps for as long as sound_on says
+#[wasm_bindgen]
+pub struct Chip8 {
+    emulator: Emulator,
+    cycles_per_frame: u32,
+    framebuffer: Vec<u8>, // a byte a pixel, 0 or 1, row by row, for the page to read in place
+}
+
+#[wasm_bindgen]
+impl Chip8 {
+    #[wasm_bindgen(constructor)]
+    pub fn new(rom: &[u8]) -> Result<Chip8, JsError> {
+        let mut emulator = Emulator::new();
+        emulator.state = RunMode::Play.into();
+        emulator.load_rom_bytes(rom).map_err(|e| JsError::new(&e.to_string()))?;
+        let mut chip8 = Chip8 {
+            cycles_per_frame: Emulator::cycles_per_frame(1.0 / emulator.time_per_cycle),
+            emulator,
+            framebuffer: Vec::new(),
+        };
+        chip8.update_framebuffer();
+        Ok(chip8)
+    }
+    // one 60Hz frame with `keys` held, bit N for key N. returns whether the display changed
+    pub fn step_frame(&mut self, keys: u32) -> bool {
+        for (key, held) in self.emulator.keys.iter_mut().enumerate() {
+            *held = keys & (1 << key) != 0;
+        }
+        let before = self.emulator.frame_hash();
+        self.emulator.step_frame(self.cycles_per_frame);
+        let changed = self.emulator.frame_hash() != before;
+        if changed {
+            self.update_framebuffer();
+        }
+        changed
+    }
+    pub fn framebuffer_ptr(&self) -> *const u8 {
+        self.framebuffer.as_ptr()
+    }
+    pub fn framebuffer_len(&self) -> usize {
+        self.framebuffer.len()
+    }
+    pub fn width(&self) -> usize {
+        SCR_W
+    }
+    // 32, or 64 for a hires ROM
+    pub fn height(&self) -> usize {
+        self.emulator.screen_height()
+    }
+    pub fn sound_on(&self) -> bool {
+        self.emulator.sound_active()
+    }
+    fn update_framebuffer(&mut self) {
+        let display = self.emulator.display();
+        self.framebuffer = (0..self.emulator.screen_height())
+            .flat_map(|y| display.iter().map(move |column| u8::from(column[y])))
+            .collect();
+    }
+}
+
+// the built-in demo, for a page with no ROM of its own
+#[wasm_bindgen]
+pub fn demo_rom() -> Vec<u8> {
+    DEMO_ROM.to_vec()
+}
@@ -0,0 +1,450 @@
+use std::time::Instant;
+
+use olc_pge as olc;
+use olc_pge::Key;
+
+use crate::headless::RunStopReason;
+#[cfg(feature = "file-dialog")]
+use crate::hot_patch::HotPatch;
+#[cfg(feature = "file-dialog")]
+use crate::rom_source;
+use crate::run_state::{Event, Modal, RunState};
+use crate::sprites::SpriteView;
+use crate::status::NOTICE_TIME;
+use crate::{Emulator, RunMode, SCR_H, SCR_W};
+
+pub const DRAW_BIGGER_PIXELS: i32 = 4;
+
+pub const KEYS: [Key; 0x10] = [
+    Key::X,
+    Key::K1,
+    Key::K2,
+    Key::K3,
+    Key::Q,
+    Key::W,
+    Key::E,
+    Key::A,
+    Key::S,
+    Key::D,
+    Key::Z,
+    Key::C,
+    Key::K4,
+    Key::R,
+    Key::F,
+    Key::V,
+];
+
+// the olc window, until it's closed
+pub fn run(emulator: Emulator) {
+    olc::PixelGameEngine::construct(
+        emulator,
+        (SCR_W + (SCR_W / 2)) * DRAW_BIGGER_PIXELS as usize,
+        (SCR_H + (SCR_H / 2)) * DRAW_BIGGER_PIXELS as usize,
+        2,
+        2,
+    )
+    .start();
+}
+
+impl olc::PGEApplication for Emulator {
+    const APP_NAME: &'static str = "Chip8 Emulator - Rust";
+
+    fn on_user_create(&mut self, _pge: &mut olc::PixelGameEngine) -> bool {
+        true
+    }
+
+    fn on_user_update(&mut self, pge: &mut olc::PixelGameEngine, delta: f32) -> bool {
+        // a stalled frame (dragging the window, say) comes back as one huge delta
+        let delta = self.driver.clamp(delta);
+        self.update_save_ram(delta);
+        if let Some(input_lag) = &mut self.input_lag {
+            input_lag.frame(delta);
+        }
+        self.update_pacing(pge, delta);
+        // a modal takes every key and freezes the machine, beeper and all
+        if self.update_modal(pge) {
+            self.update_beeper(delta);
+            self.update_status(pge, delta);
+            return true;
+        }
+        if pge.get_key(self.hotkeys.quit).pressed {
+            return false; // quits, the engine then calls on_user_destroy
+        }
+        if self.hotkeys.help.iter().any(|key| pge.get_key(*key).pressed) {
+            self.open_modal(pge, Modal::Help);
+            self.update_beeper(delta);
+            return true;
+        }
+        if pge.get_key(self.hotkeys.memory_map).pressed {
+            self.open_modal(pge, Modal::MemoryMap(self.program_counter & 0xFFF));
+            self.update_beeper(delta);
+            return true;
+        }
+        if pge.get_key(self.hotkeys.calls).pressed {
+            self.open_modal(pge, Modal::Calls);
+            self.update_beeper(delta);
+            return true;
+        }
+        if pge.get_key(self.hotkeys.draws).pressed {
+            self.open_modal(pge, Modal::Draws);
+            self.update_beeper(delta);
+            return true;
+        }
+        if pge.get_key(self.hotkeys.quirks).pressed {
+            self.open_modal(pge, Modal::Quirks(0));
+            self.update_beeper(delta);
+            return true;
+        }
+        if pge.get_key(self.hotkeys.sprites).pressed {
+            self.open_modal(pge, Modal::Sprites(SpriteView::new()));
+            self.update_beeper(delta);
+            return true;
+        }
+        if pge.get_key(self.hotkeys.coverage).pressed {
+            println!("{}", self.coverage());
+            self.notify("Coverage printed", NOTICE_TIME);
+        }
+        if pge.get_key(self.hotkeys.palette).pressed {
+            self.palette = (self.palette + 1) % self.palettes.len();
+            self.redraw(pge);
+            let name = self.palettes[self.palette].name.clone();
+            self.notify(format!("Palette: {}", name), NOTICE_TIME);
+        }
+        if pge.get_key(self.hotkeys.reset).pressed {
+            if pge.get_key(Key::Shift).held {
+                self.soft_reset();
+                self.notify("Soft reset", NOTICE_TIME);
+            } else {
+                self.hard_reset();
+                self.notify("Reset", NOTICE_TIME);
+            }
+            self.redraw(pge);
+        }
+        if pge.get_key(self.hotkeys.waveform).pressed {
+            let waveform = self.beep_wave.get().next();
+            self.beep_wave.set(waveform);
+            self.notify(format!("Beep waveform: {:?}", waveform), NOTICE_TIME);
+        }
+        #[cfg(feature = "file-dialog")]
+        if pge.get_key(Key::Control).held && pge.get_key(self.hotkeys.open_rom).pressed {
+            // the dialog blocks this thread, so emulation is paused while it's open
+            self.beeper.set_level(0.0);
+            if let Some(rom_file) = pick_rom() {
+                let loaded = rom_source::read(&rom_file.to_string_lossy()).and_then(|rom| {
+                    self.reset();
+                    self.drop_save_ram();
+                    self.load_rom_bytes(&rom)
+                });
+                match loaded {
+                    Ok(()) => {
+                        let name = rom_file.file_name().unwrap_or_default().to_string_lossy();
+                        self.rom_name = name.to_string();
+                        if self.hot_patch.is_some() {
+                            self.hot_patch = Some(HotPatch::new(&rom_file.to_string_lossy()));
+                        }
+                        self.notify(format!("Loaded {}", name), NOTICE_TIME);
+                        self.draw(pge);
+                    }
+                    Err(e) => {
+                        log::error!("Failed to load ROM {}: {}", rom_file.display(), e);
+                        self.notify_error(format!("Failed to load ROM: {}", e), NOTICE_TIME);
+                    }
+                }
+            }
+            return true;
+        }
+
+        if self.update_hot_patch(delta) {
+            self.redraw(pge);
+        }
+        if self.take_display_change() {
+            self.redraw(pge); // a ROM with the other size of display came in
+        }
+
+        let focus = if pge.is_focused() {
+            Event::FocusGained
+        } else {
+            Event::FocusLost
+        };
+        self.transition(pge, focus);
+
+        let before = self.keys;
+        let down = KEYS.map(|key| pge.get_key(key).held);
+        self.keys = self.key_repeat.shape(down, delta, self.state.mode() == RunMode::Step);
+        if let Some(input_lag) = &mut self.input_lag {
+            input_lag.key_edges(&before, &self.keys);
+        }
+        // a wait that was left for the help or a pause is picked up again here
+        let key = if self.resolve_key_block() {
+            Event::KeyPressed
+        } else {
+            Event::KeyWait
+        };
+        self.transition(pge, key);
+        let waited = if let RunState::WaitingForKey(_) = self.state { delta } else { 0.0 };
+        if self.track_key_wait(waited) {
+            self.transition(pge, Event::SetMode(RunMode::Step));
+            self.notify(format!("No key after {:.1}s, stepping", self.key_wait_time), NOTICE_TIME);
+        }
+        if self.state == RunState::WaitingForKey(RunMode::Step) {
+            self.redraw(pge); // keeps the wait time in the panel ticking
+        }
+
+        self.update_beeper(delta);
+        if self.state.mode() == RunMode::Play && (self.sound_timer > 0) != self.speaker_shown {
+            self.draw(pge); // puts up or takes down the speaker icon (stepping redraws anyway)
+        }
+        self.update_status(pge, delta);
+        if self.collision_flash > 0.0 {
+            self.collision_flash -= delta;
+            if self.collision_flash <= 0.0 {
+                self.redraw(pge); // takes the highlights back off
+            }
+        }
+
+        // as many --ipf frames or cycles as the time is owed, up to what fits in --cycle-budget
+        let per = self.ipf.unwrap_or(1);
+        let due = self.driver.advance(delta, &self.state, self.pacing.cap(per));
+        if due.frames > 0 {
+            let (started, before) = (Instant::now(), self.stats.instructions);
+            for _ in 0..due.frames {
+                self.run_frame(pge, per);
+                if !self.state.runs_cycles() {
+                    break;
+                }
+            }
+            let ran = self.stats.instructions - before;
+            self.pacing_ran(ran, started.elapsed(), due.capped);
+        }
+        for _ in 0..due.timer_ticks {
+            self.tick_timers();
+        }
+        if due.cycles > 0 {
+            // run continuously at the cycle speed
+            let (started, before) = (Instant::now(), self.stats.instructions);
+            let mut redraw = false;
+            for _ in 0..due.cycles {
+                redraw |= self.run_cycle(pge);
+                if !self.state.runs_cycles() || self.display_waiting {
+                    break;
+                }
+            }
+            let ran = self.stats.instructions - before;
+            if ran < due.cycles as u64 {
+                self.driver.stopped();
+            }
+            // with no speed asked for, every frame runs to the cap
+            self.pacing_ran(ran, started.elapsed(), due.capped && !self.unlimited());
+            if redraw {
+                self.draw(pge);
+            }
+            self.draw_debug(pge, self.summary.clone());
+        } else if self.state.steps() && pge.get_key(self.hotkeys.step).pressed {
+            // run step-by-step, with the timers going at the same pace as the cycles. a display
+            // wait holds the cycle back until they tick, as it would running
+            self.step_timers();
+            if !self.display_waiting {
+                self.run_cycle(pge);
+            }
+            self.draw(pge);
+            self.draw_debug(pge, self.summary.clone());
+        }
+        if pge.get_key(self.hotkeys.step_diff).pressed {
+            self.show_step_diff = !self.show_step_diff;
+            self.redraw(pge);
+            let shown = if self.show_step_diff { "on" } else { "off" };
+            self.notify(format!("Step diff {}", shown), NOTICE_TIME);
+        }
+        if self.state.steps() && pge.get_key(self.hotkeys.step_frame).pressed {
+            self.step_one_frame(pge);
+        }
+        if self.state.steps() && pge.get_key(self.hotkeys.step_count).pressed {
+            self.open_modal(pge, Modal::StepCount(String::new()));
+        }
+        if self.state.switches_mode() && pge.get_key(self.hotkeys.play_step).pressed {
+            self.transition(pge, Event::SetMode(self.state.mode().toggled()));
+        }
+        self.draw_input_lag(pge);
+        self.draw_overload(pge);
+        true
+    }
+
+    fn on_user_destroy(&mut self) -> bool {
+        self.shutdown();
+        true
+    }
+}
+
+#[cfg(feature = "file-dialog")]
+pub fn pick_rom() -> Option<std::path::PathBuf> {
+    rfd::FileDialog::new()
+        .set_title("Open ROM")
+        .add_filter("CHIP-8 ROM", &["ch8", "c8", "bin"])
+        .pick_file()
+}
+
+impl Emulator {
+    // one cycle from a frontend, feeding whatever it did to the state. returns whether to redraw
+    pub fn run_cycle(&mut self, pge: &mut olc::PixelGameEngine) -> bool {
+        self.last_panel = self.panel_values();
+        let before = self.step_diff_start();
+        let (redraw, summary) = self.cycle();
+        self.step_diff_end(before);
+        self.summary = summary;
+        if let Some(halt) = self.halt {
+            self.transition(pge, Event::Halt(halt));
+        } else if self.is_waiting_for_key() {
+            self.transition(pge, Event::KeyWait);
+        } else if let Some(trigger) = self.paused_at.take() {
+            self.transition(pge, Event::SetMode(RunMode::Step));
+            self.notify(format!("Paused at {}", trigger), NOTICE_TIME);
+        }
+        redraw
+    }
+    // one frame of --ipf. a key wait or a halt drops whatever's left of the frame's budget
+    pub fn run_frame(&mut self, pge: &mut olc::PixelGameEngine, ipf: u32) {
+        let mut redraw = false;
+        for _ in 0..ipf {
+            redraw |= self.run_cycle(pge);
+            if !self.state.runs_cycles() || self.display_waiting {
+                break;
+            }
+        }
+        self.tick_timers();
+        if redraw {
+            self.draw(pge);
+        }
+    }
+    pub fn transition(&mut self, pge: &mut olc::PixelGameEngine, event: Event) {
+        let next = self.state.clone().on(event.clone());
+        if next == self.state {
+            return;
+        }
+        log::debug!("{:?} => {:?} on {:?}", self.state, next, event);
+        match &next {
+            RunState::Halted(halt) => self.notify_error(format!("Halted: {}", halt), NOTICE_TIME),
+            RunState::Paused(_) => self.notify("Paused", NOTICE_TIME),
+            _ => {}
+        }
+        let modal = matches!(next, RunState::Modal(..));
+        let relayout =
+            next.mode() != self.state.mode() || modal != matches!(self.state, RunState::Modal(..));
+        self.state = next;
+        if relayout {
+            self.redraw(pge);
+        }
+    }
+    // the whole window, the step panel included
+    pub fn redraw(&mut self, pge: &mut olc::PixelGameEngine) {
+        self.draw(pge);
+        if !matches!(self.state, RunState::Modal(..)) {
+            self.draw_debug(pge, self.summary.clone());
+        }
+    }
+    // G in step mode: n cycles with one redraw at the end, which says if it stopped early
+    pub fn step_count(&mut self, pge: &mut olc::PixelGameEngine, n: u32) {
+        self.last_panel = self.panel_values();
+        let before = self.step_diff_start();
+        let stop = self.run_cycles(n);
+        self.step_diff_end(before);
+        log::debug!("Stepped {} cycles: {:?}", n, stop);
+        match stop {
+            RunStopReason::Finished => self.notify(format!("Stepped {}", n), NOTICE_TIME),
+            RunStopReason::Halted(halt, ran) => {
+                self.transition(pge, Event::Halt(halt));
+                self.notify_error(format!("Halted after {} of {}: {}", ran, n, halt), NOTICE_TIME);
+            }
+            RunStopReason::KeyWait(ran) => {
+                self.transition(pge, Event::KeyWait);
+                self.notify(format!("Waiting for a key after {} of {}", ran, n), NOTICE_TIME);
+            }
+        }
+        self.draw(pge);
+        self.draw_debug(pge, self.summary.clone());
+    }
+    // the frame key while stepping: one whole 60Hz frame the way play would run it, a frame's
+    // worth of cycles then a timer tick, stopping early on a halt or a key wait
+    pub fn step_one_frame(&mut self, pge: &mut olc::PixelGameEngine) {
+        let cycles = match self.ipf {
+            Some(ipf) => ipf,
+            None if self.unlimited() => self.pacing.cap(1), // a frame's worth of the budget
+            None => Emulator::cycles_per_frame(1.0 / self.time_per_cycle),
+        };
+        let last_panel = self.panel_values();
+        let before = self.step_diff_start();
+        for _ in 0..cycles {
+            self.run_cycle(pge);
+            if !self.state.steps() {
+                break;
+            }
+        }
+        self.tick_timers();
+        // the panel and the diff show what the whole frame changed, not its last cycle
+        self.last_panel = last_panel;
+        self.step_diff_end(before);
+        self.draw(pge);
+        self.draw_debug(pge, self.summary.clone());
+    }
+    pub fn draw(&mut self, pge: &mut olc::PixelGameEngine) {
+        let bigger_draw = if self.state.mode() == RunMode::Step {
+            DRAW_BIGGER_PIXELS
+        } else {
+            DRAW_BIGGER_PIXELS + (DRAW_BIGGER_PIXELS / 2)
+        };
+        let color_on: olc::Pixel = self.palettes[self.palette].on().into();
+        let color_off: olc::Pixel = self.palettes[self.palette].off().into();
+        self.stats.frames += 1;
+        pge.clear(olc::BLACK);
+        // hires fits twice the rows in by halving their height
+        let (width, height) = self.display_size();
+        let tall = bigger_draw * SCR_H as i32 / height as i32;
+        for x in 0..width {
+            for y in 0..height {
+                for xs in 0..bigger_draw {
+                    for ys in 0..tall {
+                        let pixel = if self.display[x][y] {
+                            color_on
+                        } else {
+                            color_off
+                        };
+                        pge.draw(
+                            x as i32 * bigger_draw + xs,
+                            y as i32 * tall + ys,
+                            pixel,
+                        );
+                    }
+                }
+            }
+        }
+        if self.collision_flash > 0.0 {
+            for (x, y) in self.last_collisions() {
+                let (x, y) = (*x as i32 * bigger_draw, *y as i32 * tall);
+                pge.fill_rect(x, y, bigger_draw as u32, tall as u32, olc::RED);
+            }
+        }
+        self.draw_step_diff(pge);
+        self.speaker_shown = self.sound_timer > 0;
+        if self.speaker_shown {
+            draw_speaker(pge);
+        }
+        self.draw_status(pge);
+        if let RunState::Modal(modal, _) = self.state.clone() {
+            self.draw_debug(pge, self.summary.clone()); // under the modal too
+            modal.render(self, pge);
+        }
+    }
+}
+
+
+// a little speaker in the top right corner, so beeps can be seen with the sound off
+fn draw_speaker(pge: &mut olc::PixelGameEngine) {
+    let x = (SCR_W + SCR_W / 2) as i32 * DRAW_BIGGER_PIXELS - 16;
+    let y = 2;
+    pge.fill_rect(x - 1, y - 1, 15, 11, olc::BLACK);
+    pge.fill_rect(x, y + 3, 3, 3, olc::YELLOW);
+    pge.fill_triangle(x + 3, y + 3, x + 6, y, x + 6, y + 8, olc::YELLOW);
+    pge.fill_triangle(x + 3, y + 3, x + 3, y + 5, x + 6, y + 8, olc::YELLOW);
+    pge.draw_line(x + 8, y + 3, x + 8, y + 5, olc::YELLOW);
+    pge.draw_line(x + 10, y + 1, x + 10, y + 7, olc::YELLOW);
+    pge.draw_line(x + 12, y, x + 12, y + 8, olc::YELLOW);
+}
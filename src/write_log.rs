@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+
+use crate::status::NOTICE_TIME;
+use crate::Emulator;
+
+// a store by the program, on the cycle it ran (stats.instructions)
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct WriteEvent {
+    pub cycle: u64,
+    pub pc: u16, // the instruction that wrote it
+    pub addr: u16,
+    pub old: u8,
+    pub new: u8,
+}
+
+// the last `size` writes the program made, to find out what wrote a byte after the fact
+// instead of setting a watch and running it again. off unless asked for, so playing
+// doesn't pay for it
+pub struct WriteLog {
+    events: VecDeque<WriteEvent>,
+    size: usize,
+    pub on: bool,
+}
+impl WriteLog {
+    pub fn new(size: usize, on: bool) -> WriteLog {
+        WriteLog {
+            events: VecDeque::new(),
+            size,
+            on,
+        }
+    }
+    fn push(&mut self, event: WriteEvent) {
+        if self.events.len() == self.size {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+impl Emulator {
+    // from store, once the write has gone to `addr`
+    pub fn log_write(&mut self, addr: u16, old: u8, new: u8) {
+        if !self.write_log.on || self.write_log.size == 0 {
+            return;
+        }
+        let event = WriteEvent {
+            cycle: self.stats.instructions,
+            pc: self.program_counter.wrapping_sub(2) & 0xFFF,
+            addr,
+            old,
+            new,
+        };
+        self.write_log.push(event);
+    }
+    // the logged writes to `addr`, newest first
+    pub fn writes_to(&self, addr: u16) -> impl Iterator<Item = &WriteEvent> {
+        self.write_log.events.iter().rev().filter(move |event| event.addr == addr)
+    }
+    // W in the memory map. what's been logged stays, for looking back at
+    pub fn toggle_write_log(&mut self) {
+        self.write_log.on = !self.write_log.on;
+        let on = if self.write_log.on { "on" } else { "off" };
+        self.notify(format!("Write log {}", on), NOTICE_TIME);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(cycle: u64) -> WriteEvent {
+        WriteEvent { cycle, pc: 0x200, addr: 0x300, old: 0, new: cycle as u8 }
+    }
+
+    #[test]
+    fn the_ring_keeps_the_last_writes_in_order() {
+        let mut log = WriteLog::new(3, true);
+        for cycle in 1..=5 {
+            log.push(event(cycle));
+        }
+        let cycles: Vec<u64> = log.events.iter().map(|event| event.cycle).collect();
+        assert_eq!(cycles, [3, 4, 5]);
+    }
+
+    // V0 = 0x7B as BCD at 0x300 (1 2 3), then V0-V1 over the first two
+    const STORES: [u8; 10] = [0x60, 0x7B, 0x61, 0x09, 0xA3, 0x00, 0xF0, 0x33, 0xF1, 0x55];
+
+    #[test]
+    fn who_last_wrote_a_byte() {
+        let mut emulator = Emulator::new();
+        emulator.write_log = WriteLog::new(16, true);
+        emulator.load_rom_bytes(&STORES).unwrap();
+        emulator.run_cycles(5);
+        let writes: Vec<WriteEvent> = emulator.writes_to(0x301).copied().collect();
+        assert_eq!(
+            writes,
+            [
+                WriteEvent { cycle: 5, pc: 0x208, addr: 0x301, old: 2, new: 0x09 },
+                WriteEvent { cycle: 4, pc: 0x206, addr: 0x301, old: 0, new: 2 },
+            ]
+        );
+        assert_eq!(emulator.writes_to(0x302).count(), 1);
+        assert_eq!(emulator.writes_to(0x303).count(), 0);
+    }
+
+    #[test]
+    fn nothing_is_logged_while_its_off() {
+        let mut emulator = Emulator::new();
+        emulator.write_log = WriteLog::new(16, false);
+        emulator.load_rom_bytes(&STORES).unwrap();
+        emulator.run_cycles(5);
+        assert_eq!(emulator.write_log.events.len(), 0);
+        assert_eq!(emulator.ram.get(0x301), 0x09);
+    }
+}
@@ -0,0 +1,134 @@
+// the binary's --json output, read back into the structs it was written from: the
+// subcommands' on stdout, and the startup errors' on stderr
+use std::process::{Command, Output};
+
+use chip8rust::disasm::ListingLine;
+use chip8rust::output::ErrorOutput;
+use chip8rust::version::VersionInfo;
+
+fn chip8rust(args: &[&str]) -> Output {
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8rust")).args(args).output().unwrap();
+    assert!(output.stdout.is_ascii());
+    output
+}
+
+// a startup error, which goes out as the last line of stderr
+fn error(args: &[&str]) -> String {
+    let output = chip8rust(args);
+    assert_eq!(output.status.code(), Some(1), "{:?}", args);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let line = stderr.lines().last().unwrap();
+    serde_json::from_str::<ErrorOutput>(line).unwrap().error
+}
+
+fn temp_path(name: &str) -> String {
+    let name = format!("chip8rust-cli-{}-{}", std::process::id(), name);
+    std::env::temp_dir().join(name).to_string_lossy().into_owned()
+}
+
+#[test]
+fn info() {
+    let output = chip8rust(&["info", "--json"]);
+    assert!(output.status.success());
+    let info: VersionInfo = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+}
+
+#[test]
+fn disasm() {
+    let rom = temp_path("disasm.ch8");
+    std::fs::write(&rom, [0x00, 0xE0, 0x12, 0x02]).unwrap();
+    let output = chip8rust(&["disasm", "--json", &rom]);
+    std::fs::remove_file(&rom).unwrap();
+    assert!(output.status.success());
+    let lines: Vec<ListingLine> = serde_json::from_slice(&output.stdout).unwrap();
+    let words: Vec<Option<u16>> = lines.iter().map(|line| line.word).collect();
+    assert_eq!(words, [Some(0x00E0), Some(0x1202)]);
+}
+
+#[test]
+fn a_missing_rom() {
+    let message = error(&["--json", "-r", "no-such-rom.ch8"]);
+    assert!(message.starts_with("Failed to load ROM no-such-rom.ch8: "), "{}", message);
+}
+
+#[test]
+fn a_missing_font() {
+    let message = error(&["--json", "--font", "no-such-font.txt", "--demo", "demo"]);
+    assert!(message.starts_with("Failed to load font no-such-font.txt: "), "{}", message);
+}
+
+#[test]
+fn an_unknown_demo() {
+    let message = error(&["--json", "--demo", "no-such-demo"]);
+    assert!(message.starts_with("Unknown demo \"no-such-demo\" (available: demo"), "{}", message);
+}
+
+#[test]
+fn persist_ram_without_a_file() {
+    let message = error(&["--json", "--demo", "demo", "--persist-ram", "0xE00..0xFFF"]);
+    assert_eq!(message, "--persist-ram needs a ROM file to keep the save next to");
+}
+
+#[test]
+fn hot_patch_without_a_file() {
+    let message = error(&["--json", "--demo", "demo", "--hot-patch"]);
+    assert_eq!(message, "--hot-patch needs a ROM file to watch");
+}
+
+#[test]
+fn an_unlimited_speed_off_the_window() {
+    let args = ["--json", "--demo", "demo", "--cycle-speed", "max", "--backend", "terminal"];
+    let message = error(&args);
+    assert_eq!(message, "--cycle-speed max only works in the window, on its own");
+}
+
+#[test]
+fn an_unknown_palette() {
+    let message = error(&["--json", "--demo", "demo", "--palette", "no-such-palette"]);
+    assert!(message.starts_with("Unknown palette \"no-such-palette\" (available: white"));
+}
+
+#[test]
+fn missing_symbols() {
+    let message = error(&["--json", "--demo", "demo", "--symbols", "no-such-file.sym"]);
+    assert!(message.starts_with("Failed to load symbols from no-such-file.sym: "), "{}", message);
+}
+
+// the font is built in, so the binary runs from anywhere, with no system/ next to it
+#[test]
+fn runs_away_from_the_repo() {
+    let dir = std::path::PathBuf::from(temp_path("elsewhere"));
+    std::fs::create_dir_all(dir.join("roms")).unwrap();
+    let rom = [0x60, 0x0A, 0xF0, 0x29, 0xD0, 0x05, 0x00, 0xFD]; // the A glyph, then EXIT
+    std::fs::write(dir.join("roms/font.ch8"), rom).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_chip8rust"))
+        .current_dir(&dir)
+        .args(["batch", "roms", "-o", "report.json", "--frames", "5"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report = std::fs::read_to_string(dir.join("report.json")).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let mut emulator = chip8rust::Emulator::new();
+    emulator.load_rom_bytes(&rom).unwrap();
+    emulator.run_cycles(10);
+    let report: serde_json::Value = serde_json::from_str(&report).unwrap();
+    let result = &report["results"][0];
+    assert_eq!(result["status"], "halted");
+    assert_eq!(result["display_hash"], format!("{:016x}", emulator.display_hash()));
+}
+
+#[test]
+fn version() {
+    let output = chip8rust(&["--version"]);
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    let first = format!("chip8rust {} (", env!("CARGO_PKG_VERSION"));
+    assert!(lines[0].starts_with(&first) && lines[0].ends_with(')'), "{}", lines[0]);
+    assert!(lines[1].starts_with("features: "));
+    assert!(lines[2].starts_with("quirks: wrap sprites left-right, "), "{}", lines[2]);
+    assert!(lines[3].starts_with("memory: "));
+}
@@ -0,0 +1,243 @@
+// the interpreter against tests/reference, on short random programs, with random quirks and
+// keys held. it takes a while, so it only runs with `cargo test -- --ignored`:
+//
+//   CHIP8_FUZZ_ITERATIONS=100000 cargo test --test differential -- --ignored
+//
+// a case that comes out differently is cut down to the fewest instructions that still do,
+// and printed with its seed. CHIP8_FUZZ_SEED=<that seed> CHIP8_FUZZ_ITERATIONS=1 runs it again
+mod reference;
+
+use std::env;
+
+use chip8rust::state_hash::Component;
+use chip8rust::Emulator;
+use reference::{Quirks, Reference};
+
+const MAX_LEN: usize = 24; // instructions in a program
+const CYCLES: usize = 64; // how long each one runs, loops and all
+
+// xorshift, so a seed means the same program on any machine
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+struct Case {
+    quirks: Quirks,
+    keys: u16,
+    program: Vec<u16>,
+}
+
+// an instruction the reference models. jumps and calls land on the program's own words, and
+// I goes anywhere, so stores can run over the program and the font
+fn instruction(rng: &mut Rng, len: usize) -> u16 {
+    let x = rng.below(16) as u16;
+    let y = rng.below(16) as u16;
+    let nn = rng.below(0x100) as u16;
+    let inside = 0x200 + 2 * rng.below(len as u64) as u16;
+    match rng.below(20) {
+        0 => 0x00E0,
+        1 => 0x00EE,
+        2 => 0x1000 | inside,
+        3 => 0x2000 | inside,
+        4 => 0x3000 | x << 8 | nn,
+        5 => 0x4000 | x << 8 | nn,
+        6 => 0x5000 | x << 8 | y << 4,
+        7 | 8 => 0x6000 | x << 8 | nn,
+        9 => 0x7000 | x << 8 | nn,
+        10 | 11 => {
+            let alu = [0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0xE];
+            0x8000 | x << 8 | y << 4 | alu[rng.below(alu.len() as u64) as usize]
+        }
+        12 => 0x9000 | x << 8 | y << 4,
+        13 => 0xA000 | rng.below(0x1000) as u16,
+        14 => 0xB000 | rng.below(0x1000) as u16,
+        15 | 16 => 0xD000 | x << 8 | y << 4 | rng.below(16) as u16,
+        17 => [0xE09E, 0xE0A1][rng.below(2) as usize] | x << 8,
+        _ => [0xF01E, 0xF029, 0xF033, 0xF055, 0xF065][rng.below(5) as usize] | x << 8,
+    }
+}
+
+fn case(seed: u64) -> Case {
+    let mut rng = Rng::new(seed);
+    let quirks = Quirks {
+        jump_x: rng.below(2) == 0,
+        clip_x: rng.below(2) == 0,
+        clip_y: rng.below(2) == 0,
+    };
+    let keys = rng.next() as u16;
+    let len = 1 + rng.below(MAX_LEN as u64) as usize;
+    let program = (0..len).map(|_| instruction(&mut rng, len)).collect();
+    Case {
+        quirks,
+        keys,
+        program,
+    }
+}
+
+enum Outcome {
+    Same,
+    Unmodelled, // the program got to something the reference doesn't do
+    Differs(String),
+}
+
+// where the two first disagree, if they do
+fn run(case: &Case) -> Outcome {
+    let rom: Vec<u8> = case.program.iter().flat_map(|word| word.to_be_bytes()).collect();
+    let mut emulator = Emulator::new();
+    emulator.jump_x = case.quirks.jump_x;
+    emulator.wrap_x = !case.quirks.clip_x;
+    emulator.wrap_y = !case.quirks.clip_y;
+    for (key, held) in emulator.keys.iter_mut().enumerate() {
+        *held = case.keys & (1 << key) != 0;
+    }
+    emulator.load_rom_bytes(&rom).unwrap();
+    let mut memory = [0; 0x1000];
+    for (addr, byte) in memory.iter_mut().enumerate() {
+        *byte = emulator.ram.get(addr as u16);
+    }
+    let mut reference = Reference::new(case.quirks, memory, emulator.program_counter, case.keys);
+    let mut expected = Emulator::new();
+
+    for cycle in 0..CYCLES {
+        let at = reference.pc;
+        if reference.step().is_err() {
+            return Outcome::Unmodelled;
+        }
+        emulator.run_cycles(1);
+        mirror(&mut expected, &reference);
+        if let Some(what) = difference(&emulator, &expected) {
+            let why = format!("after cycle {} (the word at {:03X}): {}", cycle, at, what);
+            return Outcome::Differs(why);
+        }
+    }
+    Outcome::Same
+}
+
+// the reference's state in a machine of its own, for state_eq to hold up against the real
+// one. it's the same machine every cycle, written over
+fn mirror(expected: &mut Emulator, reference: &Reference) {
+    for (reg, value) in reference.v.iter().enumerate() {
+        expected.registers.set(reg as u8, *value);
+    }
+    expected.stack_pointer = reference.i;
+    expected.program_counter = reference.pc;
+    expected.call_stack.clear();
+    for addr in &reference.stack {
+        expected.call_stack.push(*addr).unwrap();
+    }
+    for (addr, byte) in reference.memory.iter().enumerate() {
+        expected.ram.set(addr as u16, *byte);
+    }
+    for (y, row) in reference.display.iter().enumerate() {
+        for (x, pixel) in row.iter().enumerate() {
+            expected.display[x][y] = *pixel;
+        }
+    }
+}
+
+fn difference(emulator: &Emulator, expected: &Emulator) -> Option<String> {
+    if let Some(halt) = emulator.halt {
+        return Some(format!("the interpreter halted, {:?}", halt));
+    }
+    let component = emulator.state_eq(expected).err()?;
+    // which part of it
+    let first = |got: &[u8], want: &[u8]| got.iter().zip(want).position(|(a, b)| a != b);
+    let detail = match component {
+        Component::Registers => {
+            let (got, want) = (emulator.registers.as_slice(), expected.registers.as_slice());
+            let reg = first(got, want).unwrap();
+            format!("V{:X} is {:02X}, not {:02X}", reg, got[reg], want[reg])
+        }
+        Component::I => {
+            format!("{:03X}, not {:03X}", emulator.stack_pointer, expected.stack_pointer)
+        }
+        Component::ProgramCounter => {
+            format!("{:03X}, not {:03X}", emulator.program_counter, expected.program_counter)
+        }
+        Component::Timers => String::from("they were never set"),
+        Component::CallStack => format!(
+            "{:03X?}, not {:03X?}",
+            emulator.call_stack.as_slice(),
+            expected.call_stack.as_slice()
+        ),
+        Component::Ram => {
+            let (got, want) = (emulator.ram.as_slice(), expected.ram.as_slice());
+            let addr = first(got, want).unwrap();
+            format!("{:03X} is {:02X}, not {:02X}", addr, got[addr], want[addr])
+        }
+        Component::Display => {
+            let (got, want) = (emulator.display(), expected.display());
+            let pixels = (0..32).flat_map(|y| (0..64).map(move |x| (x, y)));
+            let (x, y) = pixels.into_iter().find(|&(x, y)| got[x][y] != want[x][y]).unwrap();
+            format!("the pixel at ({}, {}) is {}", x, y, got[x][y])
+        }
+    };
+    Some(format!("the {} differ from the reference: {}", component, detail))
+}
+
+// drops instructions one at a time while it still goes wrong. jumps point at the same
+// addresses afterwards, which is still a program that goes wrong, just a different one
+fn minimize(mut case: Case) -> (Case, String) {
+    let Outcome::Differs(mut why) = run(&case) else {
+        unreachable!("only a case that differs is minimized");
+    };
+    let mut i = 0;
+    while i < case.program.len() && case.program.len() > 1 {
+        let removed = case.program.remove(i);
+        match run(&case) {
+            Outcome::Differs(still) => why = still,
+            Outcome::Same | Outcome::Unmodelled => {
+                case.program.insert(i, removed);
+                i += 1;
+            }
+        }
+    }
+    (case, why)
+}
+
+fn env_or(name: &str, default: u64) -> u64 {
+    env::var(name).map_or(default, |value| {
+        value.parse().unwrap_or_else(|_| panic!("{} isn't a number: {}", name, value))
+    })
+}
+
+#[test]
+#[ignore]
+fn matches_the_reference() {
+    let iterations = env_or("CHIP8_FUZZ_ITERATIONS", 2_000);
+    let first_seed = env_or("CHIP8_FUZZ_SEED", 0);
+    let (mut same, mut unmodelled) = (0, 0);
+    for seed in first_seed..first_seed + iterations {
+        let case = case(seed);
+        match run(&case) {
+            Outcome::Same => same += 1,
+            Outcome::Unmodelled => unmodelled += 1,
+            Outcome::Differs(_) => {
+                let (case, why) = minimize(case);
+                let words: Vec<String> = case.program.iter().map(|w| format!("{:04X}", w)).collect();
+                panic!(
+                    "seed {}, {:?}, keys {:04X}: {}\n{}",
+                    seed,
+                    case.quirks,
+                    case.keys,
+                    words.join(" "),
+                    why
+                );
+            }
+        }
+    }
+    println!("{} programs the same on both, {} thrown away", same, unmodelled);
+}
@@ -0,0 +1,41 @@
+// the C API, called the way a C program would
+#![cfg(feature = "ffi")]
+
+use std::ptr;
+
+use chip8rust::ffi::*;
+
+// I = the font's 0, draw it at (0, 0), set ST = 5, then stay put
+const ROM: [u8; 12] = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x61, 0x05, 0xF1, 0x18, 0x12, 0x0A];
+
+#[test]
+fn runs_a_rom_through_the_c_api() {
+    unsafe {
+        let chip8 = chip8_new();
+        assert!(!chip8.is_null());
+        assert_eq!(chip8_set_quirks(chip8, CHIP8_QUIRK_JUMP_X), CHIP8_OK);
+        assert_eq!(chip8_load_rom(chip8, ROM.as_ptr(), ROM.len()), CHIP8_OK);
+        assert_eq!(chip8_sound_active(chip8), 0);
+        assert_eq!(chip8_step_frame(chip8, 0), CHIP8_OK);
+        assert_eq!(chip8_sound_active(chip8), 1);
+        let mut pixels = [0u8; CHIP8_FRAMEBUFFER_SIZE];
+        assert_eq!(chip8_framebuffer(chip8, pixels.as_mut_ptr()), 32);
+        assert_eq!(pixels[..8], [1, 1, 1, 1, 0, 0, 0, 0]); // the 0's top row, F0
+        assert_eq!(pixels[64..72], [1, 0, 0, 1, 0, 0, 0, 0]); // 90
+        chip8_free(chip8);
+    }
+}
+
+#[test]
+fn reports_errors_instead_of_panicking() {
+    unsafe {
+        let chip8 = chip8_new();
+        let too_big = vec![0u8; 0x1000];
+        assert_eq!(chip8_load_rom(chip8, too_big.as_ptr(), too_big.len()), CHIP8_ERR_LOAD);
+        assert_eq!(chip8_load_rom(chip8, ptr::null(), 0), CHIP8_ERR_NULL);
+        assert_eq!(chip8_framebuffer(chip8, ptr::null_mut()), CHIP8_ERR_NULL);
+        assert_eq!(chip8_step_frame(ptr::null_mut(), 0), CHIP8_ERR_NULL);
+        chip8_free(chip8);
+        chip8_free(ptr::null_mut());
+    }
+}
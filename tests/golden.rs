@@ -0,0 +1,45 @@
+// the fixture ROMs' displays against the PNGs in tests/golden/, rather than hashes that say
+// nothing about what went wrong. needs the testing and test-roms features:
+//
+//   cargo test --features testing,test-roms --test golden
+use chip8rust::{assert_display_matches, demo, Emulator, RunMode};
+
+const CYCLES_PER_FRAME: u32 = 10;
+
+fn emulator(name: &str) -> Emulator {
+    let mut emulator = Emulator::new();
+    emulator.state = RunMode::Play.into();
+    emulator.seed_rng(0);
+    emulator.load_rom_bytes(demo::find(name).unwrap()).unwrap();
+    emulator
+}
+
+#[test]
+fn ibm() {
+    let mut emulator = emulator("ibm");
+    emulator.run_cycles(100); // halted on its last jump long before
+    assert_display_matches!(emulator, "tests/golden/ibm.png");
+}
+
+#[test]
+fn keypad_before_and_after_a_key() {
+    let mut emulator = emulator("keypad");
+    emulator.step_frame(CYCLES_PER_FRAME);
+    assert_display_matches!(emulator, "tests/golden/keypad_blank.png");
+    emulator.keys[0xA] = true;
+    emulator.step_frame(CYCLES_PER_FRAME);
+    emulator.keys[0xA] = false; // FX0A takes it once it's let go
+    for _ in 0..2 {
+        emulator.step_frame(CYCLES_PER_FRAME);
+    }
+    assert_display_matches!(emulator, "tests/golden/keypad_a.png");
+}
+
+#[test]
+fn demo_title() {
+    let mut emulator = emulator("demo");
+    for _ in 0..60 {
+        emulator.step_frame(CYCLES_PER_FRAME);
+    }
+    assert_display_matches!(emulator, "tests/golden/demo_title.png");
+}
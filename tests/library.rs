@@ -0,0 +1,63 @@
+// the interpreter used as a library, the way a frontend of its own would
+use chip8rust::{Emulator, RunMode};
+
+#[test]
+fn runs_a_rom_from_bytes() {
+    let mut emulator = Emulator::new();
+    emulator.state = RunMode::Play.into(); // it starts in Step, which doesn't beep
+    // V1 = 0x05, I = the font's 0, draw it at V0,V0, ST = V1
+    let rom = [0x61, 0x05, 0xF0, 0x29, 0xD0, 0x05, 0xF1, 0x18];
+    emulator.load_rom_bytes(&rom).unwrap();
+    emulator.run_cycles(4);
+    assert_eq!(emulator.registers.get(0x1), 0x05);
+    assert_eq!(emulator.registers.get(0xF), 0); // nothing was erased
+    let display = emulator.display();
+    let top_row: Vec<bool> = (0..8).map(|x| display[x][0]).collect();
+    assert_eq!(top_row, [true, true, true, true, false, false, false, false]);
+    assert!(emulator.sound_active());
+}
+
+// a host loop that calls in at odd times, as a GUI event loop's redraws do, paces the same
+// as one that calls every 60th of a second
+#[test]
+fn runs_in_frames_from_timestamps() {
+    use std::time::{Duration, Instant};
+
+    let mut emulator = Emulator::new();
+    emulator.state = RunMode::Play.into();
+    // DT = 0x3C, then a jump to itself
+    emulator.load_rom_bytes(&[0x60, 0x3C, 0xF0, 0x15, 0x12, 0x04]).unwrap();
+    emulator.run_cycles(2);
+    let mut driver = emulator.driver.in_frames();
+    let start = Instant::now();
+    let mut frames = Vec::new();
+    for ms in [0, 10, 40, 41, 60, 1060] {
+        let due = driver.advance_to(start + Duration::from_millis(ms), &emulator.state, 10);
+        for _ in 0..due.frames {
+            emulator.step_frame(10);
+        }
+        assert_eq!(due.present(), due.frames > 0);
+        frames.push(due.frames);
+    }
+    // nothing owed for the first, then a frame for each 60th gone by, and a second's stall
+    // cut down to max_delta
+    assert_eq!(frames, [0, 0, 2, 0, 1, 6]);
+    assert_eq!(emulator.timer, 0x3C - 9);
+}
+
+#[test]
+fn runs_cycles_from_timestamps() {
+    use chip8rust::frame_driver::FrameDriver;
+    use std::time::{Duration, Instant};
+
+    let state = RunMode::Play.into();
+    let mut driver = FrameDriver::new(0.001, false);
+    let start = Instant::now();
+    let due: Vec<(u32, u32)> = [0, 2500, 9700, 30200]
+        .into_iter()
+        .map(|us| driver.advance_to(start + Duration::from_micros(us), &state, u32::MAX))
+        .map(|due| (due.cycles, due.timer_ticks))
+        .collect();
+    // what's left of a cycle carries over to the next call
+    assert_eq!(due, [(0, 0), (2, 0), (7, 0), (21, 1)]);
+}
@@ -0,0 +1,211 @@
+// the libretro core, driven the way RetroArch drives one: the callbacks first, then a game,
+// then retro_run once a frame. the core keeps a thread-local, and so do these, so each test
+// is a frontend of its own
+#![cfg(feature = "libretro")]
+
+use std::cell::RefCell;
+use std::ffi::{c_uint, c_void, CStr};
+use std::ptr;
+
+use chip8rust::libretro::*;
+
+// draw the font's 0 at (0, 0), then set ST = 5, and wait there
+const BEEP_ROM: [u8; 12] = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x61, 0x05, 0xF1, 0x18, 0x12, 0x0A];
+// V0 = 5, and round until key 5 is down, then draw the font's 5 at (0, 0)
+const KEY_ROM: [u8; 12] = [0x60, 0x05, 0xE0, 0x9E, 0x12, 0x02, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x0A];
+const JOYPAD_A: c_uint = 8;
+
+#[derive(Default)]
+struct Frontend {
+    options: Vec<(String, String)>, // what the core asked to be offered
+    chosen: Vec<(&'static CStr, &'static CStr)>,
+    frame: Vec<u32>,
+    size: (c_uint, c_uint),
+    audio_frames: usize,
+    loudest: i16,
+    joypad: u16, // bit N for RETRO_DEVICE_ID_JOYPAD N held
+}
+
+thread_local! {
+    static FRONTEND: RefCell<Frontend> = RefCell::new(Frontend::default());
+}
+
+unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    FRONTEND.with(|frontend| {
+        let mut frontend = frontend.borrow_mut();
+        match cmd {
+            10 => *(data as *const c_uint) == 1, // XRGB8888
+            15 => {
+                let variable = &mut *(data as *mut Variable);
+                let key = CStr::from_ptr(variable.key);
+                let Some((_, value)) = frontend.chosen.iter().find(|(name, _)| *name == key) else {
+                    return false;
+                };
+                variable.value = value.as_ptr();
+                true
+            }
+            16 => {
+                let mut variable = data as *const Variable;
+                while !(*variable).key.is_null() {
+                    let text = |text| CStr::from_ptr(text).to_string_lossy().into_owned();
+                    frontend.options.push((text((*variable).key), text((*variable).value)));
+                    variable = variable.add(1);
+                }
+                true
+            }
+            17 => {
+                *(data as *mut bool) = false;
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+unsafe extern "C" fn video_refresh(
+    data: *const c_void,
+    width: c_uint,
+    height: c_uint,
+    pitch: usize,
+) {
+    FRONTEND.with(|frontend| {
+        let mut frontend = frontend.borrow_mut();
+        assert_eq!(pitch, width as usize * 4);
+        let pixels = std::slice::from_raw_parts(data as *const u32, (width * height) as usize);
+        frontend.frame = pixels.to_vec();
+        frontend.size = (width, height);
+    })
+}
+
+unsafe extern "C" fn audio_sample_batch(data: *const i16, frames: usize) -> usize {
+    FRONTEND.with(|frontend| {
+        let mut frontend = frontend.borrow_mut();
+        let samples = std::slice::from_raw_parts(data, frames * 2);
+        frontend.audio_frames += frames;
+        frontend.loudest = frontend.loudest.max(samples.iter().map(|s| s.abs()).max().unwrap_or(0));
+    });
+    frames
+}
+
+unsafe extern "C" fn input_poll() {}
+
+unsafe extern "C" fn input_state(port: c_uint, device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+    let joypad = FRONTEND.with(|frontend| frontend.borrow().joypad);
+    (port == 0 && device == DEVICE_JOYPAD && joypad & (1 << id) != 0) as i16
+}
+
+fn load(rom: &[u8], chosen: Vec<(&'static CStr, &'static CStr)>) {
+    FRONTEND.with(|frontend| frontend.borrow_mut().chosen = chosen);
+    unsafe {
+        retro_set_environment(Some(environment));
+        retro_set_video_refresh(Some(video_refresh));
+        retro_set_audio_sample_batch(Some(audio_sample_batch));
+        retro_set_input_poll(Some(input_poll));
+        retro_set_input_state(Some(input_state));
+        retro_init();
+        let game = GameInfo {
+            path: ptr::null(),
+            data: rom.as_ptr() as *const c_void,
+            size: rom.len(),
+            meta: ptr::null(),
+        };
+        assert!(retro_load_game(&game));
+    }
+}
+
+fn run(frames: usize) {
+    for _ in 0..frames {
+        unsafe { retro_run() };
+    }
+}
+
+// the lit pixels of the last frame, as (x, y)
+fn lit(on: u32) -> Vec<(usize, usize)> {
+    FRONTEND.with(|frontend| {
+        let frontend = frontend.borrow();
+        let width = frontend.size.0 as usize;
+        let pixels = frontend.frame.iter().enumerate().filter(|(_, pixel)| **pixel == on);
+        pixels.map(|(i, _)| (i % width, i / width)).collect()
+    })
+}
+
+#[test]
+fn describes_itself() {
+    assert_eq!(retro_api_version(), 1);
+    let mut info = SystemAvInfo {
+        geometry: GameGeometry {
+            base_width: 0,
+            base_height: 0,
+            max_width: 0,
+            max_height: 0,
+            aspect_ratio: 0.0,
+        },
+        timing: SystemTiming {
+            fps: 0.0,
+            sample_rate: 0.0,
+        },
+    };
+    unsafe { retro_get_system_av_info(&mut info) };
+    let geometry = &info.geometry;
+    let size = (geometry.base_width, geometry.base_height, geometry.max_width, geometry.max_height);
+    assert_eq!(size, (64, 32, 64, 64));
+    assert_eq!((info.timing.fps, info.timing.sample_rate), (60.0, 44100.0));
+}
+
+#[test]
+fn offers_its_options() {
+    load(&BEEP_ROM, Vec::new());
+    let keys: Vec<String> = FRONTEND.with(|frontend| {
+        frontend.borrow().options.iter().map(|(key, _)| key.clone()).collect()
+    });
+    assert_eq!(keys, ["chip8rust_cycle_speed", "chip8rust_quirks", "chip8rust_palette"]);
+    retro_unload_game();
+}
+
+#[test]
+fn draws_and_beeps_a_frame() {
+    load(&BEEP_ROM, Vec::new());
+    run(1);
+    let zero = [(0, 0), (1, 0), (2, 0), (3, 0), (0, 1), (3, 1)];
+    let lit = lit(0xFFFFFF);
+    assert!(zero.iter().all(|pixel| lit.contains(pixel)), "{:?}", lit);
+    assert_eq!(lit.len(), 14); // the whole digit, and nothing else
+    FRONTEND.with(|frontend| {
+        let frontend = frontend.borrow();
+        assert_eq!(frontend.size, (64, 32));
+        assert_eq!(frontend.audio_frames, 735);
+        assert!(frontend.loudest > 0);
+    });
+    retro_unload_game();
+}
+
+#[test]
+fn the_joypad_presses_keys() {
+    load(&KEY_ROM, Vec::new());
+    run(2);
+    assert_eq!(lit(0xFFFFFF), []);
+    FRONTEND.with(|frontend| frontend.borrow_mut().joypad = 1 << JOYPAD_A);
+    run(1);
+    assert_eq!(lit(0xFFFFFF).len(), 14); // the font's 5
+    retro_unload_game();
+}
+
+#[test]
+fn the_palette_comes_from_the_options() {
+    load(&BEEP_ROM, vec![(c"chip8rust_palette", c"amber")]);
+    run(1);
+    assert_eq!(lit(0xFFB000).len(), 14);
+    assert_eq!(lit(0x1A0E00).len(), 64 * 32 - 14);
+    retro_unload_game();
+}
+
+#[test]
+fn a_game_without_data_is_refused() {
+    let game = GameInfo {
+        path: c"game.ch8".as_ptr(),
+        data: ptr::null(),
+        size: 0,
+        meta: ptr::null(),
+    };
+    assert!(!unsafe { retro_load_game(&game) });
+}
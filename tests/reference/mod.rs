@@ -0,0 +1,161 @@
+// a second CHIP-8, written from the spec and as plainly as it goes, for tests/differential.rs
+// to hold the real one up against. the instructions it doesn't model (randomness, timers,
+// FX0A, EXIT, the stack running out) come back as Unmodelled, and the case is thrown away
+
+pub const W: usize = 64;
+pub const H: usize = 32;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Quirks {
+    pub jump_x: bool, // BXNN jumps to XNN + VX
+    pub clip_x: bool, // sprites are cut off at the right edge
+    pub clip_y: bool, // and the bottom
+}
+
+#[derive(Debug)]
+pub struct Unmodelled;
+
+pub struct Reference {
+    pub quirks: Quirks,
+    pub memory: [u8; 0x1000],
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: Vec<u16>,
+    pub keys: u16, // bit N for key N held
+    pub display: [[bool; W]; H], // row by row
+}
+
+impl Reference {
+    // the memory as the real one has it after loading, font and ROM included
+    pub fn new(quirks: Quirks, memory: [u8; 0x1000], pc: u16, keys: u16) -> Reference {
+        Reference {
+            quirks,
+            memory,
+            v: [0; 16],
+            i: 0,
+            pc,
+            stack: Vec::new(),
+            keys,
+            display: [[false; W]; H],
+        }
+    }
+
+    fn byte_at(&self, addr: u16) -> u8 {
+        self.memory[(addr & 0xFFF) as usize]
+    }
+
+    fn key(&self, key: u8) -> bool {
+        self.keys & (1 << (key & 0xF)) != 0
+    }
+
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.pc = (self.pc + 2) & 0xFFF;
+        }
+    }
+
+    pub fn step(&mut self) -> Result<(), Unmodelled> {
+        let word = (self.byte_at(self.pc) as u16) << 8 | self.byte_at(self.pc + 1) as u16;
+        self.pc = (self.pc + 2) & 0xFFF;
+        let x = ((word >> 8) & 0xF) as usize;
+        let y = ((word >> 4) & 0xF) as usize;
+        let n = (word & 0xF) as u8;
+        let nn = (word & 0xFF) as u8;
+        let nnn = word & 0xFFF;
+        let (vx, vy) = (self.v[x], self.v[y]);
+
+        match word >> 12 {
+            0x0 if nn == 0xE0 => self.display = [[false; W]; H],
+            0x0 if nn == 0xEE => self.pc = self.stack.pop().ok_or(Unmodelled)?,
+            0x0 if word == 0x00FD => return Err(Unmodelled),
+            0x0 => {} // SYS, machine code, ignored
+            0x1 => self.pc = nnn,
+            0x2 => {
+                if self.stack.len() == 16 {
+                    return Err(Unmodelled);
+                }
+                self.stack.push(self.pc);
+                self.pc = nnn;
+            }
+            0x3 => self.skip_if(vx == nn),
+            0x4 => self.skip_if(vx != nn),
+            0x5 => self.skip_if(vx == vy),
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = vx.wrapping_add(nn),
+            0x8 => {
+                // the flag goes in first, so VF as VX ends up with the result
+                let (result, flag) = match n {
+                    0x0 => (vy, None),
+                    0x1 => (vx | vy, None),
+                    0x2 => (vx & vy, None),
+                    0x3 => (vx ^ vy, None),
+                    0x4 => (vx.wrapping_add(vy), Some((vx as u16 + vy as u16 > 0xFF) as u8)),
+                    0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+                    0x6 => (vx >> 1, Some(vx & 1)),
+                    0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+                    0xE => (vx << 1, Some(vx >> 7)),
+                    _ => return Ok(()),
+                };
+                if let Some(flag) = flag {
+                    self.v[0xF] = flag;
+                }
+                self.v[x] = result;
+            }
+            0x9 => self.skip_if(vx != vy),
+            0xA => self.i = nnn,
+            0xB => {
+                let offset = if self.quirks.jump_x { vx } else { self.v[0] };
+                self.pc = (nnn + offset as u16) & 0xFFF;
+            }
+            0xD => {
+                let mut erased = false;
+                for row in 0..n as usize {
+                    let sprite = self.byte_at(self.i.wrapping_add(row as u16));
+                    let py = vy as usize % H + row;
+                    if py >= H && self.quirks.clip_y {
+                        break;
+                    }
+                    for column in 0..8 {
+                        let px = vx as usize % W + column;
+                        if px >= W && self.quirks.clip_x {
+                            break;
+                        }
+                        if sprite & (0x80 >> column) != 0 {
+                            let pixel = &mut self.display[py % H][px % W];
+                            erased |= *pixel;
+                            *pixel = !*pixel;
+                        }
+                    }
+                }
+                self.v[0xF] = erased as u8;
+            }
+            0xE if nn == 0x9E => self.skip_if(self.key(vx)),
+            0xE if nn == 0xA1 => self.skip_if(!self.key(vx)),
+            0xE => {}
+            0xF => match nn {
+                0x1E => self.i = self.i.wrapping_add(vx as u16),
+                0x29 => self.i = vx as u16 * 5,
+                0x33 => {
+                    for (k, digit) in [vx / 100, vx / 10 % 10, vx % 10].into_iter().enumerate() {
+                        self.memory[(self.i.wrapping_add(k as u16) & 0xFFF) as usize] = digit;
+                    }
+                }
+                0x55 => {
+                    for k in 0..=x {
+                        self.memory[(self.i.wrapping_add(k as u16) & 0xFFF) as usize] = self.v[k];
+                    }
+                }
+                0x65 => {
+                    for k in 0..=x {
+                        self.v[k] = self.byte_at(self.i.wrapping_add(k as u16));
+                    }
+                }
+                0x07 | 0x0A | 0x15 | 0x18 => return Err(Unmodelled),
+                _ => {}
+            },
+            _ => return Err(Unmodelled), // CXNN
+        }
+        Ok(())
+    }
+}